@@ -1,4 +1,4 @@
-use mini_executor::MiniExecutor;
+use mini_executor::runtime::Runtime;
 use futures::{join, future::FutureExt};
 use std::time::Duration;
 use std::thread::sleep;
@@ -17,15 +17,12 @@ async fn concurrent_fetch() {
     let fetch1 = fetch_data(source1, delay1).boxed();
     let fetch2 = fetch_data(source2, delay2).boxed();
 
-    match join!(fetch1, fetch2) {
-        (data1, data2) => {
-            println!("Data1: {}\n", data1);
-            println!("Data2: {}\n", data2);
-        }
-    }
+    let (data1, data2) = join!(fetch1, fetch2);
+    println!("Data1: {}\n", data1);
+    println!("Data2: {}\n", data2);
 }
 
 fn main() {
-    let executor = MiniExecutor::new(concurrent_fetch());
-    executor.run();
+    let executor = Runtime::new();
+    executor.block_on(concurrent_fetch());
 }
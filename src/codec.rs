@@ -0,0 +1,152 @@
+//! Framing byte streams into messages, with [`Encoder`]s and [`Decoder`]s.
+//!
+//! Most protocols send messages over a byte stream, like lines of text, or blobs that start with
+//! their length, and a reader has to collect the bytes until a whole message has come in, in
+//! however many reads it takes. A [`Decoder`] knows where the messages of a protocol end, and
+//! turns the bytes read so far into messages, the frames, as soon as they are complete, while an
+//! [`Encoder`] writes frames as bytes. [`Framed`] wraps a stream that implements
+//! [`AsyncRead`](crate::io::AsyncRead) and [`AsyncWrite`](crate::io::AsyncWrite) together with a
+//! codec, and turns it into a [`Stream`](futures::Stream) of the frames that are read, and a
+//! [`Sink`](futures::Sink) of the ones to write, whose extension traits `StreamExt` and
+//! `SinkExt` of the `futures` crate add `next` and `send`.
+//!
+//! [`LinesCodec`] splits the stream into lines, and [`LengthDelimitedCodec`] into frames that
+//! start with their length. The bytes are collected in a [`Buffer`], which the codecs take the
+//! frames off the front of.
+//!
+//! # Examples
+//!
+//! A server that answers every line in upper case:
+//!
+//! ```
+//! use futures::{SinkExt, StreamExt};
+//! use mini_executor::codec::{Framed, LinesCodec};
+//! use mini_executor::net::UnixStream;
+//! use mini_executor::runtime::Runtime;
+//!
+//! let executor = Runtime::new();
+//! let answers = executor.block_on(async {
+//!     let (client, server) = UnixStream::pair().unwrap();
+//!     mini_executor::spawn(async move {
+//!         let mut lines = Framed::new(server, LinesCodec::new());
+//!         while let Some(Ok(line)) = lines.next().await {
+//!             lines.send(line.to_uppercase()).await.unwrap();
+//!         }
+//!     });
+//!
+//!     let mut lines = Framed::new(client, LinesCodec::new());
+//!     lines.send("hello").await.unwrap();
+//!     lines.send("world").await.unwrap();
+//!     let first = lines.next().await.unwrap().unwrap();
+//!     let second = lines.next().await.unwrap().unwrap();
+//!     [first, second]
+//! });
+//! assert_eq!(answers, ["HELLO", "WORLD"]);
+//! ```
+
+use std::io;
+
+mod buffer;
+mod framed;
+mod length_delimited;
+mod lines;
+
+pub use buffer::Buffer;
+pub use framed::Framed;
+pub use length_delimited::LengthDelimitedCodec;
+pub use lines::LinesCodec;
+
+/// Turns the bytes read from a stream into frames.
+///
+/// [`Framed`] appends what it reads to a [`Buffer`], and calls [`decode`](Decoder::decode) until
+/// it returns `None`, and once the stream has ended, [`decode_eof`](Decoder::decode_eof). A
+/// decoder takes the bytes of every frame it returns off the front of the buffer, and leaves the
+/// bytes of a frame that hasn't been read completely yet where they are.
+///
+/// # Examples
+///
+/// A codec whose frames end with a zero byte:
+///
+/// ```
+/// use std::io;
+///
+/// use mini_executor::codec::{Buffer, Decoder};
+///
+/// struct NulCodec;
+///
+/// impl Decoder for NulCodec {
+///     type Item = Vec<u8>;
+///     type Error = io::Error;
+///
+///     fn decode(&mut self, src: &mut Buffer) -> io::Result<Option<Vec<u8>>> {
+///         let Some(end) = src.iter().position(|&byte| byte == 0) else {
+///             return Ok(None);
+///         };
+///         let frame = src.split_to(end);
+///         src.advance(1);
+///         Ok(Some(frame))
+///     }
+/// }
+///
+/// let mut src = Buffer::from(b"one\0two\0thr".to_vec());
+/// assert_eq!(NulCodec.decode(&mut src).unwrap(), Some(b"one".to_vec()));
+/// assert_eq!(NulCodec.decode(&mut src).unwrap(), Some(b"two".to_vec()));
+/// assert_eq!(NulCodec.decode(&mut src).unwrap(), None);
+/// assert_eq!(&src[..], b"thr");
+/// ```
+pub trait Decoder {
+    /// The frames the decoder returns.
+    type Item;
+    /// The error the decoder returns, which the errors of the stream are turned into as well.
+    type Error: From<io::Error>;
+
+    /// Take the next frame off the front of `src`, or return `None` if `src` doesn't hold a whole
+    /// frame yet.
+    ///
+    /// A decoder that knows how long the rest of the frame is can [`reserve`](Buffer::reserve)
+    /// the room for it, to read it in fewer reads.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes aren't a valid frame, which ends the stream of frames of a
+    /// [`Framed`].
+    fn decode(&mut self, src: &mut Buffer) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// Take the next frame off the front of `src` after the stream has ended, or return `None` if
+    /// there are no more.
+    ///
+    /// The default calls [`decode`](Decoder::decode), and returns an error if it returns `None`
+    /// while `src` isn't empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) if the stream
+    /// ended in the middle of a frame, or the errors of `decode`.
+    fn decode_eof(&mut self, src: &mut Buffer) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the stream ended in the middle of a frame",
+            )
+            .into()),
+        }
+    }
+}
+
+/// Turns frames into the bytes to write to a stream.
+///
+/// A codec can encode frames of several types, like [`LinesCodec`], every `T: AsRef<str>`, so
+/// either a `String` or a `&str` can be sent.
+pub trait Encoder<Item> {
+    /// The error the encoder returns, which the errors of the stream are turned into as well.
+    type Error: From<io::Error>;
+
+    /// Append the bytes of `item` to `dst`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `item` can't be encoded, like when it is too long for the protocol.
+    fn encode(&mut self, item: Item, dst: &mut Buffer) -> Result<(), Self::Error>;
+}
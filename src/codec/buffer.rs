@@ -0,0 +1,145 @@
+//! [`Buffer`], the bytes a codec decodes frames from, or encodes them into.
+
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::io::AsyncRead;
+
+/// A growable buffer of bytes, whose bytes can be taken off the front without moving the rest.
+///
+/// It dereferences to the slice of its bytes. [`advance`](Buffer::advance) and
+/// [`split_to`](Buffer::split_to) take bytes off the front, which only moves the start of the
+/// buffer, so a decoder that takes many small frames out of it doesn't copy what follows them
+/// every time. The room in front is reclaimed once the buffer runs out of it at the back.
+#[derive(Default, Clone)]
+pub struct Buffer {
+    buf: Vec<u8>,
+    /// The index of the first byte in `buf` that hasn't been taken yet.
+    start: usize,
+}
+
+impl Buffer {
+    /// Create an empty buffer, which doesn't allocate until bytes are added.
+    pub fn new() -> Buffer {
+        Buffer::default()
+    }
+
+    /// Create an empty buffer with room for at least `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Buffer {
+        Buffer {
+            buf: Vec::with_capacity(capacity),
+            start: 0,
+        }
+    }
+
+    /// Take the first `count` bytes off the front, and drop them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is greater than the length of the buffer.
+    pub fn advance(&mut self, count: usize) {
+        assert!(
+            count <= self.len(),
+            "can't advance past the end of the buffer"
+        );
+        self.start += count;
+        if self.start == self.buf.len() {
+            self.clear();
+        }
+    }
+
+    /// Take the first `count` bytes off the front, and return them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is greater than the length of the buffer.
+    pub fn split_to(&mut self, count: usize) -> Vec<u8> {
+        assert!(
+            count <= self.len(),
+            "can't split past the end of the buffer"
+        );
+        let bytes = self[..count].to_vec();
+        self.advance(count);
+        bytes
+    }
+
+    /// Append `bytes` at the back.
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.reserve(bytes.len());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Make room for at least `additional` more bytes at the back, reclaiming the room at the
+    /// front first.
+    pub fn reserve(&mut self, additional: usize) {
+        if self.buf.capacity() - self.buf.len() < additional && self.start > 0 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+        self.buf.reserve(additional);
+    }
+
+    /// Drop all the bytes of the buffer, keeping its memory.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.start = 0;
+    }
+
+    /// Read at most `max` bytes from `reader` into the back of the buffer, and return how many
+    /// were read.
+    pub(super) fn poll_read_from<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        reader: &mut R,
+        max: usize,
+    ) -> Poll<io::Result<usize>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.reserve(max);
+        let len = self.buf.len();
+        self.buf.resize(len + max, 0);
+        let poll = Pin::new(reader).poll_read(cx, &mut self.buf[len..]);
+        let read = match &poll {
+            Poll::Ready(Ok(read)) => *read,
+            _ => 0,
+        };
+        self.buf.truncate(len + read);
+        poll
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[self.start..]
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.start..]
+    }
+}
+
+impl From<Vec<u8>> for Buffer {
+    fn from(buf: Vec<u8>) -> Buffer {
+        Buffer { buf, start: 0 }
+    }
+}
+
+impl From<&[u8]> for Buffer {
+    fn from(bytes: &[u8]) -> Buffer {
+        Buffer::from(bytes.to_vec())
+    }
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffer").field("len", &self.len()).finish()
+    }
+}
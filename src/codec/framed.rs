@@ -0,0 +1,208 @@
+//! [`Framed`], a stream of frames read with a [`Decoder`], and a sink of frames written with an
+//! [`Encoder`].
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{Sink, Stream};
+
+use super::{Buffer, Decoder, Encoder};
+use crate::io::{AsyncRead, AsyncWrite};
+
+/// How many bytes are read from the stream at once.
+const READ_SIZE: usize = 8 * 1024;
+
+/// How many encoded bytes [`Framed`] collects before it writes them out, when it is asked for
+/// room for the next frame.
+const BACKPRESSURE_BOUNDARY: usize = 8 * 1024;
+
+/// A byte stream together with a codec, as a [`Stream`] of the frames read from it and a [`Sink`]
+/// of the frames to write to it.
+///
+/// The stream reads from `io` until the [`Decoder`] returns a frame, and ends once `io` has ended
+/// and [`decode_eof`](Decoder::decode_eof) returns no more. It also ends after it has returned an
+/// error, since the bytes that follow a frame the decoder couldn't make sense of can't be
+/// decoded either. The sink encodes the frames it is sent with the [`Encoder`] into a buffer,
+/// which it writes out once it holds 8 KiB, or when it is flushed, so `send` and `send_all` of
+/// `SinkExt` write every frame right away, and `feed` collects them into fewer writes.
+///
+/// The stream and the sink can be used from two tasks at once with `StreamExt::split` of the
+/// `futures` crate. A codec can encode frames of several types, so the methods of `SinkExt` that
+/// don't take a frame, like `close`, may need the type spelled out, as in
+/// `SinkExt::<String>::close(&mut framed)`. See the [module documentation](super) for an example.
+#[must_use = "streams do nothing unless polled"]
+pub struct Framed<T, U> {
+    io: T,
+    codec: U,
+    read_buf: Buffer,
+    write_buf: Buffer,
+    /// Whether `io` has ended.
+    eof: bool,
+    /// Whether the stream of frames has ended, after the last frame or an error.
+    done: bool,
+}
+
+impl<T, U> Framed<T, U> {
+    /// Frame `io` with `codec`.
+    pub fn new(io: T, codec: U) -> Framed<T, U> {
+        Framed {
+            io,
+            codec,
+            read_buf: Buffer::new(),
+            write_buf: Buffer::new(),
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Return a reference to the byte stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Return a mutable reference to the byte stream.
+    ///
+    /// Reading from it, or writing to it, directly mixes those bytes up with the ones of the
+    /// frames.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Return a reference to the codec.
+    pub fn codec(&self) -> &U {
+        &self.codec
+    }
+
+    /// Return a mutable reference to the codec, to change how the frames that follow are
+    /// encoded or decoded.
+    pub fn codec_mut(&mut self) -> &mut U {
+        &mut self.codec
+    }
+
+    /// Return the bytes that have been read, but not decoded into a frame yet.
+    pub fn read_buffer(&self) -> &Buffer {
+        &self.read_buf
+    }
+
+    /// Return the byte stream, dropping the codec, and the bytes that have been read but not
+    /// decoded, or encoded but not written yet.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+// Neither the byte stream nor the codec is ever pinned, the byte stream is only used while it is
+// `Unpin`.
+impl<T: Unpin, U> Unpin for Framed<T, U> {}
+
+impl<T, U> Framed<T, U>
+where
+    T: AsyncWrite + Unpin,
+{
+    /// Write the whole write buffer to the stream, without flushing it.
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_buf.is_empty() {
+            match Pin::new(&mut self.io).poll_write(cx, &self.write_buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(written)) => self.write_buf.advance(written),
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T, U> Stream for Framed<T, U>
+where
+    T: AsyncRead + Unpin,
+    U: Decoder,
+{
+    type Item = Result<U::Item, U::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            let decoded = if this.eof {
+                this.codec.decode_eof(&mut this.read_buf)
+            } else {
+                this.codec.decode(&mut this.read_buf)
+            };
+            match decoded {
+                Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                Ok(None) if this.eof => {
+                    this.done = true;
+                    return Poll::Ready(None);
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+            match this.read_buf.poll_read_from(cx, &mut this.io, READ_SIZE) {
+                Poll::Ready(Ok(0)) => this.eof = true,
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(error)) if error.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(error)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(error.into())));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T, U, I> Sink<I> for Framed<T, U>
+where
+    T: AsyncWrite + Unpin,
+    U: Encoder<I>,
+{
+    type Error = U::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), U::Error>> {
+        let this = self.get_mut();
+        if this.write_buf.len() < BACKPRESSURE_BOUNDARY {
+            return Poll::Ready(Ok(()));
+        }
+        this.poll_write_buf(cx).map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), U::Error> {
+        let this = self.get_mut();
+        this.codec.encode(item, &mut this.write_buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), U::Error>> {
+        let this = self.get_mut();
+        match this.poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.io).poll_flush(cx).map_err(Into::into),
+            poll => poll.map_err(Into::into),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), U::Error>> {
+        let this = self.get_mut();
+        match this.poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.io).poll_close(cx).map_err(Into::into),
+            poll => poll.map_err(Into::into),
+        }
+    }
+}
+
+impl<T: fmt::Debug, U: fmt::Debug> fmt::Debug for Framed<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Framed")
+            .field("io", &self.io)
+            .field("codec", &self.codec)
+            .field("read_buf", &self.read_buf)
+            .field("write_buf", &self.write_buf)
+            .finish_non_exhaustive()
+    }
+}
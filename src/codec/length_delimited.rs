@@ -0,0 +1,112 @@
+//! [`LengthDelimitedCodec`], which frames a stream into blobs that start with their length.
+
+use std::io;
+
+use super::{Buffer, Decoder, Encoder};
+
+/// The size of the length in front of every frame.
+const HEADER_LEN: usize = 4;
+
+/// A codec whose frames are blobs of bytes, each following its length as a big-endian `u32`.
+///
+/// The frames are decoded into `Vec<u8>`s without their length, and any `T: AsRef<[u8]>` is
+/// encoded as a frame, with its length in front. Frames are at most 8 MiB long, unless another
+/// maximum is set with [`with_max_frame_length`](LengthDelimitedCodec::with_max_frame_length),
+/// so a peer can't make the reader buffer more than that by sending a large length.
+///
+/// # Examples
+///
+/// ```
+/// use futures::{SinkExt, StreamExt};
+/// use mini_executor::codec::{Framed, LengthDelimitedCodec};
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let frames = executor.block_on(async {
+///     let (client, server) = UnixStream::pair().unwrap();
+///     mini_executor::spawn(async move {
+///         let mut client = Framed::new(client, LengthDelimitedCodec::new());
+///         client.send(b"first").await.unwrap();
+///         client.send(vec![0; 1_000_000]).await.unwrap();
+///     });
+///
+///     let server = Framed::new(server, LengthDelimitedCodec::new());
+///     let frames: Vec<_> = server.map(|frame| frame.unwrap().len()).collect().await;
+///     frames
+/// });
+/// assert_eq!(frames, [5, 1_000_000]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+    max_frame_length: usize,
+}
+
+impl LengthDelimitedCodec {
+    /// Create a codec for frames of at most 8 MiB.
+    pub fn new() -> LengthDelimitedCodec {
+        LengthDelimitedCodec::with_max_frame_length(8 * 1024 * 1024)
+    }
+
+    /// Create a codec for frames of at most `max_frame_length` bytes, not counting their length.
+    ///
+    /// Decoding or encoding a longer frame fails with an error of kind
+    /// [`InvalidData`](io::ErrorKind::InvalidData), and so does encoding a frame longer than
+    /// `u32::MAX`, whatever the maximum.
+    pub fn with_max_frame_length(max_frame_length: usize) -> LengthDelimitedCodec {
+        LengthDelimitedCodec { max_frame_length }
+    }
+
+    /// Return the maximum length of a frame.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    fn check_length(&self, length: usize) -> io::Result<()> {
+        if length > self.max_frame_length || u32::try_from(length).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the frame is longer than the maximum length",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> LengthDelimitedCodec {
+        LengthDelimitedCodec::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut Buffer) -> io::Result<Option<Vec<u8>>> {
+        let Some(header) = src.get(..HEADER_LEN) else {
+            return Ok(None);
+        };
+        let length = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+        self.check_length(length)?;
+        if src.len() < HEADER_LEN + length {
+            src.reserve(HEADER_LEN + length - src.len());
+            return Ok(None);
+        }
+        src.advance(HEADER_LEN);
+        Ok(Some(src.split_to(length)))
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for LengthDelimitedCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: T, dst: &mut Buffer) -> io::Result<()> {
+        let frame = frame.as_ref();
+        self.check_length(frame.len())?;
+        dst.reserve(HEADER_LEN + frame.len());
+        dst.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        dst.extend_from_slice(frame);
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! [`LinesCodec`], which frames a stream into lines of text.
+
+use std::cmp;
+use std::io;
+
+use super::{Buffer, Decoder, Encoder};
+
+/// A codec whose frames are lines of UTF-8 text, each ending with `\n` or `\r\n`.
+///
+/// The lines are decoded into `String`s without their line ending, and the last line of a stream
+/// doesn't need one. Any `T: AsRef<str>` is encoded as a line, with `\n` appended to it.
+///
+/// Without a maximum length, a peer that never ends its line makes the reader buffer all it
+/// sends, so a server reading lines from clients it doesn't trust should set one with
+/// [`with_max_length`](LinesCodec::with_max_length).
+#[derive(Debug, Clone, Default)]
+pub struct LinesCodec {
+    /// The length of the start of the buffer that has been searched for `\n` already.
+    searched: usize,
+    max_length: Option<usize>,
+}
+
+impl LinesCodec {
+    /// Create a codec for lines of any length.
+    pub fn new() -> LinesCodec {
+        LinesCodec::default()
+    }
+
+    /// Create a codec for lines of at most `max_length` bytes, not counting their line ending.
+    ///
+    /// Decoding or encoding a longer line fails with an error of kind
+    /// [`InvalidData`](io::ErrorKind::InvalidData).
+    pub fn with_max_length(max_length: usize) -> LinesCodec {
+        LinesCodec {
+            searched: 0,
+            max_length: Some(max_length),
+        }
+    }
+
+    /// Return the maximum length of a line, if there is one.
+    pub fn max_length(&self) -> Option<usize> {
+        self.max_length
+    }
+
+    fn check_length(&self, length: usize) -> io::Result<()> {
+        match self.max_length {
+            Some(max_length) if length > max_length => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the line is longer than the maximum length",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Decode `line` as UTF-8, without its trailing `\r`.
+fn to_string(mut line: Vec<u8>) -> io::Result<String> {
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "the line isn't valid UTF-8"))
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut Buffer) -> io::Result<Option<String>> {
+        // Past the end of the longest line that is allowed, and its `\r\n`, the line is too long
+        // whether or not there is a `\n`.
+        let end = match self.max_length {
+            Some(max_length) => cmp::min(src.len(), max_length.saturating_add(2)),
+            None => src.len(),
+        };
+        let start = cmp::min(self.searched, end);
+        match src[start..end].iter().position(|&byte| byte == b'\n') {
+            Some(offset) => {
+                let newline = start + offset;
+                self.searched = 0;
+                let line = to_string(src.split_to(newline))?;
+                src.advance(1);
+                self.check_length(line.len())?;
+                Ok(Some(line))
+            }
+            None => {
+                self.searched = end;
+                // The last byte may be the `\r` of a `\r\n`.
+                self.check_length(end.saturating_sub(1))?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut Buffer) -> io::Result<Option<String>> {
+        if let Some(line) = self.decode(src)? {
+            return Ok(Some(line));
+        }
+        self.searched = 0;
+        if src.is_empty() {
+            return Ok(None);
+        }
+        let line = to_string(src.split_to(src.len()))?;
+        self.check_length(line.len())?;
+        Ok(Some(line))
+    }
+}
+
+impl<T: AsRef<str>> Encoder<T> for LinesCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, line: T, dst: &mut Buffer) -> io::Result<()> {
+        let line = line.as_ref();
+        self.check_length(line.len())?;
+        dst.reserve(line.len() + 1);
+        dst.extend_from_slice(line.as_bytes());
+        dst.extend_from_slice(b"\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Reading and writing files inside of async code, on the blocking pool.
+//!
+//! The file systems of most platforms have no way to wait for a file to become ready, like a
+//! socket: a read from a regular file is always ready, and blocks the thread until the disk has
+//! answered. Reading a file with [`std::fs`] from a task blocks every other task of the thread
+//! with it, so the functions of this module hand the operations to the blocking pool of the
+//! current runtime instead, and wait for them there, see
+//! [`Runtime::spawn_blocking`](crate::runtime::Runtime::spawn_blocking).
+//!
+//! [`read`], [`read_to_string`] and [`write()`] read or write a whole file in one go. A [`File`] is
+//! an open file that is read from and written to bit by bit, and implements
+//! [`AsyncRead`](crate::io::AsyncRead), [`AsyncWrite`](crate::io::AsyncWrite) and
+//! [`AsyncSeek`](crate::io::AsyncSeek). [`read_dir`] lists the entries of a directory as a
+//! stream, and the other functions, like [`metadata`] or [`create_dir_all`], wrap their
+//! counterparts of `std::fs`. On Linux, macOS and the BSDs, `watch` reports the changes to files
+//! and directories as they happen, through the I/O driver rather than the blocking pool.
+//!
+//! With the `io_uring` feature on Linux, the `io::uring` module reads and writes files through
+//! the kernel instead, without a thread.
+//!
+//! All functions of this module panic if called from outside of a
+//! [`Runtime`](crate::runtime::Runtime).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::runtime::Handle;
+
+mod file;
+mod read_dir;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod watch;
+
+pub use file::File;
+pub use read_dir::{read_dir, DirEntry, ReadDir};
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use watch::{watch, Event, EventKind, Watcher};
+
+/// Read the whole file at `path` into a `Vec`, like [`std::fs::read`].
+///
+/// # Errors
+///
+/// Returns an error if the file can't be opened or read.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::fs;
+/// use mini_executor::runtime::Runtime;
+///
+/// let path = std::env::temp_dir().join("mini-executor-fs-read-example");
+///
+/// let executor = Runtime::new();
+/// let contents = executor.block_on(async {
+///     fs::write(&path, b"hello").await.unwrap();
+///     fs::read(&path).await.unwrap()
+/// });
+/// assert_eq!(contents, b"hello");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::read(path)).await
+}
+
+/// Read the whole file at `path` into a `String`, like [`std::fs::read_to_string`].
+///
+/// # Errors
+///
+/// Returns an error if the file can't be opened or read, or if it isn't valid UTF-8.
+pub async fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::read_to_string(path)).await
+}
+
+/// Write `contents` to the file at `path`, which is created if it doesn't exist, and replaced if it
+/// does, like [`std::fs::write`].
+///
+/// # Errors
+///
+/// Returns an error if the file can't be created or written.
+pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    let contents = contents.as_ref().to_owned();
+    asyncify(move || fs::write(path, contents)).await
+}
+
+/// The metadata of the file or directory at `path`, following symbolic links, like
+/// [`std::fs::metadata`].
+///
+/// # Errors
+///
+/// Returns an error if nothing exists at `path`, or its metadata can't be read.
+pub async fn metadata(path: impl AsRef<Path>) -> io::Result<fs::Metadata> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::metadata(path)).await
+}
+
+/// The metadata of the file, directory or symbolic link at `path`, without following symbolic
+/// links, like [`std::fs::symlink_metadata`].
+///
+/// # Errors
+///
+/// Returns an error if nothing exists at `path`, or its metadata can't be read.
+pub async fn symlink_metadata(path: impl AsRef<Path>) -> io::Result<fs::Metadata> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::symlink_metadata(path)).await
+}
+
+/// The absolute form of `path`, with all symbolic links resolved, like [`std::fs::canonicalize`].
+///
+/// # Errors
+///
+/// Returns an error if nothing exists at `path`.
+pub async fn canonicalize(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::canonicalize(path)).await
+}
+
+/// Create a directory at `path`, like [`std::fs::create_dir`].
+///
+/// # Errors
+///
+/// Returns an error if something exists at `path` already, or its parent doesn't exist.
+pub async fn create_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::create_dir(path)).await
+}
+
+/// Create a directory at `path`, and all of its parents that don't exist, like
+/// [`std::fs::create_dir_all`].
+///
+/// # Errors
+///
+/// Returns an error if one of the directories can't be created. It isn't one if the directory
+/// exists already.
+pub async fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::create_dir_all(path)).await
+}
+
+/// Remove the file or symbolic link at `path`, like [`std::fs::remove_file`].
+///
+/// # Errors
+///
+/// Returns an error if `path` doesn't exist, or is a directory.
+pub async fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::remove_file(path)).await
+}
+
+/// Remove the empty directory at `path`, like [`std::fs::remove_dir`].
+///
+/// # Errors
+///
+/// Returns an error if `path` doesn't exist, isn't a directory, or isn't empty.
+pub async fn remove_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::remove_dir(path)).await
+}
+
+/// Remove the directory at `path` with everything in it, like [`std::fs::remove_dir_all`].
+///
+/// # Errors
+///
+/// Returns an error if `path` doesn't exist, isn't a directory, or one of its entries can't be
+/// removed.
+pub async fn remove_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || fs::remove_dir_all(path)).await
+}
+
+/// Rename the file or directory at `from` to `to`, replacing what is at `to` if that is the same
+/// kind of thing, like [`std::fs::rename`].
+///
+/// # Errors
+///
+/// Returns an error if `from` doesn't exist, or the two are on different file systems.
+pub async fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let from = from.as_ref().to_owned();
+    let to = to.as_ref().to_owned();
+    asyncify(move || fs::rename(from, to)).await
+}
+
+/// Run the blocking file operation `f` on the blocking pool of the current runtime.
+async fn asyncify<F, R>(f: F) -> io::Result<R>
+where
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    Handle::current()
+        .spawn_blocking(f)
+        .await
+        .map_err(|error| io::Error::other(error.to_string()))?
+}
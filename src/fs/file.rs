@@ -0,0 +1,383 @@
+//! [`File`], an open file whose reads and writes run on the blocking pool.
+
+use std::fmt;
+use std::fs;
+use std::future::{poll_fn, Future};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use super::asyncify;
+use crate::io::{AsyncRead, AsyncSeek, AsyncWrite};
+use crate::runtime::Handle;
+use crate::task::JoinHandle;
+
+/// The most bytes a single read or write hands to the blocking pool.
+const MAX_BUF: usize = 2 * 1024 * 1024;
+
+/// An open file, whose reads, writes and seeks run on the blocking pool of the current runtime.
+///
+/// A file is opened with [`open`](File::open) or [`create`](File::create), or wraps an open
+/// [`std::fs::File`] with [`from_std`](File::from_std). The methods for reading and writing take
+/// `&mut self`, since the file has a position that they move, and are also available through
+/// [`AsyncRead`], [`AsyncWrite`] and [`AsyncSeek`].
+///
+/// One operation runs at a time. A read reads up to the size of its buffer ahead, and hands out
+/// what it read to the reads after it, until that is used up. A write copies the bytes into a
+/// buffer of the file and returns right away, while they are written on the blocking pool; the
+/// next operation waits for it, and the error of a write that failed is returned by the next
+/// write, flush or seek. A file that was written to should be [`flush`](File::flush)ed before it
+/// is dropped, to find out whether the last write succeeded, and [`sync_all`](File::sync_all)ed
+/// if its contents have to be on the disk.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::fs::File;
+/// use mini_executor::runtime::Runtime;
+/// use std::io::SeekFrom;
+///
+/// let path = std::env::temp_dir().join("mini-executor-file-example");
+///
+/// let executor = Runtime::new();
+/// let read = executor.block_on(async {
+///     let mut file = File::create(&path).await.unwrap();
+///     file.write_all(b"hello world").await.unwrap();
+///     file.sync_all().await.unwrap();
+///
+///     let mut file = File::open(&path).await.unwrap();
+///     file.seek(SeekFrom::Start(6)).await.unwrap();
+///     let mut buf = [0; 5];
+///     let read = file.read(&mut buf).await.unwrap();
+///     buf[..read].to_vec()
+/// });
+/// assert_eq!(read, b"world");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct File {
+    std: Arc<fs::File>,
+    state: State,
+    /// The error of a write that failed after `poll_write` had returned.
+    write_error: Option<io::Error>,
+}
+
+/// What a [`File`] is doing.
+enum State {
+    Idle(Buf),
+    /// Running an operation on the blocking pool, which hands the buffer back.
+    Busy(JoinHandle<(Operation, Buf)>),
+}
+
+/// The result of an operation on the blocking pool.
+enum Operation {
+    Read(io::Result<usize>),
+    Write(io::Result<()>),
+    Seek(io::Result<u64>),
+}
+
+/// The buffer of a [`File`]: the bytes a read read ahead, or the ones a write is writing.
+#[derive(Default)]
+struct Buf {
+    bytes: Vec<u8>,
+    /// The bytes a read read ahead from here on haven't been handed out yet.
+    pos: usize,
+}
+
+impl File {
+    /// Open the file at `path` for reading, like [`std::fs::File::open`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file doesn't exist, or can't be opened.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<File> {
+        let path = path.as_ref().to_owned();
+        let std = asyncify(move || fs::File::open(path)).await?;
+        Ok(File::from_std(std))
+    }
+
+    /// Create the file at `path` for writing, or truncate it if it exists, like
+    /// [`std::fs::File::create`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created.
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<File> {
+        let path = path.as_ref().to_owned();
+        let std = asyncify(move || fs::File::create(path)).await?;
+        Ok(File::from_std(std))
+    }
+
+    /// Wrap `std`, an open file, whose position is where the file is read and written from.
+    pub fn from_std(std: fs::File) -> File {
+        File {
+            std: Arc::new(std),
+            state: State::Idle(Buf::default()),
+            write_error: None,
+        }
+    }
+
+    /// Read some bytes into `buf`, and return how many were read.
+    ///
+    /// Returns `0` at the end of the file, or if `buf` is empty.
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+
+    /// Write some bytes of `buf`, and return how many were written, which are written on the
+    /// blocking pool once this returns, see [`File`].
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)).await
+    }
+
+    /// Write all of `buf`, see [`write`](File::write).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`WriteZero`](io::ErrorKind::WriteZero) if the file accepts
+    /// no more bytes.
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                written => buf = &buf[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait until the last write has been written, and return its error if it failed.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+
+    /// Move the position of the file to `pos`, and return the new position from the start of
+    /// the file.
+    pub async fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_seek(cx, pos)).await
+    }
+
+    /// Write what was written to the file to the disk, with its metadata, like
+    /// [`std::fs::File::sync_all`].
+    pub async fn sync_all(&mut self) -> io::Result<()> {
+        self.flush().await?;
+        let std = self.std.clone();
+        asyncify(move || std.sync_all()).await
+    }
+
+    /// Write what was written to the file to the disk, without the metadata that isn't needed to
+    /// read it back, like [`std::fs::File::sync_data`].
+    pub async fn sync_data(&mut self) -> io::Result<()> {
+        self.flush().await?;
+        let std = self.std.clone();
+        asyncify(move || std.sync_data()).await
+    }
+
+    /// Truncate or extend the file to `len` bytes, like [`std::fs::File::set_len`].
+    pub async fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.flush().await?;
+        let std = self.std.clone();
+        asyncify(move || std.set_len(len)).await
+    }
+
+    /// The metadata of the file, like its length, see [`std::fs::File::metadata`].
+    pub async fn metadata(&self) -> io::Result<fs::Metadata> {
+        let std = self.std.clone();
+        asyncify(move || std.metadata()).await
+    }
+
+    /// Poll for the operation in flight, and keep the error of a write that failed.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<Option<Operation>> {
+        let State::Busy(handle) = &mut self.state else {
+            return Poll::Ready(None);
+        };
+        let (operation, buf) = match Pin::new(handle).poll(cx) {
+            Poll::Ready(Ok(done)) => done,
+            // The operation panicked, which loses the buffer.
+            Poll::Ready(Err(error)) => (
+                Operation::Write(Err(io::Error::other(error.to_string()))),
+                Buf::default(),
+            ),
+            Poll::Pending => return Poll::Pending,
+        };
+        self.state = State::Idle(buf);
+        match operation {
+            Operation::Write(Err(error)) => {
+                self.write_error = Some(error);
+                Poll::Ready(None)
+            }
+            operation => Poll::Ready(Some(operation)),
+        }
+    }
+
+    /// The buffer of the idle file, which is taken for the next operation.
+    fn take_buf(&mut self) -> Buf {
+        match &mut self.state {
+            State::Idle(buf) => mem::take(buf),
+            State::Busy(_) => unreachable!("the file is busy"),
+        }
+    }
+
+    /// Run `operation` with the file and `buf` on the blocking pool.
+    fn start<F>(&mut self, buf: Buf, operation: F)
+    where
+        F: FnOnce(&fs::File, Buf) -> (Operation, Buf) + Send + 'static,
+    {
+        let std = self.std.clone();
+        let handle = Handle::current().spawn_blocking(move || operation(&std, buf));
+        self.state = State::Busy(handle);
+    }
+}
+
+impl Buf {
+    /// The number of bytes read ahead that haven't been handed out.
+    fn unread(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Hand out as many bytes read ahead as fit into `dst`.
+    fn copy_to(&mut self, dst: &mut [u8]) -> usize {
+        let len = self.unread().min(dst.len());
+        dst[..len].copy_from_slice(&self.bytes[self.pos..self.pos + len]);
+        self.pos += len;
+        len
+    }
+
+    /// Forget the bytes read ahead, and return how many there were, which the position of the
+    /// file is past where the reader is.
+    fn discard(&mut self) -> usize {
+        let unread = self.unread();
+        self.bytes.clear();
+        self.pos = 0;
+        unread
+    }
+}
+
+/// Move the position of `file` back by the `unread` bytes that were read ahead.
+fn seek_back(mut file: &fs::File, unread: usize) -> io::Result<()> {
+    if unread > 0 {
+        file.seek(SeekFrom::Current(-(unread as i64)))?;
+    }
+    Ok(())
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        dst: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if dst.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            match this.poll_idle(cx) {
+                Poll::Ready(Some(Operation::Read(result))) => {
+                    let State::Idle(buf) = &mut this.state else {
+                        unreachable!("the file is idle");
+                    };
+                    return Poll::Ready(result.map(|_| buf.copy_to(dst)));
+                }
+                Poll::Ready(_) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+
+            let State::Idle(buf) = &mut this.state else {
+                unreachable!("the file is idle");
+            };
+            if buf.unread() > 0 {
+                return Poll::Ready(Ok(buf.copy_to(dst)));
+            }
+            let len = dst.len().min(MAX_BUF);
+            let buf = this.take_buf();
+            this.start(buf, move |mut file, mut buf| {
+                buf.bytes.resize(len, 0);
+                buf.pos = 0;
+                let result = file.read(&mut buf.bytes);
+                buf.bytes.truncate(*result.as_ref().unwrap_or(&0));
+                (Operation::Read(result), buf)
+            });
+        }
+    }
+}
+
+impl AsyncWrite for File {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        src: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.poll_idle(cx).is_pending() {
+            return Poll::Pending;
+        }
+        if let Some(error) = this.write_error.take() {
+            return Poll::Ready(Err(error));
+        }
+
+        let len = src.len().min(MAX_BUF);
+        let mut buf = this.take_buf();
+        let unread = buf.discard();
+        buf.bytes.extend_from_slice(&src[..len]);
+        this.start(buf, move |mut file, mut buf| {
+            let result = seek_back(file, unread).and_then(|()| file.write_all(&buf.bytes));
+            buf.discard();
+            (Operation::Write(result), buf)
+        });
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.poll_idle(cx).is_pending() {
+            return Poll::Pending;
+        }
+        Poll::Ready(this.write_error.take().map_or(Ok(()), Err))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for File {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        loop {
+            match this.poll_idle(cx) {
+                // The seek this started before has completed.
+                Poll::Ready(Some(Operation::Seek(result))) => return Poll::Ready(result),
+                Poll::Ready(_) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+            if let Some(error) = this.write_error.take() {
+                return Poll::Ready(Err(error));
+            }
+
+            let buf = this.take_buf();
+            this.start(buf, move |mut file, mut buf| {
+                // The position of the file is past the bytes that were read ahead.
+                let pos = match (pos, buf.discard()) {
+                    (SeekFrom::Current(offset), unread) => {
+                        SeekFrom::Current(offset - unread as i64)
+                    }
+                    (pos, _) => pos,
+                };
+                (Operation::Seek(file.seek(pos)), buf)
+            });
+        }
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("File").field(&self.std).finish()
+    }
+}
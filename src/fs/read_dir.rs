@@ -0,0 +1,178 @@
+//! [`read_dir`], which lists the entries of a directory as a [`Stream`].
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+use super::asyncify;
+use crate::runtime::Handle;
+use crate::task::JoinHandle;
+
+/// The number of entries that are read at once on the blocking pool.
+const CHUNK_SIZE: usize = 32;
+
+/// List the entries of the directory at `path`, like [`std::fs::read_dir`], as a [`Stream`].
+///
+/// The entries are read on the blocking pool a few at a time, as the stream is polled. They come
+/// in the order of the file system, without `.` and `..`.
+///
+/// # Errors
+///
+/// Returns an error if `path` isn't a directory, or can't be opened. The stream returns an error
+/// for an entry that can't be read.
+///
+/// # Examples
+///
+/// ```
+/// use futures::TryStreamExt;
+/// use mini_executor::fs;
+/// use mini_executor::runtime::Runtime;
+///
+/// let dir = std::env::temp_dir().join("mini-executor-read-dir-example");
+///
+/// let executor = Runtime::new();
+/// let mut names = executor.block_on(async {
+///     fs::create_dir_all(dir.join("nested")).await.unwrap();
+///     fs::write(dir.join("file"), b"hello").await.unwrap();
+///
+///     let entries: Vec<_> = fs::read_dir(&dir).await.unwrap().try_collect().await.unwrap();
+///     entries
+///         .iter()
+///         .map(|entry| entry.file_name().into_string().unwrap())
+///         .collect::<Vec<_>>()
+/// });
+/// names.sort();
+/// assert_eq!(names, ["file", "nested"]);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub async fn read_dir(path: impl AsRef<Path>) -> io::Result<ReadDir> {
+    let path = path.as_ref().to_owned();
+    let dir = asyncify(move || fs::read_dir(path)).await?;
+    Ok(ReadDir {
+        entries: VecDeque::new(),
+        state: State::Idle(Some(Box::new(dir))),
+    })
+}
+
+/// The entries of a directory, returned by [`read_dir`].
+#[must_use = "streams do nothing unless polled"]
+pub struct ReadDir {
+    /// The entries that were read, but haven't been returned yet.
+    entries: VecDeque<io::Result<DirEntry>>,
+    state: State,
+}
+
+/// What a [`ReadDir`] is doing.
+enum State {
+    /// Waiting to be polled, with the directory unless all of its entries have been read.
+    ///
+    /// Boxed, since the directory of `std` is large on some platforms, like Windows.
+    Idle(Option<Box<fs::ReadDir>>),
+    /// Reading the next entries on the blocking pool.
+    Busy(JoinHandle<Chunk>),
+}
+
+/// The entries read on the blocking pool, and the directory unless they were its last.
+type Chunk = (VecDeque<io::Result<DirEntry>>, Option<Box<fs::ReadDir>>);
+
+impl ReadDir {
+    /// The next entry of the directory, or `None` once all of them have been returned.
+    pub async fn next_entry(&mut self) -> io::Result<Option<DirEntry>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .transpose()
+    }
+}
+
+impl Stream for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<DirEntry>>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(entry) = this.entries.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+            match &mut this.state {
+                State::Idle(None) => return Poll::Ready(None),
+                State::Idle(dir) => {
+                    let mut dir = dir.take();
+                    let read = move || {
+                        let entries = dir.as_mut().map_or_else(VecDeque::new, |entries| {
+                            entries
+                                .by_ref()
+                                .take(CHUNK_SIZE)
+                                .map(|entry| entry.map(|entry| DirEntry(Arc::new(entry))))
+                                .collect()
+                        });
+                        // Fewer entries than asked for means the directory has no more.
+                        let dir = dir.filter(|_| entries.len() == CHUNK_SIZE);
+                        (entries, dir)
+                    };
+                    this.state = State::Busy(Handle::current().spawn_blocking(read));
+                }
+                State::Busy(handle) => match Pin::new(handle).poll(cx) {
+                    Poll::Ready(Ok((entries, dir))) => {
+                        this.entries = entries;
+                        this.state = State::Idle(dir);
+                    }
+                    Poll::Ready(Err(error)) => {
+                        this.state = State::Idle(None);
+                        return Poll::Ready(Some(Err(io::Error::other(error.to_string()))));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ReadDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadDir").finish_non_exhaustive()
+    }
+}
+
+/// An entry of a directory, returned by [`ReadDir`].
+pub struct DirEntry(Arc<fs::DirEntry>);
+
+impl DirEntry {
+    /// The path of the entry, the one of the directory joined with its name.
+    pub fn path(&self) -> PathBuf {
+        self.0.path()
+    }
+
+    /// The name of the entry, without the path of the directory.
+    pub fn file_name(&self) -> OsString {
+        self.0.file_name()
+    }
+
+    /// The metadata of the entry, which isn't the one of the file a symbolic link points to, see
+    /// [`std::fs::DirEntry::metadata`].
+    pub async fn metadata(&self) -> io::Result<fs::Metadata> {
+        let entry = self.0.clone();
+        asyncify(move || entry.metadata()).await
+    }
+
+    /// The type of the entry, see [`std::fs::DirEntry::file_type`], which most platforms know
+    /// from reading the directory already.
+    pub async fn file_type(&self) -> io::Result<fs::FileType> {
+        let entry = self.0.clone();
+        asyncify(move || entry.file_type()).await
+    }
+}
+
+impl fmt::Debug for DirEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
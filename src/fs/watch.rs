@@ -0,0 +1,210 @@
+//! [`watch`], which reports the changes to files and directories as a [`Stream`], with `inotify` on
+//! Linux, and with `kqueue` on macOS and the BSDs.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+use crate::io::{Interest, Registration};
+
+#[cfg(target_os = "linux")]
+mod inotify;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod kqueue;
+
+#[cfg(target_os = "linux")]
+use inotify as sys;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+use kqueue as sys;
+
+/// Watch the file or directory at `path` for changes, and return them as a [`Stream`] of
+/// [`Event`]s.
+///
+/// A watched directory reports the changes to its entries, like a file that is created or
+/// written to in it, but not the ones further down in its subdirectories, which can be watched
+/// with [`Watcher::add`] too. The events come from `inotify` on Linux, and from a `kqueue` on
+/// macOS and the BSDs, whose file descriptor is registered with the I/O driver, so a task that
+/// waits for the next change doesn't block its thread.
+///
+/// A `kqueue` only tells that a directory changed, not which entry, so the watcher lists the
+/// entries of a directory again to find out, and holds every watched file and entry open.
+/// Renaming an entry is reported as its removal and the creation of the new name, like on Linux.
+/// Windows and the other Unix platforms have no watcher.
+///
+/// # Errors
+///
+/// Returns an error if nothing exists at `path`, or the system has run out of watches, or of file
+/// descriptors on macOS and the BSDs.
+///
+/// # Panics
+///
+/// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+/// whose I/O driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use futures::StreamExt;
+/// use mini_executor::fs::{self, EventKind};
+/// use mini_executor::runtime::Runtime;
+///
+/// let dir = std::env::temp_dir().join("mini-executor-watch-example");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// std::fs::create_dir_all(&dir).unwrap();
+///
+/// let executor = Runtime::new();
+/// let event = executor.block_on(async {
+///     let mut watcher = fs::watch(&dir).unwrap();
+///     fs::write(dir.join("config.toml"), b"reload = true").await.unwrap();
+///     watcher.next().await.unwrap().unwrap()
+/// });
+/// assert_eq!(event.kind(), EventKind::Created);
+/// assert_eq!(event.path(), dir.join("config.toml"));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[track_caller]
+pub fn watch(path: impl AsRef<Path>) -> io::Result<Watcher> {
+    let watches = sys::Watches::new()?;
+    let mut watcher = Watcher {
+        registration: Registration::new(watches.as_raw_fd(), Interest::READABLE)?,
+        watches,
+        events: VecDeque::new(),
+    };
+    watcher.add(path)?;
+    Ok(watcher)
+}
+
+/// The changes to the watched files and directories, returned by [`watch`].
+#[must_use = "streams do nothing unless polled"]
+pub struct Watcher {
+    // Dropped before the file descriptor, so it is still open when it is deregistered.
+    registration: Registration,
+    watches: sys::Watches,
+    /// The events that were read, but haven't been returned yet.
+    events: VecDeque<io::Result<Event>>,
+}
+
+impl Watcher {
+    /// Watch the file or directory at `path` too.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing exists at `path`, or the system has run out of watches.
+    pub fn add(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.watches.add(path.as_ref())
+    }
+
+    /// Stop watching the file or directory at `path`, which was watched with the same path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`NotFound`](io::ErrorKind::NotFound) if `path` isn't
+    /// watched.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        if !self.watches.remove(path.as_ref())? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the path isn't watched",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Wait for the next change, see [`watch`].
+    pub async fn next_event(&mut self) -> io::Result<Event> {
+        let event = poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await;
+        event.expect("a watcher never ends")
+    }
+}
+
+impl Stream for Watcher {
+    type Item = io::Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<Event>>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.events.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            let read = this.registration.poll_io(cx, Interest::READABLE, || {
+                this.watches.read(&mut this.events)
+            });
+            match read {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(error)) => return Poll::Ready(Some(Err(error))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl AsRawFd for Watcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.watches.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("paths", &self.watches.paths())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A change to a watched file or directory, or to an entry of a watched directory, returned by a
+/// [`Watcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    kind: EventKind,
+    path: PathBuf,
+}
+
+impl Event {
+    /// What changed.
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// The path of what changed, the watched path joined with the name of the entry if it was an
+    /// entry of a watched directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// What changed in an [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EventKind {
+    /// A file or directory was created, or moved here.
+    Created,
+    /// A file was written to.
+    Modified,
+    /// The metadata of a file or directory changed, like its permissions or its times.
+    Metadata,
+    /// A file or directory was removed, or moved away. A watch on the removed path itself ends
+    /// with it.
+    Removed,
+}
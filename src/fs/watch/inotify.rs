@@ -0,0 +1,168 @@
+//! The watches of a [`Watcher`](super::Watcher) on Linux, with `inotify`.
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use super::{Event, EventKind};
+
+/// The changes a watch is added for.
+const MASK: u32 = libc::IN_CREATE
+    | libc::IN_MODIFY
+    | libc::IN_ATTRIB
+    | libc::IN_DELETE
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_MOVE_SELF;
+
+/// The size of the buffer the events are read into, which has room for many of them, and for
+/// at least one with the longest name.
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// An `inotify` instance, and the paths of its watches.
+pub(super) struct Watches {
+    fd: OwnedFd,
+    /// The paths of the watches, by their descriptors.
+    watches: HashMap<libc::c_int, PathBuf>,
+    buf: Box<[u8]>,
+}
+
+impl Watches {
+    pub(super) fn new() -> io::Result<Watches> {
+        // Safety: the call has no arguments that point anywhere.
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Watches {
+            // Safety: the descriptor was just created, and is owned from here on.
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            watches: HashMap::new(),
+            buf: vec![0; BUFFER_SIZE].into_boxed_slice(),
+        })
+    }
+
+    pub(super) fn add(&mut self, path: &Path) -> io::Result<()> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "paths must not contain interior null bytes",
+            )
+        })?;
+        // Safety: the path is terminated with a null byte.
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), c_path.as_ptr(), MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.watches.insert(wd, path.to_owned());
+        Ok(())
+    }
+
+    /// Stop watching `path`, and return whether it was watched.
+    pub(super) fn remove(&mut self, path: &Path) -> io::Result<bool> {
+        let Some(wd) = self
+            .watches
+            .iter()
+            .find_map(|(&wd, watched)| (watched == path).then_some(wd))
+        else {
+            return Ok(false);
+        };
+        self.watches.remove(&wd);
+        // Safety: the call has no arguments that point anywhere.
+        if unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(true)
+    }
+
+    pub(super) fn paths(&self) -> Vec<&Path> {
+        self.watches.values().map(PathBuf::as_path).collect()
+    }
+
+    /// Read the next events into `events`, or fail with `WouldBlock` if there are none yet.
+    pub(super) fn read(&mut self, events: &mut VecDeque<io::Result<Event>>) -> io::Result<()> {
+        // Safety: the kernel writes at most `buf.len()` bytes into the buffer.
+        let read = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                self.buf.as_mut_ptr().cast(),
+                self.buf.len(),
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.parse(read as usize, events);
+        Ok(())
+    }
+
+    /// Parse the first `len` bytes of the buffer into `events`.
+    fn parse(&mut self, len: usize, events: &mut VecDeque<io::Result<Event>>) {
+        let header = mem::size_of::<libc::inotify_event>();
+        let mut pos = 0;
+        while pos + header <= len {
+            // Safety: the kernel wrote a whole event here, whose header may not be aligned.
+            let event: libc::inotify_event = unsafe {
+                self.buf[pos..]
+                    .as_ptr()
+                    .cast::<libc::inotify_event>()
+                    .read_unaligned()
+            };
+            let name = &self.buf[pos + header..pos + header + event.len as usize];
+            pos += header + event.len as usize;
+
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                events.push_back(Err(io::Error::other(
+                    "the queue of changes overflowed, some of them were lost",
+                )));
+                continue;
+            }
+            if event.mask & libc::IN_IGNORED != 0 {
+                // The watch was removed, or what it watched is gone.
+                self.watches.remove(&event.wd);
+                continue;
+            }
+            let Some(watched) = self.watches.get(&event.wd) else {
+                continue;
+            };
+            // The name is padded with null bytes.
+            let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+            let path = match name {
+                [] => watched.clone(),
+                name => watched.join(OsStr::from_bytes(name)),
+            };
+            if let Some(kind) = kind(event.mask) {
+                events.push_back(Ok(Event { kind, path }));
+            }
+        }
+    }
+}
+
+impl AsRawFd for Watches {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// The kind of the change of an `inotify` event with `mask`.
+fn kind(mask: u32) -> Option<EventKind> {
+    if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+        Some(EventKind::Created)
+    } else if mask & libc::IN_MODIFY != 0 {
+        Some(EventKind::Modified)
+    } else if mask & libc::IN_ATTRIB != 0 {
+        Some(EventKind::Metadata)
+    } else if mask
+        & (libc::IN_DELETE | libc::IN_DELETE_SELF | libc::IN_MOVED_FROM | libc::IN_MOVE_SELF)
+        != 0
+    {
+        Some(EventKind::Removed)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,318 @@
+//! The watches of a [`Watcher`](super::Watcher) on macOS and the BSDs, with the `EVFILT_VNODE`
+//! filter of a `kqueue`.
+//!
+//! A vnode event doesn't name the entry of a directory that changed, so a watched directory keeps
+//! the names of its entries, and lists them again whenever it is written to, to tell which ones
+//! were created or removed. Its entries are watched too, for the changes to them that `inotify`
+//! reports on the directory. Every watch holds the file it watches open.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{CString, OsString};
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use super::{Event, EventKind};
+
+/// The changes a watch is added for.
+const NOTES: u32 = libc::NOTE_WRITE
+    | libc::NOTE_EXTEND
+    | libc::NOTE_ATTRIB
+    | libc::NOTE_DELETE
+    | libc::NOTE_RENAME
+    | libc::NOTE_REVOKE;
+
+/// The changes after which a watched file is gone from its path.
+const GONE: u32 = libc::NOTE_DELETE | libc::NOTE_RENAME | libc::NOTE_REVOKE;
+
+/// The most events that are read at once.
+const EVENTS_SIZE: usize = 64;
+
+/// How a watched path is opened, only for its events where the platform can.
+#[cfg(target_vendor = "apple")]
+const OPEN_FLAGS: libc::c_int = libc::O_EVTONLY | libc::O_CLOEXEC;
+#[cfg(not(target_vendor = "apple"))]
+const OPEN_FLAGS: libc::c_int = libc::O_RDONLY | libc::O_CLOEXEC;
+
+/// A `kqueue`, and the files it watches, by the tokens of their events.
+pub(super) struct Watches {
+    kqueue: OwnedFd,
+    watches: HashMap<usize, Watch>,
+    next_token: usize,
+}
+
+/// A watched file or directory.
+struct Watch {
+    /// Only kept open, closing it removes its event from the queue.
+    _file: File,
+    path: PathBuf,
+    is_dir: bool,
+    /// The token of the watched directory this is an entry of, if it is only watched for that.
+    parent: Option<usize>,
+    /// The entries of a watched directory, and the tokens of their watches, if they could be
+    /// watched.
+    entries: HashMap<OsString, Option<usize>>,
+}
+
+impl Watches {
+    pub(super) fn new() -> io::Result<Watches> {
+        // Safety: the returned descriptor is checked, and owned from then on.
+        let kqueue = unsafe { libc::kqueue() };
+        if kqueue < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let kqueue = unsafe { OwnedFd::from_raw_fd(kqueue) };
+        if unsafe { libc::fcntl(kqueue.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Watches {
+            kqueue,
+            watches: HashMap::new(),
+            next_token: 0,
+        })
+    }
+
+    pub(super) fn add(&mut self, path: &Path) -> io::Result<()> {
+        if self.find(path).is_none() {
+            self.watch(path, None)?;
+        }
+        Ok(())
+    }
+
+    /// Stop watching `path`, and return whether it was watched.
+    pub(super) fn remove(&mut self, path: &Path) -> io::Result<bool> {
+        let Some(token) = self.find(path) else {
+            return Ok(false);
+        };
+        self.unwatch(token);
+        Ok(true)
+    }
+
+    pub(super) fn paths(&self) -> Vec<&Path> {
+        self.watches
+            .values()
+            .filter(|watch| watch.parent.is_none())
+            .map(|watch| watch.path.as_path())
+            .collect()
+    }
+
+    /// Read the next events into `events`, or fail with `WouldBlock` if there are none yet.
+    pub(super) fn read(&mut self, events: &mut VecDeque<io::Result<Event>>) -> io::Result<()> {
+        let mut buf: Vec<libc::kevent> = Vec::with_capacity(EVENTS_SIZE);
+        let timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // Safety: the buffer has room for `EVENTS_SIZE` events, and `kevent` initializes the ones
+        // it returns.
+        let count = unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                ptr::null(),
+                0,
+                buf.as_mut_ptr(),
+                EVENTS_SIZE as _,
+                &timeout,
+            )
+        };
+        if count < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if count == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        unsafe { buf.set_len(count as usize) };
+        for event in buf {
+            self.handle(event.udata as usize, event.fflags, events);
+        }
+        Ok(())
+    }
+
+    /// The token of the watch that was added for `path`.
+    fn find(&self, path: &Path) -> Option<usize> {
+        self.watches.iter().find_map(|(&token, watch)| {
+            (watch.parent.is_none() && watch.path == path).then_some(token)
+        })
+    }
+
+    /// Watch `path`, and the entries of it if it is a directory that was added itself, and return
+    /// the token of its watch.
+    fn watch(&mut self, path: &Path, parent: Option<usize>) -> io::Result<usize> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "paths must not contain interior null bytes",
+            )
+        })?;
+        // Safety: the path is terminated with a null byte, and the returned descriptor is
+        // checked, and owned from then on.
+        let fd = unsafe { libc::open(c_path.as_ptr(), OPEN_FLAGS) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        let is_dir = file.metadata()?.is_dir();
+
+        let token = self.next_token;
+        self.next_token += 1;
+        // Safety: `kevent` is plain data, and some platforms have fields beyond the ones set here.
+        let mut change: libc::kevent = unsafe { std::mem::zeroed() };
+        change.ident = file.as_raw_fd() as _;
+        change.filter = libc::EVFILT_VNODE as _;
+        change.flags = (libc::EV_ADD | libc::EV_CLEAR) as _;
+        change.fflags = NOTES as _;
+        change.udata = token as _;
+        let added = unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                &change,
+                1,
+                ptr::null_mut(),
+                0,
+                ptr::null(),
+            )
+        };
+        if added < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let watch = Watch {
+            _file: file,
+            path: path.to_owned(),
+            is_dir,
+            parent,
+            entries: HashMap::new(),
+        };
+        self.watches.insert(token, watch);
+
+        if is_dir && parent.is_none() {
+            let names = match list(path) {
+                Ok(names) => names,
+                Err(error) => {
+                    self.unwatch(token);
+                    return Err(error);
+                }
+            };
+            let entries = names
+                .into_iter()
+                .map(|name| {
+                    let child = self.watch(&path.join(&name), Some(token)).ok();
+                    (name, child)
+                })
+                .collect();
+            if let Some(watch) = self.watches.get_mut(&token) {
+                watch.entries = entries;
+            }
+        }
+        Ok(token)
+    }
+
+    /// Stop watching what `token` is the watch of, and its entries.
+    fn unwatch(&mut self, token: usize) {
+        if let Some(watch) = self.watches.remove(&token) {
+            for child in watch.entries.into_values().flatten() {
+                self.watches.remove(&child);
+            }
+        }
+    }
+
+    /// Turn the changes `notes` of the watch of `token` into `events`.
+    fn handle(&mut self, token: usize, notes: u32, events: &mut VecDeque<io::Result<Event>>) {
+        // The watch may have been removed since the kernel queued the event.
+        let Some(watch) = self.watches.get(&token) else {
+            return;
+        };
+        let path = watch.path.clone();
+        let event = |kind| {
+            Ok(Event {
+                kind,
+                path: path.clone(),
+            })
+        };
+        let (is_dir, parent) = (watch.is_dir, watch.parent);
+
+        if notes & (libc::NOTE_WRITE | libc::NOTE_EXTEND) != 0 {
+            if !is_dir {
+                events.push_back(event(EventKind::Modified));
+            } else if parent.is_none() {
+                self.rescan(token, events);
+            }
+        }
+        if notes & libc::NOTE_ATTRIB != 0 {
+            events.push_back(event(EventKind::Metadata));
+        }
+        if notes & GONE != 0 {
+            events.push_back(event(EventKind::Removed));
+            let Some(parent) = parent else {
+                self.unwatch(token);
+                return;
+            };
+            // Whatever has the name of the entry from now on is a new one, like a file that was
+            // renamed over it, which the directory reports once it lists its entries again.
+            self.watches.remove(&token);
+            if let Some(dir) = self.watches.get_mut(&parent) {
+                dir.entries.retain(|_, child| *child != Some(token));
+            }
+            self.rescan(parent, events);
+        }
+    }
+
+    /// List the entries of the watched directory of `token` again, and report the ones that were
+    /// created or removed since.
+    fn rescan(&mut self, token: usize, events: &mut VecDeque<io::Result<Event>>) {
+        let Some(watch) = self.watches.get_mut(&token) else {
+            return;
+        };
+        let path = watch.path.clone();
+        let Ok(names) = list(&path) else {
+            // The directory is gone, which its next event reports.
+            return;
+        };
+        let mut entries = std::mem::take(&mut watch.entries);
+
+        entries.retain(|name, child| {
+            if names.contains(name) {
+                return true;
+            }
+            if let Some(child) = child {
+                self.watches.remove(child);
+            }
+            events.push_back(Ok(Event {
+                kind: EventKind::Removed,
+                path: path.join(name),
+            }));
+            false
+        });
+        for name in names {
+            if entries.contains_key(&name) {
+                continue;
+            }
+            let child = self.watch(&path.join(&name), Some(token)).ok();
+            events.push_back(Ok(Event {
+                kind: EventKind::Created,
+                path: path.join(&name),
+            }));
+            entries.insert(name, child);
+        }
+
+        if let Some(watch) = self.watches.get_mut(&token) {
+            watch.entries = entries;
+        }
+    }
+}
+
+/// The names of the entries of the directory at `path`.
+fn list(path: &Path) -> io::Result<HashSet<OsString>> {
+    fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect()
+}
+
+impl AsRawFd for Watches {
+    fn as_raw_fd(&self) -> RawFd {
+        self.kqueue.as_raw_fd()
+    }
+}
@@ -0,0 +1,874 @@
+//! Waiting for I/O resources, like sockets, to become ready inside of async code.
+//!
+//! The I/O driver of a [`Runtime`] watches the file descriptors registered with it, and wakes the
+//! tasks that wait for them once they are ready to be read from or written to. It is built on
+//! `epoll` on Linux, and on `kqueue` on macOS and the BSDs, or on [`mio`] on every Unix platform
+//! with the `mio` feature. On Windows, it emulates the same behavior for sockets with `WSAPoll`.
+//! The driver is enabled with [`Builder::enable_io`], and by the constructors of `Runtime`, like
+//! [`Runtime::new`], on the platforms that have one.
+//!
+//! A thread of the runtime that has run out of work waits for the next I/O event, rather than
+//! only for the next task to be woken, so an idle runtime doesn't use any CPU.
+//!
+//! An [`AsyncFd`] wraps a file descriptor of any kind, like a serial port, a `timerfd` or a netlink
+//! socket, and registers it with the driver, to wait until it is ready. A [`Registration`] does
+//! the same for a bare file descriptor, to write I/O types by hand. On Unix, [`pipe`] creates an
+//! anonymous pipe whose two ends are registered with the driver, to talk to a child process or to
+//! code that blocks.
+//! With the `io_uring` feature on Linux, the `uring` module hands whole reads and writes to the
+//! kernel instead, and the same driver wakes the tasks once they have completed.
+//!
+//! The byte streams of the crate, like [`TcpStream`](crate::net::TcpStream), implement the
+//! [`AsyncRead`] and [`AsyncWrite`] traits of the `futures` crate, which are re-exported here with
+//! their extension traits [`AsyncReadExt`] and [`AsyncWriteExt`]. Those add methods like
+//! [`read_exact`](AsyncReadExt::read_exact), [`read_to_end`](AsyncReadExt::read_to_end) and
+//! [`write_all`](AsyncWriteExt::write_all), and let the streams be used with the code written
+//! against the traits, like the codecs and adapters of other crates. The streams read into and
+//! write from several buffers at once, with `readv` and `writev`, through the
+//! [`poll_read_vectored`](AsyncRead::poll_read_vectored) and
+//! [`poll_write_vectored`](AsyncWrite::poll_write_vectored) methods of the traits. Like those of
+//! `std`, the traits read into initialized buffers; the streams also read into a [`ReadBuf`],
+//! whose memory doesn't have to be initialized first.
+//! [`BufReader`] and [`BufWriter`] buffer them, and [`AsyncBufReadExt::lines`] splits what is
+//! read into lines. [`copy`] moves the bytes of a reader into a writer, and
+//! [`copy_bidirectional`] moves them both ways between two streams, like a proxy does. On Linux,
+//! `splice` and `sendfile` move the bytes of sockets and files without copying them through
+//! userspace, and `copy_fd` is the `copy` of the streams of the crate that does so.
+//!
+//! [`stdin`], [`stdout`] and [`stderr`] are the standard streams of the process, which are read
+//! from and written to on the blocking pool, since a terminal can't be waited for like a socket
+//! without changing it for the other processes that share it. The other way around, a
+//! [`SyncIoBridge`] lets blocking code on the blocking pool read from and write to an async
+//! stream, to feed it to a library that only takes [`std::io::Read`] or [`std::io::Write`].
+//!
+//! [`Runtime`]: crate::runtime::Runtime
+//! [`Runtime::new`]: crate::runtime::Runtime::new
+//! [`Builder::enable_io`]: crate::runtime::Builder::enable_io
+//! [`mio`]: https://docs.rs/mio
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::ops::BitOr;
+#[cfg(unix)]
+use std::os::fd::RawFd;
+#[cfg(windows)]
+use std::os::windows::io::RawSocket;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use std::sync::OnceLock;
+use std::sync::{Arc, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::runtime::Handle;
+use crate::sync::Mutex;
+
+#[cfg(unix)]
+mod async_fd;
+mod copy;
+#[cfg(unix)]
+mod pipe;
+mod read_buf;
+mod stdio;
+mod sync_bridge;
+#[cfg(target_os = "linux")]
+mod zero_copy;
+
+#[cfg(unix)]
+pub use async_fd::{AsyncFd, AsyncFdReadyGuard, TryIoError};
+pub use copy::{copy, copy_bidirectional};
+pub use futures::io::{AsyncBufRead, AsyncBufReadExt, BufWriter, Lines};
+pub use futures::io::{
+    AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+};
+#[cfg(unix)]
+pub(crate) use pipe::pipe_fds;
+#[cfg(unix)]
+pub use pipe::{pipe, PipeReader, PipeWriter};
+#[cfg(unix)]
+pub(crate) use read_buf::read_fd;
+pub use read_buf::ReadBuf;
+pub use stdio::{stderr, stdin, stdout, Stderr, Stdin, Stdout};
+pub use sync_bridge::SyncIoBridge;
+#[cfg(target_os = "linux")]
+pub use zero_copy::{copy_fd, sendfile, splice, AsRegistration};
+
+/// A buffered reader for the byte streams, re-exported from the `futures` crate.
+///
+/// It reads ahead into a buffer, and implements [`AsyncBufRead`], whose extension trait
+/// [`AsyncBufReadExt`] reads up to a delimiter, like [`read_line`](AsyncBufReadExt::read_line)
+/// and [`lines`](AsyncBufReadExt::lines) do for line-oriented protocols. A [`BufWriter`] collects
+/// small writes until its buffer is full or it is flushed.
+///
+/// # Examples
+///
+/// A server that answers every line with its length:
+///
+/// ```
+/// use futures::StreamExt;
+/// use mini_executor::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let answers = executor.block_on(async {
+///     let (client, server) = UnixStream::pair().unwrap();
+///     mini_executor::spawn(async move {
+///         let mut lines = BufReader::new(&server).lines();
+///         let mut writer = BufWriter::new(&server);
+///         while let Some(Ok(line)) = lines.next().await {
+///             writer.write_all(format!("{}\n", line.len()).as_bytes()).await.unwrap();
+///             writer.flush().await.unwrap();
+///         }
+///     });
+///
+///     client.write_all(b"hello\nworld!\n").await.unwrap();
+///     let mut reader = BufReader::new(&client);
+///     let mut answers = String::new();
+///     reader.read_line(&mut answers).await.unwrap();
+///     reader.read_line(&mut answers).await.unwrap();
+///     answers
+/// });
+/// assert_eq!(answers, "5\n6\n");
+/// ```
+pub use futures::io::BufReader;
+
+// The platforms of every backend are listed again for the fallback, which has none. With the
+// `mio` feature, `mio` is used on every Unix platform in place of `epoll` and `kqueue`.
+#[cfg(all(target_os = "linux", not(feature = "mio")))]
+mod epoll;
+#[cfg(all(
+    any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ),
+    not(feature = "mio")
+))]
+mod kqueue;
+#[cfg(all(feature = "mio", unix))]
+mod mio;
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    all(feature = "mio", unix),
+    windows
+)))]
+mod unsupported;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+pub mod uring;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(all(target_os = "linux", not(feature = "mio")))]
+use epoll as sys;
+#[cfg(all(
+    any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ),
+    not(feature = "mio")
+))]
+use kqueue as sys;
+#[cfg(all(feature = "mio", unix))]
+use mio as sys;
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    all(feature = "mio", unix),
+    windows
+)))]
+use unsupported as sys;
+#[cfg(windows)]
+use windows as sys;
+
+/// The readiness a task is interested in, when it registers a source with the I/O driver or
+/// waits for it.
+///
+/// Interests are combined with `|`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Interest(u8);
+
+impl Interest {
+    /// Interest in the source becoming readable.
+    pub const READABLE: Interest = Interest(0b01);
+    /// Interest in the source becoming writable.
+    pub const WRITABLE: Interest = Interest(0b10);
+
+    /// Whether this includes [`Interest::READABLE`].
+    pub const fn is_readable(self) -> bool {
+        self.0 & Self::READABLE.0 != 0
+    }
+
+    /// Whether this includes [`Interest::WRITABLE`].
+    pub const fn is_writable(self) -> bool {
+        self.0 & Self::WRITABLE.0 != 0
+    }
+
+    /// The readiness that a task with this interest is woken for.
+    fn mask(self) -> Ready {
+        let mut mask = Ready::ERROR;
+        if self.is_readable() {
+            mask = mask | Ready::READABLE | Ready::READ_CLOSED;
+        }
+        if self.is_writable() {
+            mask = mask | Ready::WRITABLE | Ready::WRITE_CLOSED;
+        }
+        mask
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+}
+
+impl fmt::Debug for Interest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.is_readable(), self.is_writable()) {
+            (true, true) => f.write_str("READABLE | WRITABLE"),
+            (true, false) => f.write_str("READABLE"),
+            (false, true) => f.write_str("WRITABLE"),
+            (false, false) => f.write_str("(empty)"),
+        }
+    }
+}
+
+/// The readiness of a source that the I/O driver reported, combined with `|`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Ready(u8);
+
+impl Ready {
+    /// No readiness.
+    pub const EMPTY: Ready = Ready(0);
+    /// The source can be read from.
+    pub const READABLE: Ready = Ready(0b0001);
+    /// The source can be written to.
+    pub const WRITABLE: Ready = Ready(0b0010);
+    /// The read half of the source was closed by the other side, so reading it reaches the end.
+    pub const READ_CLOSED: Ready = Ready(0b0100);
+    /// The write half of the source was closed, so writing it fails.
+    pub const WRITE_CLOSED: Ready = Ready(0b1000);
+    /// An error is pending on the source.
+    pub const ERROR: Ready = Ready(0b1_0000);
+
+    /// Whether there is no readiness at all.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether this includes [`Ready::READABLE`].
+    pub const fn is_readable(self) -> bool {
+        self.0 & Self::READABLE.0 != 0
+    }
+
+    /// Whether this includes [`Ready::WRITABLE`].
+    pub const fn is_writable(self) -> bool {
+        self.0 & Self::WRITABLE.0 != 0
+    }
+
+    /// Whether this includes [`Ready::READ_CLOSED`].
+    pub const fn is_read_closed(self) -> bool {
+        self.0 & Self::READ_CLOSED.0 != 0
+    }
+
+    /// Whether this includes [`Ready::WRITE_CLOSED`].
+    pub const fn is_write_closed(self) -> bool {
+        self.0 & Self::WRITE_CLOSED.0 != 0
+    }
+
+    /// Whether this includes [`Ready::ERROR`].
+    pub const fn is_error(self) -> bool {
+        self.0 & Self::ERROR.0 != 0
+    }
+
+    const fn intersection(self, other: Ready) -> Ready {
+        Ready(self.0 & other.0)
+    }
+}
+
+impl BitOr for Ready {
+    type Output = Ready;
+
+    fn bitor(self, other: Ready) -> Ready {
+        Ready(self.0 | other.0)
+    }
+}
+
+impl fmt::Debug for Ready {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Ready::READABLE, "READABLE"),
+            (Ready::WRITABLE, "WRITABLE"),
+            (Ready::READ_CLOSED, "READ_CLOSED"),
+            (Ready::WRITE_CLOSED, "WRITE_CLOSED"),
+            (Ready::ERROR, "ERROR"),
+        ];
+        let mut names = names
+            .into_iter()
+            .filter(|(ready, _)| !self.intersection(*ready).is_empty())
+            .map(|(_, name)| name);
+        match names.next() {
+            Some(first) => {
+                f.write_str(first)?;
+                names.try_for_each(|name| write!(f, " | {}", name))
+            }
+            None => f.write_str("EMPTY"),
+        }
+    }
+}
+
+/// A file descriptor registered with the I/O driver of a [`Runtime`], to wait until it is ready.
+///
+/// The driver reports readiness edge-triggered: once it has reported a source readable, the
+/// source stays readable for the registration until an operation on it fails with
+/// [`WouldBlock`](io::ErrorKind::WouldBlock). [`poll_io`](Registration::poll_io) and
+/// [`async_io`](Registration::async_io) take care of that, by retrying an operation whenever it
+/// would block once the source is ready again. The file descriptor must be in non-blocking mode.
+///
+/// The registration doesn't own the file descriptor, which has to stay open until the
+/// registration is dropped. Only the task that waited for a direction last is woken, so at most
+/// one task should read, and one write, at a time.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::{Interest, Registration};
+/// use mini_executor::runtime::Runtime;
+/// use std::io::{Read, Write};
+/// use std::os::fd::AsRawFd;
+/// use std::os::unix::net::UnixStream;
+///
+/// let executor = Runtime::new();
+/// let message = executor.block_on(async {
+///     let (mut sender, mut receiver) = UnixStream::pair().unwrap();
+///     receiver.set_nonblocking(true).unwrap();
+///     let registration = Registration::new(receiver.as_raw_fd(), Interest::READABLE).unwrap();
+///
+///     mini_executor::spawn(async move { sender.write_all(b"hello").unwrap() });
+///
+///     let mut buf = [0; 5];
+///     let read = registration
+///         .async_io(Interest::READABLE, || receiver.read(&mut buf))
+///         .await
+///         .unwrap();
+///     buf[..read].to_vec()
+/// });
+/// assert_eq!(message, b"hello");
+/// ```
+///
+/// [`Runtime`]: crate::runtime::Runtime
+pub struct Registration {
+    driver: Arc<Driver>,
+    token: usize,
+    source: sys::Source,
+    io: Arc<ScheduledIo>,
+}
+
+impl Registration {
+    /// Register `fd` with the I/O driver of the current runtime, for the readiness in `interest`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the driver can't watch `fd`, for example because it is a regular file,
+    /// or if the runtime has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[cfg(unix)]
+    #[track_caller]
+    pub fn new(fd: RawFd, interest: Interest) -> io::Result<Registration> {
+        Registration::with_driver(Driver::current(), fd, interest)
+    }
+
+    /// Register `socket` with the I/O driver of the current runtime, for the readiness in
+    /// `interest`.
+    ///
+    /// This is how sources are registered on Windows, where only sockets can be.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the runtime has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[cfg(windows)]
+    #[track_caller]
+    pub fn new(socket: RawSocket, interest: Interest) -> io::Result<Registration> {
+        Registration::with_driver(Driver::current(), socket, interest)
+    }
+
+    fn with_driver(
+        driver: Arc<Driver>,
+        source: sys::Source,
+        interest: Interest,
+    ) -> io::Result<Registration> {
+        let (token, io) = driver.register(source, interest)?;
+        Ok(Registration {
+            driver,
+            token,
+            source,
+            io,
+        })
+    }
+
+    /// Poll whether the source is ready for any of `interest`, and register the waker of `cx` to
+    /// be woken once it is if not.
+    ///
+    /// This doesn't clear the readiness, so it keeps returning it until an operation on the source
+    /// would block in [`poll_io`](Registration::poll_io). Waiting for an interest the source
+    /// wasn't registered with never completes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error once the runtime has shut down.
+    pub fn poll_ready(&self, cx: &mut Context<'_>, interest: Interest) -> Poll<io::Result<Ready>> {
+        self.io.poll_ready(cx, interest).map_ok(|event| event.ready)
+    }
+
+    /// Wait until the source is ready for any of `interest`, see
+    /// [`poll_ready`](Registration::poll_ready).
+    pub async fn ready(&self, interest: Interest) -> io::Result<Ready> {
+        poll_fn(|cx| self.poll_ready(cx, interest)).await
+    }
+
+    /// Run the non-blocking operation `f` once the source is ready for any of `interest`, and
+    /// again whenever it fails with [`WouldBlock`](io::ErrorKind::WouldBlock) and the source is
+    /// ready again, until it completes or fails with another error.
+    pub fn poll_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        interest: Interest,
+        mut f: impl FnMut() -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            let event = match self.io.poll_ready(cx, interest) {
+                Poll::Ready(Ok(event)) => event,
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => return Poll::Pending,
+            };
+            match f() {
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    if let Err(error) = self.clear_readiness(event) {
+                        return Poll::Ready(Err(error));
+                    }
+                }
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    /// Run the non-blocking operation `f` once the source is ready for any of `interest`, see
+    /// [`poll_io`](Registration::poll_io).
+    pub async fn async_io<R>(
+        &self,
+        interest: Interest,
+        mut f: impl FnMut() -> io::Result<R>,
+    ) -> io::Result<R> {
+        poll_fn(|cx| self.poll_io(cx, interest, &mut f)).await
+    }
+
+    /// Clear the readiness of `event`, once an operation on the source would block, so that the
+    /// driver reports it again.
+    fn clear_readiness(&self, event: ReadyEvent) -> io::Result<()> {
+        self.io.clear_readiness(event);
+        self.driver.selector.rearm(self.token, event.ready)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.driver.deregister(self.token, self.source);
+    }
+}
+
+impl fmt::Debug for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registration")
+            .field("source", &self.source)
+            .field("ready", &self.io.ready())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The readiness of a registered source, and the tasks that wait for it.
+struct ScheduledIo {
+    /// The [`Ready`] bits in the lowest byte, the `SHUTDOWN` bit, and above them how many times
+    /// readiness was reported, so that clearing it doesn't lose a report that came in between.
+    readiness: AtomicUsize,
+    waiters: Mutex<Waiters>,
+}
+
+/// Set in the readiness of every source once the driver has shut down.
+const SHUTDOWN: usize = 1 << 8;
+/// The bit at which the number of reports starts.
+const TICK_SHIFT: u32 = 9;
+
+#[derive(Default)]
+struct Waiters {
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+/// The readiness of a source, as it was when [`ScheduledIo::poll_ready`] returned it.
+#[derive(Clone, Copy)]
+struct ReadyEvent {
+    tick: usize,
+    ready: Ready,
+}
+
+impl ScheduledIo {
+    fn new() -> Self {
+        Self {
+            readiness: AtomicUsize::new(0),
+            waiters: Mutex::new(Waiters::default()),
+        }
+    }
+
+    fn ready(&self) -> Ready {
+        Ready(self.readiness.load(Ordering::Acquire) as u8)
+    }
+
+    /// Record the readiness `ready`, or the shutdown of the driver, and wake the tasks that wait
+    /// for it.
+    fn set_readiness(&self, ready: Ready, shutdown: bool) {
+        let _ = self
+            .readiness
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                let tick = (current >> TICK_SHIFT).wrapping_add(1);
+                let mut bits = (current & (SHUTDOWN | 0xff)) | usize::from(ready.0);
+                if shutdown {
+                    bits |= SHUTDOWN;
+                }
+                Some(tick << TICK_SHIFT | bits)
+            });
+
+        let mut waiters = self.waiters.lock();
+        let reader = if shutdown || !ready.intersection(Interest::READABLE.mask()).is_empty() {
+            waiters.reader.take()
+        } else {
+            None
+        };
+        let writer = if shutdown || !ready.intersection(Interest::WRITABLE.mask()).is_empty() {
+            waiters.writer.take()
+        } else {
+            None
+        };
+        drop(waiters);
+        // Woken outside of the lock, since waking a task may poll this source again.
+        reader.into_iter().chain(writer).for_each(Waker::wake);
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>, interest: Interest) -> Poll<io::Result<ReadyEvent>> {
+        let mut waiters = self.waiters.lock();
+        // Read under the lock that `set_readiness` takes to wake the waiters, so that readiness
+        // that is set concurrently is either seen here or wakes the waker registered here.
+        let current = self.readiness.load(Ordering::Acquire);
+        if current & SHUTDOWN != 0 {
+            return Poll::Ready(Err(io::Error::other(
+                "the I/O driver of the runtime has shut down",
+            )));
+        }
+        let ready = Ready(current as u8).intersection(interest.mask());
+        if !ready.is_empty() {
+            return Poll::Ready(Ok(ReadyEvent {
+                tick: current >> TICK_SHIFT,
+                ready,
+            }));
+        }
+
+        if interest.is_readable() {
+            register(&mut waiters.reader, cx.waker());
+        }
+        if interest.is_writable() {
+            register(&mut waiters.writer, cx.waker());
+        }
+        Poll::Pending
+    }
+
+    /// Clear the readiness of `event`, after an operation would block, unless it was reported
+    /// again since.
+    ///
+    /// Only the readable and writable bits are cleared: a closed or failed source stays so.
+    fn clear_readiness(&self, event: ReadyEvent) {
+        let clear = usize::from(
+            event
+                .ready
+                .intersection(Ready::READABLE | Ready::WRITABLE)
+                .0,
+        );
+        let _ = self
+            .readiness
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (current >> TICK_SHIFT == event.tick).then_some(current & !clear)
+            });
+    }
+}
+
+/// Keep `waker` in `slot`, unless it already holds one that wakes the same task.
+fn register(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(registered) if registered.will_wake(waker) => {}
+        _ => *slot = Some(waker.clone()),
+    }
+}
+
+/// The token the `io_uring` instance is registered with, which no registration gets.
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+const URING_TOKEN: usize = usize::MAX - 1;
+
+/// The I/O driver of a [`Runtime`](crate::runtime::Runtime), which wakes the tasks waiting for
+/// registered sources once the operating system reports them ready.
+///
+/// Like the time driver, it has no thread of its own. One thread of the runtime at a time that has
+/// run out of work waits for events in the selector, see [`park`](Driver::park), and the others
+/// wake it with [`unpark`](Driver::unpark) when they queue a task. Busy threads check for events
+/// without waiting every now and then, see [`poll`](Driver::poll).
+pub(crate) struct Driver {
+    selector: sys::Selector,
+    /// The buffer for the events, locked by the thread that waits for them.
+    events: Mutex<sys::Events>,
+    registrations: Mutex<Registrations>,
+    /// Whether a thread is waiting in the selector, and has to be woken through it.
+    parked: AtomicBool,
+    /// The `io_uring` instance, set up by the first operation on it.
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    uring: OnceLock<Result<Arc<uring::Ring>, (io::ErrorKind, String)>>,
+}
+
+/// The sources registered with a [`Driver`].
+struct Registrations {
+    /// The sources by the token they are registered with.
+    sources: HashMap<usize, Arc<ScheduledIo>>,
+    /// The token of the next registration, never reused, so a stale event can't reach it.
+    next_token: usize,
+    /// Set once the runtime has shut down, to reject new registrations.
+    shutdown: bool,
+}
+
+impl Driver {
+    /// Create an I/O driver, or `None` on a platform that doesn't have one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the platform has an I/O driver, but it can't be created.
+    pub(crate) fn new() -> Option<Self> {
+        let selector = match sys::Selector::new() {
+            Ok(selector) => selector,
+            Err(error) if error.kind() == io::ErrorKind::Unsupported => return None,
+            Err(error) => panic!("failed to create the I/O driver: {}", error),
+        };
+        Some(Self {
+            selector,
+            events: Mutex::new(sys::Events::with_capacity(1024)),
+            registrations: Mutex::new(Registrations {
+                sources: HashMap::new(),
+                next_token: 0,
+                shutdown: false,
+            }),
+            parked: AtomicBool::new(false),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            uring: OnceLock::new(),
+        })
+    }
+
+    /// Get the driver of the runtime the current thread is in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread isn't in a runtime, or if its I/O driver isn't enabled.
+    #[track_caller]
+    fn current() -> Arc<Self> {
+        let Ok(handle) = Handle::try_current() else {
+            panic!("there is no I/O driver, `io` must be used from within a `Runtime`");
+        };
+        match handle.io_driver() {
+            Some(driver) => driver,
+            None => panic!(
+                "the I/O driver of the current `Runtime` is disabled, enable it with \
+                 `Builder::enable_io`"
+            ),
+        }
+    }
+
+    fn register(
+        &self,
+        source: sys::Source,
+        interest: Interest,
+    ) -> io::Result<(usize, Arc<ScheduledIo>)> {
+        let io = Arc::new(ScheduledIo::new());
+        let mut registrations = self.registrations.lock();
+        if registrations.shutdown {
+            return Err(io::Error::other(
+                "the I/O driver of the runtime has shut down",
+            ));
+        }
+        let token = registrations.next_token;
+        self.selector.register(source, token, interest)?;
+        registrations.next_token += 1;
+        registrations.sources.insert(token, io.clone());
+        Ok((token, io))
+    }
+
+    fn deregister(&self, token: usize, source: sys::Source) {
+        let io = self.registrations.lock().sources.remove(&token);
+        // The source may have been closed already, which removed it from the selector as well.
+        let _ = self.selector.deregister(source);
+        // Dropped outside of the lock, since it may hold the last reference to a waker.
+        drop(io);
+    }
+
+    /// Take the right to wait for events, unless another thread has it.
+    ///
+    /// From then on, [`unpark`](Driver::unpark) wakes the thread, so it has to check whether it
+    /// has been notified after this, and before it waits.
+    pub(crate) fn park(&self) -> Option<Park<'_>> {
+        let events = self.events.try_lock()?;
+        self.parked.store(true, Ordering::SeqCst);
+        Some(Park {
+            driver: self,
+            events,
+        })
+    }
+
+    /// Wake the tasks of the sources that are ready, without waiting, unless another thread is
+    /// waiting for events. Returns whether any tasks were woken.
+    pub(crate) fn poll(&self) -> bool {
+        match self.events.try_lock() {
+            Some(mut events) => self.turn(&mut events, Some(Duration::ZERO)),
+            None => false,
+        }
+    }
+
+    /// Wake the thread that waits for events, if any.
+    pub(crate) fn unpark(&self) {
+        if self.parked.load(Ordering::SeqCst) {
+            if let Err(error) = self.selector.wake() {
+                panic!("failed to wake the I/O driver: {}", error);
+            }
+        }
+    }
+
+    /// Wait for events until `timeout`, and wake the tasks of the sources that are ready.
+    fn turn(&self, events: &mut sys::Events, timeout: Option<Duration>) -> bool {
+        let result = self.selector.select(events, timeout);
+        self.parked.store(false, Ordering::SeqCst);
+        if let Err(error) = result {
+            panic!("failed to wait for I/O events: {}", error);
+        }
+
+        let ready: Vec<_> = {
+            let registrations = self.registrations.lock();
+            events
+                .iter()
+                .filter_map(|(token, ready)| {
+                    let io = registrations.sources.get(&token)?;
+                    Some((io.clone(), ready))
+                })
+                .collect()
+        };
+        // Woken outside of the lock, since waking a task may register another source.
+        let woken = !ready.is_empty();
+        for (io, ready) in ready {
+            io.set_readiness(ready, false);
+        }
+        // The ring is checked on every turn, not only when it is reported, since that is cheaper
+        // than finding out which event was the ring.
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if let Some(Ok(ring)) = self.uring.get() {
+            return ring.complete() || woken;
+        }
+        woken
+    }
+
+    /// Get the `io_uring` instance of the driver, and set it up if this is the first operation.
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    fn uring(&self) -> io::Result<Arc<uring::Ring>> {
+        if self.registrations.lock().shutdown {
+            return Err(io::Error::other(
+                "the I/O driver of the runtime has shut down",
+            ));
+        }
+        let ring = self.uring.get_or_init(|| {
+            let ring = uring::Ring::new().and_then(|ring| {
+                self.selector
+                    .register(ring.as_raw_fd(), URING_TOKEN, Interest::READABLE)?;
+                Ok(Arc::new(ring))
+            });
+            ring.map_err(|error| (error.kind(), error.to_string()))
+        });
+        match ring {
+            Ok(ring) => Ok(ring.clone()),
+            Err((kind, message)) => Err(io::Error::new(*kind, message.clone())),
+        }
+    }
+
+    /// Wake every task that waits for a source with an error, once the runtime has shut down, and
+    /// reject new registrations.
+    pub(crate) fn shutdown(&self) {
+        let sources: Vec<_> = {
+            let mut registrations = self.registrations.lock();
+            registrations.shutdown = true;
+            registrations.sources.values().cloned().collect()
+        };
+        for io in sources {
+            io.set_readiness(Ready::EMPTY, true);
+        }
+    }
+}
+
+/// Turn the return value of a system call into an error, if it is negative.
+#[cfg(unix)]
+pub(crate) fn cvt<T: Default + PartialOrd>(result: T) -> io::Result<T> {
+    if result < T::default() {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+/// The right to wait for I/O events, held by one thread of the runtime at a time.
+pub(crate) struct Park<'a> {
+    driver: &'a Driver,
+    events: MutexGuard<'a, sys::Events>,
+}
+
+impl Park<'_> {
+    /// Wait for events until `timeout`, or forever if it is `None`, or until the driver is
+    /// unparked. Returns whether any tasks were woken.
+    pub(crate) fn wait(mut self, timeout: Option<Duration>) -> bool {
+        self.driver.turn(&mut self.events, timeout)
+    }
+}
+
+impl Drop for Park<'_> {
+    fn drop(&mut self) {
+        self.driver.parked.store(false, Ordering::SeqCst);
+    }
+}
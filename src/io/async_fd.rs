@@ -0,0 +1,290 @@
+//! [`AsyncFd`], which registers a file descriptor of any kind with the I/O driver.
+
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::task::{Context, Poll};
+
+use super::{Interest, Ready, ReadyEvent, Registration};
+
+/// An I/O object that owns a file descriptor, registered with the I/O driver of a [`Runtime`],
+/// to wait until it is ready.
+///
+/// This is how the file descriptors that the crate has no type for, like a serial port, a
+/// `timerfd` or a netlink socket, are used from async code. The file descriptor must be in
+/// non-blocking mode, and stays registered until the `AsyncFd` is dropped or
+/// [`into_inner`](AsyncFd::into_inner) is called.
+///
+/// Readiness is reported edge-triggered, see [`Registration`]: waiting for it with
+/// [`readable`](AsyncFd::readable) or [`writable`](AsyncFd::writable) returns an
+/// [`AsyncFdReadyGuard`], and an operation that would block has to clear the readiness through
+/// it, with [`try_io`](AsyncFdReadyGuard::try_io) or
+/// [`clear_ready`](AsyncFdReadyGuard::clear_ready), before waiting again.
+/// [`async_io`](AsyncFd::async_io) does all of that in one go.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::AsyncFd;
+/// use mini_executor::runtime::Runtime;
+/// use std::io::{Read, Write};
+/// use std::os::unix::net::UnixStream;
+///
+/// let executor = Runtime::new();
+/// let message = executor.block_on(async {
+///     let (mut sender, receiver) = UnixStream::pair().unwrap();
+///     receiver.set_nonblocking(true).unwrap();
+///     let receiver = AsyncFd::new(receiver).unwrap();
+///
+///     mini_executor::spawn(async move { sender.write_all(b"hello").unwrap() });
+///
+///     let mut buf = [0; 5];
+///     loop {
+///         let mut guard = receiver.readable().await.unwrap();
+///         match guard.try_io(|receiver| receiver.get_ref().read(&mut buf)) {
+///             Ok(read) => break buf[..read.unwrap()].to_vec(),
+///             // The readiness was cleared, so the next `readable` waits for more.
+///             Err(_would_block) => continue,
+///         }
+///     }
+/// });
+/// assert_eq!(message, b"hello");
+/// ```
+///
+/// [`Runtime`]: crate::runtime::Runtime
+pub struct AsyncFd<T: AsRawFd> {
+    // Dropped before the file descriptor, so it is still open when it is deregistered.
+    registration: Registration,
+    /// Only taken by `into_inner`.
+    inner: Option<T>,
+}
+
+impl<T: AsRawFd> AsyncFd<T> {
+    /// Register `inner` with the I/O driver of the current runtime, to wait until it is readable
+    /// or writable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the driver can't watch the file descriptor, for example because it is
+    /// a regular file, or if the runtime has shut down. `inner` is dropped then.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn new(inner: T) -> io::Result<Self> {
+        AsyncFd::with_interest(inner, Interest::READABLE | Interest::WRITABLE)
+    }
+
+    /// Register `inner` with the I/O driver of the current runtime, to wait until it is ready for
+    /// `interest`, see [`new`](AsyncFd::new).
+    #[track_caller]
+    pub fn with_interest(inner: T, interest: Interest) -> io::Result<Self> {
+        let registration = Registration::new(inner.as_raw_fd(), interest)?;
+        Ok(AsyncFd {
+            registration,
+            inner: Some(inner),
+        })
+    }
+
+    /// The wrapped I/O object.
+    pub fn get_ref(&self) -> &T {
+        self.inner
+            .as_ref()
+            .expect("the inner value is only taken by `into_inner`")
+    }
+
+    /// The wrapped I/O object, mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner
+            .as_mut()
+            .expect("the inner value is only taken by `into_inner`")
+    }
+
+    /// Deregister the file descriptor from the I/O driver, and return the wrapped I/O object.
+    pub fn into_inner(mut self) -> T {
+        self.inner
+            .take()
+            .expect("the inner value is only taken by `into_inner`")
+    }
+
+    /// Poll whether the file descriptor is readable, and register the waker of `cx` to be woken
+    /// once it is if not.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error once the runtime has shut down.
+    pub fn poll_read_ready(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<AsyncFdReadyGuard<'_, T>>> {
+        self.poll_ready(cx, Interest::READABLE)
+    }
+
+    /// Poll whether the file descriptor is writable, and register the waker of `cx` to be woken
+    /// once it is if not.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error once the runtime has shut down.
+    pub fn poll_write_ready(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<AsyncFdReadyGuard<'_, T>>> {
+        self.poll_ready(cx, Interest::WRITABLE)
+    }
+
+    /// Wait until the file descriptor is ready for any of `interest`.
+    ///
+    /// Waiting for an interest the file descriptor wasn't registered with never completes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error once the runtime has shut down.
+    pub async fn ready(&self, interest: Interest) -> io::Result<AsyncFdReadyGuard<'_, T>> {
+        poll_fn(|cx| self.poll_ready(cx, interest)).await
+    }
+
+    /// Wait until the file descriptor is readable, see [`ready`](AsyncFd::ready).
+    pub async fn readable(&self) -> io::Result<AsyncFdReadyGuard<'_, T>> {
+        self.ready(Interest::READABLE).await
+    }
+
+    /// Wait until the file descriptor is writable, see [`ready`](AsyncFd::ready).
+    pub async fn writable(&self) -> io::Result<AsyncFdReadyGuard<'_, T>> {
+        self.ready(Interest::WRITABLE).await
+    }
+
+    /// Run the non-blocking operation `f` on the wrapped I/O object once the file descriptor is
+    /// ready for any of `interest`, and again whenever it fails with
+    /// [`WouldBlock`](io::ErrorKind::WouldBlock) and the file descriptor is ready again, until it
+    /// completes or fails with another error.
+    pub async fn async_io<R>(
+        &self,
+        interest: Interest,
+        mut f: impl FnMut(&T) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let inner = self.get_ref();
+        self.registration.async_io(interest, || f(inner)).await
+    }
+
+    /// Like [`async_io`](AsyncFd::async_io), with mutable access to the wrapped I/O object.
+    pub async fn async_io_mut<R>(
+        &mut self,
+        interest: Interest,
+        mut f: impl FnMut(&mut T) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let inner = self
+            .inner
+            .as_mut()
+            .expect("the inner value is only taken by `into_inner`");
+        self.registration.async_io(interest, || f(inner)).await
+    }
+
+    fn poll_ready(
+        &self,
+        cx: &mut Context<'_>,
+        interest: Interest,
+    ) -> Poll<io::Result<AsyncFdReadyGuard<'_, T>>> {
+        self.registration
+            .io
+            .poll_ready(cx, interest)
+            .map_ok(|event| AsyncFdReadyGuard {
+                async_fd: self,
+                event: Some(event),
+            })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<T: AsRawFd> super::AsRegistration for AsyncFd<T> {
+    fn registration(&self) -> &Registration {
+        &self.registration
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for AsyncFd<T> {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.get_ref().as_raw_fd()
+    }
+}
+
+impl<T: AsRawFd + fmt::Debug> fmt::Debug for AsyncFd<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncFd")
+            .field("inner", &self.inner)
+            .field("ready", &self.registration.io.ready())
+            .finish()
+    }
+}
+
+/// The readiness of an [`AsyncFd`], returned by waiting for it.
+///
+/// Dropping the guard keeps the readiness, so waiting again returns right away. Once an operation
+/// on the file descriptor would block, the readiness has to be cleared, with
+/// [`clear_ready`](AsyncFdReadyGuard::clear_ready) or by running the operation in
+/// [`try_io`](AsyncFdReadyGuard::try_io), so that the next wait lasts until the driver reports the
+/// file descriptor ready again.
+pub struct AsyncFdReadyGuard<'a, T: AsRawFd> {
+    async_fd: &'a AsyncFd<T>,
+    /// Taken once the readiness has been cleared.
+    event: Option<ReadyEvent>,
+}
+
+impl<'a, T: AsRawFd> AsyncFdReadyGuard<'a, T> {
+    /// The readiness that was waited for, or [`Ready::EMPTY`] once it has been cleared.
+    pub fn ready(&self) -> Ready {
+        self.event.map_or(Ready::EMPTY, |event| event.ready)
+    }
+
+    /// Clear the readiness, after an operation on the file descriptor would block.
+    ///
+    /// Readiness that was reported since the guard was returned is kept, so it isn't lost.
+    pub fn clear_ready(&mut self) {
+        if let Some(event) = self.event.take() {
+            // Rearming a source only fails on Windows, which has no file descriptors.
+            let _ = self.async_fd.registration.clear_readiness(event);
+        }
+    }
+
+    /// Run the non-blocking operation `f`, and clear the readiness if it fails with
+    /// [`WouldBlock`](io::ErrorKind::WouldBlock), which is returned as a [`TryIoError`] then.
+    pub fn try_io<R>(
+        &mut self,
+        f: impl FnOnce(&'a AsyncFd<T>) -> io::Result<R>,
+    ) -> Result<io::Result<R>, TryIoError> {
+        match f(self.async_fd) {
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                self.clear_ready();
+                Err(TryIoError(()))
+            }
+            result => Ok(result),
+        }
+    }
+
+    /// The [`AsyncFd`] the guard belongs to.
+    pub fn get_ref(&self) -> &'a AsyncFd<T> {
+        self.async_fd
+    }
+
+    /// The I/O object wrapped by the [`AsyncFd`] the guard belongs to.
+    pub fn get_inner(&self) -> &'a T {
+        self.async_fd.get_ref()
+    }
+}
+
+impl<T: AsRawFd + fmt::Debug> fmt::Debug for AsyncFdReadyGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncFdReadyGuard")
+            .field("async_fd", &self.async_fd)
+            .field("ready", &self.ready())
+            .finish()
+    }
+}
+
+/// The error of [`AsyncFdReadyGuard::try_io`] when the operation would block, after the readiness
+/// has been cleared.
+#[derive(Debug)]
+pub struct TryIoError(());
@@ -0,0 +1,250 @@
+//! [`copy`] and [`copy_bidirectional`], which move the bytes of one stream to another.
+
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::{AsyncRead, AsyncWrite};
+
+/// The size of the buffer a copy reads into.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Copy everything `reader` reads into `writer`, until `reader` reaches its end, and return the
+/// number of bytes that were copied.
+///
+/// The bytes are read into a buffer, which is written out in full before more is read. `writer`
+/// is flushed whenever `reader` has to wait, so what was read doesn't wait in a buffer of
+/// `writer` meanwhile, and once the copy is done. It isn't closed, to write more to it afterwards.
+/// On Linux, [`copy_fd`](super::copy_fd) copies between two streams of the crate, like sockets,
+/// inside the kernel instead.
+///
+/// # Errors
+///
+/// Returns the first error of reading or writing, with some of the bytes copied already. A
+/// `writer` that accepts no more bytes fails with [`WriteZero`](io::ErrorKind::WriteZero).
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io;
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let copied = executor.block_on(async {
+///     let (left, right) = UnixStream::pair().unwrap();
+///     left.write_all(b"hello").await.unwrap();
+///     drop(left);
+///
+///     let mut copied = Vec::new();
+///     let len = io::copy(&mut &right, &mut copied).await.unwrap();
+///     assert_eq!(len, 5);
+///     copied
+/// });
+/// assert_eq!(copied, b"hello");
+/// ```
+pub async fn copy<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut buffer = CopyBuffer::new();
+    poll_fn(|cx| buffer.poll_copy(cx, Pin::new(&mut *reader), Pin::new(&mut *writer))).await
+}
+
+/// Copy everything `a` reads into `b`, and everything `b` reads into `a`, at the same time, until
+/// both have reached their end, and return the number of bytes copied from `a` to `b` and from
+/// `b` to `a`.
+///
+/// Once one of them has reached its end, the other one is closed, after all that was read has
+/// been written to it. For a [`TcpStream`](crate::net::TcpStream), closing shuts down writing,
+/// so the shutdown of one connection is passed on to the other one, while the bytes still go the
+/// other way until that one ends too. This is what a proxy does between the connection of a
+/// client and the one it opened to the server.
+///
+/// # Errors
+///
+/// Returns the first error of reading, writing or closing, in either direction, which ends the
+/// copy in the other direction too.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::{self, AsyncReadExt};
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let (client, mut proxy_client) = UnixStream::pair().unwrap();
+///     let (mut proxy_server, server) = UnixStream::pair().unwrap();
+///     let proxy = mini_executor::spawn(async move {
+///         io::copy_bidirectional(&mut proxy_client, &mut proxy_server).await
+///     });
+///
+///     client.write_all(b"ping").await.unwrap();
+///     client.shutdown(std::net::Shutdown::Write).unwrap();
+///     let mut request = Vec::new();
+///     (&server).read_to_end(&mut request).await.unwrap();
+///     assert_eq!(request, b"ping");
+///
+///     server.write_all(b"pong").await.unwrap();
+///     drop(server);
+///     let mut response = Vec::new();
+///     (&client).read_to_end(&mut response).await.unwrap();
+///     assert_eq!(response, b"pong");
+///
+///     assert_eq!(proxy.await.unwrap().unwrap(), (4, 4));
+/// });
+/// ```
+pub async fn copy_bidirectional<A, B>(a: &mut A, b: &mut B) -> io::Result<(u64, u64)>
+where
+    A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+    B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    let mut a_to_b = Transfer::Copying(CopyBuffer::new());
+    let mut b_to_a = Transfer::Copying(CopyBuffer::new());
+    poll_fn(|cx| {
+        let a_to_b = a_to_b.poll(cx, &mut *a, &mut *b)?;
+        let b_to_a = b_to_a.poll(cx, &mut *b, &mut *a)?;
+        match (a_to_b, b_to_a) {
+            (Poll::Ready(a_to_b), Poll::Ready(b_to_a)) => Poll::Ready(Ok((a_to_b, b_to_a))),
+            _ => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// The bytes that were read, but haven't been written yet, of a copy.
+struct CopyBuffer {
+    buf: Box<[u8]>,
+    /// The bytes of `buf` from `pos` up to `len` haven't been written yet.
+    pos: usize,
+    len: usize,
+    /// The number of bytes written so far.
+    copied: u64,
+    /// Whether the reader has reached its end.
+    read_done: bool,
+    /// Whether bytes were written since the writer was last flushed.
+    need_flush: bool,
+}
+
+impl CopyBuffer {
+    fn new() -> CopyBuffer {
+        CopyBuffer {
+            buf: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            copied: 0,
+            read_done: false,
+            need_flush: false,
+        }
+    }
+
+    /// Poll for the copy of the rest of `reader` into `writer`, and the number of bytes copied.
+    fn poll_copy<R, W>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut reader: Pin<&mut R>,
+        mut writer: Pin<&mut W>,
+    ) -> Poll<io::Result<u64>>
+    where
+        R: AsyncRead + ?Sized,
+        W: AsyncWrite + ?Sized,
+    {
+        loop {
+            if self.pos == self.len && !self.read_done {
+                match reader.as_mut().poll_read(cx, &mut self.buf) {
+                    Poll::Ready(Ok(0)) => self.read_done = true,
+                    Poll::Ready(Ok(len)) => {
+                        self.pos = 0;
+                        self.len = len;
+                    }
+                    Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                    Poll::Pending => {
+                        // Hand on what was written while waiting for more.
+                        if self.need_flush {
+                            match writer.as_mut().poll_flush(cx) {
+                                Poll::Ready(Ok(())) => self.need_flush = false,
+                                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                                Poll::Pending => {}
+                            }
+                        }
+                        return Poll::Pending;
+                    }
+                }
+            }
+
+            while self.pos < self.len {
+                match writer
+                    .as_mut()
+                    .poll_write(cx, &self.buf[self.pos..self.len])
+                {
+                    Poll::Ready(Ok(0)) => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "write zero byte into writer",
+                        )));
+                    }
+                    Poll::Ready(Ok(written)) => {
+                        self.pos += written;
+                        self.copied += written as u64;
+                        self.need_flush = true;
+                    }
+                    Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            if self.read_done {
+                return match writer.as_mut().poll_flush(cx) {
+                    Poll::Ready(Ok(())) => Poll::Ready(Ok(self.copied)),
+                    Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+                    Poll::Pending => Poll::Pending,
+                };
+            }
+        }
+    }
+}
+
+/// One direction of [`copy_bidirectional`].
+enum Transfer {
+    Copying(CopyBuffer),
+    /// Closing the writer, after the number of bytes that were copied.
+    Closing(u64),
+    Done(u64),
+}
+
+impl Transfer {
+    /// Poll for the copy of `reader` into `writer`, and the closing of `writer` after it, which
+    /// fails with the first error of either.
+    fn poll<R, W>(
+        &mut self,
+        cx: &mut Context<'_>,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<Poll<u64>, io::Error>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        loop {
+            match self {
+                Transfer::Copying(buffer) => {
+                    match buffer.poll_copy(cx, Pin::new(&mut *reader), Pin::new(&mut *writer)) {
+                        Poll::Ready(Ok(copied)) => *self = Transfer::Closing(copied),
+                        Poll::Ready(Err(error)) => return Err(error),
+                        Poll::Pending => return Ok(Poll::Pending),
+                    }
+                }
+                Transfer::Closing(copied) => match Pin::new(&mut *writer).poll_close(cx) {
+                    Poll::Ready(Ok(())) => *self = Transfer::Done(*copied),
+                    Poll::Ready(Err(error)) => return Err(error),
+                    Poll::Pending => return Ok(Poll::Pending),
+                },
+                Transfer::Done(copied) => return Ok(Poll::Ready(*copied)),
+            }
+        }
+    }
+}
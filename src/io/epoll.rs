@@ -0,0 +1,159 @@
+//! The selector of the I/O driver on Linux, built on `epoll`.
+//!
+//! Every source is registered edge-triggered, so `epoll_wait` reports it once each time it
+//! becomes ready, and an `eventfd` in the same set wakes the thread that waits in it.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+use super::{cvt, Interest, Ready};
+
+/// What a source is registered by.
+pub(super) type Source = RawFd;
+
+/// The token of the `eventfd` that wakes the selector, which no registration gets.
+const WAKE_TOKEN: usize = usize::MAX;
+
+/// An `epoll` instance, and the `eventfd` that wakes the thread waiting in it.
+pub(super) struct Selector {
+    epoll: OwnedFd,
+    wake: OwnedFd,
+}
+
+/// The events returned by [`Selector::select`].
+pub(super) struct Events(Vec<libc::epoll_event>);
+
+impl Events {
+    pub(super) fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    /// The token and readiness of every event, leaving out the wake-ups of the selector.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, Ready)> + '_ {
+        self.0
+            .iter()
+            .map(|event| (event.u64 as usize, ready(event.events)))
+            .filter(|&(token, _)| token != WAKE_TOKEN)
+    }
+}
+
+impl Selector {
+    pub(super) fn new() -> io::Result<Self> {
+        // Safety: the returned descriptors are checked, and owned from then on.
+        let epoll = unsafe { OwnedFd::from_raw_fd(cvt(libc::epoll_create1(libc::EPOLL_CLOEXEC))?) };
+        let flags = libc::EFD_CLOEXEC | libc::EFD_NONBLOCK;
+        let wake = unsafe { OwnedFd::from_raw_fd(cvt(libc::eventfd(0, flags))?) };
+        let selector = Self { epoll, wake };
+        selector.control(
+            libc::EPOLL_CTL_ADD,
+            selector.wake.as_raw_fd(),
+            libc::EPOLLIN as u32 | libc::EPOLLET as u32,
+            WAKE_TOKEN,
+        )?;
+        Ok(selector)
+    }
+
+    pub(super) fn register(&self, fd: Source, token: usize, interest: Interest) -> io::Result<()> {
+        let mut events = libc::EPOLLET as u32;
+        if interest.is_readable() {
+            events |= libc::EPOLLIN as u32 | libc::EPOLLRDHUP as u32;
+        }
+        if interest.is_writable() {
+            events |= libc::EPOLLOUT as u32;
+        }
+        self.control(libc::EPOLL_CTL_ADD, fd, events, token)
+    }
+
+    pub(super) fn deregister(&self, fd: Source) -> io::Result<()> {
+        self.control(libc::EPOLL_CTL_DEL, fd, 0, 0)
+    }
+
+    /// Report the directions of `ready` again, once an operation on them would block, which the
+    /// kernel does on its own.
+    pub(super) fn rearm(&self, _token: usize, _ready: Ready) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Wait until a registered source is ready, the selector is woken, or `timeout` has passed,
+    /// and fill `events` with what happened.
+    pub(super) fn select(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        // Rounded up, so that a timeout below a millisecond doesn't turn into a busy loop.
+        let timeout = timeout.map_or(-1, |timeout| {
+            let millis = timeout.as_nanos().div_ceil(1_000_000);
+            millis.min(libc::c_int::MAX as u128) as libc::c_int
+        });
+
+        events.0.clear();
+        // Safety: the buffer has room for `capacity` events, and `epoll_wait` initializes the
+        // ones it returns.
+        let count = unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                events.0.as_mut_ptr(),
+                events.0.capacity().min(libc::c_int::MAX as usize) as libc::c_int,
+                timeout,
+            )
+        };
+        let count = match cvt(count) {
+            Ok(count) => count as usize,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => 0,
+            Err(error) => return Err(error),
+        };
+        unsafe { events.0.set_len(count) };
+
+        if events
+            .0
+            .iter()
+            .any(|event| event.u64 as usize == WAKE_TOKEN)
+        {
+            // Reset the counter, so that it doesn't overflow.
+            let mut buf = [0u8; 8];
+            unsafe { libc::read(self.wake.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        }
+        Ok(())
+    }
+
+    /// Wake the thread that waits in [`select`](Selector::select), or make its next call return
+    /// right away.
+    pub(super) fn wake(&self) -> io::Result<()> {
+        let buf = 1u64.to_ne_bytes();
+        let written = unsafe { libc::write(self.wake.as_raw_fd(), buf.as_ptr().cast(), buf.len()) };
+        match cvt(written) {
+            Ok(_) => Ok(()),
+            // The counter is full, so the thread is going to wake up anyway.
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn control(&self, op: libc::c_int, fd: RawFd, events: u32, token: usize) -> io::Result<()> {
+        let mut event = libc::epoll_event {
+            events,
+            u64: token as u64,
+        };
+        cvt(unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) }).map(drop)
+    }
+}
+
+/// The readiness an `epoll` event reports.
+fn ready(events: u32) -> Ready {
+    let is = |flag: libc::c_int| events & flag as u32 != 0;
+    let mut ready = Ready::EMPTY;
+    if is(libc::EPOLLIN) || is(libc::EPOLLPRI) {
+        ready = ready | Ready::READABLE;
+    }
+    if is(libc::EPOLLOUT) {
+        ready = ready | Ready::WRITABLE;
+    }
+    if is(libc::EPOLLRDHUP) || is(libc::EPOLLHUP) {
+        ready = ready | Ready::READ_CLOSED;
+    }
+    if is(libc::EPOLLHUP) || (is(libc::EPOLLOUT) && is(libc::EPOLLERR)) {
+        ready = ready | Ready::WRITE_CLOSED;
+    }
+    if is(libc::EPOLLERR) {
+        ready = ready | Ready::ERROR;
+    }
+    ready
+}
@@ -0,0 +1,229 @@
+//! The selector of the I/O driver on macOS and the BSDs, built on `kqueue`.
+//!
+//! Every source is registered with `EV_CLEAR`, so `kevent` reports it once each time it becomes
+//! ready, like the edge-triggered `epoll` on Linux. A pipe in the same queue wakes the thread that
+//! waits in it, since not every BSD has `EVFILT_USER`.
+
+use std::io;
+use std::ops::BitAnd;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::time::Duration;
+
+use super::{cvt, Interest, Ready};
+
+/// What a source is registered by.
+pub(super) type Source = RawFd;
+
+/// The token of the pipe that wakes the selector, which no registration gets.
+const WAKE_TOKEN: usize = usize::MAX;
+
+/// A `kqueue`, and the pipe that wakes the thread waiting in it.
+pub(super) struct Selector {
+    kqueue: OwnedFd,
+    wake_receiver: OwnedFd,
+    wake_sender: OwnedFd,
+}
+
+/// The events returned by [`Selector::select`].
+pub(super) struct Events(Vec<libc::kevent>);
+
+// Safety: the `udata` pointer of an event only carries a token, and is never dereferenced.
+unsafe impl Send for Events {}
+
+impl Events {
+    pub(super) fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    /// The token and readiness of every event, leaving out the wake-ups of the selector.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, Ready)> + '_ {
+        self.0
+            .iter()
+            .map(|event| (event.udata as usize, ready(event)))
+            .filter(|&(token, _)| token != WAKE_TOKEN)
+    }
+}
+
+impl Selector {
+    pub(super) fn new() -> io::Result<Self> {
+        // Safety: the returned descriptors are checked, and owned from then on.
+        let kqueue = unsafe { OwnedFd::from_raw_fd(cvt(libc::kqueue())?) };
+        set_cloexec(kqueue.as_raw_fd())?;
+
+        let mut fds = [0; 2];
+        cvt(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+        let (wake_receiver, wake_sender) =
+            unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        for fd in [&wake_receiver, &wake_sender] {
+            set_cloexec(fd.as_raw_fd())?;
+            let flags = cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) })?;
+            cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+        }
+
+        let selector = Self {
+            kqueue,
+            wake_receiver,
+            wake_sender,
+        };
+        let read = change(
+            selector.wake_receiver.as_raw_fd(),
+            libc::EVFILT_READ,
+            WAKE_TOKEN,
+        );
+        selector.apply(&[read])?;
+        Ok(selector)
+    }
+
+    pub(super) fn register(&self, fd: Source, token: usize, interest: Interest) -> io::Result<()> {
+        let mut changes = Vec::with_capacity(2);
+        if interest.is_readable() {
+            changes.push(change(fd, libc::EVFILT_READ, token));
+        }
+        if interest.is_writable() {
+            changes.push(change(fd, libc::EVFILT_WRITE, token));
+        }
+        self.apply(&changes)
+    }
+
+    pub(super) fn deregister(&self, fd: Source) -> io::Result<()> {
+        // Each filter is deleted on its own, since the source may only have been registered with
+        // one of them, and deleting the other one fails.
+        for filter in [libc::EVFILT_READ, libc::EVFILT_WRITE] {
+            let mut delete = change(fd, filter, 0);
+            delete.flags = libc::EV_DELETE as _;
+            match self.apply(&[delete]) {
+                Err(error) if error.raw_os_error() != Some(libc::ENOENT) => return Err(error),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Report the directions of `ready` again, once an operation on them would block, which the
+    /// kernel does on its own.
+    pub(super) fn rearm(&self, _token: usize, _ready: Ready) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Wait until a registered source is ready, the selector is woken, or `timeout` has passed,
+    /// and fill `events` with what happened.
+    pub(super) fn select(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        let timeout = timeout.map(|timeout| libc::timespec {
+            tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as _,
+        });
+        let timeout = timeout
+            .as_ref()
+            .map_or(ptr::null(), |timeout| timeout as *const _);
+
+        events.0.clear();
+        // Safety: the buffer has room for `capacity` events, and `kevent` initializes the ones it
+        // returns.
+        let count = unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                ptr::null(),
+                0,
+                events.0.as_mut_ptr(),
+                events.0.capacity().min(libc::c_int::MAX as usize) as _,
+                timeout,
+            )
+        };
+        let count = match cvt(count) {
+            Ok(count) => count as usize,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => 0,
+            Err(error) => return Err(error),
+        };
+        unsafe { events.0.set_len(count) };
+
+        if events
+            .0
+            .iter()
+            .any(|event| event.udata as usize == WAKE_TOKEN)
+        {
+            // Empty the pipe, so that it doesn't fill up.
+            let mut buf = [0u8; 64];
+            while unsafe {
+                libc::read(
+                    self.wake_receiver.as_raw_fd(),
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                )
+            } > 0
+            {}
+        }
+        Ok(())
+    }
+
+    /// Wake the thread that waits in [`select`](Selector::select), or make its next call return
+    /// right away.
+    pub(super) fn wake(&self) -> io::Result<()> {
+        let written =
+            unsafe { libc::write(self.wake_sender.as_raw_fd(), [1u8].as_ptr().cast(), 1) };
+        match cvt(written) {
+            Ok(_) => Ok(()),
+            // The pipe is full, so the thread is going to wake up anyway.
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Apply `changes` to the queue, without waiting for events.
+    fn apply(&self, changes: &[libc::kevent]) -> io::Result<()> {
+        cvt(unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                changes.as_ptr(),
+                changes.len() as _,
+                ptr::null_mut(),
+                0,
+                ptr::null(),
+            )
+        })
+        .map(drop)
+    }
+}
+
+/// A change that adds `fd` to the queue with `filter`, reported with `token`.
+fn change(fd: RawFd, filter: impl Into<i64>, token: usize) -> libc::kevent {
+    // Safety: `kevent` is plain data, and some platforms have fields beyond the ones set here.
+    let mut event: libc::kevent = unsafe { std::mem::zeroed() };
+    event.ident = fd as _;
+    event.filter = filter.into() as _;
+    event.flags = (libc::EV_ADD | libc::EV_CLEAR) as _;
+    event.udata = token as _;
+    event
+}
+
+/// The readiness a `kqueue` event reports.
+fn ready(event: &libc::kevent) -> Ready {
+    let filter = i64::from(event.filter);
+    let eof = is_set(event.flags, libc::EV_EOF as _);
+    let mut ready = Ready::EMPTY;
+    if filter == i64::from(libc::EVFILT_READ) {
+        ready = ready | Ready::READABLE;
+        if eof {
+            ready = ready | Ready::READ_CLOSED;
+        }
+    } else if filter == i64::from(libc::EVFILT_WRITE) {
+        ready = ready | Ready::WRITABLE;
+        if eof {
+            ready = ready | Ready::WRITE_CLOSED;
+        }
+    }
+    // An error that happened on the source is reported with `EV_EOF` and a non-zero `fflags`.
+    if is_set(event.flags, libc::EV_ERROR as _) || (eof && event.fflags != 0) {
+        ready = ready | Ready::ERROR;
+    }
+    ready
+}
+
+/// Whether `flag` is set in `flags`, whose type differs between the platforms.
+fn is_set<T: BitAnd<Output = T> + PartialEq + Default>(flags: T, flag: T) -> bool {
+    flags & flag != T::default()
+}
+
+fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    cvt(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) }).map(drop)
+}
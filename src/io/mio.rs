@@ -0,0 +1,118 @@
+//! The selector of the I/O driver on Unix with the `mio` feature, which lets `mio` poll for the
+//! events instead of the crate's own `epoll` and `kqueue` code.
+//!
+//! `mio` reports readiness edge-triggered on every platform, like the other backends, and wakes
+//! the thread that waits in it with a [`mio::Waker`].
+
+use std::io;
+use std::os::fd::RawFd;
+use std::time::Duration;
+
+use ::mio::unix::SourceFd;
+use ::mio::{Poll, Registry, Token, Waker};
+
+use super::{Interest, Ready};
+use crate::sync::Mutex;
+
+/// What a source is registered by.
+pub(super) type Source = RawFd;
+
+/// The token of the waker of the selector, which no registration gets.
+const WAKE_TOKEN: Token = Token(usize::MAX);
+
+/// A `mio` poll instance, with a handle to its registry for the threads that don't wait in it.
+pub(super) struct Selector {
+    /// Only locked by the one thread that waits for events.
+    poll: Mutex<Poll>,
+    registry: Registry,
+    waker: Waker,
+}
+
+/// The events returned by [`Selector::select`].
+pub(super) struct Events(::mio::Events);
+
+impl Events {
+    pub(super) fn with_capacity(capacity: usize) -> Self {
+        Self(::mio::Events::with_capacity(capacity))
+    }
+
+    /// The token and readiness of every event, leaving out the wake-ups of the selector.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, Ready)> + '_ {
+        self.0
+            .iter()
+            .filter(|event| event.token() != WAKE_TOKEN)
+            .map(|event| (event.token().0, ready(event)))
+    }
+}
+
+impl Selector {
+    pub(super) fn new() -> io::Result<Self> {
+        let poll = Poll::new()?;
+        let registry = poll.registry().try_clone()?;
+        let waker = Waker::new(&registry, WAKE_TOKEN)?;
+        Ok(Self {
+            poll: Mutex::new(poll),
+            registry,
+            waker,
+        })
+    }
+
+    pub(super) fn register(&self, fd: Source, token: usize, interest: Interest) -> io::Result<()> {
+        let interest = match (interest.is_readable(), interest.is_writable()) {
+            (true, true) => ::mio::Interest::READABLE | ::mio::Interest::WRITABLE,
+            (false, true) => ::mio::Interest::WRITABLE,
+            _ => ::mio::Interest::READABLE,
+        };
+        self.registry
+            .register(&mut SourceFd(&fd), Token(token), interest)
+    }
+
+    pub(super) fn deregister(&self, fd: Source) -> io::Result<()> {
+        self.registry.deregister(&mut SourceFd(&fd))
+    }
+
+    /// Report the directions of `ready` again, once an operation on them would block, which
+    /// `mio` does on its own.
+    pub(super) fn rearm(&self, _token: usize, _ready: Ready) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Wait until a registered source is ready, the selector is woken, or `timeout` has passed,
+    /// and fill `events` with what happened.
+    pub(super) fn select(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        match self.poll.lock().poll(&mut events.0, timeout) {
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {
+                events.0.clear();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Wake the thread that waits in [`select`](Selector::select), or make its next call return
+    /// right away.
+    pub(super) fn wake(&self) -> io::Result<()> {
+        self.waker.wake()
+    }
+}
+
+/// The readiness a `mio` event reports.
+fn ready(event: &::mio::event::Event) -> Ready {
+    let mut ready = Ready::EMPTY;
+    if event.is_readable() || event.is_priority() {
+        ready = ready | Ready::READABLE;
+    }
+    if event.is_writable() {
+        ready = ready | Ready::WRITABLE;
+    }
+    if event.is_read_closed() {
+        ready = ready | Ready::READ_CLOSED;
+    }
+    if event.is_write_closed() {
+        ready = ready | Ready::WRITE_CLOSED;
+    }
+    if event.is_error() {
+        ready = ready | Ready::ERROR;
+    }
+    ready
+}
@@ -0,0 +1,484 @@
+//! [`pipe`], an anonymous pipe whose two ends are registered with the I/O driver, on Unix.
+
+use std::fmt;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(target_os = "linux")]
+use super::AsRegistration;
+use super::{cvt, read_fd, AsyncRead, AsyncWrite, Interest, ReadBuf, Registration};
+
+/// Create an anonymous pipe, and return its two ends: the bytes written to the [`PipeWriter`]
+/// are read from the [`PipeReader`], in the same order.
+///
+/// Both ends are in non-blocking mode and registered with the I/O driver, so reading waits until
+/// there are bytes in the pipe, and writing until there is room for them, without blocking the
+/// thread. The reader reaches its end once the writer, and every other file descriptor of the
+/// write end, like one a child process inherited, has been closed; a write fails with
+/// [`BrokenPipe`](io::ErrorKind::BrokenPipe) once the reader has been.
+///
+/// An end of a pipe that was created elsewhere, like the standard output of a child process, is
+/// registered with [`PipeReader::from_owned_fd`] or [`PipeWriter::from_owned_fd`], and handed to
+/// code that blocks with [`into_owned_fd`](PipeReader::into_owned_fd).
+///
+/// # Errors
+///
+/// Returns an error if the process has run out of file descriptors.
+///
+/// # Panics
+///
+/// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+/// whose I/O driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::{self, AsyncReadExt};
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let (mut reader, writer) = io::pipe().unwrap();
+///     writer.write_all(b"hello").await.unwrap();
+///     drop(writer);
+///
+///     let mut received = Vec::new();
+///     reader.read_to_end(&mut received).await.unwrap();
+///     received
+/// });
+/// assert_eq!(received, b"hello");
+/// ```
+#[track_caller]
+pub fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
+    let (read, write) = pipe_fds()?;
+    Ok((PipeReader::new(read)?, PipeWriter::new(write)?))
+}
+
+/// The end of a pipe that is read from, returned by [`pipe`].
+///
+/// Only the task that waited for it last is woken, so at most one task should read at a time.
+/// Both `PipeReader` and `&PipeReader` implement [`AsyncRead`].
+pub struct PipeReader {
+    // Dropped before the file descriptor, so it is still open when it is deregistered.
+    registration: Registration,
+    fd: OwnedFd,
+}
+
+/// The end of a pipe that is written to, returned by [`pipe`].
+///
+/// Only the task that waited for it last is woken, so at most one task should write at a time.
+/// Both `PipeWriter` and `&PipeWriter` implement [`AsyncWrite`]. The pipe is closed when the
+/// writer is dropped, not by [`poll_close`](AsyncWrite::poll_close), since the reader would have
+/// nothing left to wait for.
+pub struct PipeWriter {
+    // Dropped before the file descriptor, so it is still open when it is deregistered.
+    registration: Registration,
+    fd: OwnedFd,
+}
+
+impl PipeReader {
+    /// Register `fd`, the read end of a pipe, with the I/O driver of the current runtime, and put
+    /// it into non-blocking mode.
+    ///
+    /// Other file descriptors of the same end, like the ones of a child process, are put into
+    /// non-blocking mode with it, since they share the mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `fd` isn't a
+    /// pipe, or an error if the runtime has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_owned_fd(fd: OwnedFd) -> io::Result<PipeReader> {
+        check_pipe(&fd)?;
+        set_nonblocking(&fd)?;
+        PipeReader::new(fd)
+    }
+
+    /// Register a read end that is in non-blocking mode already.
+    #[track_caller]
+    fn new(fd: OwnedFd) -> io::Result<PipeReader> {
+        Ok(PipeReader {
+            registration: Registration::new(fd.as_raw_fd(), Interest::READABLE)?,
+            fd,
+        })
+    }
+
+    /// Deregister the read end from the I/O driver, and return its file descriptor, which is left
+    /// in non-blocking mode.
+    pub fn into_owned_fd(self) -> OwnedFd {
+        let PipeReader { registration, fd } = self;
+        drop(registration);
+        fd
+    }
+
+    /// Read some bytes into `buf`, once there are any, and return how many were read.
+    ///
+    /// Returns `0` once every write end of the pipe has been closed, or if `buf` is empty.
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::READABLE, || self.file().read(buf))
+            .await
+    }
+
+    /// Read some bytes into `bufs`, filling one after the other, with a single `readv` call, see
+    /// [`read`](PipeReader::read).
+    pub async fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::READABLE, || self.file().read_vectored(bufs))
+            .await
+    }
+
+    /// Read some bytes into the part of `buf` that isn't filled yet, which doesn't have to be
+    /// initialized, see [`ReadBuf`], and return how many were read.
+    pub async fn read_buf(&self, buf: &mut ReadBuf<'_>) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::READABLE, || read_fd(self.fd.as_raw_fd(), buf))
+            .await
+    }
+
+    /// The file descriptor as a [`std::fs::File`], to read from it with the standard library.
+    fn file(&self) -> ManuallyDrop<std::fs::File> {
+        // Safety: the file descriptor stays open while it is borrowed, and the file is never
+        // dropped, so it isn't closed by it.
+        ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(self.fd.as_raw_fd()) })
+    }
+}
+
+impl PipeWriter {
+    /// Register `fd`, the write end of a pipe, with the I/O driver of the current runtime, and put
+    /// it into non-blocking mode.
+    ///
+    /// Other file descriptors of the same end, like the ones of a child process, are put into
+    /// non-blocking mode with it, since they share the mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `fd` isn't a
+    /// pipe, or an error if the runtime has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_owned_fd(fd: OwnedFd) -> io::Result<PipeWriter> {
+        check_pipe(&fd)?;
+        set_nonblocking(&fd)?;
+        PipeWriter::new(fd)
+    }
+
+    /// Register a write end that is in non-blocking mode already.
+    #[track_caller]
+    fn new(fd: OwnedFd) -> io::Result<PipeWriter> {
+        Ok(PipeWriter {
+            registration: Registration::new(fd.as_raw_fd(), Interest::WRITABLE)?,
+            fd,
+        })
+    }
+
+    /// Deregister the write end from the I/O driver, and return its file descriptor, which is
+    /// left in non-blocking mode.
+    pub fn into_owned_fd(self) -> OwnedFd {
+        let PipeWriter { registration, fd } = self;
+        drop(registration);
+        fd
+    }
+
+    /// Write some bytes of `buf`, once there is room for any, and return how many were written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`BrokenPipe`](io::ErrorKind::BrokenPipe) if every read end
+    /// of the pipe has been closed.
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::WRITABLE, || self.file().write(buf))
+            .await
+    }
+
+    /// Write some bytes of `bufs`, one after the other, with a single `writev` call, see
+    /// [`write`](PipeWriter::write).
+    pub async fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::WRITABLE, || self.file().write_vectored(bufs))
+            .await
+    }
+
+    /// Write all of `buf`, waiting for room in the pipe as often as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`BrokenPipe`](io::ErrorKind::BrokenPipe) if every read end
+    /// of the pipe has been closed, with some of `buf` written already.
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                written => buf = &buf[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// The file descriptor as a [`std::fs::File`], to write to it with the standard library.
+    fn file(&self) -> ManuallyDrop<std::fs::File> {
+        // Safety: the file descriptor stays open while it is borrowed, and the file is never
+        // dropped, so it isn't closed by it.
+        ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(self.fd.as_raw_fd()) })
+    }
+}
+
+impl PipeReader {
+    /// Poll for a read into `buf`, for [`AsyncRead`].
+    fn poll_read_some(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.registration
+            .poll_io(cx, Interest::READABLE, || self.file().read(buf))
+    }
+
+    /// Poll for a read into `bufs`, for [`AsyncRead`].
+    fn poll_read_vectored_some(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.registration
+            .poll_io(cx, Interest::READABLE, || self.file().read_vectored(bufs))
+    }
+}
+
+impl PipeWriter {
+    /// Poll for a write of `buf`, for [`AsyncWrite`].
+    fn poll_write_some(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.registration
+            .poll_io(cx, Interest::WRITABLE, || self.file().write(buf))
+    }
+
+    /// Poll for a write of `bufs`, for [`AsyncWrite`].
+    fn poll_write_vectored_some(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.registration
+            .poll_io(cx, Interest::WRITABLE, || self.file().write_vectored(bufs))
+    }
+}
+
+impl AsyncRead for PipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_some(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_some(cx, bufs)
+    }
+}
+
+impl AsyncRead for &PipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_some(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_some(cx, bufs)
+    }
+}
+
+impl AsyncWrite for PipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_some(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored_some(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Writes go to the pipe right away, there is no buffer to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // A pipe has no way to close one direction, it is closed when the writer is dropped.
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for &PipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_some(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored_some(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRegistration for PipeReader {
+    fn registration(&self) -> &Registration {
+        &self.registration
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRegistration for PipeWriter {
+    fn registration(&self) -> &Registration {
+        &self.registration
+    }
+}
+
+impl AsRawFd for PipeReader {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsRawFd for PipeWriter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for PipeReader {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsFd for PipeWriter {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl fmt::Debug for PipeReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PipeReader").field(&self.fd).finish()
+    }
+}
+
+impl fmt::Debug for PipeWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PipeWriter").field(&self.fd).finish()
+    }
+}
+
+/// Create the two ends of a pipe, in non-blocking mode.
+pub(crate) fn pipe_fds() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        // Safety: `fds` has room for the two file descriptors.
+        cvt(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) })?;
+    }
+
+    // The other platforms can't create it that way in one go.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    {
+        // Safety: `fds` has room for the two file descriptors.
+        cvt(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+    }
+
+    // Safety: the descriptors were just created, and are owned from here on.
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    for fd in [&read, &write] {
+        cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+        set_nonblocking(fd)?;
+    }
+    Ok((read, write))
+}
+
+/// Make sure that `fd` is a pipe, anonymous or named.
+fn check_pipe(fd: &OwnedFd) -> io::Result<()> {
+    // Safety: `fstat` only writes to `stat`, which is valid when zeroed.
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    cvt(unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) })?;
+    if stat.st_mode & libc::S_IFMT != libc::S_IFIFO {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the file descriptor isn't a pipe",
+        ));
+    }
+    Ok(())
+}
+
+/// Put `fd` into non-blocking mode.
+fn set_nonblocking(fd: &OwnedFd) -> io::Result<()> {
+    let flags = cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) })?;
+    if flags & libc::O_NONBLOCK == 0 {
+        cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,243 @@
+//! [`ReadBuf`], a buffer for reads that may not be initialized yet.
+
+use std::fmt;
+#[cfg(unix)]
+use std::io;
+use std::mem::MaybeUninit;
+#[cfg(unix)]
+use std::os::fd::RawFd;
+
+/// A buffer that is read into, which keeps track of how much of it has been filled with the
+/// bytes that were read, and how much of it has been initialized, by a read or otherwise.
+///
+/// A `&mut [u8]` has to be initialized before anything is read into it, so a buffer that is read
+/// into once, like the spare capacity of a `Vec`, is zeroed first only to be overwritten. A
+/// `ReadBuf` can wrap memory that isn't initialized yet, with [`uninit`](ReadBuf::uninit), which
+/// the read methods that take one, like [`TcpStream::read_buf`](crate::net::TcpStream::read_buf),
+/// hand to the system as it is. The bytes a read returns are filled and initialized from then on,
+/// and a buffer that is read into again doesn't have to be initialized again either.
+///
+/// The buffer is made up of three regions, one after the other: the filled bytes, the bytes that
+/// are initialized but not filled, and the bytes that aren't initialized yet.
+///
+/// # Examples
+///
+/// Reading into the spare capacity of a `Vec`, without zeroing it:
+///
+/// ```
+/// use mini_executor::io::ReadBuf;
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let (left, right) = UnixStream::pair().unwrap();
+///     left.write_all(b"hello").await.unwrap();
+///
+///     let mut received = Vec::with_capacity(1024);
+///     let mut buf = ReadBuf::uninit(received.spare_capacity_mut());
+///     let read = right.read_buf(&mut buf).await.unwrap();
+///     // Safety: the read initialized the first `read` bytes of the spare capacity.
+///     unsafe { received.set_len(received.len() + read) };
+///     received
+/// });
+/// assert_eq!(received, b"hello");
+/// ```
+pub struct ReadBuf<'a> {
+    buf: &'a mut [MaybeUninit<u8>],
+    filled: usize,
+    initialized: usize,
+}
+
+impl<'a> ReadBuf<'a> {
+    /// Wrap `buf`, which is initialized already, with none of it filled.
+    pub fn new(buf: &'a mut [u8]) -> ReadBuf<'a> {
+        let initialized = buf.len();
+        // Safety: an initialized byte is a valid `MaybeUninit<u8>`, and the slice is never made
+        // uninitialized through the `ReadBuf`.
+        let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        ReadBuf {
+            buf,
+            filled: 0,
+            initialized,
+        }
+    }
+
+    /// Wrap `buf`, which may not be initialized, with none of it filled or initialized.
+    pub fn uninit(buf: &'a mut [MaybeUninit<u8>]) -> ReadBuf<'a> {
+        ReadBuf {
+            buf,
+            filled: 0,
+            initialized: 0,
+        }
+    }
+
+    /// The size of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The number of bytes that can still be filled.
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.filled
+    }
+
+    /// The filled bytes.
+    pub fn filled(&self) -> &[u8] {
+        // Safety: the filled bytes are initialized.
+        unsafe { slice_assume_init(&self.buf[..self.filled]) }
+    }
+
+    /// The filled bytes, to change them.
+    pub fn filled_mut(&mut self) -> &mut [u8] {
+        // Safety: the filled bytes are initialized.
+        unsafe { slice_assume_init_mut(&mut self.buf[..self.filled]) }
+    }
+
+    /// The initialized bytes, the filled ones among them.
+    pub fn initialized(&self) -> &[u8] {
+        // Safety: the bytes up to `initialized` are initialized.
+        unsafe { slice_assume_init(&self.buf[..self.initialized]) }
+    }
+
+    /// The initialized bytes, the filled ones among them, to change them.
+    pub fn initialized_mut(&mut self) -> &mut [u8] {
+        // Safety: the bytes up to `initialized` are initialized.
+        unsafe { slice_assume_init_mut(&mut self.buf[..self.initialized]) }
+    }
+
+    /// The bytes that aren't filled yet, some of which may not be initialized.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not make any of the bytes uninitialized, by writing
+    /// [`MaybeUninit::uninit`] to them, since the `ReadBuf` may consider them initialized.
+    pub unsafe fn unfilled_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        &mut self.buf[self.filled..]
+    }
+
+    /// Initialize the bytes that aren't filled yet, by zeroing the ones that aren't initialized,
+    /// and return them.
+    pub fn initialize_unfilled(&mut self) -> &mut [u8] {
+        self.initialize_unfilled_to(self.remaining())
+    }
+
+    /// Initialize the next `len` bytes that aren't filled yet, by zeroing the ones that aren't
+    /// initialized, and return them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `len` bytes remain.
+    pub fn initialize_unfilled_to(&mut self, len: usize) -> &mut [u8] {
+        assert!(self.remaining() >= len, "`len` must be within the buffer");
+        let end = self.filled + len;
+        if self.initialized < end {
+            for byte in &mut self.buf[self.initialized..end] {
+                byte.write(0);
+            }
+            self.initialized = end;
+        }
+        // Safety: the bytes up to `end` were just made sure to be initialized.
+        unsafe { slice_assume_init_mut(&mut self.buf[self.filled..end]) }
+    }
+
+    /// Mark the next `len` bytes as filled, after they have been written to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `len` of the bytes that aren't filled are initialized.
+    pub fn advance(&mut self, len: usize) {
+        let filled = self.filled.checked_add(len).expect("filled overflow");
+        self.set_filled(filled);
+    }
+
+    /// Mark the first `filled` bytes of the buffer as filled, and the rest as unfilled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `filled` bytes are initialized.
+    pub fn set_filled(&mut self, filled: usize) {
+        assert!(
+            filled <= self.initialized,
+            "filled must not become larger than initialized"
+        );
+        self.filled = filled;
+    }
+
+    /// Mark the next `len` bytes that aren't filled as initialized, after they have been written
+    /// to through [`unfilled_mut`](ReadBuf::unfilled_mut).
+    ///
+    /// # Safety
+    ///
+    /// The caller must have initialized those bytes.
+    pub unsafe fn assume_init(&mut self, len: usize) {
+        let end = self.filled + len;
+        if self.initialized < end {
+            self.initialized = end;
+        }
+    }
+
+    /// Mark none of the bytes as filled, while the ones that were stay initialized.
+    pub fn clear(&mut self) {
+        self.filled = 0;
+    }
+
+    /// Fill the next bytes with `bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `bytes.len()` bytes remain.
+    pub fn put_slice(&mut self, bytes: &[u8]) {
+        assert!(
+            self.remaining() >= bytes.len(),
+            "`bytes` must fit into the buffer"
+        );
+        let end = self.filled + bytes.len();
+        for (byte, &value) in self.buf[self.filled..end].iter_mut().zip(bytes) {
+            byte.write(value);
+        }
+        self.initialized = self.initialized.max(end);
+        self.filled = end;
+    }
+}
+
+impl fmt::Debug for ReadBuf<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadBuf")
+            .field("filled", &self.filled)
+            .field("initialized", &self.initialized)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// Read from `fd` into the bytes of `buf` that aren't filled yet, which may not be initialized,
+/// and fill the ones that were read.
+#[cfg(unix)]
+pub(crate) fn read_fd(fd: RawFd, buf: &mut ReadBuf<'_>) -> io::Result<usize> {
+    // Safety: `read` only writes bytes to the buffer, it never makes them uninitialized.
+    let unfilled = unsafe { buf.unfilled_mut() };
+    let read = unsafe { libc::read(fd, unfilled.as_mut_ptr().cast(), unfilled.len()) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let read = read as usize;
+    // Safety: `read` initialized the bytes it returned.
+    unsafe { buf.assume_init(read) };
+    buf.advance(read);
+    Ok(read)
+}
+
+/// # Safety
+///
+/// All bytes of `slice` must be initialized.
+unsafe fn slice_assume_init(slice: &[MaybeUninit<u8>]) -> &[u8] {
+    &*(slice as *const [MaybeUninit<u8>] as *const [u8])
+}
+
+/// # Safety
+///
+/// All bytes of `slice` must be initialized.
+unsafe fn slice_assume_init_mut(slice: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    &mut *(slice as *mut [MaybeUninit<u8>] as *mut [u8])
+}
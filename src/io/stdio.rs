@@ -0,0 +1,384 @@
+//! [`stdin`], [`stdout`] and [`stderr`], the standard streams of the process, whose reads and
+//! writes run on the blocking pool.
+
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::runtime::Handle;
+use crate::task::JoinHandle;
+
+/// The most bytes a single read or write hands to the blocking pool.
+const MAX_BUF: usize = 64 * 1024;
+
+/// A handle to the standard input of the process, whose reads run on the blocking pool of the
+/// current runtime.
+///
+/// The standard streams are often a terminal or a pipe, whose file descriptors are shared with
+/// other processes, so they aren't made non-blocking and registered with the I/O driver. A read
+/// waits for the next input on the blocking pool instead, so the other tasks of the thread keep
+/// running while a task waits for a line from the terminal, which they wouldn't if it read from
+/// [`std::io::stdin`].
+///
+/// A read that is in flight can't be cancelled: if the `Stdin` is dropped before it returns,
+/// what it reads is lost. Every handle reads from the same input, one at a time.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use mini_executor::io::{self, AsyncBufReadExt, BufReader};
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let mut lines = BufReader::new(io::stdin()).lines();
+///     let mut stdout = io::stdout();
+///     while let Some(line) = lines.next().await {
+///         let line = line.unwrap();
+///         stdout.write_all(format!("> {line}\n").as_bytes()).await.unwrap();
+///     }
+/// });
+/// ```
+pub struct Stdin {
+    state: ReadState,
+}
+
+/// What a [`Stdin`] is doing.
+enum ReadState {
+    /// Waiting to be polled, with the bytes a read returned that haven't been handed out.
+    Idle(Buf),
+    /// Reading on the blocking pool, which hands the buffer back.
+    Busy(JoinHandle<(io::Result<usize>, Buf)>),
+}
+
+/// The bytes a read returned, or the ones a write is writing.
+#[derive(Default)]
+struct Buf {
+    bytes: Vec<u8>,
+    /// The bytes a read returned from here on haven't been handed out yet.
+    pos: usize,
+}
+
+/// A handle to the standard input of the process, see [`Stdin`].
+pub fn stdin() -> Stdin {
+    Stdin {
+        state: ReadState::Idle(Buf::default()),
+    }
+}
+
+impl Stdin {
+    /// Read some bytes into `buf`, and return how many were read.
+    ///
+    /// Returns `0` at the end of the input, or if `buf` is empty.
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+}
+
+impl AsyncRead for Stdin {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        dst: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if dst.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            match &mut this.state {
+                ReadState::Idle(buf) if buf.pos < buf.bytes.len() => {
+                    return Poll::Ready(Ok(buf.copy_to(dst)));
+                }
+                ReadState::Idle(buf) => {
+                    let mut buf = std::mem::take(buf);
+                    let len = dst.len().min(MAX_BUF);
+                    let read = move || {
+                        buf.bytes.resize(len, 0);
+                        buf.pos = 0;
+                        let result = io::stdin().read(&mut buf.bytes);
+                        buf.bytes.truncate(*result.as_ref().unwrap_or(&0));
+                        (result, buf)
+                    };
+                    this.state = ReadState::Busy(Handle::current().spawn_blocking(read));
+                }
+                ReadState::Busy(handle) => match Pin::new(handle).poll(cx) {
+                    Poll::Ready(Ok((result, mut buf))) => {
+                        let copied = result.map(|_| buf.copy_to(dst));
+                        this.state = ReadState::Idle(buf);
+                        return Poll::Ready(copied);
+                    }
+                    Poll::Ready(Err(error)) => {
+                        this.state = ReadState::Idle(Buf::default());
+                        return Poll::Ready(Err(io::Error::other(error.to_string())));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for Stdin {
+    fn as_raw_fd(&self) -> RawFd {
+        io::stdin().as_raw_fd()
+    }
+}
+
+impl fmt::Debug for Stdin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stdin").finish_non_exhaustive()
+    }
+}
+
+/// A handle to the standard output of the process, whose writes run on the blocking pool of the
+/// current runtime.
+///
+/// A write copies the bytes into a buffer of the handle and returns right away, while they are
+/// written and flushed on the blocking pool; the next write waits for it, and the error of a
+/// write that failed is returned by the next write or flush. Since every write is flushed, small
+/// writes are better buffered with a [`BufWriter`](crate::io::BufWriter) first. The handle should
+/// be [`flush`](Stdout::flush)ed before it is dropped, or the runtime is shut down, to make sure
+/// the last write has been written.
+///
+/// The writes of one handle are written in order, but those of several handles, or of
+/// [`std::io::stdout`], may be interleaved.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let mut stdout = io::stdout();
+///     stdout.write_all(b"hello world\n").await.unwrap();
+///     stdout.flush().await.unwrap();
+/// });
+/// ```
+pub struct Stdout {
+    writer: Writer<io::Stdout>,
+}
+
+/// A handle to the standard error of the process, whose writes run on the blocking pool of the
+/// current runtime, like those of [`Stdout`].
+pub struct Stderr {
+    writer: Writer<io::Stderr>,
+}
+
+/// A handle to the standard output of the process, see [`Stdout`].
+pub fn stdout() -> Stdout {
+    Stdout {
+        writer: Writer::new(io::stdout),
+    }
+}
+
+/// A handle to the standard error of the process, see [`Stderr`].
+pub fn stderr() -> Stderr {
+    Stderr {
+        writer: Writer::new(io::stderr),
+    }
+}
+
+/// The writes to a standard stream of `std`, on the blocking pool.
+struct Writer<W> {
+    /// Returns the handle of `std` that is written to.
+    std: fn() -> W,
+    state: WriteState,
+    /// The error of a write that failed after `poll_write` had returned.
+    write_error: Option<io::Error>,
+}
+
+/// What a [`Writer`] is doing.
+enum WriteState {
+    Idle(Buf),
+    /// Writing on the blocking pool, which hands the buffer back.
+    Busy(JoinHandle<(io::Result<()>, Buf)>),
+}
+
+impl<W: Write + 'static> Writer<W> {
+    fn new(std: fn() -> W) -> Writer<W> {
+        Writer {
+            std,
+            state: WriteState::Idle(Buf::default()),
+            write_error: None,
+        }
+    }
+
+    /// Poll for the write in flight, and keep its error if it failed.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let WriteState::Busy(handle) = &mut self.state else {
+            return Poll::Ready(());
+        };
+        let (result, buf) = match Pin::new(handle).poll(cx) {
+            Poll::Ready(Ok(done)) => done,
+            // The write panicked, which loses the buffer.
+            Poll::Ready(Err(error)) => (Err(io::Error::other(error.to_string())), Buf::default()),
+            Poll::Pending => return Poll::Pending,
+        };
+        self.state = WriteState::Idle(buf);
+        if let Err(error) = result {
+            self.write_error = Some(error);
+        }
+        Poll::Ready(())
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, src: &[u8]) -> Poll<io::Result<usize>> {
+        if self.poll_idle(cx).is_pending() {
+            return Poll::Pending;
+        }
+        if let Some(error) = self.write_error.take() {
+            return Poll::Ready(Err(error));
+        }
+        if src.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let WriteState::Idle(buf) = &mut self.state else {
+            unreachable!("the writer is idle");
+        };
+
+        let len = src.len().min(MAX_BUF);
+        let mut buf = std::mem::take(buf);
+        buf.bytes.clear();
+        buf.bytes.extend_from_slice(&src[..len]);
+        let std = self.std;
+        let write = move || {
+            let mut std = std();
+            let result = std.write_all(&buf.bytes).and_then(|()| std.flush());
+            (result, buf)
+        };
+        self.state = WriteState::Busy(Handle::current().spawn_blocking(write));
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.poll_idle(cx).is_pending() {
+            return Poll::Pending;
+        }
+        Poll::Ready(self.write_error.take().map_or(Ok(()), Err))
+    }
+}
+
+impl Stdout {
+    /// Write some bytes of `buf`, and return how many were written, which are written on the
+    /// blocking pool once this returns, see [`Stdout`].
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.writer.poll_write(cx, buf)).await
+    }
+
+    /// Write all of `buf`, see [`write`](Stdout::write).
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let written = self.write(buf).await?;
+            buf = &buf[written..];
+        }
+        Ok(())
+    }
+
+    /// Wait until the last write has been written, and return its error if it failed.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        poll_fn(|cx| self.writer.poll_flush(cx)).await
+    }
+}
+
+impl Stderr {
+    /// Write some bytes of `buf`, and return how many were written, which are written on the
+    /// blocking pool once this returns, see [`Stdout`].
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.writer.poll_write(cx, buf)).await
+    }
+
+    /// Write all of `buf`, see [`write`](Stderr::write).
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let written = self.write(buf).await?;
+            buf = &buf[written..];
+        }
+        Ok(())
+    }
+
+    /// Wait until the last write has been written, and return its error if it failed.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        poll_fn(|cx| self.writer.poll_flush(cx)).await
+    }
+}
+
+impl AsyncWrite for Stdout {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().writer.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().writer.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncWrite for Stderr {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().writer.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().writer.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for Stdout {
+    fn as_raw_fd(&self) -> RawFd {
+        io::stdout().as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for Stderr {
+    fn as_raw_fd(&self) -> RawFd {
+        io::stderr().as_raw_fd()
+    }
+}
+
+impl fmt::Debug for Stdout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stdout").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for Stderr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stderr").finish_non_exhaustive()
+    }
+}
+
+impl Buf {
+    /// Hand out as many bytes that haven't been handed out as fit into `dst`.
+    fn copy_to(&mut self, dst: &mut [u8]) -> usize {
+        let len = (self.bytes.len() - self.pos).min(dst.len());
+        dst[..len].copy_from_slice(&self.bytes[self.pos..self.pos + len]);
+        self.pos += len;
+        len
+    }
+}
@@ -0,0 +1,142 @@
+//! [`SyncIoBridge`], which lets blocking code read from and write to an async stream.
+
+use std::future::poll_fn;
+use std::io::{self, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
+use std::pin::Pin;
+
+use super::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use crate::runtime::block_on;
+
+/// An async stream as a blocking [`Read`], [`Write`] and [`Seek`], for the libraries that only
+/// take those, like the ones that compress or archive.
+///
+/// Every call blocks the thread until the stream is ready, while the I/O driver of the runtime
+/// the stream was created on goes on running on the threads of the runtime, and wakes this one.
+/// It is meant for a closure on the blocking pool, passed to
+/// [`spawn_blocking`](crate::runtime::Runtime::spawn_blocking), which streams the bytes through
+/// the library in chunks, instead of collecting all of them in memory first to hand them over at
+/// once.
+///
+/// # Panics
+///
+/// The methods of the traits panic if called from within a future that is being driven by an
+/// executor, like a task, since blocking that thread would keep the executor from waking it.
+///
+/// # Examples
+///
+/// Counting the lines a peer sends with the blocking [`BufRead`](std::io::BufRead) of `std`:
+///
+/// ```
+/// use std::io::{BufRead, BufReader};
+///
+/// use mini_executor::io::SyncIoBridge;
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::{Handle, Runtime};
+///
+/// let executor = Runtime::new();
+/// let lines = executor.block_on(async {
+///     let (client, server) = UnixStream::pair().unwrap();
+///     mini_executor::spawn(async move {
+///         for _ in 0..1000 {
+///             client.write_all(b"a line\n").await.unwrap();
+///         }
+///     });
+///
+///     let count = Handle::current().spawn_blocking(move || {
+///         let reader = BufReader::new(SyncIoBridge::new(server));
+///         reader.lines().map(|line| line.unwrap()).count()
+///     });
+///     count.await.unwrap()
+/// });
+/// assert_eq!(lines, 1000);
+/// ```
+#[derive(Debug)]
+pub struct SyncIoBridge<T> {
+    io: T,
+}
+
+impl<T> SyncIoBridge<T> {
+    /// Wrap `io`.
+    pub fn new(io: T) -> SyncIoBridge<T> {
+        SyncIoBridge { io }
+    }
+
+    /// Return a reference to the stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Return a mutable reference to the stream.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Return the stream.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+impl<T: AsyncWrite + Unpin> SyncIoBridge<T> {
+    /// Close the stream, blocking until it is closed, like [`close`](AsyncWriteExt::close).
+    ///
+    /// [`Write`] has no counterpart of it, so once the library is done writing, this shuts down
+    /// the writing half of a [`TcpStream`](crate::net::TcpStream), for the peer to see the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within a future that is being driven by an executor.
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        block_on(self.io.close())
+    }
+}
+
+impl<T: AsyncRead + Unpin> Read for SyncIoBridge<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        block_on(self.io.read(buf))
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        block_on(poll_fn(|cx| {
+            Pin::new(&mut self.io).poll_read_vectored(cx, bufs)
+        }))
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        block_on(self.io.read_to_end(buf))
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        block_on(self.io.read_to_string(buf))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        block_on(self.io.read_exact(buf))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> Write for SyncIoBridge<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on(self.io.write(buf))
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        block_on(poll_fn(|cx| {
+            Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+        }))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        block_on(self.io.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        block_on(self.io.flush())
+    }
+}
+
+impl<T: AsyncSeek + Unpin> Seek for SyncIoBridge<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        block_on(self.io.seek(pos))
+    }
+}
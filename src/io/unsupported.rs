@@ -0,0 +1,55 @@
+//! The selector of the I/O driver on platforms that don't have one, which can't be created.
+
+use std::io;
+use std::time::Duration;
+
+use super::{Interest, Ready};
+
+/// What a source is registered by.
+#[cfg(unix)]
+pub(super) type Source = std::os::fd::RawFd;
+#[cfg(not(unix))]
+pub(super) type Source = ();
+
+pub(super) enum Selector {}
+
+pub(super) struct Events;
+
+impl Events {
+    pub(super) fn with_capacity(_capacity: usize) -> Self {
+        Self
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, Ready)> + '_ {
+        std::iter::empty()
+    }
+}
+
+impl Selector {
+    pub(super) fn new() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "there is no I/O driver for this platform",
+        ))
+    }
+
+    pub(super) fn register(&self, _: Source, _: usize, _: Interest) -> io::Result<()> {
+        match *self {}
+    }
+
+    pub(super) fn deregister(&self, _: Source) -> io::Result<()> {
+        match *self {}
+    }
+
+    pub(super) fn rearm(&self, _: usize, _: Ready) -> io::Result<()> {
+        match *self {}
+    }
+
+    pub(super) fn select(&self, _: &mut Events, _: Option<Duration>) -> io::Result<()> {
+        match *self {}
+    }
+
+    pub(super) fn wake(&self) -> io::Result<()> {
+        match *self {}
+    }
+}
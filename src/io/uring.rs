@@ -0,0 +1,628 @@
+//! Reading and writing files and sockets through `io_uring` on Linux, with the `io_uring`
+//! feature.
+//!
+//! The rest of the [`io`](crate::io) module is built on readiness: the driver tells a task when a
+//! source can be read from, and the task then does the read itself, which may still find nothing
+//! and have to wait again. `io_uring` works with completions instead: a task hands the whole
+//! operation, with its buffer, to the kernel, and is woken once the kernel is done with it. The
+//! two models meet in the I/O driver of the runtime, which watches the ring for completions like
+//! any other source, so a thread that waits for I/O events also wakes up for them.
+//!
+//! Since the kernel writes into the buffer of an operation while it is in flight, the functions of
+//! this module take ownership of the buffer, and hand it back with the result. An operation whose
+//! future is dropped early is canceled, and its buffer is only freed once the kernel has let go of
+//! it. The same goes for an operation the kernel fails to take from the queue: the kernel may
+//! still take it later, so its error is handed back with an empty buffer instead.
+//!
+//! The ring is set up the first time an operation is started on a runtime. On a kernel without
+//! `io_uring`, or where it isn't allowed, like in some containers, every operation fails with the
+//! error of that setup.
+//!
+//! # Examples
+//!
+//! ```
+//! use mini_executor::io::uring;
+//! use mini_executor::runtime::Runtime;
+//! use std::io::Write;
+//!
+//! let path = std::env::temp_dir().join("mini-executor-uring-example");
+//! std::fs::File::create(&path)
+//!     .unwrap()
+//!     .write_all(b"hello world")
+//!     .unwrap();
+//!
+//! let executor = Runtime::new();
+//! let read = executor.block_on(async {
+//!     let file = std::fs::File::open(&path).unwrap();
+//!     match uring::read_at(&file, vec![0; 5], 6).await {
+//!         (Ok(read), buf) => Some(buf[..read].to_vec()),
+//!         // Skipped on a kernel without `io_uring`, or one that doesn't allow it here.
+//!         (Err(error), _) if matches!(error.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)) => {
+//!             None
+//!         }
+//!         (Err(error), _) => panic!("the read failed: {error}"),
+//!     }
+//! });
+//! if let Some(read) = read {
+//!     assert_eq!(read, b"world");
+//! }
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use super::Driver;
+use crate::sync::Mutex;
+
+/// The number of entries of the submission queue; the completion queue gets twice as many.
+const ENTRIES: u32 = 256;
+
+/// The `user_data` of the cancellations, whose completions aren't waited for.
+const CANCEL: u64 = u64::MAX;
+
+/// The offset that makes a read or write use, and move, the position of the file instead.
+const CURRENT_POSITION: u64 = u64::MAX;
+
+const IORING_OP_ASYNC_CANCEL: u8 = 14;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_FEAT_SINGLE_MMAP: u32 = 1;
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+
+/// Read from `fd` into `buf`, at the current position if it is a file, and hand back the buffer
+/// with the number of bytes read.
+///
+/// This reads up to `buf.len()` bytes, so the buffer is usually created with `vec![0; len]`.
+///
+/// # Panics
+///
+/// Panics if called outside of a [`Runtime`], or if its I/O driver isn't enabled.
+///
+/// [`Runtime`]: crate::runtime::Runtime
+pub async fn read<F: AsFd>(fd: &F, buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+    read_at(fd, buf, CURRENT_POSITION).await
+}
+
+/// Read from the file `fd` into `buf`, starting at `offset`, and hand back the buffer with the
+/// number of bytes read. The position of the file is left as it is.
+///
+/// # Panics
+///
+/// Panics if called outside of a [`Runtime`], or if its I/O driver isn't enabled.
+///
+/// [`Runtime`]: crate::runtime::Runtime
+pub async fn read_at<F: AsFd>(
+    fd: &F,
+    mut buf: Vec<u8>,
+    offset: u64,
+) -> (io::Result<usize>, Vec<u8>) {
+    let mut entry = Entry::new(IORING_OP_READ, fd.as_fd().as_raw_fd());
+    entry.off = offset;
+    entry.addr = buf.as_mut_ptr() as u64;
+    entry.len = buf.len().min(u32::MAX as usize) as u32;
+    Op::run(entry, buf).await
+}
+
+/// Write `buf` to `fd`, at the current position if it is a file, and hand back the buffer with
+/// the number of bytes written.
+///
+/// # Panics
+///
+/// Panics if called outside of a [`Runtime`], or if its I/O driver isn't enabled.
+///
+/// [`Runtime`]: crate::runtime::Runtime
+pub async fn write<F: AsFd>(fd: &F, buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+    write_at(fd, buf, CURRENT_POSITION).await
+}
+
+/// Write `buf` to the file `fd`, starting at `offset`, and hand back the buffer with the number
+/// of bytes written. The position of the file is left as it is.
+///
+/// # Panics
+///
+/// Panics if called outside of a [`Runtime`], or if its I/O driver isn't enabled.
+///
+/// [`Runtime`]: crate::runtime::Runtime
+pub async fn write_at<F: AsFd>(fd: &F, buf: Vec<u8>, offset: u64) -> (io::Result<usize>, Vec<u8>) {
+    let mut entry = Entry::new(IORING_OP_WRITE, fd.as_fd().as_raw_fd());
+    entry.off = offset;
+    entry.addr = buf.as_ptr() as u64;
+    entry.len = buf.len().min(u32::MAX as usize) as u32;
+    Op::run(entry, buf).await
+}
+
+/// An operation in flight, which owns the buffer the kernel reads from or writes to.
+struct Op {
+    ring: Arc<Ring>,
+    id: u64,
+    /// Taken once the operation has completed, or handed to the ring if it is dropped before.
+    buf: Option<Vec<u8>>,
+}
+
+impl Op {
+    async fn run(entry: Entry, buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+        let ring = match Driver::current().uring() {
+            Ok(ring) => ring,
+            Err(error) => return (Err(error), buf),
+        };
+        let id = match ring.push_op(entry) {
+            Ok(id) => id,
+            Err(error) => return (Err(error), buf),
+        };
+        let mut op = Op {
+            ring,
+            id,
+            buf: Some(buf),
+        };
+        if let Err(error) = op.ring.enter(0) {
+            // The entry is still queued, and the next submission may hand it to the kernel, so
+            // the buffer is left with the ring until the operation is done or canceled.
+            drop(op);
+            return (Err(error), Vec::new());
+        }
+        let result = poll_fn(|cx| op.ring.poll(op.id, cx)).await;
+        let buf = op
+            .buf
+            .take()
+            .expect("the buffer of an operation is only taken once");
+        if result < 0 {
+            (Err(io::Error::from_raw_os_error(-result)), buf)
+        } else {
+            (Ok(result as usize), buf)
+        }
+    }
+}
+
+impl Drop for Op {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.ring.abandon(self.id, buf);
+        }
+    }
+}
+
+/// A submission queue entry, as the kernel lays it out.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Entry {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+impl Entry {
+    fn new(opcode: u8, fd: RawFd) -> Self {
+        // Safety: an entry is plain data, and all zeros is an entry without options.
+        let mut entry: Entry = unsafe { mem::zeroed() };
+        entry.opcode = opcode;
+        entry.fd = fd;
+        entry
+    }
+}
+
+/// A completion queue entry, as the kernel lays it out.
+#[repr(C)]
+struct Completion {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SubmissionOffsets,
+    cq_off: CompletionOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SubmissionOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CompletionOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+/// An `io_uring` instance, with the operations in flight on it.
+pub(super) struct Ring {
+    submissions: Mutex<SubmissionQueue>,
+    completions: Mutex<CompletionQueue>,
+    ops: Mutex<Ops>,
+    // Unmapped after the queues that point into them are gone, and before the ring is closed.
+    _mappings: Vec<Mapping>,
+    fd: OwnedFd,
+}
+
+/// The queue the kernel takes operations from, mapped from the ring.
+struct SubmissionQueue {
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    entries: u32,
+    array: *mut u32,
+    sqes: *mut Entry,
+}
+
+/// The queue the kernel puts the results of operations in, mapped from the ring.
+struct CompletionQueue {
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    cqes: *const Completion,
+}
+
+// Safety: the pointers point into the mappings of the ring, which live as long as it does, and
+// are only used under the locks of the queues.
+unsafe impl Send for SubmissionQueue {}
+unsafe impl Send for CompletionQueue {}
+
+/// The operations in flight on a ring, by the `user_data` they were submitted with.
+struct Ops {
+    states: HashMap<u64, State>,
+    /// The id of the next operation, never reused, so a late completion can't reach another one.
+    next_id: u64,
+}
+
+enum State {
+    /// Submitted, with the waker of the task that waits for it.
+    Waiting(Option<Waker>),
+    /// Completed with this result, which the task hasn't seen yet.
+    Completed(i32),
+    /// Canceled by dropping its future, with the buffer the kernel may still be using.
+    Abandoned { _buf: Vec<u8> },
+}
+
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// Safety: a mapping is only unmapped, on drop.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// The field at `offset` bytes into the mapping.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.cast::<u8>().add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+impl Ring {
+    pub(super) fn new() -> io::Result<Self> {
+        let mut params = Params::default();
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, ENTRIES, &mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: the descriptor was just created, and is owned from here on.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        let raw = fd.as_raw_fd();
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Completion>();
+        let mut mappings = Vec::with_capacity(3);
+        if params.features & IORING_FEAT_SINGLE_MMAP != 0 {
+            mappings.push(Mapping::new(raw, sq_len.max(cq_len), IORING_OFF_SQ_RING)?);
+        } else {
+            mappings.push(Mapping::new(raw, sq_len, IORING_OFF_SQ_RING)?);
+            mappings.push(Mapping::new(raw, cq_len, IORING_OFF_CQ_RING)?);
+        }
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Entry>();
+        mappings.push(Mapping::new(raw, sqes_len, IORING_OFF_SQES)?);
+
+        let (sq, cq, sqes) = (
+            &mappings[0],
+            &mappings[mappings.len() - 2],
+            &mappings[mappings.len() - 1],
+        );
+        // Safety: the offsets come from the kernel, and point into the mappings.
+        let submissions = unsafe {
+            SubmissionQueue {
+                head: sq.at(params.sq_off.head),
+                tail: sq.at(params.sq_off.tail),
+                mask: *sq.at::<u32>(params.sq_off.ring_mask),
+                entries: *sq.at::<u32>(params.sq_off.ring_entries),
+                array: sq.at(params.sq_off.array),
+                sqes: sqes.at(0),
+            }
+        };
+        let completions = unsafe {
+            CompletionQueue {
+                head: cq.at(params.cq_off.head),
+                tail: cq.at(params.cq_off.tail),
+                mask: *cq.at::<u32>(params.cq_off.ring_mask),
+                cqes: cq.at(params.cq_off.cqes),
+            }
+        };
+        Ok(Self {
+            submissions: Mutex::new(submissions),
+            completions: Mutex::new(completions),
+            ops: Mutex::new(Ops {
+                states: HashMap::new(),
+                next_id: 0,
+            }),
+            _mappings: mappings,
+            fd,
+        })
+    }
+
+    /// The descriptor of the ring, which is readable while there are completions to take.
+    pub(super) fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Put `entry` in the submission queue, and return the id its completion is reported with.
+    ///
+    /// It is handed to the kernel by the next [`enter`](Ring::enter).
+    fn push_op(&self, mut entry: Entry) -> io::Result<u64> {
+        let id = {
+            let mut ops = self.ops.lock();
+            let id = ops.next_id;
+            ops.next_id += 1;
+            ops.states.insert(id, State::Waiting(None));
+            id
+        };
+        entry.user_data = id;
+        if !self.push(entry) {
+            self.ops.lock().states.remove(&id);
+            return Err(io::Error::other("the `io_uring` submission queue is full"));
+        }
+        Ok(id)
+    }
+
+    /// Put `entry` in the submission queue, unless it is full.
+    fn push(&self, entry: Entry) -> bool {
+        let queue = self.submissions.lock();
+        // Safety: the head is only written by the kernel, and the tail only under this lock.
+        let (head, tail) = unsafe {
+            (
+                (*queue.head).load(Ordering::Acquire),
+                (*queue.tail).load(Ordering::Relaxed),
+            )
+        };
+        if tail.wrapping_sub(head) == queue.entries {
+            return false;
+        }
+        let index = tail & queue.mask;
+        unsafe {
+            queue.sqes.add(index as usize).write(entry);
+            queue.array.add(index as usize).write(index);
+            (*queue.tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        true
+    }
+
+    /// Submit the entries the kernel hasn't taken yet, and wait for `min_complete` completions.
+    fn enter(&self, min_complete: u32) -> io::Result<()> {
+        let pending = {
+            let queue = self.submissions.lock();
+            unsafe {
+                (*queue.tail)
+                    .load(Ordering::Relaxed)
+                    .wrapping_sub((*queue.head).load(Ordering::Acquire))
+            }
+        };
+        let flags = if min_complete > 0 {
+            IORING_ENTER_GETEVENTS
+        } else {
+            0
+        };
+        loop {
+            let result = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.fd.as_raw_fd(),
+                    pending,
+                    min_complete,
+                    flags,
+                    ptr::null::<libc::sigset_t>(),
+                    0usize,
+                )
+            };
+            if result >= 0 {
+                return Ok(());
+            }
+            let error = io::Error::last_os_error();
+            match error.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // The completion queue is full, so the entries are submitted again once
+                // completions have been taken, by `complete`.
+                Some(libc::EAGAIN) | Some(libc::EBUSY) => return Ok(()),
+                _ => return Err(error),
+            }
+        }
+    }
+
+    /// Poll the operation `id`, and return its result once it has completed.
+    fn poll(&self, id: u64, cx: &mut Context<'_>) -> Poll<i32> {
+        let mut ops = self.ops.lock();
+        match ops.states.get_mut(&id) {
+            Some(State::Completed(result)) => {
+                let result = *result;
+                ops.states.remove(&id);
+                Poll::Ready(result)
+            }
+            Some(State::Waiting(waker)) => {
+                super::register(waker, cx.waker());
+                Poll::Pending
+            }
+            Some(State::Abandoned { .. }) | None => {
+                unreachable!("polled an operation that is gone")
+            }
+        }
+    }
+
+    /// Give up on the operation `id`, whose future was dropped, and keep `buf` until the kernel
+    /// is done with it.
+    fn abandon(&self, id: u64, buf: Vec<u8>) {
+        let mut ops = self.ops.lock();
+        let Some(state) = ops.states.get_mut(&id) else {
+            return;
+        };
+        if let State::Completed(_) = state {
+            ops.states.remove(&id);
+            drop(ops);
+            return;
+        }
+        *state = State::Abandoned { _buf: buf };
+        drop(ops);
+        self.cancel(id);
+    }
+
+    /// Ask the kernel to cancel the operation `id`, which may fail if it is about to complete.
+    fn cancel(&self, id: u64) {
+        let mut entry = Entry::new(IORING_OP_ASYNC_CANCEL, -1);
+        entry.addr = id;
+        entry.user_data = CANCEL;
+        if self.push(entry) {
+            let _ = self.enter(0);
+        }
+    }
+
+    /// Take the completions the kernel has put in the queue, and wake the tasks that wait for
+    /// them. Returns whether any tasks were woken.
+    pub(super) fn complete(&self) -> bool {
+        // Entries the kernel couldn't take while the completion queue was full.
+        let _ = self.enter(0);
+
+        let completed: Vec<(u64, i32)> = {
+            let queue = self.completions.lock();
+            // Safety: the tail is only written by the kernel, and the head only under this lock.
+            let (mut head, tail) = unsafe {
+                (
+                    (*queue.head).load(Ordering::Relaxed),
+                    (*queue.tail).load(Ordering::Acquire),
+                )
+            };
+            let mut completed = Vec::with_capacity(tail.wrapping_sub(head) as usize);
+            while head != tail {
+                let completion = unsafe { &*queue.cqes.add((head & queue.mask) as usize) };
+                completed.push((completion.user_data, completion.res));
+                head = head.wrapping_add(1);
+            }
+            unsafe { (*queue.head).store(head, Ordering::Release) };
+            completed
+        };
+
+        let mut wakers = Vec::new();
+        let mut abandoned = Vec::new();
+        {
+            let mut ops = self.ops.lock();
+            for (id, result) in completed {
+                if id == CANCEL {
+                    continue;
+                }
+                match ops.states.get_mut(&id) {
+                    Some(State::Waiting(waker)) => {
+                        wakers.extend(waker.take());
+                        ops.states.insert(id, State::Completed(result));
+                    }
+                    Some(State::Abandoned { .. }) => abandoned.extend(ops.states.remove(&id)),
+                    Some(State::Completed(_)) | None => {}
+                }
+            }
+        }
+        // Woken and dropped outside of the lock, since waking a task may start another operation.
+        let woken = !wakers.is_empty();
+        for waker in wakers {
+            waker.wake();
+        }
+        drop(abandoned);
+        woken
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // Every operation left was abandoned, and the kernel may still be using its buffer, so
+        // the ring waits for it before it unmaps the queues and the buffers are freed.
+        // They are canceled again, in case the queue was full when they were abandoned.
+        let abandoned: Vec<u64> = self.ops.lock().states.keys().copied().collect();
+        for id in abandoned {
+            self.cancel(id);
+        }
+        while !self.ops.lock().states.is_empty() {
+            if self.enter(1).is_err() {
+                return;
+            }
+            self.complete();
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! The selector of the I/O driver on Windows, which emulates readiness events with `WSAPoll`.
+//!
+//! `WSAPoll` only reports whether a socket is ready right now, so the selector keeps the sockets
+//! registered with it, and polls all of them at once. To report a source once each time it becomes
+//! ready, like the edge-triggered `epoll` on Linux, a direction that was reported is left out of
+//! the poll until an operation on it would block and [`rearm`](Selector::rearm) is called. A
+//! loopback UDP socket that is sent a byte wakes the thread that waits in `WSAPoll`, which also
+//! happens whenever the set of sockets to poll changes.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::UdpSocket;
+use std::os::windows::io::{AsRawSocket, RawSocket};
+use std::time::Duration;
+
+use super::{Interest, Ready};
+use crate::sync::Mutex;
+
+/// What a source is registered by.
+pub(super) type Source = RawSocket;
+
+/// The token of the socket that wakes the selector, which no registration gets.
+const WAKE_TOKEN: usize = usize::MAX;
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[repr(C)]
+struct WSAPOLLFD {
+    fd: usize,
+    events: i16,
+    revents: i16,
+}
+
+const POLLRDNORM: i16 = 0x0100;
+const POLLRDBAND: i16 = 0x0200;
+const POLLWRNORM: i16 = 0x0010;
+const POLLERR: i16 = 0x0001;
+const POLLHUP: i16 = 0x0002;
+const POLLNVAL: i16 = 0x0004;
+
+#[link(name = "ws2_32")]
+extern "system" {
+    fn WSAPoll(fds: *mut WSAPOLLFD, count: u32, timeout: i32) -> i32;
+    fn WSAGetLastError() -> i32;
+}
+
+/// The sockets registered with the selector, and the socket pair that wakes it.
+pub(super) struct Selector {
+    sources: Mutex<HashMap<usize, Entry>>,
+    wake_receiver: UdpSocket,
+    wake_sender: UdpSocket,
+}
+
+/// A registered socket.
+struct Entry {
+    socket: RawSocket,
+    /// The directions the socket was registered for, and hasn't been reported ready in since it
+    /// was last rearmed.
+    armed: Interest,
+    interest: Interest,
+}
+
+/// The events returned by [`Selector::select`].
+pub(super) struct Events(Vec<(usize, Ready)>);
+
+impl Events {
+    pub(super) fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    /// The token and readiness of every event.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, Ready)> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl Selector {
+    pub(super) fn new() -> io::Result<Self> {
+        let wake_receiver = UdpSocket::bind("127.0.0.1:0")?;
+        let wake_sender = UdpSocket::bind("127.0.0.1:0")?;
+        wake_sender.connect(wake_receiver.local_addr()?)?;
+        wake_receiver.set_nonblocking(true)?;
+        wake_sender.set_nonblocking(true)?;
+        Ok(Self {
+            sources: Mutex::new(HashMap::new()),
+            wake_receiver,
+            wake_sender,
+        })
+    }
+
+    pub(super) fn register(
+        &self,
+        socket: Source,
+        token: usize,
+        interest: Interest,
+    ) -> io::Result<()> {
+        let entry = Entry {
+            socket,
+            armed: interest,
+            interest,
+        };
+        self.sources.lock().insert(token, entry);
+        // The thread waiting in `WSAPoll` polls the new socket from its next call on.
+        self.wake()
+    }
+
+    pub(super) fn deregister(&self, socket: Source) -> io::Result<()> {
+        self.sources
+            .lock()
+            .retain(|_, entry| entry.socket != socket);
+        Ok(())
+    }
+
+    /// Poll the directions of `ready` again, once an operation on them would block.
+    pub(super) fn rearm(&self, token: usize, ready: Ready) -> io::Result<()> {
+        let mut sources = self.sources.lock();
+        let Some(entry) = sources.get_mut(&token) else {
+            return Ok(());
+        };
+        let mut armed = entry.armed;
+        if ready.is_readable() && entry.interest.is_readable() {
+            armed = armed | Interest::READABLE;
+        }
+        if ready.is_writable() && entry.interest.is_writable() {
+            armed = armed | Interest::WRITABLE;
+        }
+        if armed == entry.armed {
+            return Ok(());
+        }
+        entry.armed = armed;
+        drop(sources);
+        self.wake()
+    }
+
+    /// Wait until a registered socket is ready, the selector is woken, or `timeout` has passed,
+    /// and fill `events` with what happened.
+    pub(super) fn select(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        // Rounded up, so that a timeout below a millisecond doesn't turn into a busy loop.
+        let timeout = timeout.map_or(-1, |timeout| {
+            let millis = timeout.as_nanos().div_ceil(1_000_000);
+            millis.min(i32::MAX as u128) as i32
+        });
+
+        let mut tokens = vec![WAKE_TOKEN];
+        let mut fds = vec![WSAPOLLFD {
+            fd: self.wake_receiver.as_raw_socket() as usize,
+            events: POLLRDNORM,
+            revents: 0,
+        }];
+        for (&token, entry) in self.sources.lock().iter() {
+            let mut poll = 0;
+            if entry.armed.is_readable() {
+                poll |= POLLRDNORM;
+            }
+            if entry.armed.is_writable() {
+                poll |= POLLWRNORM;
+            }
+            // A socket that failed or was closed would otherwise be reported over and over.
+            if poll != 0 {
+                tokens.push(token);
+                fds.push(WSAPOLLFD {
+                    fd: entry.socket as usize,
+                    events: poll,
+                    revents: 0,
+                });
+            }
+        }
+
+        events.0.clear();
+        // Safety: `fds` is a valid array of `fds.len()` entries.
+        let count = unsafe { WSAPoll(fds.as_mut_ptr(), fds.len() as u32, timeout) };
+        if count < 0 {
+            return Err(io::Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+        }
+
+        let mut sources = self.sources.lock();
+        for (&token, fd) in tokens.iter().zip(&fds) {
+            if fd.revents == 0 {
+                continue;
+            }
+            if token == WAKE_TOKEN {
+                // Empty the socket, so that it doesn't fill up.
+                let mut buf = [0; 64];
+                while self.wake_receiver.recv(&mut buf).is_ok() {}
+                continue;
+            }
+            let ready = ready(fd.revents);
+            // The source may have been deregistered while the thread was waiting.
+            let Some(entry) = sources.get_mut(&token) else {
+                continue;
+            };
+            entry.armed = disarm(entry.armed, ready);
+            events.0.push((token, ready));
+        }
+        Ok(())
+    }
+
+    /// Wake the thread that waits in [`select`](Selector::select), or make its next call return
+    /// right away.
+    pub(super) fn wake(&self) -> io::Result<()> {
+        match self.wake_sender.send(&[1]) {
+            Ok(_) => Ok(()),
+            // The socket is full, so the thread is going to wake up anyway.
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// The directions of `armed` that are left to poll, once `ready` was reported.
+fn disarm(armed: Interest, ready: Ready) -> Interest {
+    if ready.is_error() || (ready.is_read_closed() && ready.is_write_closed()) {
+        return Interest(0);
+    }
+    let mut left = 0;
+    if armed.is_readable() && !ready.is_readable() && !ready.is_read_closed() {
+        left |= Interest::READABLE.0;
+    }
+    if armed.is_writable() && !ready.is_writable() && !ready.is_write_closed() {
+        left |= Interest::WRITABLE.0;
+    }
+    Interest(left)
+}
+
+/// The readiness that `WSAPoll` reports.
+fn ready(revents: i16) -> Ready {
+    let mut ready = Ready::EMPTY;
+    if revents & (POLLRDNORM | POLLRDBAND) != 0 {
+        ready = ready | Ready::READABLE;
+    }
+    if revents & POLLWRNORM != 0 {
+        ready = ready | Ready::WRITABLE;
+    }
+    if revents & POLLHUP != 0 {
+        ready = ready | Ready::READABLE | Ready::READ_CLOSED | Ready::WRITE_CLOSED;
+    }
+    if revents & (POLLERR | POLLNVAL) != 0 {
+        ready = ready | Ready::ERROR;
+    }
+    ready
+}
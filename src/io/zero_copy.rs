@@ -0,0 +1,320 @@
+//! [`splice`] and [`sendfile`], which move bytes between file descriptors inside the kernel on
+//! Linux.
+
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+
+use super::{cvt, pipe_fds, read_fd, Interest, ReadBuf, Registration};
+
+/// The most bytes that are moved through the pipe of a [`splice`] at once, which is the size of a
+/// pipe unless it was changed.
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// The most bytes a single `sendfile` call moves, on every version of Linux.
+const MAX_SENDFILE: u64 = 0x7fff_f000;
+
+/// The size of the buffer [`copy_fd`] copies through, if the kernel can't splice.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// An I/O object whose file descriptor is registered with the I/O driver, which [`splice`] and
+/// [`sendfile`] wait for until it is ready.
+///
+/// It is implemented by the byte streams of the crate, like [`TcpStream`](crate::net::TcpStream),
+/// and the ends of a [`pipe`](super::pipe), and by [`AsyncFd`](super::AsyncFd), for the file
+/// descriptors the crate has no type for, like a character device. An I/O type written by hand
+/// on top of a [`Registration`] implements it by returning that.
+pub trait AsRegistration: AsRawFd {
+    /// The registration of the file descriptor of `self`.
+    fn registration(&self) -> &Registration;
+}
+
+/// Move everything `reader` reads into `writer`, until `reader` reaches its end, without copying
+/// it through userspace, and return the number of bytes that were moved.
+///
+/// This is [`copy`](super::copy) for two file descriptors on Linux: the bytes go from `reader`
+/// into a pipe, and from the pipe into `writer`, with `splice`, so the kernel hands on its own
+/// buffers rather than copying them into a buffer of the process and back. The pipe is created
+/// for the call, and the read and write timeouts of the streams don't apply to it. [`copy_fd`]
+/// does the same, but copies through a buffer instead of failing if the kernel can't splice.
+///
+/// # Errors
+///
+/// Returns the first error of reading or writing, with some of the bytes moved already, or an
+/// error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if the kernel can't splice
+/// one of the file descriptors, like a UDP socket or a file opened for appending.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::{self, AsyncReadExt};
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let (client, proxy_in) = UnixStream::pair().unwrap();
+///     let (proxy_out, server) = UnixStream::pair().unwrap();
+///     client.write_all(b"hello").await.unwrap();
+///     drop(client);
+///
+///     let moved = io::splice(&proxy_in, &proxy_out).await.unwrap();
+///     assert_eq!(moved, 5);
+///     drop(proxy_out);
+///
+///     let mut received = Vec::new();
+///     (&server).read_to_end(&mut received).await.unwrap();
+///     received
+/// });
+/// assert_eq!(received, b"hello");
+/// ```
+pub async fn splice<R, W>(reader: &R, writer: &W) -> io::Result<u64>
+where
+    R: AsRegistration + ?Sized,
+    W: AsRegistration + ?Sized,
+{
+    splice_all(reader, writer, false).await
+}
+
+/// Copy everything `reader` reads into `writer`, until `reader` reaches its end, and return the
+/// number of bytes that were copied, through the kernel where it can.
+///
+/// This is [`copy`](super::copy) for the byte streams of the crate on Linux, like a proxy between
+/// two sockets: it moves the bytes with [`splice`], and if the kernel can't splice one of the file
+/// descriptors, like that of an `inotify` instance, it copies them through a buffer instead, with
+/// `read` and `write`. Unlike `copy`, which takes any reader and writer, it has to be given the
+/// streams themselves, since only those have file descriptors to hand to the kernel.
+///
+/// # Errors
+///
+/// Returns the first error of reading or writing, with some of the bytes copied already. A
+/// `writer` that accepts no more bytes fails with [`WriteZero`](io::ErrorKind::WriteZero).
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::{self, AsyncReadExt};
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let (client, proxy_in) = UnixStream::pair().unwrap();
+///     let (proxy_out, server) = UnixStream::pair().unwrap();
+///     client.write_all(b"hello").await.unwrap();
+///     drop(client);
+///
+///     let copied = io::copy_fd(&proxy_in, &proxy_out).await.unwrap();
+///     assert_eq!(copied, 5);
+///     drop(proxy_out);
+///
+///     let mut received = Vec::new();
+///     (&server).read_to_end(&mut received).await.unwrap();
+///     received
+/// });
+/// assert_eq!(received, b"hello");
+/// ```
+pub async fn copy_fd<R, W>(reader: &R, writer: &W) -> io::Result<u64>
+where
+    R: AsRegistration + ?Sized,
+    W: AsRegistration + ?Sized,
+{
+    splice_all(reader, writer, true).await
+}
+
+/// Splice everything `reader` reads into `writer`, and copy it through a buffer instead if
+/// `fallback` is set and the kernel can't splice, before anything was moved.
+async fn splice_all<R, W>(reader: &R, writer: &W, fallback: bool) -> io::Result<u64>
+where
+    R: AsRegistration + ?Sized,
+    W: AsRegistration + ?Sized,
+{
+    let (pipe_read, pipe_write) = pipe_fds()?;
+    let mut moved = 0;
+    loop {
+        // The pipe is empty here, so the call only waits for `reader`.
+        let filled = reader
+            .registration()
+            .async_io(Interest::READABLE, || {
+                splice_fd(reader.as_raw_fd(), pipe_write.as_raw_fd(), PIPE_SIZE)
+            })
+            .await;
+        let filled = match filled {
+            Ok(0) => return Ok(moved),
+            Ok(filled) => filled,
+            Err(error) if fallback && moved == 0 && is_unsupported(&error) => {
+                return copy_through(reader, writer, &[]).await;
+            }
+            Err(error) => return Err(error),
+        };
+
+        // And it isn't empty here, so these calls only wait for `writer`.
+        let mut in_pipe = filled;
+        while in_pipe > 0 {
+            let drained = writer
+                .registration()
+                .async_io(Interest::WRITABLE, || {
+                    splice_fd(pipe_read.as_raw_fd(), writer.as_raw_fd(), in_pipe)
+                })
+                .await;
+            let drained = match drained {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(drained) => drained,
+                Err(error) if fallback && moved == 0 && is_unsupported(&error) => {
+                    // Take back what was spliced into the pipe, to write it first.
+                    let mut pending = vec![0; in_pipe];
+                    let mut taken = 0;
+                    while taken < in_pipe {
+                        let mut buf = ReadBuf::new(&mut pending[taken..]);
+                        taken += read_fd(pipe_read.as_raw_fd(), &mut buf)?;
+                    }
+                    return copy_through(reader, writer, &pending).await;
+                }
+                Err(error) => return Err(error),
+            };
+            in_pipe -= drained;
+            moved += drained as u64;
+        }
+    }
+}
+
+/// Whether `error` is the one `splice` fails with for a file descriptor it can't splice.
+fn is_unsupported(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::InvalidInput
+}
+
+/// Write `pending` to `writer`, and then copy everything `reader` reads into it through a buffer,
+/// with `read` and `write`.
+async fn copy_through<R, W>(reader: &R, writer: &W, pending: &[u8]) -> io::Result<u64>
+where
+    R: AsRegistration + ?Sized,
+    W: AsRegistration + ?Sized,
+{
+    write_all_fd(writer, pending).await?;
+    let mut copied = pending.len() as u64;
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        let read = reader
+            .registration()
+            .async_io(Interest::READABLE, || {
+                read_fd(reader.as_raw_fd(), &mut ReadBuf::new(&mut buf))
+            })
+            .await?;
+        if read == 0 {
+            return Ok(copied);
+        }
+        write_all_fd(writer, &buf[..read]).await?;
+        copied += read as u64;
+    }
+}
+
+/// Write all of `buf` to `writer`.
+async fn write_all_fd<W: AsRegistration + ?Sized>(writer: &W, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let written = writer
+            .registration()
+            .async_io(Interest::WRITABLE, || {
+                let fd = writer.as_raw_fd();
+                // Safety: the kernel only reads the `buf.len()` bytes of `buf`.
+                let written = cvt(unsafe { libc::write(fd, buf.as_ptr().cast(), buf.len()) })?;
+                Ok(written as usize)
+            })
+            .await?;
+        if written == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "write zero byte into writer",
+            ));
+        }
+        buf = &buf[written..];
+    }
+    Ok(())
+}
+
+/// Send up to `len` bytes of `file`, starting at `offset`, to `writer`, without copying them
+/// through userspace, and return the number of bytes that were sent.
+///
+/// The kernel reads the file straight into the socket, or whatever `writer` is, with `sendfile`,
+/// which is how a server sends static files. Fewer than `len` bytes are sent only if the file
+/// ends before them. The position of `file` isn't changed, so it may be shared, and the write
+/// timeout of the stream doesn't apply.
+///
+/// # Errors
+///
+/// Returns the first error of reading `file` or writing to `writer`, with some of the bytes sent
+/// already.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::{self, AsyncReadExt};
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let path = std::env::temp_dir().join("mini-executor-sendfile-example");
+/// std::fs::write(&path, b"hello world").unwrap();
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let file = std::fs::File::open(&path).unwrap();
+///     let (left, right) = UnixStream::pair().unwrap();
+///     let sent = io::sendfile(&file, 6, 100, &left).await.unwrap();
+///     assert_eq!(sent, 5);
+///     drop(left);
+///
+///     let mut received = Vec::new();
+///     (&right).read_to_end(&mut received).await.unwrap();
+///     received
+/// });
+/// assert_eq!(received, b"world");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub async fn sendfile<F, W>(file: &F, offset: u64, len: u64, writer: &W) -> io::Result<u64>
+where
+    F: AsFd + ?Sized,
+    W: AsRegistration + ?Sized,
+{
+    let file = file.as_fd().as_raw_fd();
+    let mut sent = 0;
+    while sent < len {
+        let offset = offset
+            .checked_add(sent)
+            .and_then(|offset| offset.try_into().ok());
+        let Some(mut offset) = offset else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "offset is too large",
+            ));
+        };
+        let count = (len - sent).min(MAX_SENDFILE) as usize;
+        let written = writer
+            .registration()
+            .async_io(Interest::WRITABLE, || {
+                // Safety: the call only writes to `offset`, which `sendfile` advances.
+                cvt(unsafe { libc::sendfile(writer.as_raw_fd(), file, &mut offset, count) })
+            })
+            .await?;
+        if written == 0 {
+            // The file has ended.
+            break;
+        }
+        sent += written as u64;
+    }
+    Ok(sent)
+}
+
+/// Move up to `len` bytes from `from` to `to` with `splice`, one of which is a pipe.
+fn splice_fd(from: RawFd, to: RawFd, len: usize) -> io::Result<usize> {
+    let flags = libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK;
+    // Safety: no memory is passed, the null offsets make both descriptors use their own.
+    let moved = cvt(unsafe {
+        libc::splice(
+            from,
+            std::ptr::null_mut(),
+            to,
+            std::ptr::null_mut(),
+            len,
+            flags,
+        )
+    })?;
+    Ok(moved as usize)
+}
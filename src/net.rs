@@ -0,0 +1,208 @@
+//! Networking with TCP, UDP and Unix domain sockets, built on the I/O driver.
+//!
+//! A [`TcpListener`] accepts the connections that come in, and a [`TcpStream`] is one connection,
+//! accepted or made with [`TcpStream::connect`]. On Unix, a [`TcpSocket`] sets the options of a
+//! socket before it becomes either of them. A [`UdpSocket`] sends and receives datagrams, also
+//! to and from a broadcast address or a multicast group.
+//! On Unix, [`UnixListener`] and [`UnixStream`] are the counterparts of the TCP types for processes
+//! on the same machine, and [`UnixDatagram`] the one of `UdpSocket`. [`lookup_host`] resolves
+//! host names without blocking the thread, and [`limited_accept`] bounds how many connections a
+//! server handles at once. The [`tcp`] and [`unix`] modules hold the halves streams are split
+//! into, to read in one task while writing in another.
+//!
+//! The types of this module wrap the sockets of [`std::net`] and [`std::os::unix::net`] in
+//! non-blocking mode, and register them with the I/O driver of the [`Runtime`] they are created
+//! on. An operation that would block waits until the driver reports the socket ready, and is then
+//! tried again, so a task that waits for a socket doesn't block the thread it runs on. The I/O
+//! driver has to be enabled, with [`Builder::enable_io`], or by the constructors of `Runtime`,
+//! like [`Runtime::new`].
+//!
+//! [`Runtime`]: crate::runtime::Runtime
+//! [`Runtime::new`]: crate::runtime::Runtime::new
+//! [`Builder::enable_io`]: crate::runtime::Builder::enable_io
+
+use std::future::Future;
+use std::io;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+#[cfg(windows)]
+use std::os::windows::io::AsRawSocket;
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::io::{Interest, Registration};
+use crate::sync::Mutex;
+use crate::time::{self, Sleep};
+
+mod limited_accept;
+mod lookup;
+#[cfg(all(feature = "resolver", unix))]
+mod resolver;
+#[cfg(unix)]
+mod socket;
+pub mod tcp;
+mod tcp_listener;
+#[cfg(unix)]
+mod tcp_socket;
+mod tcp_stream;
+mod udp;
+#[cfg(unix)]
+pub mod unix;
+#[cfg(unix)]
+mod unix_datagram;
+#[cfg(unix)]
+mod unix_listener;
+#[cfg(unix)]
+mod unix_stream;
+
+pub use limited_accept::{limited_accept, ConnectionPermit, LimitedAccept};
+pub use lookup::lookup_host;
+pub use tcp_listener::{Incoming, TcpListener};
+#[cfg(unix)]
+pub use tcp_socket::TcpSocket;
+pub use tcp_stream::TcpStream;
+pub use udp::UdpSocket;
+#[cfg(unix)]
+pub use unix_datagram::UnixDatagram;
+#[cfg(unix)]
+pub use unix_listener::{UnixIncoming, UnixListener};
+#[cfg(unix)]
+pub use unix_stream::UnixStream;
+
+/// Register `socket` with the I/O driver of the current runtime, to read from and write to it.
+#[cfg(unix)]
+#[track_caller]
+fn register(socket: &impl AsRawFd) -> io::Result<Registration> {
+    Registration::new(socket.as_raw_fd(), Interest::READABLE | Interest::WRITABLE)
+}
+
+/// Register `socket` with the I/O driver of the current runtime, to read from and write to it.
+#[cfg(windows)]
+#[track_caller]
+fn register(socket: &impl AsRawSocket) -> io::Result<Registration> {
+    Registration::new(
+        socket.as_raw_socket(),
+        Interest::READABLE | Interest::WRITABLE,
+    )
+}
+
+/// The read and write timeouts of a stream, see [`TcpStream::set_read_timeout`].
+#[derive(Debug, Default)]
+struct Timeouts {
+    /// In nanoseconds, or 0 without a timeout.
+    read: AtomicU64,
+    write: AtomicU64,
+    /// The deadlines of the read and the write that [`AsyncRead`](crate::io::AsyncRead) and
+    /// [`AsyncWrite`](crate::io::AsyncWrite) are polling for, until it completes.
+    read_deadline: Mutex<Option<Sleep>>,
+    write_deadline: Mutex<Option<Sleep>>,
+}
+
+impl Timeouts {
+    fn set_read(&self, timeout: Option<Duration>) -> io::Result<()> {
+        Timeouts::set(&self.read, timeout)
+    }
+
+    fn set_write(&self, timeout: Option<Duration>) -> io::Result<()> {
+        Timeouts::set(&self.write, timeout)
+    }
+
+    fn read(&self) -> Option<Duration> {
+        Timeouts::get(&self.read)
+    }
+
+    fn write(&self) -> Option<Duration> {
+        Timeouts::get(&self.write)
+    }
+
+    /// Run `read` until it completes, or until the read timeout has passed.
+    async fn reading<R>(&self, read: impl Future<Output = io::Result<R>>) -> io::Result<R> {
+        Timeouts::run(self.read(), read).await
+    }
+
+    /// Run `write` until it completes, or until the write timeout has passed.
+    async fn writing<R>(&self, write: impl Future<Output = io::Result<R>>) -> io::Result<R> {
+        Timeouts::run(self.write(), write).await
+    }
+
+    /// Poll a read with `poll`, and fail it once the read timeout has passed since it was first
+    /// polled.
+    fn poll_reading<R>(
+        &self,
+        cx: &mut Context<'_>,
+        poll: impl FnOnce(&mut Context<'_>) -> Poll<io::Result<R>>,
+    ) -> Poll<io::Result<R>> {
+        Timeouts::poll(self.read(), &self.read_deadline, cx, poll)
+    }
+
+    /// Poll a write with `poll`, and fail it once the write timeout has passed since it was first
+    /// polled.
+    fn poll_writing<R>(
+        &self,
+        cx: &mut Context<'_>,
+        poll: impl FnOnce(&mut Context<'_>) -> Poll<io::Result<R>>,
+    ) -> Poll<io::Result<R>> {
+        Timeouts::poll(self.write(), &self.write_deadline, cx, poll)
+    }
+
+    fn set(slot: &AtomicU64, timeout: Option<Duration>) -> io::Result<()> {
+        let nanos = match timeout {
+            Some(Duration::ZERO) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cannot set a 0 duration timeout",
+                ));
+            }
+            Some(timeout) => timeout.as_nanos().try_into().unwrap_or(u64::MAX),
+            None => 0,
+        };
+        slot.store(nanos, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get(slot: &AtomicU64) -> Option<Duration> {
+        match slot.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    async fn run<R>(
+        timeout: Option<Duration>,
+        operation: impl Future<Output = io::Result<R>>,
+    ) -> io::Result<R> {
+        let Some(timeout) = timeout else {
+            return operation.await;
+        };
+        time::timeout(timeout, operation)
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+    }
+
+    fn poll<R>(
+        timeout: Option<Duration>,
+        deadline: &Mutex<Option<Sleep>>,
+        cx: &mut Context<'_>,
+        poll: impl FnOnce(&mut Context<'_>) -> Poll<io::Result<R>>,
+    ) -> Poll<io::Result<R>> {
+        let result = poll(cx);
+        let mut deadline = deadline.lock();
+        match (result, timeout) {
+            (Poll::Pending, Some(timeout)) => {
+                let sleep = deadline.get_or_insert_with(|| time::sleep(timeout));
+                if Pin::new(sleep).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                *deadline = None;
+                Poll::Ready(Err(io::ErrorKind::TimedOut.into()))
+            }
+            (result, _) => {
+                *deadline = None;
+                result
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! [`limited_accept`], which accepts connections only while fewer than a limit are open.
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+use super::{TcpListener, TcpStream};
+use crate::sync::AtomicWaker;
+
+/// Accept the connections that come in on `listener` as a [`Stream`], but only while fewer than
+/// `max_in_flight` of them are being handled.
+///
+/// Every connection comes with a [`ConnectionPermit`], which the handler of the connection keeps
+/// until it is done with it. Once `max_in_flight` permits are held, the stream stops accepting
+/// until one of them is dropped, so the connections that come in queue up in the backlog of the
+/// listener, and the system refuses more once that is full, instead of the server taking on an
+/// unbounded number of them at once.
+///
+/// # Panics
+///
+/// Panics if `max_in_flight` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use futures::StreamExt;
+/// use mini_executor::net::{limited_accept, TcpListener, TcpStream};
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let addr = listener.local_addr().unwrap();
+///
+///     mini_executor::spawn(async move {
+///         let mut connections = limited_accept(listener, 2);
+///         while let Some(Ok((stream, permit))) = connections.next().await {
+///             mini_executor::spawn(async move {
+///                 stream.write_all(b"hello").await.unwrap();
+///                 // The next connection is accepted once the permit is dropped.
+///                 drop(permit);
+///             });
+///         }
+///     });
+///
+///     for _ in 0..5 {
+///         let stream = TcpStream::connect(addr).await.unwrap();
+///         let mut buf = [0; 5];
+///         let mut read = 0;
+///         while read < buf.len() {
+///             read += stream.read(&mut buf[read..]).await.unwrap();
+///         }
+///         assert_eq!(&buf, b"hello");
+///     }
+/// });
+/// ```
+pub fn limited_accept(listener: TcpListener, max_in_flight: usize) -> LimitedAccept {
+    assert!(
+        max_in_flight > 0,
+        "`max_in_flight` must be greater than zero"
+    );
+    LimitedAccept {
+        listener,
+        limit: Arc::new(Limit {
+            max: max_in_flight,
+            in_flight: AtomicUsize::new(0),
+            waker: AtomicWaker::new(),
+        }),
+    }
+}
+
+/// The connections of a listener, accepted only while fewer than a limit are being handled,
+/// returned by [`limited_accept`].
+#[must_use = "streams do nothing unless polled"]
+pub struct LimitedAccept {
+    listener: TcpListener,
+    limit: Arc<Limit>,
+}
+
+/// The number of connections that are being handled, shared by a [`LimitedAccept`] and its
+/// permits.
+struct Limit {
+    max: usize,
+    /// Only raised by the stream, so it never goes past `max`.
+    in_flight: AtomicUsize,
+    /// The waker of the task that polled the stream while the limit was reached.
+    waker: AtomicWaker,
+}
+
+impl LimitedAccept {
+    /// The listener the connections are accepted from.
+    pub fn get_ref(&self) -> &TcpListener {
+        &self.listener
+    }
+
+    /// The number of connections whose permit hasn't been dropped yet.
+    pub fn in_flight(&self) -> usize {
+        self.limit.in_flight.load(Ordering::Acquire)
+    }
+
+    /// The number of connections that may be handled at once.
+    pub fn max_in_flight(&self) -> usize {
+        self.limit.max
+    }
+}
+
+impl Stream for LimitedAccept {
+    type Item = io::Result<(TcpStream, ConnectionPermit)>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<(TcpStream, ConnectionPermit)>>> {
+        let limit = &self.limit;
+        if limit.in_flight.load(Ordering::Acquire) >= limit.max {
+            limit.waker.register(cx.waker());
+            // Check again, in case a permit was dropped before the waker was registered.
+            if limit.in_flight.load(Ordering::Acquire) >= limit.max {
+                return Poll::Pending;
+            }
+        }
+
+        let (stream, _) = match self.listener.poll_accept(cx) {
+            Poll::Ready(Ok(accepted)) => accepted,
+            Poll::Ready(Err(error)) => return Poll::Ready(Some(Err(error))),
+            Poll::Pending => return Poll::Pending,
+        };
+        limit.in_flight.fetch_add(1, Ordering::AcqRel);
+        let permit = ConnectionPermit {
+            limit: limit.clone(),
+        };
+        Poll::Ready(Some(Ok((stream, permit))))
+    }
+}
+
+impl fmt::Debug for LimitedAccept {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LimitedAccept")
+            .field("listener", &self.listener)
+            .field("in_flight", &self.in_flight())
+            .field("max_in_flight", &self.limit.max)
+            .finish()
+    }
+}
+
+/// The right of a connection accepted by a [`LimitedAccept`] to be handled, which makes room for
+/// the next connection once it is dropped.
+#[must_use = "the connection stops counting towards the limit once the permit is dropped"]
+pub struct ConnectionPermit {
+    limit: Arc<Limit>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        if self.limit.in_flight.fetch_sub(1, Ordering::AcqRel) == self.limit.max {
+            self.limit.waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for ConnectionPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionPermit").finish_non_exhaustive()
+    }
+}
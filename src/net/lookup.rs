@@ -0,0 +1,72 @@
+//! [`lookup_host`], which resolves a host name without blocking the executor.
+
+use std::io;
+use std::net::SocketAddr;
+#[cfg(not(all(feature = "resolver", unix)))]
+use std::net::ToSocketAddrs;
+use std::vec;
+
+#[cfg(not(all(feature = "resolver", unix)))]
+use crate::runtime::Handle;
+
+/// Resolve `host`, a host name or IP address with a port like `"example.com:80"`, to the
+/// addresses of its sockets.
+///
+/// Resolving the name with [`ToSocketAddrs`](std::net::ToSocketAddrs) from a task blocks the
+/// thread it runs on, and every other task of the thread with it, until the name servers have
+/// answered. This hands the lookup to the blocking pool of the current runtime instead, or, with
+/// the `resolver` feature on Unix, looks the name up in `/etc/hosts` and asks the name servers of
+/// `/etc/resolv.conf` over UDP sockets of the I/O driver, so no thread waits for the answers at
+/// all. An IP address is
+/// returned right away either way.
+///
+/// # Errors
+///
+/// Returns an error if `host` has no port, or if the name can't be resolved.
+///
+/// # Panics
+///
+/// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime). With the `resolver`
+/// feature, this also panics if the I/O or time driver of the runtime isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::{lookup_host, TcpListener, TcpStream};
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let port = listener.local_addr().unwrap().port();
+///
+///     let addrs: Vec<_> = lookup_host(&format!("127.0.0.1:{port}")).await.unwrap().collect();
+///     assert_eq!(addrs, [listener.local_addr().unwrap()]);
+///     TcpStream::connect(&addrs[..]).await.unwrap();
+///
+///     assert!(lookup_host("localhost").await.is_err());
+/// });
+/// ```
+pub async fn lookup_host(host: &str) -> io::Result<impl Iterator<Item = SocketAddr>> {
+    if let Ok(addr) = host.parse::<SocketAddr>() {
+        return Ok(vec![addr].into_iter());
+    }
+    resolve(host).await
+}
+
+/// Resolve `host` through the resolver of the platform, on the blocking pool.
+#[cfg(not(all(feature = "resolver", unix)))]
+async fn resolve(host: &str) -> io::Result<vec::IntoIter<SocketAddr>> {
+    let host = host.to_owned();
+    Handle::current()
+        .spawn_blocking(move || host.to_socket_addrs().map(Vec::from_iter))
+        .await
+        .map_err(|error| io::Error::other(error.to_string()))?
+        .map(Vec::into_iter)
+}
+
+/// Resolve `host` with the crate's own resolver, see [`super::resolver`].
+#[cfg(all(feature = "resolver", unix))]
+async fn resolve(host: &str) -> io::Result<vec::IntoIter<SocketAddr>> {
+    super::resolver::resolve(host).await.map(Vec::into_iter)
+}
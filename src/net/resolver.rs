@@ -0,0 +1,312 @@
+//! A DNS stub resolver for [`lookup_host`](super::lookup_host) with the `resolver` feature, which
+//! looks host names up in `/etc/hosts`, and asks the name servers of `/etc/resolv.conf` for the
+//! rest over [`UdpSocket`]s, instead of waiting for the resolver of the platform on a thread.
+//!
+//! It is a small resolver: the `A` and `AAAA` records of a name are asked for at once, and the
+//! name servers are tried in turn, as often as `options attempts:` of `resolv.conf` says, each for
+//! as long as `options timeout:` says. The `search` domains aren't appended to names, an answer
+//! that was truncated isn't asked for again over TCP, and nothing is cached.
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use super::UdpSocket;
+use crate::time;
+
+const HOSTS: &str = "/etc/hosts";
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// The largest message that is sent over UDP, without the extensions of EDNS.
+const MAX_MESSAGE_LEN: usize = 512;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// The response code of a name that doesn't exist.
+const NXDOMAIN: u16 = 3;
+
+/// Resolve `host`, a host name with a port, to the addresses of its sockets.
+pub(super) async fn resolve(host: &str) -> io::Result<Vec<SocketAddr>> {
+    let Some((name, port)) = host.rsplit_once(':') else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid socket address",
+        ));
+    };
+    let Ok(port) = port.parse() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid port value",
+        ));
+    };
+
+    let ips = match lookup_hosts(name) {
+        ips if !ips.is_empty() => ips,
+        _ => query(&Config::read(), name).await?,
+    };
+    Ok(ips
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect())
+}
+
+/// The addresses of `name` in `/etc/hosts`.
+fn lookup_hosts(name: &str) -> Vec<IpAddr> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    // The file is small and local, so reading it doesn't keep the thread waiting for long.
+    let Ok(hosts) = fs::read_to_string(HOSTS) else {
+        return Vec::new();
+    };
+    hosts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('#').next()?.split_whitespace();
+            let ip = fields.next()?.parse().ok()?;
+            fields
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+                .then_some(ip)
+        })
+        .collect()
+}
+
+/// The settings of `/etc/resolv.conf` the resolver follows.
+struct Config {
+    name_servers: Vec<SocketAddr>,
+    /// How long to wait for a name server to answer.
+    timeout: Duration,
+    /// How often to go through all name servers.
+    attempts: u32,
+}
+
+impl Config {
+    /// Read `/etc/resolv.conf`, with the defaults of glibc for what it leaves out.
+    fn read() -> Config {
+        let mut config = Config {
+            name_servers: Vec::new(),
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+        };
+        for line in fs::read_to_string(RESOLV_CONF).unwrap_or_default().lines() {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("nameserver") => {
+                    // Scoped IPv6 addresses, like `fe80::1%eth0`, are skipped.
+                    if let Some(Ok(ip)) = fields.next().map(str::parse::<IpAddr>) {
+                        config.name_servers.push(SocketAddr::new(ip, 53));
+                    }
+                }
+                Some("options") => {
+                    for option in fields {
+                        let (name, value) = option.split_once(':').unwrap_or((option, ""));
+                        match (name, value.parse::<u32>()) {
+                            ("timeout", Ok(secs)) => {
+                                config.timeout = Duration::from_secs(secs.clamp(1, 30).into());
+                            }
+                            ("attempts", Ok(attempts)) => config.attempts = attempts.clamp(1, 5),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if config.name_servers.is_empty() {
+            config
+                .name_servers
+                .push(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 53));
+        }
+        config
+    }
+}
+
+/// Ask the name servers of `config` for the addresses of `name`, the IPv6 ones first.
+async fn query(config: &Config, name: &str) -> io::Result<Vec<IpAddr>> {
+    let name = encode_name(name)?;
+    let mut last_error = None;
+    for _ in 0..config.attempts {
+        for &server in &config.name_servers {
+            match time::timeout(config.timeout, ask(server, &name)).await {
+                Ok(Ok(ips)) => return Ok(ips),
+                // The name doesn't exist, which another name server won't answer differently.
+                Ok(Err(error)) if error.kind() == io::ErrorKind::NotFound => return Err(error),
+                Ok(Err(error)) => last_error = Some(error),
+                Err(_) => {}
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::TimedOut, "the name servers didn't answer")
+    }))
+}
+
+/// Ask `server` for the `A` and `AAAA` records of the encoded `name`, and wait for both answers.
+async fn ask(server: SocketAddr, name: &[u8]) -> io::Result<Vec<IpAddr>> {
+    let local: IpAddr = match server {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(local, 0))?;
+    // Only the answers of the server are received on a connected socket.
+    socket.connect(server)?;
+
+    // The IDs are random, so an answer can't easily be forged by someone who can't see the query.
+    let id = random_id();
+    let ids = [id, id ^ 1];
+    let types = [TYPE_AAAA, TYPE_A];
+    let mut answers = [None, None];
+    for (&id, &ty) in ids.iter().zip(&types) {
+        socket.send(&encode_query(id, name, ty)).await?;
+    }
+
+    let mut buf = [0; MAX_MESSAGE_LEN];
+    while answers.iter().any(Option::is_none) {
+        let len = socket.recv(&mut buf).await?;
+        let message = &buf[..len];
+        let Some(index) = message.get(..2).and_then(|id| {
+            ids.iter()
+                .position(|&expected| id == expected.to_be_bytes())
+        }) else {
+            continue;
+        };
+        if answers[index].is_none() {
+            answers[index] = Some(parse_answer(message, types[index])?);
+        }
+    }
+
+    let ips: Vec<IpAddr> = answers.into_iter().flatten().flatten().collect();
+    if ips.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "the host has no addresses",
+        ));
+    }
+    Ok(ips)
+}
+
+/// A random ID for a query.
+fn random_id() -> u16 {
+    // Every `RandomState` is seeded differently, which is all the randomness needed here.
+    RandomState::new().build_hasher().finish() as u16
+}
+
+/// Encode `name` as the labels of a DNS message.
+fn encode_name(name: &str) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid host name");
+    let name = name.strip_suffix('.').unwrap_or(name);
+    let mut encoded = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(invalid());
+        }
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    if encoded.len() > 255 {
+        return Err(invalid());
+    }
+    Ok(encoded)
+}
+
+/// A query with the ID `id`, for the records of type `ty` of the encoded `name`.
+fn encode_query(id: u16, name: &[u8], ty: u16) -> Vec<u8> {
+    let mut query = Vec::with_capacity(12 + name.len() + 4);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Only the flag that asks the server to resolve the name recursively is set.
+    query.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question, and no records.
+    for count in [1u16, 0, 0, 0] {
+        query.extend_from_slice(&count.to_be_bytes());
+    }
+    query.extend_from_slice(name);
+    query.extend_from_slice(&ty.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    query
+}
+
+/// The addresses in the records of type `ty` of the answer `message`.
+fn parse_answer(message: &[u8], ty: u16) -> io::Result<Vec<IpAddr>> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed answer to a query");
+    let mut reader = Reader { message, pos: 2 };
+    let flags = reader.u16().ok_or_else(malformed)?;
+    if flags & 0x8000 == 0 {
+        return Err(malformed());
+    }
+    match flags & 0x000f {
+        0 => {}
+        NXDOMAIN => {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such host"));
+        }
+        _ => return Err(io::Error::other("the name server failed to answer")),
+    }
+
+    let questions = reader.u16().ok_or_else(malformed)?;
+    let records = reader.u16().ok_or_else(malformed)?;
+    reader.skip(4).ok_or_else(malformed)?;
+    for _ in 0..questions {
+        reader.skip_name().ok_or_else(malformed)?;
+        reader.skip(4).ok_or_else(malformed)?;
+    }
+
+    // The records may also be aliases, which the answer goes on to resolve.
+    let mut ips = Vec::new();
+    for _ in 0..records {
+        reader.skip_name().ok_or_else(malformed)?;
+        let record_ty = reader.u16().ok_or_else(malformed)?;
+        let class = reader.u16().ok_or_else(malformed)?;
+        // The time-to-live doesn't matter without a cache.
+        reader.skip(4).ok_or_else(malformed)?;
+        let len = reader.u16().ok_or_else(malformed)?;
+        let data = reader.bytes(len.into()).ok_or_else(malformed)?;
+        if record_ty != ty || class != CLASS_IN {
+            continue;
+        }
+        match (ty, <[u8; 4]>::try_from(data), <[u8; 16]>::try_from(data)) {
+            (TYPE_A, Ok(octets), _) => ips.push(Ipv4Addr::from(octets).into()),
+            (TYPE_AAAA, _, Ok(octets)) => ips.push(Ipv6Addr::from(octets).into()),
+            _ => return Err(malformed()),
+        }
+    }
+    Ok(ips)
+}
+
+/// Reads a DNS message from front to back.
+struct Reader<'a> {
+    message: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.message.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(drop)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skip a name, which ends with an empty label, or with a pointer to the rest of it.
+    fn skip_name(&mut self) -> Option<()> {
+        loop {
+            let len = self.bytes(1)?[0];
+            match len & 0xc0 {
+                0 if len == 0 => return Some(()),
+                0 => self.skip(len.into())?,
+                0xc0 => return self.skip(1),
+                _ => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Creating sockets in non-blocking mode on Unix, which the standard library can't do.
+
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::io::cvt;
+
+/// Create a non-blocking socket of type `ty`, in the communication domain `domain`.
+pub(super) fn socket(domain: libc::c_int, ty: libc::c_int) -> io::Result<OwnedFd> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        let ty = ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+        let fd = cvt(unsafe { libc::socket(domain, ty, 0) })?;
+        // Safety: the descriptor was just created, and is owned from here on.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    // The other platforms can't create it that way in one go.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    {
+        let fd = cvt(unsafe { libc::socket(domain, ty, 0) })?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+        let flags = cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) })?;
+        cvt(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            // Writing to a closed socket fails with `EPIPE`, rather than killing the process.
+            let on: libc::c_int = 1;
+            cvt(unsafe {
+                libc::setsockopt(
+                    fd.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_NOSIGPIPE,
+                    (&on as *const libc::c_int).cast(),
+                    mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            })?;
+        }
+        Ok(fd)
+    }
+}
+
+/// Create a non-blocking Unix domain socket of type `ty`.
+pub(super) fn unix_socket(ty: libc::c_int) -> io::Result<OwnedFd> {
+    socket(libc::AF_UNIX, ty)
+}
+
+/// Start connecting the non-blocking socket `fd` to `addr`, which completes once the socket is
+/// writable.
+pub(super) fn connect(fd: &OwnedFd, addr: &SocketAddr) -> io::Result<()> {
+    let (storage, len) = sockaddr(addr);
+    connect_to(fd, (&storage as *const libc::sockaddr_storage).cast(), len)
+}
+
+/// Start connecting the non-blocking Unix domain socket `fd` to the socket bound to `path`, like
+/// [`connect`].
+///
+/// A connection to a listener whose backlog is full fails with
+/// [`WouldBlock`](io::ErrorKind::WouldBlock) on Linux, rather than waiting for room.
+pub(super) fn connect_unix(fd: &OwnedFd, path: &Path) -> io::Result<()> {
+    let (addr, len) = sockaddr_un(path)?;
+    connect_to(fd, (&addr as *const libc::sockaddr_un).cast(), len)
+}
+
+/// Start connecting `fd` to the address `addr` points to, which is `len` bytes long.
+fn connect_to(fd: &OwnedFd, addr: *const libc::sockaddr, len: libc::socklen_t) -> io::Result<()> {
+    match cvt(unsafe { libc::connect(fd.as_raw_fd(), addr, len) }) {
+        Err(error) if error.raw_os_error() == Some(libc::EINPROGRESS) => Ok(()),
+        result => result.map(drop),
+    }
+}
+
+/// Bind `fd` to `addr`.
+pub(super) fn bind(fd: &OwnedFd, addr: &SocketAddr) -> io::Result<()> {
+    let (storage, len) = sockaddr(addr);
+    let addr = (&storage as *const libc::sockaddr_storage).cast();
+    cvt(unsafe { libc::bind(fd.as_raw_fd(), addr, len) }).map(drop)
+}
+
+/// Mark `fd` as a socket that accepts connections, with room for `backlog` of them that haven't
+/// been accepted yet.
+pub(super) fn listen(fd: &OwnedFd, backlog: u32) -> io::Result<()> {
+    let backlog = backlog.try_into().unwrap_or(libc::c_int::MAX);
+    cvt(unsafe { libc::listen(fd.as_raw_fd(), backlog) }).map(drop)
+}
+
+/// The address `fd` is bound to.
+pub(super) fn local_addr(fd: &OwnedFd) -> io::Result<SocketAddr> {
+    // Safety: the storage is plain data, which `getsockname` fills in.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let addr = (&mut storage as *mut libc::sockaddr_storage).cast();
+    cvt(unsafe { libc::getsockname(fd.as_raw_fd(), addr, &mut len) })?;
+    socket_addr(&storage)
+}
+
+/// Set the socket option `name` at `level` of `fd` to the integer `value`.
+pub(super) fn set_option(
+    fd: &OwnedFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    cvt(unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            level,
+            name,
+            (&value as *const libc::c_int).cast(),
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    })
+    .map(drop)
+}
+
+/// The integer value of the socket option `name` at `level` of `fd`.
+pub(super) fn option(
+    fd: &OwnedFd,
+    level: libc::c_int,
+    name: libc::c_int,
+) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    cvt(unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            level,
+            name,
+            (&mut value as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    })?;
+    Ok(value)
+}
+
+/// Bind `fd` to the network interface named `interface`, or unbind it if that is `None`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(super) fn bind_device(fd: &OwnedFd, interface: Option<&[u8]>) -> io::Result<()> {
+    let interface = interface.unwrap_or(&[]);
+    cvt(unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            interface.as_ptr().cast(),
+            interface.len() as libc::socklen_t,
+        )
+    })
+    .map(drop)
+}
+
+/// The name of the network interface `fd` is bound to, if it is bound to one.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(super) fn device(fd: &OwnedFd) -> io::Result<Option<Vec<u8>>> {
+    let mut interface = [0u8; libc::IFNAMSIZ];
+    let mut len = interface.len() as libc::socklen_t;
+    cvt(unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            interface.as_mut_ptr().cast(),
+            &mut len,
+        )
+    })?;
+    // The name may be terminated with a null byte, which isn't part of it.
+    let interface = &interface[..len as usize];
+    let interface = interface
+        .split(|&byte| byte == 0)
+        .next()
+        .unwrap_or_default();
+    Ok((!interface.is_empty()).then(|| interface.to_vec()))
+}
+
+/// The C representation of `addr`.
+fn sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // Safety: the storage is plain data, and all zeros leaves the fields that aren't set empty.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sin = unsafe {
+                &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in>()
+            };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr = libc::in_addr {
+                s_addr: u32::from_ne_bytes(addr.ip().octets()),
+            };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6 = unsafe {
+                &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+            };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            sin6.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// The address `storage` is the C representation of.
+fn socket_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let sin =
+                unsafe { &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            let ip = Ipv4Addr::from(sin.sin_addr.s_addr.to_ne_bytes());
+            Ok(SocketAddrV4::new(ip, u16::from_be(sin.sin_port)).into())
+        }
+        libc::AF_INET6 => {
+            let sin6 = unsafe {
+                &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+            };
+            let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+            let port = u16::from_be(sin6.sin6_port);
+            Ok(SocketAddrV6::new(ip, port, sin6.sin6_flowinfo, sin6.sin6_scope_id).into())
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid address family",
+        )),
+    }
+}
+
+/// The C representation of the address of a Unix domain socket bound to `path`.
+fn sockaddr_un(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    // Safety: the address is plain data, and all zeros terminates the path with a null byte.
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.contains(&0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "paths must not contain interior null bytes",
+        ));
+    }
+    // The path needs room for the null byte that terminates it.
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path must be shorter than SUN_LEN",
+        ));
+    }
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+    let len = mem::offset_of!(libc::sockaddr_un, sun_path) + bytes.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
@@ -0,0 +1,204 @@
+//! [`TcpListener`], a socket that accepts TCP connections.
+
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+use super::TcpStream;
+use crate::io::{Interest, Registration};
+
+/// A TCP socket that listens for connections, registered with the I/O driver.
+///
+/// [`accept`](TcpListener::accept) waits until the driver reports the socket readable, which it
+/// is once a connection comes in, so a task waiting for one doesn't spin.
+/// [`incoming`](TcpListener::incoming) returns the connections as a [`Stream`].
+///
+/// # Examples
+///
+/// An echo server, and a client talking to it:
+///
+/// ```
+/// use futures::StreamExt;
+/// use mini_executor::net::{TcpListener, TcpStream};
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let echoed = executor.block_on(async {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let addr = listener.local_addr().unwrap();
+///
+///     mini_executor::spawn(async move {
+///         let mut incoming = listener.incoming();
+///         while let Some(Ok(stream)) = incoming.next().await {
+///             mini_executor::spawn(async move {
+///                 let mut buf = [0; 1024];
+///                 loop {
+///                     match stream.read(&mut buf).await {
+///                         Ok(0) | Err(_) => break,
+///                         Ok(read) => stream.write_all(&buf[..read]).await.unwrap(),
+///                     }
+///                 }
+///             });
+///         }
+///     });
+///
+///     let stream = TcpStream::connect(addr).await.unwrap();
+///     stream.write_all(b"hello").await.unwrap();
+///     let mut buf = [0; 5];
+///     let mut read = 0;
+///     while read < buf.len() {
+///         read += stream.read(&mut buf[read..]).await.unwrap();
+///     }
+///     buf
+/// });
+/// assert_eq!(&echoed, b"hello");
+/// ```
+pub struct TcpListener {
+    // Dropped before the socket, so it is still open when it is deregistered.
+    registration: Registration,
+    listener: net::TcpListener,
+}
+
+impl TcpListener {
+    /// Create a listener bound to `addr`, which starts listening for connections right away.
+    ///
+    /// If `addr` resolves to more than one address, each one is tried in turn until the socket
+    /// could be bound to one, like [`std::net::TcpListener::bind`]. Binding to port 0 picks a free
+    /// port, which [`local_addr`](TcpListener::local_addr) returns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<TcpListener> {
+        TcpListener::from_std(net::TcpListener::bind(addr)?)
+    }
+
+    /// Register a listener with the I/O driver of the current runtime, and put it into
+    /// non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener can't be put into non-blocking mode, or if the runtime
+    /// has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_std(listener: net::TcpListener) -> io::Result<TcpListener> {
+        listener.set_nonblocking(true)?;
+        let registration = super::register(&listener)?;
+        Ok(TcpListener {
+            registration,
+            listener,
+        })
+    }
+
+    /// Deregister the listener from the I/O driver, and return it as a
+    /// [`std::net::TcpListener`], which is left in non-blocking mode.
+    pub fn into_std(self) -> net::TcpListener {
+        let TcpListener {
+            registration,
+            listener,
+        } = self;
+        drop(registration);
+        listener
+    }
+
+    /// Wait for the next connection, and return it with the address of the remote socket.
+    ///
+    /// The returned stream is registered with the I/O driver of the current runtime.
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Poll for the next connection, and register the waker of `cx` to be woken once one comes in
+    /// if there is none.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        let (stream, addr) = match self
+            .registration
+            .poll_io(cx, Interest::READABLE, || self.listener.accept())
+        {
+            Poll::Ready(Ok(accepted)) => accepted,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(TcpStream::from_std(stream).map(|stream| (stream, addr)))
+    }
+
+    /// The connections that come in, as a [`Stream`] that never ends.
+    ///
+    /// Accepting a connection can fail, for example when the process has run out of file
+    /// descriptors, which the stream returns as an error before it goes on.
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming { listener: self }
+    }
+
+    /// The address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The time-to-live of the packets sent from the accepted sockets.
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.listener.ttl()
+    }
+
+    /// Set the time-to-live of the packets sent from the accepted sockets.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.listener.set_ttl(ttl)
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawSocket for TcpListener {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.listener.as_raw_socket()
+    }
+}
+
+impl fmt::Debug for TcpListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.listener.fmt(f)
+    }
+}
+
+/// The connections that come in on a [`TcpListener`], returned by
+/// [`TcpListener::incoming`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct Incoming<'a> {
+    listener: &'a TcpListener,
+}
+
+impl Stream for Incoming<'_> {
+    type Item = io::Result<TcpStream>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<TcpStream>>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|accepted| Some(accepted.map(|(stream, _)| stream)))
+    }
+}
@@ -0,0 +1,235 @@
+//! [`TcpSocket`], a TCP socket that is set up before it connects or listens.
+
+use std::fmt;
+use std::io;
+use std::net::{self, SocketAddr};
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+
+use super::{socket, TcpListener, TcpStream};
+
+/// A TCP socket that is neither connected nor listening yet, to set options that have to be set
+/// before it is.
+///
+/// A socket is created for IPv4 or IPv6 with [`new_v4`](TcpSocket::new_v4) or
+/// [`new_v6`](TcpSocket::new_v6), then configured, optionally [`bind`](TcpSocket::bind)ed to a
+/// local address, and finally turned into a [`TcpStream`] with
+/// [`connect`](TcpSocket::connect), or into a [`TcpListener`] with
+/// [`listen`](TcpSocket::listen). [`TcpStream::connect`] and [`TcpListener::bind`] do all of
+/// that with the default options.
+///
+/// The socket is only available on Unix, since it is created with the system calls of the
+/// platform.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::TcpSocket;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let socket = TcpSocket::new_v4().unwrap();
+///     // Rebinding the address of a listener that was just closed works right away.
+///     socket.set_reuseaddr(true).unwrap();
+///     socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+///     let listener = socket.listen(128).unwrap();
+///     let addr = listener.local_addr().unwrap();
+///
+///     let socket = TcpSocket::new_v4().unwrap();
+///     socket.set_nodelay(true).unwrap();
+///     socket.set_recv_buffer_size(64 * 1024).unwrap();
+///     let stream = socket.connect(addr).await.unwrap();
+///     assert!(stream.nodelay().unwrap());
+///
+///     let (accepted, peer) = listener.accept().await.unwrap();
+///     assert_eq!(peer, stream.local_addr().unwrap());
+///     assert_eq!(accepted.peer_addr().unwrap(), peer);
+/// });
+/// ```
+pub struct TcpSocket {
+    socket: OwnedFd,
+}
+
+impl TcpSocket {
+    /// Create an IPv4 socket.
+    pub fn new_v4() -> io::Result<TcpSocket> {
+        TcpSocket::new(libc::AF_INET)
+    }
+
+    /// Create an IPv6 socket.
+    pub fn new_v6() -> io::Result<TcpSocket> {
+        TcpSocket::new(libc::AF_INET6)
+    }
+
+    fn new(domain: libc::c_int) -> io::Result<TcpSocket> {
+        let socket = socket::socket(domain, libc::SOCK_STREAM)?;
+        Ok(TcpSocket { socket })
+    }
+
+    /// Allow the socket to bind to a local address that is still in use by a closed socket, whose
+    /// connections linger in the `TIME_WAIT` state.
+    pub fn set_reuseaddr(&self, reuseaddr: bool) -> io::Result<()> {
+        self.set_flag(libc::SOL_SOCKET, libc::SO_REUSEADDR, reuseaddr)
+    }
+
+    /// Whether the socket may bind to an address that is still in use, see
+    /// [`set_reuseaddr`](TcpSocket::set_reuseaddr).
+    pub fn reuseaddr(&self) -> io::Result<bool> {
+        self.flag(libc::SOL_SOCKET, libc::SO_REUSEADDR)
+    }
+
+    /// Allow other sockets to bind to the same local address and port, if they set this too, to
+    /// share the connections that come in among several listeners.
+    #[cfg(not(any(target_os = "solaris", target_os = "illumos")))]
+    pub fn set_reuseport(&self, reuseport: bool) -> io::Result<()> {
+        self.set_flag(libc::SOL_SOCKET, libc::SO_REUSEPORT, reuseport)
+    }
+
+    /// Whether other sockets may bind to the same address and port, see
+    /// [`set_reuseport`](TcpSocket::set_reuseport).
+    #[cfg(not(any(target_os = "solaris", target_os = "illumos")))]
+    pub fn reuseport(&self) -> io::Result<bool> {
+        self.flag(libc::SOL_SOCKET, libc::SO_REUSEPORT)
+    }
+
+    /// Set the size of the buffer the system keeps the bytes in that were written to the socket,
+    /// but not sent yet.
+    ///
+    /// The system may round the size, or double it for its bookkeeping, as Linux does, which
+    /// [`send_buffer_size`](TcpSocket::send_buffer_size) shows.
+    pub fn set_send_buffer_size(&self, size: u32) -> io::Result<()> {
+        let size = size.try_into().unwrap_or(libc::c_int::MAX);
+        socket::set_option(&self.socket, libc::SOL_SOCKET, libc::SO_SNDBUF, size)
+    }
+
+    /// The size of the buffer for the bytes that weren't sent yet.
+    pub fn send_buffer_size(&self) -> io::Result<u32> {
+        let size = socket::option(&self.socket, libc::SOL_SOCKET, libc::SO_SNDBUF)?;
+        Ok(size as u32)
+    }
+
+    /// Set the size of the buffer the system keeps the bytes in that were received, but not read
+    /// yet, see [`set_send_buffer_size`](TcpSocket::set_send_buffer_size).
+    ///
+    /// The size of the receive window a connection starts with depends on it, so it has to be set
+    /// before connecting or listening to have its full effect.
+    pub fn set_recv_buffer_size(&self, size: u32) -> io::Result<()> {
+        let size = size.try_into().unwrap_or(libc::c_int::MAX);
+        socket::set_option(&self.socket, libc::SOL_SOCKET, libc::SO_RCVBUF, size)
+    }
+
+    /// The size of the buffer for the bytes that weren't read yet.
+    pub fn recv_buffer_size(&self) -> io::Result<u32> {
+        let size = socket::option(&self.socket, libc::SOL_SOCKET, libc::SO_RCVBUF)?;
+        Ok(size as u32)
+    }
+
+    /// Send keepalive probes on a connection that has been idle for a while, to find out if the
+    /// other side has gone away.
+    pub fn set_keepalive(&self, keepalive: bool) -> io::Result<()> {
+        self.set_flag(libc::SOL_SOCKET, libc::SO_KEEPALIVE, keepalive)
+    }
+
+    /// Whether keepalive probes are sent, see [`set_keepalive`](TcpSocket::set_keepalive).
+    pub fn keepalive(&self) -> io::Result<bool> {
+        self.flag(libc::SOL_SOCKET, libc::SO_KEEPALIVE)
+    }
+
+    /// Disable Nagle's algorithm, see [`TcpStream::set_nodelay`].
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.set_flag(libc::IPPROTO_TCP, libc::TCP_NODELAY, nodelay)
+    }
+
+    /// Whether Nagle's algorithm is disabled, see [`set_nodelay`](TcpSocket::set_nodelay).
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.flag(libc::IPPROTO_TCP, libc::TCP_NODELAY)
+    }
+
+    /// Only send and receive through the network interface named `interface`, like `eth0`, or
+    /// through any interface again if it is `None`.
+    ///
+    /// Binding to an interface usually needs the `CAP_NET_RAW` capability.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_device(&self, interface: Option<&[u8]>) -> io::Result<()> {
+        socket::bind_device(&self.socket, interface)
+    }
+
+    /// The name of the network interface the socket is bound to, see
+    /// [`bind_device`](TcpSocket::bind_device).
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn device(&self) -> io::Result<Option<Vec<u8>>> {
+        socket::device(&self.socket)
+    }
+
+    /// Bind the socket to the local address `addr`, which a listener listens on, and a stream
+    /// connects from.
+    ///
+    /// Binding to port 0 picks a free port, which [`local_addr`](TcpSocket::local_addr) returns.
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<()> {
+        socket::bind(&self.socket, &addr)
+    }
+
+    /// The local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket::local_addr(&self.socket)
+    }
+
+    /// Take the pending error of the socket, if there is one.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        match socket::option(&self.socket, libc::SOL_SOCKET, libc::SO_ERROR)? {
+            0 => Ok(None),
+            error => Ok(Some(io::Error::from_raw_os_error(error))),
+        }
+    }
+
+    /// Connect to `addr`, and wait until the connection is made.
+    ///
+    /// If the socket wasn't bound, it is bound to a free port on an address the system picks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    pub async fn connect(self, addr: SocketAddr) -> io::Result<TcpStream> {
+        socket::connect(&self.socket, &addr)?;
+        TcpStream::connected(self.socket).await
+    }
+
+    /// Start listening for connections, with room for `backlog` of them that haven't been
+    /// accepted yet.
+    ///
+    /// If the socket wasn't bound, it is bound to a free port on every address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn listen(self, backlog: u32) -> io::Result<TcpListener> {
+        socket::listen(&self.socket, backlog)?;
+        TcpListener::from_std(net::TcpListener::from(self.socket))
+    }
+
+    fn set_flag(&self, level: libc::c_int, name: libc::c_int, on: bool) -> io::Result<()> {
+        socket::set_option(&self.socket, level, name, on.into())
+    }
+
+    fn flag(&self, level: libc::c_int, name: libc::c_int) -> io::Result<bool> {
+        Ok(socket::option(&self.socket, level, name)? != 0)
+    }
+}
+
+impl AsRawFd for TcpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for TcpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpSocket")
+            .field("fd", &self.socket.as_raw_fd())
+            .field("addr", &self.local_addr().ok())
+            .finish()
+    }
+}
@@ -0,0 +1,738 @@
+//! [`TcpStream`], a TCP connection.
+
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+
+use super::tcp::{self, OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+#[cfg(unix)]
+use super::TcpSocket;
+use super::Timeouts;
+#[cfg(unix)]
+use crate::io::read_fd;
+#[cfg(target_os = "linux")]
+use crate::io::AsRegistration;
+use crate::io::{AsyncRead, AsyncWrite, Interest, ReadBuf, Registration};
+use crate::runtime::Handle;
+use crate::time;
+
+/// How long [`TcpStream::connect`] waits for an attempt to connect, before it starts the next one
+/// in parallel, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// A TCP connection between a local and a remote socket, registered with the I/O driver.
+///
+/// A stream is created by connecting to a remote host with [`connect`](TcpStream::connect), or
+/// from a connected [`std::net::TcpStream`] with [`from_std`](TcpStream::from_std). Reading and
+/// writing wait until the socket is ready, instead of blocking the thread. The connection is
+/// closed when the stream is dropped.
+///
+/// Only the task that waited for a direction last is woken, so at most one task should read, and
+/// one write, at a time. [`split`](TcpStream::split) and [`into_split`](TcpStream::into_split)
+/// hand out a half of the stream for each direction, for one task to read while another writes.
+///
+/// Both `TcpStream` and `&TcpStream` implement [`AsyncRead`] and [`AsyncWrite`], for the code
+/// written against those traits, and for the methods of [`AsyncReadExt`](crate::io::AsyncReadExt)
+/// and [`AsyncWriteExt`](crate::io::AsyncWriteExt), like `read_exact`.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::TcpStream;
+/// use mini_executor::runtime::Runtime;
+/// use std::io::{Read, Write};
+///
+/// // A blocking echo server on another thread.
+/// let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap();
+/// std::thread::spawn(move || {
+///     let (mut socket, _) = listener.accept().unwrap();
+///     let mut buf = [0; 5];
+///     socket.read_exact(&mut buf).unwrap();
+///     socket.write_all(&buf).unwrap();
+/// });
+///
+/// let executor = Runtime::new();
+/// let echoed = executor.block_on(async {
+///     let stream = TcpStream::connect(addr).await.unwrap();
+///     stream.write_all(b"hello").await.unwrap();
+///
+///     let mut buf = [0; 5];
+///     let mut read = 0;
+///     while read < buf.len() {
+///         read += stream.read(&mut buf[read..]).await.unwrap();
+///     }
+///     buf
+/// });
+/// assert_eq!(&echoed, b"hello");
+/// ```
+pub struct TcpStream {
+    // Dropped before the socket, so it is still open when it is deregistered.
+    registration: Registration,
+    stream: net::TcpStream,
+    timeouts: Timeouts,
+}
+
+impl TcpStream {
+    /// Connect to a remote host at `addr`.
+    ///
+    /// If `addr` resolves to more than one address, they are tried with the Happy Eyeballs
+    /// algorithm of [RFC 8305]: the addresses of the family of the first one and of the other
+    /// family take turns, and the next attempt starts once the previous one has failed, or once
+    /// it hasn't succeeded within 250 milliseconds, while the earlier ones go on. The first
+    /// connection that is made is returned, and the others are closed. That way, a host whose
+    /// IPv6 addresses are unreachable is still connected to quickly, over IPv4. The error of the
+    /// attempt that failed last is returned if none succeeds.
+    ///
+    /// Resolving a host name blocks the thread, like [`std::net::TcpStream::connect`], which
+    /// [`lookup_host`](super::lookup_host) avoids, and whose addresses can be passed here. If the
+    /// time driver isn't enabled, each attempt only starts once the previous one has failed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    ///
+    /// [RFC 8305]: https://www.rfc-editor.org/rfc/rfc8305
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<TcpStream> {
+        let mut addrs = interleave(addr.to_socket_addrs()?.collect()).into_iter();
+        let staggered = Handle::try_current().is_ok_and(|handle| handle.time_driver().is_some());
+
+        let mut attempts = FuturesUnordered::new();
+        let mut delay = None;
+        let mut last_error = None;
+        poll_fn(|cx| loop {
+            if attempts.is_empty() {
+                let Some(addr) = addrs.next() else {
+                    return Poll::Ready(Err(last_error.take().unwrap_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "could not resolve to any addresses",
+                        )
+                    })));
+                };
+                attempts.push(TcpStream::connect_addr(addr));
+                delay = staggered.then(|| time::sleep(CONNECTION_ATTEMPT_DELAY));
+            }
+
+            match attempts.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(stream))) => return Poll::Ready(Ok(stream)),
+                Poll::Ready(Some(Err(error))) => {
+                    last_error = Some(error);
+                    // The next attempt starts right away, rather than after the delay.
+                    if let Some(addr) = addrs.next() {
+                        attempts.push(TcpStream::connect_addr(addr));
+                        delay = staggered.then(|| time::sleep(CONNECTION_ATTEMPT_DELAY));
+                    }
+                    continue;
+                }
+                Poll::Ready(None) => continue,
+                Poll::Pending => {}
+            }
+
+            match delay.as_mut().map(|delay| Pin::new(delay).poll(cx)) {
+                Some(Poll::Ready(())) => match addrs.next() {
+                    Some(addr) => {
+                        attempts.push(TcpStream::connect_addr(addr));
+                        delay = Some(time::sleep(CONNECTION_ATTEMPT_DELAY));
+                    }
+                    None => delay = None,
+                },
+                Some(Poll::Pending) | None => return Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Connect to `addr` without blocking, and wait until the connection is made.
+    #[cfg(unix)]
+    async fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.connect(addr).await
+    }
+
+    /// Register `socket`, which is connecting already, and wait until the connection is made.
+    #[cfg(unix)]
+    pub(super) async fn connected(socket: OwnedFd) -> io::Result<TcpStream> {
+        let stream = TcpStream::new(net::TcpStream::from(socket))?;
+
+        // The socket becomes writable once the connection is made, or has failed.
+        stream.registration.ready(Interest::WRITABLE).await?;
+        if let Some(error) = stream.stream.take_error()? {
+            return Err(error);
+        }
+        Ok(stream)
+    }
+
+    /// Connect to `addr` on the blocking pool, since the standard library can't start a
+    /// connection without waiting for it on Windows.
+    #[cfg(windows)]
+    async fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let handle = Handle::current();
+        let stream = handle
+            .spawn_blocking(move || net::TcpStream::connect(addr))
+            .await
+            .map_err(|error| io::Error::other(error.to_string()))??;
+        TcpStream::from_std(stream)
+    }
+
+    /// Register a connected stream with the I/O driver of the current runtime, and put it into
+    /// non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream can't be put into non-blocking mode, or if the runtime has
+    /// shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_std(stream: net::TcpStream) -> io::Result<TcpStream> {
+        stream.set_nonblocking(true)?;
+        TcpStream::new(stream)
+    }
+
+    /// Register a stream that is in non-blocking mode already.
+    #[track_caller]
+    fn new(stream: net::TcpStream) -> io::Result<TcpStream> {
+        let registration = super::register(&stream)?;
+        Ok(TcpStream {
+            registration,
+            stream,
+            timeouts: Timeouts::default(),
+        })
+    }
+
+    /// Deregister the stream from the I/O driver, and return it as a [`std::net::TcpStream`].
+    ///
+    /// The stream is left in non-blocking mode, which can be turned off with
+    /// [`set_nonblocking`](std::net::TcpStream::set_nonblocking).
+    pub fn into_std(self) -> net::TcpStream {
+        let TcpStream {
+            registration,
+            stream,
+            ..
+        } = self;
+        drop(registration);
+        stream
+    }
+
+    /// Read some bytes into `buf`, once there are any, and return how many were read.
+    ///
+    /// Returns `0` once the remote side has closed its half of the connection, or if `buf` is
+    /// empty.
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self
+            .registration
+            .async_io(Interest::READABLE, || (&self.stream).read(buf));
+        self.timeouts.reading(read).await
+    }
+
+    /// Read some bytes into `bufs`, filling one after the other, with a single `readv` call, see
+    /// [`read`](TcpStream::read).
+    pub async fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let read = self
+            .registration
+            .async_io(Interest::READABLE, || (&self.stream).read_vectored(bufs));
+        self.timeouts.reading(read).await
+    }
+
+    /// Read some bytes into the part of `buf` that isn't filled yet, which doesn't have to be
+    /// initialized, like [`read`](TcpStream::read) does, and return how many were read, which
+    /// are filled from then on.
+    ///
+    /// Returns `0` once the other side has closed its half of the connection, or if `buf` is
+    /// filled already. See [`ReadBuf`] for an example.
+    pub async fn read_buf(&self, buf: &mut ReadBuf<'_>) -> io::Result<usize> {
+        let read = self.registration.async_io(Interest::READABLE, || {
+            #[cfg(unix)]
+            {
+                read_fd(self.stream.as_raw_fd(), buf)
+            }
+            // Windows has no way to read into memory that isn't initialized.
+            #[cfg(windows)]
+            {
+                let read = (&self.stream).read(buf.initialize_unfilled())?;
+                buf.advance(read);
+                Ok(read)
+            }
+        });
+        self.timeouts.reading(read).await
+    }
+
+    /// Read some bytes into `buf` like [`read`](TcpStream::read), but leave them in the queue of
+    /// the socket, so that the next read returns them again.
+    pub async fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let peek = self
+            .registration
+            .async_io(Interest::READABLE, || self.stream.peek(buf));
+        self.timeouts.reading(peek).await
+    }
+
+    /// Write some bytes of `buf`, once there is room for any, and return how many were written.
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        let write = self
+            .registration
+            .async_io(Interest::WRITABLE, || (&self.stream).write(buf));
+        self.timeouts.writing(write).await
+    }
+
+    /// Write some bytes of `bufs`, one buffer after the other, with a single `writev` call, and
+    /// return how many were written, see [`write`](TcpStream::write).
+    ///
+    /// A framed protocol writes the header and the body of a frame from where they are, rather
+    /// than copying them into one buffer first. Like `write`, it may write only some of the
+    /// bytes, even only some of a buffer.
+    pub async fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let write = self
+            .registration
+            .async_io(Interest::WRITABLE, || (&self.stream).write_vectored(bufs));
+        self.timeouts.writing(write).await
+    }
+
+    /// Whether [`write_vectored`](TcpStream::write_vectored) writes all buffers at once, rather
+    /// than only the first one that isn't empty, which it always does for a socket.
+    ///
+    /// Code that is generic over writers, like a [`BufWriter`](crate::io::BufWriter), checks it to
+    /// decide between writing the buffers as they are, and copying them into one first.
+    pub fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    /// Write all of `buf`, waiting for room as often as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`WriteZero`](io::ErrorKind::WriteZero) if the socket
+    /// accepts no more bytes.
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                written => buf = &buf[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Make [`read`](TcpStream::read) and [`peek`](TcpStream::peek) fail with an error of the kind
+    /// [`TimedOut`](io::ErrorKind::TimedOut) if no bytes come in for `timeout`, or wait for as
+    /// long as it takes if it is `None`, which it is by default.
+    ///
+    /// The timeout covers one call, so a protocol handler whose peer stalls gets an error rather
+    /// than waiting forever, while a peer that keeps sending, however slowly, doesn't run into
+    /// it. The bytes that were in flight when a read timed out aren't lost, the next read returns
+    /// them. Reading panics if a timeout is set, but the time driver isn't enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `timeout`
+    /// is zero, like [`std::net::TcpStream::set_read_timeout`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::net::{TcpListener, TcpStream};
+    /// use mini_executor::runtime::Runtime;
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    ///     let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    ///     // The peer is accepted, but never sends anything.
+    ///     let (_peer, _) = listener.accept().await.unwrap();
+    ///
+    ///     stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+    ///     let mut buf = [0; 16];
+    ///     let error = stream.read(&mut buf).await.unwrap_err();
+    ///     assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    /// });
+    /// ```
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeouts.set_read(timeout)
+    }
+
+    /// The read timeout, see [`set_read_timeout`](TcpStream::set_read_timeout).
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.timeouts.read())
+    }
+
+    /// Make [`write`](TcpStream::write) fail with an error of the kind
+    /// [`TimedOut`](io::ErrorKind::TimedOut) if there is no room for any bytes for `timeout`, or
+    /// wait for as long as it takes if it is `None`, which it is by default, see
+    /// [`set_read_timeout`](TcpStream::set_read_timeout).
+    ///
+    /// [`write_all`](TcpStream::write_all) fails once one of the writes it is made of times out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `timeout`
+    /// is zero.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeouts.set_write(timeout)
+    }
+
+    /// The write timeout, see [`set_write_timeout`](TcpStream::set_write_timeout).
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.timeouts.write())
+    }
+
+    /// Shut down the read half, the write half, or both halves of the connection.
+    ///
+    /// Shutting down the write half sends the peer the end of the stream, after the bytes written
+    /// before, so its reads return 0 once it has read them, while the connection stays open for
+    /// the peer to answer. That is how protocols where the end of a request is the end of the
+    /// stream let the peer know it is complete. [`close`](crate::io::AsyncWriteExt::close) shuts
+    /// down the write half too, and doesn't fail if it was shut down already, or the peer has
+    /// reset the connection, where this method returns an error of the kind
+    /// [`NotConnected`](io::ErrorKind::NotConnected).
+    ///
+    /// # Examples
+    ///
+    /// A request that ends with the stream, and its answer:
+    ///
+    /// ```
+    /// use std::net::Shutdown;
+    ///
+    /// use mini_executor::io::AsyncReadExt;
+    /// use mini_executor::net::{TcpListener, TcpStream};
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// let answer = executor.block_on(async {
+    ///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    ///     let addr = listener.local_addr().unwrap();
+    ///     mini_executor::spawn(async move {
+    ///         let (mut stream, _) = listener.accept().await.unwrap();
+    ///         let mut request = Vec::new();
+    ///         stream.read_to_end(&mut request).await.unwrap();
+    ///         let answer = format!("{} bytes", request.len());
+    ///         stream.write_all(answer.as_bytes()).await.unwrap();
+    ///     });
+    ///
+    ///     let mut stream = TcpStream::connect(addr).await.unwrap();
+    ///     stream.write_all(b"hello").await.unwrap();
+    ///     stream.shutdown(Shutdown::Write).unwrap();
+    ///     let mut answer = String::new();
+    ///     stream.read_to_string(&mut answer).await.unwrap();
+    ///     answer
+    /// });
+    /// assert_eq!(answer, "5 bytes");
+    /// ```
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    /// Split the stream into a half that reads from it, and a half that writes to it, which
+    /// borrow it, to read in one future while writing in another.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::net::{TcpListener, TcpStream};
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    ///     let mut stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    ///     let (peer, _) = listener.accept().await.unwrap();
+    ///
+    ///     let (reader, writer) = stream.split();
+    ///     let echo = async {
+    ///         let mut buf = [0; 4];
+    ///         let read = peer.read(&mut buf).await.unwrap();
+    ///         peer.write_all(&buf[..read]).await.unwrap();
+    ///     };
+    ///     let roundtrip = async {
+    ///         writer.write_all(b"ping").await.unwrap();
+    ///         let mut buf = [0; 4];
+    ///         let read = reader.read(&mut buf).await.unwrap();
+    ///         buf[..read].to_vec()
+    ///     };
+    ///     let ((), echoed) = futures::join!(echo, roundtrip);
+    ///     assert_eq!(echoed, b"ping");
+    /// });
+    /// ```
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        tcp::split(self)
+    }
+
+    /// Split the stream into a half that reads from it, and a half that writes to it, which own
+    /// it together.
+    ///
+    /// Dropping the [`OwnedWriteHalf`] shuts down the writing half of the connection, unless it
+    /// was [`forget`](OwnedWriteHalf::forget)ed. The connection is closed once both halves are
+    /// dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::io::AsyncReadExt;
+    /// use mini_executor::net::{TcpListener, TcpStream};
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// let received = executor.block_on(async {
+    ///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    ///     let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    ///     let (mut peer, _) = listener.accept().await.unwrap();
+    ///
+    ///     let (_reader, writer) = stream.into_split();
+    ///     mini_executor::spawn(async move {
+    ///         writer.write_all(b"hello").await.unwrap();
+    ///         // Dropping the writer shuts the connection down, which ends the read below.
+    ///     });
+    ///
+    ///     let mut received = Vec::new();
+    ///     peer.read_to_end(&mut received).await.unwrap();
+    ///     received
+    /// });
+    /// assert_eq!(received, b"hello");
+    /// ```
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        tcp::into_split(self)
+    }
+
+    /// The address of the local socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    /// The address of the remote socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Whether Nagle's algorithm is disabled, see [`set_nodelay`](TcpStream::set_nodelay).
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.stream.nodelay()
+    }
+
+    /// Disable Nagle's algorithm, which holds back small writes until earlier ones are
+    /// acknowledged, to send them right away instead.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.stream.set_nodelay(nodelay)
+    }
+
+    /// The time-to-live of the packets sent from the socket.
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.stream.ttl()
+    }
+
+    /// Set the time-to-live of the packets sent from the socket.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.stream.set_ttl(ttl)
+    }
+}
+
+impl TcpStream {
+    /// Poll for a read into `buf`, for [`AsyncRead`].
+    fn poll_read_some(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_reading(cx, |cx| {
+            self.registration
+                .poll_io(cx, Interest::READABLE, || (&self.stream).read(buf))
+        })
+    }
+
+    /// Poll for a read into `bufs`, for [`AsyncRead`].
+    fn poll_read_vectored_some(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_reading(cx, |cx| {
+            self.registration.poll_io(cx, Interest::READABLE, || {
+                (&self.stream).read_vectored(bufs)
+            })
+        })
+    }
+
+    /// Poll for a write of `buf`, for [`AsyncWrite`].
+    fn poll_write_some(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_writing(cx, |cx| {
+            self.registration
+                .poll_io(cx, Interest::WRITABLE, || (&self.stream).write(buf))
+        })
+    }
+
+    /// Poll for a write of `bufs`, for [`AsyncWrite`].
+    fn poll_write_vectored_some(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_writing(cx, |cx| {
+            self.registration.poll_io(cx, Interest::WRITABLE, || {
+                (&self.stream).write_vectored(bufs)
+            })
+        })
+    }
+
+    /// Shut down the writing half, for [`AsyncWrite`].
+    ///
+    /// A connection that isn't connected anymore, since the writing half was shut down before, or
+    /// the peer reset it, has nothing left to shut down, so that counts as success, and closing
+    /// the stream twice doesn't fail.
+    fn close_write(&self) -> io::Result<()> {
+        match self.stream.shutdown(Shutdown::Write) {
+            Err(error) if error.kind() == io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_some(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_some(cx, bufs)
+    }
+}
+
+impl AsyncRead for &TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_some(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_some(cx, bufs)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_some(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored_some(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Writes go to the socket right away, there is no buffer to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.close_write())
+    }
+}
+
+impl AsyncWrite for &TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_some(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored_some(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.close_write())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRegistration for TcpStream {
+    fn registration(&self) -> &Registration {
+        &self.registration
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawSocket for TcpStream {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.stream.as_raw_socket()
+    }
+}
+
+impl fmt::Debug for TcpStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.stream.fmt(f)
+    }
+}
+
+/// Order `addrs` so that the addresses of the family of the first one and of the other family take
+/// turns, keeping the order within each family.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_is_ipv6 = first.is_ipv6();
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs
+        .iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut interleaved = Vec::with_capacity(addrs.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (preferred, other) => interleaved.extend(preferred.into_iter().chain(other)),
+        }
+    }
+    interleaved
+}
@@ -0,0 +1,337 @@
+//! [`UdpSocket`], a socket that sends and receives UDP datagrams.
+
+use std::fmt;
+use std::io;
+use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+
+use crate::io::{Interest, Registration};
+
+/// A UDP socket, registered with the I/O driver.
+///
+/// A socket sends datagrams to any address with [`send_to`](UdpSocket::send_to), and receives
+/// them from any address with [`recv_from`](UdpSocket::recv_from). Once it is
+/// [`connect`](UdpSocket::connect)ed, [`send`](UdpSocket::send) and [`recv`](UdpSocket::recv)
+/// talk to that one address only. Each of them waits until the socket is ready, instead of
+/// blocking the thread.
+///
+/// Only the task that waited for a direction last is woken, so at most one task should receive,
+/// and one send, at a time.
+///
+/// A socket can send to a broadcast address once [`set_broadcast`](UdpSocket::set_broadcast)
+/// allows it, and receives the datagrams of a multicast group once it has joined it with
+/// [`join_multicast_v4`](UdpSocket::join_multicast_v4) or
+/// [`join_multicast_v6`](UdpSocket::join_multicast_v6).
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::UdpSocket;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let (received, from) = executor.block_on(async {
+///     let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+///     let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+///     sender.connect(receiver.local_addr().unwrap()).unwrap();
+///
+///     sender.send(b"ping").await.unwrap();
+///
+///     let mut buf = [0; 16];
+///     let (len, from) = receiver.recv_from(&mut buf).await.unwrap();
+///     assert_eq!(from, sender.local_addr().unwrap());
+///     (buf[..len].to_vec(), from)
+/// });
+/// assert_eq!(received, b"ping");
+/// assert!(from.ip().is_loopback());
+/// ```
+pub struct UdpSocket {
+    // Dropped before the socket, so it is still open when it is deregistered.
+    registration: Registration,
+    socket: net::UdpSocket,
+}
+
+impl UdpSocket {
+    /// Create a socket bound to `addr`.
+    ///
+    /// If `addr` resolves to more than one address, each one is tried in turn until the socket
+    /// could be bound to one, like [`std::net::UdpSocket::bind`]. Binding to port 0 picks a free
+    /// port, which [`local_addr`](UdpSocket::local_addr) returns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<UdpSocket> {
+        UdpSocket::from_std(net::UdpSocket::bind(addr)?)
+    }
+
+    /// Register a socket with the I/O driver of the current runtime, and put it into non-blocking
+    /// mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket can't be put into non-blocking mode, or if the runtime has
+    /// shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_std(socket: net::UdpSocket) -> io::Result<UdpSocket> {
+        socket.set_nonblocking(true)?;
+        let registration = super::register(&socket)?;
+        Ok(UdpSocket {
+            registration,
+            socket,
+        })
+    }
+
+    /// Deregister the socket from the I/O driver, and return it as a [`std::net::UdpSocket`],
+    /// which is left in non-blocking mode.
+    pub fn into_std(self) -> net::UdpSocket {
+        let UdpSocket {
+            registration,
+            socket,
+        } = self;
+        drop(registration);
+        socket
+    }
+
+    /// Connect the socket to `addr`, so that [`send`](UdpSocket::send) sends to it, and
+    /// [`recv`](UdpSocket::recv) only receives from it.
+    ///
+    /// No datagram is sent, so this returns right away. If `addr` resolves to more than one
+    /// address, the socket is connected to the first one it can be, like
+    /// [`std::net::UdpSocket::connect`].
+    pub fn connect(&self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        self.socket.connect(addr)
+    }
+
+    /// Send `buf` as one datagram to `addr`, once there is room for it, and return how many bytes
+    /// were sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `addr` doesn't resolve to any address. Only the first one it resolves
+    /// to is sent to.
+    pub async fn send_to(&self, buf: &[u8], addr: impl ToSocketAddrs) -> io::Result<usize> {
+        let Some(addr) = addr.to_socket_addrs()?.next() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no addresses to send data to",
+            ));
+        };
+        self.registration
+            .async_io(Interest::WRITABLE, || self.socket.send_to(buf, addr))
+            .await
+    }
+
+    /// Receive one datagram into `buf`, once one comes in, and return how many bytes were
+    /// received, and where the datagram came from.
+    ///
+    /// The bytes of a datagram that don't fit into `buf` are discarded.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.registration
+            .async_io(Interest::READABLE, || self.socket.recv_from(buf))
+            .await
+    }
+
+    /// Receive one datagram like [`recv_from`](UdpSocket::recv_from), but leave it in the queue
+    /// of the socket, so that the next receive returns it again.
+    pub async fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.registration
+            .async_io(Interest::READABLE, || self.socket.peek_from(buf))
+            .await
+    }
+
+    /// Send `buf` as one datagram to the address the socket is connected to, once there is room
+    /// for it, and return how many bytes were sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket isn't connected.
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::WRITABLE, || self.socket.send(buf))
+            .await
+    }
+
+    /// Receive one datagram from the address the socket is connected to into `buf`, once one
+    /// comes in, and return how many bytes were received.
+    ///
+    /// The bytes of a datagram that don't fit into `buf` are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket isn't connected.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::READABLE, || self.socket.recv(buf))
+            .await
+    }
+
+    /// Receive one datagram like [`recv`](UdpSocket::recv), but leave it in the queue of the
+    /// socket, so that the next receive returns it again.
+    pub async fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::READABLE, || self.socket.peek(buf))
+            .await
+    }
+
+    /// The address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// The address the socket is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Whether the socket may send datagrams to a broadcast address.
+    pub fn broadcast(&self) -> io::Result<bool> {
+        self.socket.broadcast()
+    }
+
+    /// Allow the socket to send datagrams to a broadcast address, like
+    /// [`Ipv4Addr::BROADCAST`], or not. It isn't allowed by default.
+    pub fn set_broadcast(&self, on: bool) -> io::Result<()> {
+        self.socket.set_broadcast(on)
+    }
+
+    /// The time-to-live of the datagrams sent from the socket.
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.socket.ttl()
+    }
+
+    /// Set the time-to-live of the datagrams sent from the socket.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.socket.set_ttl(ttl)
+    }
+
+    /// Join the IPv4 multicast group `multiaddr` on the interface with the address `interface`,
+    /// to receive the datagrams sent to the group.
+    ///
+    /// [`Ipv4Addr::UNSPECIFIED`] lets the system pick the interface. The socket has to be bound to
+    /// the port the datagrams are sent to, and to an unspecified address, or to `multiaddr`, to
+    /// receive them.
+    ///
+    /// # Examples
+    ///
+    /// Asking for the services on the local network, like mDNS, and listening for the answers:
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, SocketAddr};
+    ///
+    /// use mini_executor::net::UdpSocket;
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let group = Ipv4Addr::new(224, 0, 0, 251);
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 5353)).unwrap();
+    ///     socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED).unwrap();
+    ///     socket.set_multicast_loop_v4(false).unwrap();
+    ///     socket.set_multicast_ttl_v4(255).unwrap();
+    ///
+    ///     let query = [0; 12];
+    ///     socket.send_to(&query, SocketAddr::from((group, 5353))).await.unwrap();
+    ///     let mut buf = [0; 9000];
+    ///     loop {
+    ///         let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+    ///         println!("{} bytes from {}", len, from);
+    ///     }
+    /// });
+    /// ```
+    pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        self.socket.join_multicast_v4(multiaddr, interface)
+    }
+
+    /// Join the IPv6 multicast group `multiaddr` on the interface with the index `interface`, to
+    /// receive the datagrams sent to the group.
+    ///
+    /// The index `0` lets the system pick the interface. See
+    /// [`join_multicast_v4`](UdpSocket::join_multicast_v4).
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.socket.join_multicast_v6(multiaddr, interface)
+    }
+
+    /// Leave the IPv4 multicast group `multiaddr` on the interface with the address `interface`,
+    /// which was joined with [`join_multicast_v4`](UdpSocket::join_multicast_v4).
+    pub fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        self.socket.leave_multicast_v4(multiaddr, interface)
+    }
+
+    /// Leave the IPv6 multicast group `multiaddr` on the interface with the index `interface`,
+    /// which was joined with [`join_multicast_v6`](UdpSocket::join_multicast_v6).
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.socket.leave_multicast_v6(multiaddr, interface)
+    }
+
+    /// Whether the IPv4 multicast datagrams sent from the socket are looped back to the sockets
+    /// of the same host that joined the group.
+    pub fn multicast_loop_v4(&self) -> io::Result<bool> {
+        self.socket.multicast_loop_v4()
+    }
+
+    /// Loop the IPv4 multicast datagrams sent from the socket back to the sockets of the same
+    /// host that joined the group, or not. They are looped back by default.
+    pub fn set_multicast_loop_v4(&self, on: bool) -> io::Result<()> {
+        self.socket.set_multicast_loop_v4(on)
+    }
+
+    /// Whether the IPv6 multicast datagrams sent from the socket are looped back to the sockets
+    /// of the same host that joined the group.
+    pub fn multicast_loop_v6(&self) -> io::Result<bool> {
+        self.socket.multicast_loop_v6()
+    }
+
+    /// Loop the IPv6 multicast datagrams sent from the socket back to the sockets of the same
+    /// host that joined the group, or not. They are looped back by default.
+    pub fn set_multicast_loop_v6(&self, on: bool) -> io::Result<()> {
+        self.socket.set_multicast_loop_v6(on)
+    }
+
+    /// The time-to-live of the IPv4 multicast datagrams sent from the socket.
+    pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        self.socket.multicast_ttl_v4()
+    }
+
+    /// Set the time-to-live of the IPv4 multicast datagrams sent from the socket, which is 1 by
+    /// default, so they don't leave the local network.
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> io::Result<()> {
+        self.socket.set_multicast_ttl_v4(ttl)
+    }
+
+    /// Take the pending error of the socket, like an ICMP message that a previous datagram
+    /// couldn't be delivered, if there is one.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.socket.take_error()
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawSocket for UdpSocket {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.socket.as_raw_socket()
+    }
+}
+
+impl fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.socket.fmt(f)
+    }
+}
@@ -0,0 +1,348 @@
+//! The halves of a [`UnixStream`], to read from it in one task and write to it in another.
+//!
+//! [`UnixStream::split`] borrows the stream for a [`ReadHalf`] and a [`WriteHalf`], which
+//! can be used by two futures that run at once, like the branches of a `join!`.
+//! [`UnixStream::into_split`] moves it into an [`OwnedReadHalf`] and an [`OwnedWriteHalf`] instead,
+//! which can be moved into tasks of their own, and put back together with
+//! [`reunite`](OwnedReadHalf::reunite).
+
+use std::fmt;
+use std::io::{self, IoSlice, IoSliceMut};
+use std::net::Shutdown;
+use std::os::unix::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use super::UnixStream;
+use crate::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The reading half of a [`UnixStream`], borrowed with [`UnixStream::split`].
+#[derive(Debug)]
+pub struct ReadHalf<'a> {
+    stream: &'a UnixStream,
+}
+
+/// The writing half of a [`UnixStream`], borrowed with [`UnixStream::split`].
+#[derive(Debug)]
+pub struct WriteHalf<'a> {
+    stream: &'a UnixStream,
+}
+
+/// Borrow `stream` for a reading and a writing half.
+pub(super) fn split(stream: &UnixStream) -> (ReadHalf<'_>, WriteHalf<'_>) {
+    (ReadHalf { stream }, WriteHalf { stream })
+}
+
+/// Move `stream` into a reading and a writing half.
+pub(super) fn into_split(stream: UnixStream) -> (OwnedReadHalf, OwnedWriteHalf) {
+    let stream = Arc::new(stream);
+    let write = OwnedWriteHalf {
+        stream: stream.clone(),
+        shutdown_on_drop: true,
+    };
+    (OwnedReadHalf { stream }, write)
+}
+
+impl ReadHalf<'_> {
+    /// Read some bytes into `buf`, see [`UnixStream::read`].
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf).await
+    }
+
+    /// Read some bytes into `bufs`, see [`UnixStream::read_vectored`].
+    pub async fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.stream.read_vectored(bufs).await
+    }
+
+    /// Read some bytes into `buf`, which doesn't have to be initialized, see
+    /// [`UnixStream::read_buf`].
+    pub async fn read_buf(&self, buf: &mut ReadBuf<'_>) -> io::Result<usize> {
+        self.stream.read_buf(buf).await
+    }
+
+    /// The address of the local socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    /// The address of the remote socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl WriteHalf<'_> {
+    /// Write some bytes of `buf`, see [`UnixStream::write`].
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf).await
+    }
+
+    /// Write some bytes of `bufs`, see [`UnixStream::write_vectored`].
+    pub async fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.stream.write_vectored(bufs).await
+    }
+
+    /// Write all of `buf`, see [`UnixStream::write_all`].
+    pub async fn write_all(&self, buf: &[u8]) -> io::Result<()> {
+        self.stream.write_all(buf).await
+    }
+
+    /// Whether [`write_vectored`](Self::write_vectored) writes all buffers at once, see
+    /// [`UnixStream::is_write_vectored`].
+    pub fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    /// The address of the local socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    /// The address of the remote socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl AsRef<UnixStream> for ReadHalf<'_> {
+    fn as_ref(&self) -> &UnixStream {
+        self.stream
+    }
+}
+
+impl AsRef<UnixStream> for WriteHalf<'_> {
+    fn as_ref(&self) -> &UnixStream {
+        self.stream
+    }
+}
+
+impl AsyncRead for ReadHalf<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl AsyncWrite for WriteHalf<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_close(cx)
+    }
+}
+
+/// The reading half of a [`UnixStream`], owned with [`UnixStream::into_split`].
+#[derive(Debug)]
+pub struct OwnedReadHalf {
+    stream: Arc<UnixStream>,
+}
+
+/// The writing half of a [`UnixStream`], owned with [`UnixStream::into_split`].
+///
+/// Dropping it shuts down the writing half of the connection, unless it was
+/// [`forget`](OwnedWriteHalf::forget)ed.
+#[derive(Debug)]
+pub struct OwnedWriteHalf {
+    stream: Arc<UnixStream>,
+    shutdown_on_drop: bool,
+}
+
+impl OwnedReadHalf {
+    /// Put the stream back together from the two halves it was split into.
+    ///
+    /// # Errors
+    ///
+    /// Returns both halves in a [`ReuniteError`] if `write` is the half of another stream.
+    pub fn reunite(self, write: OwnedWriteHalf) -> Result<UnixStream, ReuniteError> {
+        reunite(self, write)
+    }
+
+    /// Read some bytes into `buf`, see [`UnixStream::read`].
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf).await
+    }
+
+    /// Read some bytes into `bufs`, see [`UnixStream::read_vectored`].
+    pub async fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.stream.read_vectored(bufs).await
+    }
+
+    /// Read some bytes into `buf`, which doesn't have to be initialized, see
+    /// [`UnixStream::read_buf`].
+    pub async fn read_buf(&self, buf: &mut ReadBuf<'_>) -> io::Result<usize> {
+        self.stream.read_buf(buf).await
+    }
+
+    /// The address of the local socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    /// The address of the remote socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl OwnedWriteHalf {
+    /// Put the stream back together from the two halves it was split into, see
+    /// [`OwnedReadHalf::reunite`].
+    pub fn reunite(self, read: OwnedReadHalf) -> Result<UnixStream, ReuniteError> {
+        reunite(read, self)
+    }
+
+    /// Drop the half without shutting down the writing half of the connection.
+    pub fn forget(mut self) {
+        self.shutdown_on_drop = false;
+    }
+
+    /// Write some bytes of `buf`, see [`UnixStream::write`].
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf).await
+    }
+
+    /// Write some bytes of `bufs`, see [`UnixStream::write_vectored`].
+    pub async fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.stream.write_vectored(bufs).await
+    }
+
+    /// Write all of `buf`, see [`UnixStream::write_all`].
+    pub async fn write_all(&self, buf: &[u8]) -> io::Result<()> {
+        self.stream.write_all(buf).await
+    }
+
+    /// Whether [`write_vectored`](Self::write_vectored) writes all buffers at once, see
+    /// [`UnixStream::is_write_vectored`].
+    pub fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    /// The address of the local socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    /// The address of the remote socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl Drop for OwnedWriteHalf {
+    fn drop(&mut self) {
+        if self.shutdown_on_drop {
+            // The connection may have been closed by the other side already.
+            let _ = self.stream.shutdown(Shutdown::Write);
+        }
+    }
+}
+
+impl AsRef<UnixStream> for OwnedReadHalf {
+    fn as_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+}
+
+impl AsRef<UnixStream> for OwnedWriteHalf {
+    fn as_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.stream).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.stream).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self.stream).poll_close(cx)
+    }
+}
+
+fn reunite(read: OwnedReadHalf, mut write: OwnedWriteHalf) -> Result<UnixStream, ReuniteError> {
+    if !Arc::ptr_eq(&read.stream, &write.stream) {
+        return Err(ReuniteError(read, write));
+    }
+    write.shutdown_on_drop = false;
+    drop(write);
+    let stream =
+        Arc::try_unwrap(read.stream).expect("the two halves are the only owners of the stream");
+    Ok(stream)
+}
+
+/// The error of [`OwnedReadHalf::reunite`] when the two halves belong to different streams,
+/// which holds them.
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite the halves of two different streams")
+    }
+}
+
+impl std::error::Error for ReuniteError {}
@@ -0,0 +1,208 @@
+//! [`UnixDatagram`], a Unix domain socket that sends and receives datagrams.
+
+use std::fmt;
+use std::io;
+use std::net::Shutdown;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+
+use crate::io::{Interest, Registration};
+
+/// A Unix domain socket that sends and receives datagrams, registered with the I/O driver.
+///
+/// This is the [`UdpSocket`](super::UdpSocket) of processes on the same machine, where the
+/// datagrams are addressed by paths instead. A socket is [`bind`](UnixDatagram::bind)ed to a path
+/// to receive the datagrams sent to it, and may be [`unbound`](UnixDatagram::unbound) if it only
+/// sends. In contrast to UDP, datagrams are never lost or reordered, and sending waits for room
+/// if the receiver doesn't keep up.
+///
+/// Only the task that waited for a direction last is woken, so at most one task should receive,
+/// and one send, at a time.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::UnixDatagram;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let (left, right) = UnixDatagram::pair().unwrap();
+///     left.send(b"first").await.unwrap();
+///     left.send(b"second").await.unwrap();
+///
+///     let mut buf = [0; 16];
+///     let first = right.recv(&mut buf).await.unwrap();
+///     assert_eq!(&buf[..first], b"first");
+///     let second = right.recv(&mut buf).await.unwrap();
+///     buf[..second].to_vec()
+/// });
+/// assert_eq!(received, b"second");
+/// ```
+pub struct UnixDatagram {
+    // Dropped before the socket, so it is still open when it is deregistered.
+    registration: Registration,
+    socket: net::UnixDatagram,
+}
+
+impl UnixDatagram {
+    /// Create a socket bound to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file exists at `path` already. Like the socket file of a
+    /// [`UnixListener`](super::UnixListener), the one of the socket isn't removed when it is
+    /// dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<UnixDatagram> {
+        UnixDatagram::from_std(net::UnixDatagram::bind(path)?)
+    }
+
+    /// Create a socket that isn't bound to a path, which can send datagrams, but not receive the
+    /// ones sent to a path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn unbound() -> io::Result<UnixDatagram> {
+        UnixDatagram::from_std(net::UnixDatagram::unbound()?)
+    }
+
+    /// Create a pair of unnamed sockets that are connected to each other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (left, right) = net::UnixDatagram::pair()?;
+        Ok((
+            UnixDatagram::from_std(left)?,
+            UnixDatagram::from_std(right)?,
+        ))
+    }
+
+    /// Register a socket with the I/O driver of the current runtime, and put it into non-blocking
+    /// mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket can't be put into non-blocking mode, or if the runtime has
+    /// shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_std(socket: net::UnixDatagram) -> io::Result<UnixDatagram> {
+        socket.set_nonblocking(true)?;
+        let registration = super::register(&socket)?;
+        Ok(UnixDatagram {
+            registration,
+            socket,
+        })
+    }
+
+    /// Deregister the socket from the I/O driver, and return it as a
+    /// [`std::os::unix::net::UnixDatagram`], which is left in non-blocking mode.
+    pub fn into_std(self) -> net::UnixDatagram {
+        let UnixDatagram {
+            registration,
+            socket,
+        } = self;
+        drop(registration);
+        socket
+    }
+
+    /// Connect the socket to the socket bound to `path`, so that [`send`](UnixDatagram::send)
+    /// sends to it, and [`recv`](UnixDatagram::recv) only receives from it.
+    ///
+    /// No datagram is sent, so this returns right away.
+    pub fn connect(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.socket.connect(path)
+    }
+
+    /// Send `buf` as one datagram to the socket bound to `path`, once there is room for it, and
+    /// return how many bytes were sent.
+    pub async fn send_to(&self, buf: &[u8], path: impl AsRef<Path>) -> io::Result<usize> {
+        let path = path.as_ref();
+        self.registration
+            .async_io(Interest::WRITABLE, || self.socket.send_to(buf, path))
+            .await
+    }
+
+    /// Receive one datagram into `buf`, once one comes in, and return how many bytes were
+    /// received, and the address of the socket that sent it, which is unnamed if that one isn't
+    /// bound.
+    ///
+    /// The bytes of a datagram that don't fit into `buf` are discarded.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.registration
+            .async_io(Interest::READABLE, || self.socket.recv_from(buf))
+            .await
+    }
+
+    /// Send `buf` as one datagram to the socket this one is connected to, once there is room for
+    /// it, and return how many bytes were sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket isn't connected.
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::WRITABLE, || self.socket.send(buf))
+            .await
+    }
+
+    /// Receive one datagram into `buf`, once one comes in, and return how many bytes were
+    /// received.
+    ///
+    /// The bytes of a datagram that don't fit into `buf` are discarded.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .async_io(Interest::READABLE, || self.socket.recv(buf))
+            .await
+    }
+
+    /// Shut down the receiving half, the sending half, or both halves of the socket.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.socket.shutdown(how)
+    }
+
+    /// The address the socket is bound to, which is unnamed unless it was bound to a path.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// The address of the socket this one is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Take the pending error of the socket, if there is one.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.socket.take_error()
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for UnixDatagram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.socket.fmt(f)
+    }
+}
@@ -0,0 +1,173 @@
+//! [`UnixListener`], a Unix domain socket that accepts connections.
+
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+use super::UnixStream;
+use crate::io::{Interest, Registration};
+
+/// A Unix domain socket that listens for connections, registered with the I/O driver.
+///
+/// This is the [`TcpListener`](super::TcpListener) of processes on the same machine: it is bound
+/// to a path in the file system, [`accept`](UnixListener::accept) waits for the next connection,
+/// and [`incoming`](UnixListener::incoming) returns them as a [`Stream`]. The socket file isn't
+/// removed when the listener is dropped, so binding to the same path again fails until it is.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::{UnixListener, UnixStream};
+/// use mini_executor::runtime::Runtime;
+///
+/// let path = std::env::temp_dir().join(format!("mini-executor-{}.sock", std::process::id()));
+/// let _ = std::fs::remove_file(&path);
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let listener = UnixListener::bind(&path).unwrap();
+///     mini_executor::spawn(async move {
+///         let (stream, _) = listener.accept().await.unwrap();
+///         stream.write_all(b"hello").await.unwrap();
+///     });
+///
+///     let stream = UnixStream::connect(&path).await.unwrap();
+///     let mut buf = [0; 5];
+///     let mut read = 0;
+///     while read < buf.len() {
+///         read += stream.read(&mut buf[read..]).await.unwrap();
+///     }
+///     buf
+/// });
+/// assert_eq!(&received, b"hello");
+/// std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct UnixListener {
+    // Dropped before the socket, so it is still open when it is deregistered.
+    registration: Registration,
+    listener: net::UnixListener,
+}
+
+impl UnixListener {
+    /// Create a listener bound to `path`, which starts listening for connections right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file exists at `path` already, including the socket file of a
+    /// listener that was bound to it before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+        UnixListener::from_std(net::UnixListener::bind(path)?)
+    }
+
+    /// Register a listener with the I/O driver of the current runtime, and put it into
+    /// non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener can't be put into non-blocking mode, or if the runtime
+    /// has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_std(listener: net::UnixListener) -> io::Result<UnixListener> {
+        listener.set_nonblocking(true)?;
+        let registration = super::register(&listener)?;
+        Ok(UnixListener {
+            registration,
+            listener,
+        })
+    }
+
+    /// Deregister the listener from the I/O driver, and return it as a
+    /// [`std::os::unix::net::UnixListener`], which is left in non-blocking mode.
+    pub fn into_std(self) -> net::UnixListener {
+        let UnixListener {
+            registration,
+            listener,
+        } = self;
+        drop(registration);
+        listener
+    }
+
+    /// Wait for the next connection, and return it with the address of the socket on the other
+    /// side, which is usually unnamed.
+    ///
+    /// The returned stream is registered with the I/O driver of the current runtime.
+    pub async fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Poll for the next connection, and register the waker of `cx` to be woken once one comes in
+    /// if there is none.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(UnixStream, SocketAddr)>> {
+        let (stream, addr) = match self
+            .registration
+            .poll_io(cx, Interest::READABLE, || self.listener.accept())
+        {
+            Poll::Ready(Ok(accepted)) => accepted,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(UnixStream::from_std(stream).map(|stream| (stream, addr)))
+    }
+
+    /// The connections that come in, as a [`Stream`] that never ends, see
+    /// [`TcpListener::incoming`](super::TcpListener::incoming).
+    pub fn incoming(&self) -> UnixIncoming<'_> {
+        UnixIncoming { listener: self }
+    }
+
+    /// The address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for UnixListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.listener.fmt(f)
+    }
+}
+
+/// The connections that come in on a [`UnixListener`], returned by
+/// [`UnixListener::incoming`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct UnixIncoming<'a> {
+    listener: &'a UnixListener,
+}
+
+impl Stream for UnixIncoming<'_> {
+    type Item = io::Result<UnixStream>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<UnixStream>>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|accepted| Some(accepted.map(|(stream, _)| stream)))
+    }
+}
@@ -0,0 +1,557 @@
+//! [`UnixStream`], a connection between two Unix domain sockets.
+
+use std::fmt;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::unix::{self, OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+use super::Timeouts;
+#[cfg(target_os = "linux")]
+use crate::io::AsRegistration;
+use crate::io::{read_fd, AsyncRead, AsyncWrite, Interest, ReadBuf, Registration};
+
+/// A connection between two Unix domain sockets on the same machine, registered with the I/O
+/// driver.
+///
+/// A stream is created by connecting to a listening socket with [`connect`](UnixStream::connect),
+/// as one end of a [`pair`](UnixStream::pair), or from a connected
+/// [`std::os::unix::net::UnixStream`] with [`from_std`](UnixStream::from_std). Like a
+/// [`TcpStream`](super::TcpStream), reading and writing wait until the socket is ready, instead
+/// of blocking the thread, and the connection is closed when the stream is dropped.
+///
+/// Only the task that waited for a direction last is woken, so at most one task should read, and
+/// one write, at a time. Like a `TcpStream`, both `UnixStream` and `&UnixStream` implement
+/// [`AsyncRead`] and [`AsyncWrite`], and it is split into halves with
+/// [`split`](UnixStream::split) and [`into_split`](UnixStream::into_split).
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let received = executor.block_on(async {
+///     let (left, right) = UnixStream::pair().unwrap();
+///     left.write_all(b"hello").await.unwrap();
+///
+///     let mut buf = [0; 5];
+///     let mut read = 0;
+///     while read < buf.len() {
+///         read += right.read(&mut buf[read..]).await.unwrap();
+///     }
+///     buf
+/// });
+/// assert_eq!(&received, b"hello");
+/// ```
+///
+/// The methods of [`AsyncReadExt`](crate::io::AsyncReadExt) work on it too:
+///
+/// ```
+/// use mini_executor::io::AsyncReadExt;
+/// use mini_executor::net::UnixStream;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let (greeting, rest) = executor.block_on(async {
+///     let (left, mut right) = UnixStream::pair().unwrap();
+///     left.write_all(b"hello, world").await.unwrap();
+///     drop(left);
+///
+///     let mut greeting = [0; 5];
+///     right.read_exact(&mut greeting).await.unwrap();
+///     let mut rest = Vec::new();
+///     right.read_to_end(&mut rest).await.unwrap();
+///     (greeting, rest)
+/// });
+/// assert_eq!(&greeting, b"hello");
+/// assert_eq!(rest, b", world");
+/// ```
+pub struct UnixStream {
+    // Dropped before the socket, so it is still open when it is deregistered.
+    registration: Registration,
+    stream: net::UnixStream,
+    timeouts: Timeouts,
+}
+
+impl UnixStream {
+    /// Connect to the socket bound to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no socket listens on `path`. On Linux, that includes a listener whose
+    /// backlog of connections that haven't been accepted yet is full, which fails with
+    /// [`WouldBlock`](io::ErrorKind::WouldBlock).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    pub async fn connect(path: impl AsRef<Path>) -> io::Result<UnixStream> {
+        let socket = super::socket::unix_socket(libc::SOCK_STREAM)?;
+        super::socket::connect_unix(&socket, path.as_ref())?;
+        let stream = UnixStream::new(net::UnixStream::from(socket))?;
+
+        // The socket becomes writable once the connection is made, or has failed.
+        stream.registration.ready(Interest::WRITABLE).await?;
+        if let Some(error) = stream.stream.take_error()? {
+            return Err(error);
+        }
+        Ok(stream)
+    }
+
+    /// Create a pair of streams that are connected to each other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (left, right) = net::UnixStream::pair()?;
+        Ok((UnixStream::from_std(left)?, UnixStream::from_std(right)?))
+    }
+
+    /// Register a connected stream with the I/O driver of the current runtime, and put it into
+    /// non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream can't be put into non-blocking mode, or if the runtime has
+    /// shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn from_std(stream: net::UnixStream) -> io::Result<UnixStream> {
+        stream.set_nonblocking(true)?;
+        UnixStream::new(stream)
+    }
+
+    /// Register a stream that is in non-blocking mode already.
+    #[track_caller]
+    fn new(stream: net::UnixStream) -> io::Result<UnixStream> {
+        let registration = super::register(&stream)?;
+        Ok(UnixStream {
+            registration,
+            stream,
+            timeouts: Timeouts::default(),
+        })
+    }
+
+    /// Deregister the stream from the I/O driver, and return it as a
+    /// [`std::os::unix::net::UnixStream`], which is left in non-blocking mode.
+    pub fn into_std(self) -> net::UnixStream {
+        let UnixStream {
+            registration,
+            stream,
+            ..
+        } = self;
+        drop(registration);
+        stream
+    }
+
+    /// Read some bytes into `buf`, once there are any, and return how many were read.
+    ///
+    /// Returns `0` once the other side has closed its half of the connection, or if `buf` is
+    /// empty.
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self
+            .registration
+            .async_io(Interest::READABLE, || (&self.stream).read(buf));
+        self.timeouts.reading(read).await
+    }
+
+    /// Read some bytes into `bufs`, filling one after the other, with a single `readv` call, see
+    /// [`read`](UnixStream::read).
+    pub async fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let read = self
+            .registration
+            .async_io(Interest::READABLE, || (&self.stream).read_vectored(bufs));
+        self.timeouts.reading(read).await
+    }
+
+    /// Read some bytes into the part of `buf` that isn't filled yet, which doesn't have to be
+    /// initialized, like [`read`](UnixStream::read) does, and return how many were read, which
+    /// are filled from then on.
+    ///
+    /// Returns `0` once the other side has closed its half of the connection, or if `buf` is
+    /// filled already. See [`ReadBuf`] for an example.
+    pub async fn read_buf(&self, buf: &mut ReadBuf<'_>) -> io::Result<usize> {
+        let read = self
+            .registration
+            .async_io(Interest::READABLE, || read_fd(self.stream.as_raw_fd(), buf));
+        self.timeouts.reading(read).await
+    }
+
+    /// Write some bytes of `buf`, once there is room for any, and return how many were written.
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        let write = self
+            .registration
+            .async_io(Interest::WRITABLE, || (&self.stream).write(buf));
+        self.timeouts.writing(write).await
+    }
+
+    /// Write some bytes of `bufs`, one buffer after the other, with a single `writev` call, and
+    /// return how many were written, see [`write`](UnixStream::write).
+    ///
+    /// A framed protocol writes the header and the body of a frame from where they are, rather
+    /// than copying them into one buffer first. Like `write`, it may write only some of the
+    /// bytes, even only some of a buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::net::UnixStream;
+    /// use mini_executor::runtime::Runtime;
+    /// use std::io::IoSlice;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let (left, right) = UnixStream::pair().unwrap();
+    ///     let body = b"hello";
+    ///     let header = (body.len() as u32).to_be_bytes();
+    ///     // The frame goes out in one call, without copying the header and the body together.
+    ///     let written = left
+    ///         .write_vectored(&[IoSlice::new(&header), IoSlice::new(body)])
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(written, 9);
+    ///
+    ///     let mut frame = [0; 9];
+    ///     assert_eq!(right.read(&mut frame).await.unwrap(), 9);
+    ///     assert_eq!(&frame[4..], b"hello");
+    /// });
+    /// ```
+    pub async fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let write = self
+            .registration
+            .async_io(Interest::WRITABLE, || (&self.stream).write_vectored(bufs));
+        self.timeouts.writing(write).await
+    }
+
+    /// Whether [`write_vectored`](UnixStream::write_vectored) writes all buffers at once, rather
+    /// than only the first one that isn't empty, which it always does for a socket.
+    ///
+    /// Code that is generic over writers, like a [`BufWriter`](crate::io::BufWriter), checks it to
+    /// decide between writing the buffers as they are, and copying them into one first.
+    pub fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    /// Write all of `buf`, waiting for room as often as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`WriteZero`](io::ErrorKind::WriteZero) if the socket
+    /// accepts no more bytes.
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                written => buf = &buf[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Make [`read`](UnixStream::read) fail with an error of the kind
+    /// [`TimedOut`](io::ErrorKind::TimedOut) if no bytes come in for `timeout`, or wait for as
+    /// long as it takes if it is `None`, see
+    /// [`TcpStream::set_read_timeout`](super::TcpStream::set_read_timeout).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `timeout`
+    /// is zero.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeouts.set_read(timeout)
+    }
+
+    /// The read timeout, see [`set_read_timeout`](UnixStream::set_read_timeout).
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.timeouts.read())
+    }
+
+    /// Make [`write`](UnixStream::write) fail with an error of the kind
+    /// [`TimedOut`](io::ErrorKind::TimedOut) if there is no room for any bytes for `timeout`, or
+    /// wait for as long as it takes if it is `None`, see
+    /// [`TcpStream::set_write_timeout`](super::TcpStream::set_write_timeout).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `timeout`
+    /// is zero.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeouts.set_write(timeout)
+    }
+
+    /// The write timeout, see [`set_write_timeout`](UnixStream::set_write_timeout).
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.timeouts.write())
+    }
+
+    /// Shut down the read half, the write half, or both halves of the connection.
+    ///
+    /// Shutting down the write half lets the peer read the end of the stream, while it can still
+    /// answer, see [`TcpStream::shutdown`](crate::net::TcpStream::shutdown).
+    /// [`close`](crate::io::AsyncWriteExt::close) shuts down the write half too, and doesn't fail
+    /// if the connection isn't connected anymore.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    /// Split the stream into a half that reads from it, and a half that writes to it, which
+    /// borrow it, to read in one future while writing in another.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::net::UnixStream;
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let (mut stream, peer) = UnixStream::pair().unwrap();
+    ///
+    ///     let (reader, writer) = stream.split();
+    ///     let echo = async {
+    ///         let mut buf = [0; 4];
+    ///         let read = peer.read(&mut buf).await.unwrap();
+    ///         peer.write_all(&buf[..read]).await.unwrap();
+    ///     };
+    ///     let roundtrip = async {
+    ///         writer.write_all(b"ping").await.unwrap();
+    ///         let mut buf = [0; 4];
+    ///         let read = reader.read(&mut buf).await.unwrap();
+    ///         buf[..read].to_vec()
+    ///     };
+    ///     let ((), echoed) = futures::join!(echo, roundtrip);
+    ///     assert_eq!(echoed, b"ping");
+    /// });
+    /// ```
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        unix::split(self)
+    }
+
+    /// Split the stream into a half that reads from it, and a half that writes to it, which own
+    /// it together.
+    ///
+    /// Dropping the [`OwnedWriteHalf`] shuts down the writing half of the connection, unless it
+    /// was [`forget`](OwnedWriteHalf::forget)ed. The connection is closed once both halves are
+    /// dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::io::AsyncReadExt;
+    /// use mini_executor::net::UnixStream;
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// let received = executor.block_on(async {
+    ///     let (stream, mut peer) = UnixStream::pair().unwrap();
+    ///
+    ///     let (_reader, writer) = stream.into_split();
+    ///     mini_executor::spawn(async move {
+    ///         writer.write_all(b"hello").await.unwrap();
+    ///         // Dropping the writer shuts the connection down, which ends the read below.
+    ///     });
+    ///
+    ///     let mut received = Vec::new();
+    ///     peer.read_to_end(&mut received).await.unwrap();
+    ///     received
+    /// });
+    /// assert_eq!(received, b"hello");
+    /// ```
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        unix::into_split(self)
+    }
+
+    /// The address of the local socket, which is unnamed unless it was bound to a path.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    /// The address of the socket on the other side.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Take the pending error of the socket, if there is one.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.stream.take_error()
+    }
+}
+
+impl UnixStream {
+    /// Poll for a read into `buf`, for [`AsyncRead`].
+    fn poll_read_some(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_reading(cx, |cx| {
+            self.registration
+                .poll_io(cx, Interest::READABLE, || (&self.stream).read(buf))
+        })
+    }
+
+    /// Poll for a read into `bufs`, for [`AsyncRead`].
+    fn poll_read_vectored_some(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_reading(cx, |cx| {
+            self.registration.poll_io(cx, Interest::READABLE, || {
+                (&self.stream).read_vectored(bufs)
+            })
+        })
+    }
+
+    /// Poll for a write of `buf`, for [`AsyncWrite`].
+    fn poll_write_some(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_writing(cx, |cx| {
+            self.registration
+                .poll_io(cx, Interest::WRITABLE, || (&self.stream).write(buf))
+        })
+    }
+
+    /// Poll for a write of `bufs`, for [`AsyncWrite`].
+    fn poll_write_vectored_some(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.timeouts.poll_writing(cx, |cx| {
+            self.registration.poll_io(cx, Interest::WRITABLE, || {
+                (&self.stream).write_vectored(bufs)
+            })
+        })
+    }
+
+    /// Shut down the writing half, for [`AsyncWrite`].
+    ///
+    /// A connection that isn't connected anymore, since the writing half was shut down before, or
+    /// the peer reset it, has nothing left to shut down, so that counts as success, and closing
+    /// the stream twice doesn't fail.
+    fn close_write(&self) -> io::Result<()> {
+        match self.stream.shutdown(Shutdown::Write) {
+            Err(error) if error.kind() == io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_some(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_some(cx, bufs)
+    }
+}
+
+impl AsyncRead for &UnixStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_some(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_some(cx, bufs)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_some(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored_some(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Writes go to the socket right away, there is no buffer to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.close_write())
+    }
+}
+
+impl AsyncWrite for &UnixStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_some(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored_some(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.close_write())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRegistration for UnixStream {
+    fn registration(&self) -> &Registration {
+        &self.registration
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for UnixStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.stream.fmt(f)
+    }
+}
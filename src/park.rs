@@ -0,0 +1,125 @@
+//! What the thread that drives an executor blocks on while it has nothing to do.
+//!
+//! By default, a [`Runtime`](crate::runtime::Runtime) that runs out of work parks its thread until
+//! a task is woken or the next timer is due, or waits for I/O events in the meantime if its I/O
+//! driver is enabled. A [`Park`] set with [`Builder::park`](crate::runtime::Builder::park) replaces
+//! that, so the executor can share its thread with another source of events, like the event loop
+//! of a GUI toolkit, or a device that raises interrupts.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A way for the thread that drives an executor to block until there is work to do.
+///
+/// The thread that drives the executor in [`block_on`](crate::runtime::Runtime::block_on) or
+/// [`run`](crate::runtime::Runtime::run) calls [`park`](Park::park) once it has polled every task
+/// that was ready and fired the timers that were due. Any thread calls [`unpark`](Park::unpark)
+/// when a task of the executor is woken, so that the parked thread goes back to polling it. Like
+/// [`thread::park`](std::thread::park), an `unpark` that comes before the `park` it is meant for
+/// must make that `park` return right away, or the wake-up is lost.
+///
+/// The workers of a multi-threaded executor keep waiting for queued tasks on their own, only the
+/// thread that drives the executor parks here. If the I/O driver is enabled, it is polled without
+/// waiting before and after each `park`, so a `Park` that doesn't return for a long time delays
+/// the I/O events on a current-thread executor.
+///
+/// # Examples
+///
+/// An event loop that handles the events posted to it while the executor is idle:
+///
+/// ```
+/// use mini_executor::park::Park;
+/// use mini_executor::runtime::Runtime;
+/// use std::sync::{Arc, Condvar, Mutex};
+/// use std::time::Duration;
+///
+/// #[derive(Default)]
+/// struct EventLoop {
+///     /// The events posted to the loop, and whether the executor has been unparked.
+///     state: Mutex<(Vec<String>, bool)>,
+///     condvar: Condvar,
+///     handled: Mutex<Vec<String>>,
+/// }
+///
+/// impl EventLoop {
+///     fn post(&self, event: &str) {
+///         self.state.lock().unwrap().0.push(event.to_string());
+///         self.condvar.notify_one();
+///     }
+/// }
+///
+/// impl Park for EventLoop {
+///     fn park(&self, timeout: Option<Duration>) {
+///         let mut state = self.state.lock().unwrap();
+///         loop {
+///             let events = std::mem::take(&mut state.0);
+///             self.handled.lock().unwrap().extend(events);
+///             if std::mem::take(&mut state.1) {
+///                 return;
+///             }
+///             state = match timeout {
+///                 Some(timeout) => {
+///                     let (state, result) = self.condvar.wait_timeout(state, timeout).unwrap();
+///                     if result.timed_out() {
+///                         return;
+///                     }
+///                     state
+///                 }
+///                 None => self.condvar.wait(state).unwrap(),
+///             };
+///         }
+///     }
+///
+///     fn unpark(&self) {
+///         self.state.lock().unwrap().1 = true;
+///         self.condvar.notify_one();
+///     }
+/// }
+///
+/// let event_loop = Arc::new(EventLoop::default());
+/// let executor = Runtime::builder().park(event_loop.clone()).build();
+///
+/// let (sender, receiver) = futures::channel::oneshot::channel();
+/// let poster = event_loop.clone();
+/// std::thread::spawn(move || {
+///     poster.post("clicked");
+///     sender.send(42).unwrap();
+/// });
+///
+/// assert_eq!(executor.block_on(receiver).unwrap(), 42);
+/// // The event may still be waiting, if the task was woken before the loop got to it.
+/// event_loop.park(Some(Duration::ZERO));
+/// assert_eq!(*event_loop.handled.lock().unwrap(), ["clicked"]);
+/// ```
+pub trait Park: Send + Sync + 'static {
+    /// Block the current thread until [`unpark`](Park::unpark) is called, or until `timeout` has
+    /// passed, if it is `Some`.
+    ///
+    /// Returning early, without being unparked, is fine: the executor checks for work, and parks
+    /// again if there is none.
+    fn park(&self, timeout: Option<Duration>);
+
+    /// Make the thread that is blocked in [`park`](Park::park) return, or the next call to it if
+    /// no thread is.
+    fn unpark(&self);
+}
+
+impl<P: Park + ?Sized> Park for Arc<P> {
+    fn park(&self, timeout: Option<Duration>) {
+        (**self).park(timeout);
+    }
+
+    fn unpark(&self) {
+        (**self).unpark();
+    }
+}
+
+impl<P: Park + ?Sized> Park for Box<P> {
+    fn park(&self, timeout: Option<Duration>) {
+        (**self).park(timeout);
+    }
+
+    fn unpark(&self) {
+        (**self).unpark();
+    }
+}
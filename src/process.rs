@@ -0,0 +1,686 @@
+//! Running child processes inside of async code, on Unix.
+//!
+//! A [`Command`] is configured like a [`std::process::Command`], and [`spawn`](Command::spawn)s a
+//! [`Child`], whose exit is waited for with [`Child::wait`] without blocking the thread. The
+//! standard streams of the child that are [`Stdio::piped`] become a [`ChildStdin`], a
+//! [`ChildStdout`] and a [`ChildStderr`], which implement [`AsyncWrite`] and [`AsyncRead`] on top
+//! of the ends of a [`pipe`](crate::io::pipe).
+//! [`Command::output`] runs a command to its end and collects everything it wrote.
+//!
+//! On Linux, the exit of a child is waited for through a `pidfd`, a file descriptor of the
+//! process that becomes readable once it has exited, which is registered with the I/O driver like
+//! a socket. On the other platforms, and on kernels older than 5.3, a wait is woken by the
+//! `SIGCHLD` the process receives when one of its children exits, through
+//! [`signal::unix::signal`](crate::signal::unix::signal), which installs a handler for it.
+//!
+//! A child that is dropped keeps running, like one of `std`, unless its command was configured
+//! with [`kill_on_drop`](Command::kill_on_drop), in which case it is killed, and reaped on the
+//! blocking pool, or on a thread of its own outside of a runtime.
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::fd::FromRawFd;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::process;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures::Stream;
+
+pub use std::process::{ExitStatus, Output, Stdio};
+
+use crate::io::{AsyncRead, AsyncReadExt, AsyncWrite, PipeReader, PipeWriter};
+#[cfg(target_os = "linux")]
+use crate::io::{Interest, Registration};
+use crate::runtime::{BlockingJob, Handle};
+use crate::signal::unix::{signal, Signal, SignalKind};
+
+/// A process to spawn, configured like a [`std::process::Command`].
+///
+/// The standard streams of the child are inherited from the parent unless configured otherwise,
+/// except by [`output`](Command::output), which collects what it writes to them.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::process::Command;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let output = executor.block_on(async {
+///     Command::new("echo").arg("hello").output().await.unwrap()
+/// });
+/// assert!(output.status.success());
+/// assert_eq!(output.stdout, b"hello\n");
+/// ```
+pub struct Command {
+    std: process::Command,
+    kill_on_drop: bool,
+}
+
+impl Command {
+    /// A command that runs `program`, see [`std::process::Command::new`].
+    pub fn new(program: impl AsRef<OsStr>) -> Command {
+        Command::from(process::Command::new(program))
+    }
+
+    /// Add `arg` to the arguments of the program.
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Command {
+        self.std.arg(arg);
+        self
+    }
+
+    /// Add `args` to the arguments of the program.
+    pub fn args<I, S>(&mut self, args: I) -> &mut Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.std.args(args);
+        self
+    }
+
+    /// Set the environment variable `key` of the child to `value`.
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Command {
+        self.std.env(key, value);
+        self
+    }
+
+    /// Set the environment variables `vars` of the child.
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Command
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.std.envs(vars);
+        self
+    }
+
+    /// Remove the environment variable `key` from the ones the child inherits.
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Command {
+        self.std.env_remove(key);
+        self
+    }
+
+    /// Let the child inherit none of the environment variables of the parent.
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.std.env_clear();
+        self
+    }
+
+    /// Run the child in the directory `dir`.
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Command {
+        self.std.current_dir(dir);
+        self
+    }
+
+    /// Configure the standard input of the child, which becomes [`Child::stdin`] if it is
+    /// [`Stdio::piped`].
+    pub fn stdin(&mut self, cfg: impl Into<Stdio>) -> &mut Command {
+        self.std.stdin(cfg);
+        self
+    }
+
+    /// Configure the standard output of the child, which becomes [`Child::stdout`] if it is
+    /// [`Stdio::piped`].
+    pub fn stdout(&mut self, cfg: impl Into<Stdio>) -> &mut Command {
+        self.std.stdout(cfg);
+        self
+    }
+
+    /// Configure the standard error of the child, which becomes [`Child::stderr`] if it is
+    /// [`Stdio::piped`].
+    pub fn stderr(&mut self, cfg: impl Into<Stdio>) -> &mut Command {
+        self.std.stderr(cfg);
+        self
+    }
+
+    /// Whether a [`Child`] of the command is killed when it is dropped before it has exited, or
+    /// left running, which it is by default.
+    ///
+    /// A future that is dropped while it waits for a child, like the one of
+    /// [`output`](Command::output) inside of a [`timeout`](crate::time::timeout), drops the
+    /// child with it.
+    pub fn kill_on_drop(&mut self, kill_on_drop: bool) -> &mut Command {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// The [`std::process::Command`] this wraps, to read its configuration.
+    pub fn as_std(&self) -> &process::Command {
+        &self.std
+    }
+
+    /// The [`std::process::Command`] this wraps, to configure what this doesn't, like the
+    /// platform specific options of `std::os::unix::process::CommandExt`.
+    pub fn as_std_mut(&mut self) -> &mut process::Command {
+        &mut self.std
+    }
+
+    /// Spawn the child, and register its pipes with the I/O driver of the current runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the program can't be found or run, or if the runtime has shut down.
+    /// A child that was spawned already when one of its pipes fails to be registered is killed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    #[track_caller]
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        let std = self.std.spawn()?;
+        Child::new(std, self.kill_on_drop)
+    }
+
+    /// Spawn the child, and wait for it to exit, see [`Child::wait`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the child can't be spawned, see [`spawn`](Command::spawn).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    pub async fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn()?.wait().await
+    }
+
+    /// Spawn the child with its standard output and error piped, wait for it to exit, and return
+    /// everything it wrote to them, see [`Child::wait_with_output`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the child can't be spawned, see [`spawn`](Command::spawn), or its
+    /// output can't be read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+    /// whose I/O driver isn't enabled.
+    pub async fn output(&mut self) -> io::Result<Output> {
+        self.std.stdout(Stdio::piped()).stderr(Stdio::piped());
+        self.spawn()?.wait_with_output().await
+    }
+}
+
+impl From<process::Command> for Command {
+    fn from(std: process::Command) -> Command {
+        Command {
+            std,
+            kill_on_drop: false,
+        }
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.std.fmt(f)
+    }
+}
+
+/// A child process, spawned by [`Command::spawn`].
+///
+/// The pipes to the standard streams of the child are public fields, which are taken out to be
+/// read from or written to by other tasks while one waits for the child.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::io::AsyncReadExt;
+/// use mini_executor::process::{Command, Stdio};
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+/// let (status, reversed) = executor.block_on(async {
+///     let mut child = Command::new("rev")
+///         .stdin(Stdio::piped())
+///         .stdout(Stdio::piped())
+///         .spawn()
+///         .unwrap();
+///     let stdin = child.stdin.take().unwrap();
+///     stdin.write_all(b"hello\n").await.unwrap();
+///     drop(stdin);
+///
+///     let mut reversed = String::new();
+///     let mut stdout = child.stdout.take().unwrap();
+///     stdout.read_to_string(&mut reversed).await.unwrap();
+///     (child.wait().await.unwrap(), reversed)
+/// });
+/// assert!(status.success());
+/// assert_eq!(reversed, "olleh\n");
+/// ```
+pub struct Child {
+    std: process::Child,
+    waiter: Waiter,
+    /// The status of the child, once it has been waited for.
+    status: Option<ExitStatus>,
+    kill_on_drop: bool,
+    /// The standard input of the child, if it was [`Stdio::piped`].
+    pub stdin: Option<ChildStdin>,
+    /// The standard output of the child, if it was [`Stdio::piped`].
+    pub stdout: Option<ChildStdout>,
+    /// The standard error of the child, if it was [`Stdio::piped`].
+    pub stderr: Option<ChildStderr>,
+}
+
+/// How a [`Child`] waits for its exit.
+enum Waiter {
+    /// The `pidfd` of the child becomes readable once it has exited.
+    #[cfg(target_os = "linux")]
+    PidFd {
+        // Dropped before the file descriptor, so it is still open when it is deregistered.
+        registration: Registration,
+        /// Only kept open, the registration is what waits for it.
+        _fd: OwnedFd,
+    },
+    /// The `SIGCHLD`s of the process, after each of which the child may have exited.
+    Signal(Signal),
+}
+
+impl Child {
+    /// Wrap the child that was just spawned, and register its exit and its pipes.
+    #[track_caller]
+    fn new(mut std: process::Child, kill_on_drop: bool) -> io::Result<Child> {
+        let waiter = match Waiter::new(std.id()) {
+            Ok(waiter) => waiter,
+            Err(error) => {
+                // The child would be left running without anything to wait for it.
+                let _ = std.kill();
+                let _ = std.wait();
+                return Err(error);
+            }
+        };
+        let mut child = Child {
+            std,
+            waiter,
+            status: None,
+            kill_on_drop,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+        };
+        if let Err(error) = child.register() {
+            let _ = child.std.kill();
+            let _ = child.std.wait();
+            return Err(error);
+        }
+        Ok(child)
+    }
+
+    /// Register the pipes of the child.
+    #[track_caller]
+    fn register(&mut self) -> io::Result<()> {
+        if let Some(stdin) = self.std.stdin.take() {
+            let pipe = PipeWriter::from_owned_fd(stdin.into())?;
+            self.stdin = Some(ChildStdin { pipe });
+        }
+        if let Some(stdout) = self.std.stdout.take() {
+            let pipe = PipeReader::from_owned_fd(stdout.into())?;
+            self.stdout = Some(ChildStdout { pipe });
+        }
+        if let Some(stderr) = self.std.stderr.take() {
+            let pipe = PipeReader::from_owned_fd(stderr.into())?;
+            self.stderr = Some(ChildStderr { pipe });
+        }
+        Ok(())
+    }
+
+    /// The process id of the child, or `None` once it has been waited for, after which the id
+    /// may belong to another process.
+    pub fn id(&self) -> Option<u32> {
+        self.status.is_none().then(|| self.std.id())
+    }
+
+    /// Wait for the child to exit, and return its status.
+    ///
+    /// The standard input of the child is closed first, if it is still in [`stdin`](Child::stdin),
+    /// so a child that reads until its end isn't left waiting for more. The status is kept, and
+    /// returned again by the waits after it.
+    pub async fn wait(&mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        poll_fn(|cx| self.poll_wait(cx)).await
+    }
+
+    /// The status of the child if it has exited, or `None` if it is still running, without
+    /// waiting.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        if self.status.is_none() {
+            self.status = self.std.try_wait()?;
+        }
+        Ok(self.status)
+    }
+
+    /// Send `SIGKILL` to the child, without waiting for it to exit.
+    ///
+    /// Does nothing if the child has exited already.
+    pub fn start_kill(&mut self) -> io::Result<()> {
+        match self.try_wait()? {
+            Some(_) => Ok(()),
+            None => self.std.kill(),
+        }
+    }
+
+    /// Send `SIGKILL` to the child, and wait for it to exit, see [`start_kill`](Child::start_kill).
+    pub async fn kill(&mut self) -> io::Result<()> {
+        self.start_kill()?;
+        self.wait().await.map(drop)
+    }
+
+    /// Wait for the child to exit while reading everything it writes to its standard output and
+    /// error, if they are piped, and return both with its status.
+    ///
+    /// Reading while waiting keeps a child that writes more than fits into a pipe from waiting
+    /// for the parent to read it, while the parent waits for it to exit.
+    pub async fn wait_with_output(mut self) -> io::Result<Output> {
+        drop(self.stdin.take());
+        let stdout = self.stdout.take();
+        let stderr = self.stderr.take();
+        let (status, stdout, stderr) =
+            futures::try_join!(self.wait(), read_to_end(stdout), read_to_end(stderr))?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// Poll for the exit of the child.
+    fn poll_wait(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<ExitStatus>> {
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Poll::Ready(Ok(status));
+            }
+            match &mut self.waiter {
+                #[cfg(target_os = "linux")]
+                Waiter::PidFd { registration, .. } => {
+                    let std = &mut self.std;
+                    let status = registration.poll_io(cx, Interest::READABLE, || {
+                        std.try_wait()?
+                            .ok_or_else(|| io::ErrorKind::WouldBlock.into())
+                    });
+                    if let Poll::Ready(Ok(status)) = status {
+                        self.status = Some(status);
+                    }
+                    return status;
+                }
+                // The signal may be for another child, so every one is checked again.
+                Waiter::Signal(signal) => match Pin::new(signal).poll_next(cx) {
+                    Poll::Ready(Some(Ok(()))) => {}
+                    Poll::Ready(Some(Err(error))) => return Poll::Ready(Err(error)),
+                    Poll::Ready(None) => unreachable!("a signal stream never ends"),
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
+
+impl Waiter {
+    /// Open and register the `pidfd` of the child `pid`, if the kernel has them.
+    #[cfg(target_os = "linux")]
+    #[track_caller]
+    fn new(pid: u32) -> io::Result<Waiter> {
+        // Safety: the call has no arguments that point anywhere.
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        if fd < 0 {
+            // Kernels older than 5.3 don't have the system call.
+            return Ok(Waiter::Signal(signal(SignalKind::child())?));
+        }
+        // Safety: the descriptor was just created, and is owned from here on.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        Ok(Waiter::PidFd {
+            registration: Registration::new(fd.as_raw_fd(), Interest::READABLE)?,
+            _fd: fd,
+        })
+    }
+
+    /// Wait for the `SIGCHLD`s of the process, which is done before the exit of the child is
+    /// first checked, so that none is missed.
+    #[cfg(not(target_os = "linux"))]
+    #[track_caller]
+    fn new(_: u32) -> io::Result<Waiter> {
+        Ok(Waiter::Signal(signal(SignalKind::child())?))
+    }
+}
+
+/// Read everything from `pipe`, if there is one.
+async fn read_to_end(pipe: Option<impl AsyncRead + Unpin>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if let Some(mut pipe) = pipe {
+        pipe.read_to_end(&mut buf).await?;
+    }
+    Ok(buf)
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        if !self.kill_on_drop || !matches!(self.try_wait(), Ok(None)) {
+            return;
+        }
+        if self.std.kill().is_err() {
+            return;
+        }
+        // Reap the killed child on the blocking pool, rather than leave it a zombie, since
+        // nothing else waits for it.
+        let pid = self.std.id() as libc::pid_t;
+        let reap: BlockingJob = Box::new(move || {
+            // Safety: `waitpid` is allowed to not write the status anywhere.
+            unsafe { libc::waitpid(pid, std::ptr::null_mut(), 0) };
+        });
+        let rejected = match Handle::try_current() {
+            Ok(handle) => handle.try_spawn_blocking(reap),
+            Err(_) => Err(reap),
+        };
+        // Outside of a runtime, or once its blocking pool has shut down, a thread of its own
+        // waits for it instead.
+        if let Err(reap) = rejected {
+            let _ = thread::Builder::new()
+                .name("mini-executor-reaper".to_string())
+                .spawn(reap);
+        }
+    }
+}
+
+impl fmt::Debug for Child {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Child")
+            .field("id", &self.std.id())
+            .field("status", &self.status)
+            .field("stdin", &self.stdin)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The standard input of a [`Child`], written to through a pipe that is registered with the I/O
+/// driver.
+///
+/// The child reaches the end of its input once this is dropped.
+pub struct ChildStdin {
+    pipe: PipeWriter,
+}
+
+/// The standard output of a [`Child`], read from through a pipe that is registered with the I/O
+/// driver.
+pub struct ChildStdout {
+    pipe: PipeReader,
+}
+
+/// The standard error of a [`Child`], read from through a pipe that is registered with the I/O
+/// driver.
+pub struct ChildStderr {
+    pipe: PipeReader,
+}
+
+impl ChildStdin {
+    /// Write some bytes of `buf`, once there is room for any, and return how many were written,
+    /// see [`PipeWriter::write`].
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.pipe.write(buf).await
+    }
+
+    /// Write all of `buf`, see [`PipeWriter::write_all`].
+    pub async fn write_all(&self, buf: &[u8]) -> io::Result<()> {
+        self.pipe.write_all(buf).await
+    }
+
+    /// Deregister the pipe from the I/O driver, and return its file descriptor, see
+    /// [`PipeWriter::into_owned_fd`].
+    pub fn into_owned_fd(self) -> OwnedFd {
+        self.pipe.into_owned_fd()
+    }
+}
+
+impl ChildStdout {
+    /// Read some bytes into `buf`, once there are any, and return how many were read, see
+    /// [`PipeReader::read`].
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.pipe.read(buf).await
+    }
+
+    /// Deregister the pipe from the I/O driver, and return its file descriptor, see
+    /// [`PipeReader::into_owned_fd`].
+    pub fn into_owned_fd(self) -> OwnedFd {
+        self.pipe.into_owned_fd()
+    }
+}
+
+impl ChildStderr {
+    /// Read some bytes into `buf`, once there are any, and return how many were read, see
+    /// [`PipeReader::read`].
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.pipe.read(buf).await
+    }
+
+    /// Deregister the pipe from the I/O driver, and return its file descriptor, see
+    /// [`PipeReader::into_owned_fd`].
+    pub fn into_owned_fd(self) -> OwnedFd {
+        self.pipe.into_owned_fd()
+    }
+}
+
+impl AsyncWrite for ChildStdin {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().pipe).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().pipe).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().pipe).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().pipe).poll_close(cx)
+    }
+}
+
+impl AsyncRead for ChildStdout {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().pipe).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().pipe).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl AsyncRead for ChildStderr {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().pipe).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().pipe).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl AsRawFd for ChildStdin {
+    fn as_raw_fd(&self) -> RawFd {
+        self.pipe.as_raw_fd()
+    }
+}
+
+impl AsRawFd for ChildStdout {
+    fn as_raw_fd(&self) -> RawFd {
+        self.pipe.as_raw_fd()
+    }
+}
+
+impl AsRawFd for ChildStderr {
+    fn as_raw_fd(&self) -> RawFd {
+        self.pipe.as_raw_fd()
+    }
+}
+
+impl AsFd for ChildStdin {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.pipe.as_fd()
+    }
+}
+
+impl AsFd for ChildStdout {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.pipe.as_fd()
+    }
+}
+
+impl AsFd for ChildStderr {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.pipe.as_fd()
+    }
+}
+
+impl fmt::Debug for ChildStdin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ChildStdin").field(&self.pipe).finish()
+    }
+}
+
+impl fmt::Debug for ChildStdout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ChildStdout").field(&self.pipe).finish()
+    }
+}
+
+impl fmt::Debug for ChildStderr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ChildStderr").field(&self.pipe).finish()
+    }
+}
@@ -0,0 +1,3732 @@
+//! The executor itself, how to configure it, and what it tells about the tasks it runs.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use crate::io;
+use crate::park::Park;
+use crate::scheduler::{Prioritized, ScheduledTask, Scheduler};
+use crate::sync::{Condvar, Mutex};
+use crate::task::{
+    self, cancelled, joinable, poll_local_tasks, spawn_local_task, JoinHandle, LocalBoxedFuture,
+    LocalReady, LocalTask, Priority,
+};
+use crate::time;
+use crate::waker::waker_for;
+
+/// A pinned, boxed future that can be sent to another thread, as stored by the executor.
+type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// `Runtime` is a minimal task executor that runs futures passed to
+/// [`block_on`](Runtime::block_on), and any tasks spawned with [`Runtime::spawn`], to
+/// completion.
+///
+/// The executor is created empty and can be handed futures for as long as it lives. Its worker
+/// threads and its blocking pool are kept around in between, and are only stopped when it is shut
+/// down or dropped.
+///
+/// It is meant to be used for educational purposes to demonstrate how an executor works at a basic level.
+pub struct Runtime {
+    /// The state shared with the worker threads and the wakers.
+    shared: Arc<Shared>,
+    /// Whether spawned tasks are polled by the thread that drives the executor or by a pool of
+    /// worker threads.
+    flavor: Flavor,
+}
+
+/// Where the spawned tasks of a [`Runtime`] are polled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flavor {
+    /// All tasks are polled on the thread that calls `block_on()` or `run()`.
+    CurrentThread,
+    /// Tasks are polled by a pool of worker threads, the future passed to `block_on()` by the thread
+    /// that calls it.
+    MultiThread,
+}
+
+/// The part of the executor that is shared with the worker threads and the wakers.
+pub(crate) struct Shared {
+    /// The global queue of tasks that are ready to be polled, also called the injector.
+    ///
+    /// The lock is only held while a task is pushed or popped, never while it is polled, so a
+    /// running task can spawn or wake other tasks.
+    queue: Mutex<Queue>,
+    /// Every spawned task that has not completed yet, by ID.
+    owned: Mutex<HashMap<task::Id, Arc<Task>>>,
+    /// The per-worker state of a multi-threaded executor, empty for the current-thread flavor.
+    workers: Vec<Worker>,
+    /// The join handles of the worker threads, including the ones that took over from a worker in
+    /// `block_in_place`.
+    worker_handles: Mutex<Vec<thread::JoinHandle<()>>>,
+    /// The name of the worker threads, to which their index is appended.
+    thread_name: String,
+    /// The stack size of the worker threads, if configured.
+    thread_stack_size: Option<usize>,
+    /// Set when the worker threads should exit.
+    ///
+    /// Kept outside of `queue` so busy workers can check it without taking the lock. It is only
+    /// written while holding the `queue` lock, so idle workers can't miss it.
+    shutdown: AtomicBool,
+    /// Signalled when a task is queued or the executor shuts down, so idle workers pick it up.
+    work_available: Condvar,
+    /// Signalled when a task completes or a worker panics, so `run()` can stop waiting.
+    task_finished: Condvar,
+    /// Parks and unparks the thread that is driving the executor.
+    signal: Signal,
+    /// Set by the waker of the future passed to `block_on()`, and cleared right before that future
+    /// is polled again.
+    main_woken: AtomicBool,
+    /// What to do when a spawned task panics.
+    unhandled_panic: UnhandledPanic,
+    /// What to do when a spawned task returns `Poll::Pending` without a way to be woken.
+    lost_wakeup: LostWakeup,
+    /// Whether to warn about a panic of a spawned task whose `JoinHandle` was dropped.
+    warn_unobserved_panics: bool,
+    /// How many tasks are polled in a row before checking for other work, see
+    /// [`Builder::event_interval`].
+    event_interval: u32,
+    /// The callbacks for the lifecycle events of the tasks, if any.
+    hooks: Option<Arc<dyn TaskHooks>>,
+    /// The threads that run the closures passed to `spawn_blocking`.
+    blocking: Arc<BlockingPool>,
+    /// The time driver, if it is enabled.
+    time: Option<Arc<time::Driver>>,
+    /// The I/O driver, if it is enabled and the platform has one.
+    io: Option<Arc<io::Driver>>,
+    /// What the thread that drives the executor blocks on instead of `signal` or the I/O driver,
+    /// if set with [`Builder::park`].
+    park: Option<Arc<dyn Park>>,
+}
+
+/// The queued tasks and the bookkeeping needed to know when all of them are done.
+struct Queue {
+    /// The tasks that have been spawned or woken and are waiting to be polled.
+    tasks: Box<dyn Scheduler>,
+    /// The number of spawned tasks that have not completed yet, wherever they are: in a queue,
+    /// being polled, or waiting to be woken.
+    unfinished: usize,
+    /// Set when a worker thread panicked while polling a task.
+    panicked: bool,
+}
+
+/// What a [`Runtime`] does when a spawned task panics, configured with
+/// [`Builder::unhandled_panic`].
+///
+/// Either way, the panic is caught and awaiting the [`JoinHandle`] of the task returns a
+/// [`JoinError::Panic`](crate::task::JoinError::Panic) with the panic's payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnhandledPanic {
+    /// Drop the task that panicked and keep running the others.
+    #[default]
+    Ignore,
+    /// Stop the executor, which makes [`block_on()`](Runtime::block_on) and
+    /// [`run()`](Runtime::run) panic.
+    ShutdownRuntime,
+}
+
+/// What a [`Runtime`] does when a spawned task returns `Poll::Pending` without having woken or
+/// kept a clone of its waker, configured with [`Builder::lost_wakeup`].
+///
+/// Nothing can wake such a task anymore, so it waits forever. The executor notices when, after a
+/// poll, no clone of the task's waker is left outside of the executor. A waker that was cloned and
+/// stored somewhere that is never woken can't be told apart from one that will be, so not every
+/// lost wakeup is caught.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LostWakeup {
+    /// Leave the task waiting.
+    #[default]
+    Ignore,
+    /// Print a warning naming the task to standard error, and leave the task waiting.
+    Warn,
+    /// Panic, naming the task. Like a panic with [`UnhandledPanic::ShutdownRuntime`], this makes
+    /// [`block_on()`](Runtime::block_on) and [`run()`](Runtime::run) panic.
+    Panic,
+}
+
+/// The state of a single worker thread of a multi-threaded executor.
+struct Worker {
+    /// The worker's local queue.
+    ///
+    /// Tasks spawned from the worker, and tasks woken from it that were bumped out of the LIFO
+    /// slot, go here. The worker and idle workers that steal from it take tasks in the order its
+    /// scheduler decides.
+    local: Mutex<Box<dyn Scheduler>>,
+    /// The task that was woken last by the task the worker is polling, polled next.
+    ///
+    /// A task usually wakes another one to hand it a message, so polling the receiver right away
+    /// finds the message, and whatever else the two share, still in the cache. The slot can't be
+    /// stolen from.
+    lifo_slot: Mutex<Option<Arc<Task>>>,
+    /// The number of tasks this worker has stolen from other workers.
+    steal_count: AtomicU64,
+    /// The number of times this worker has successfully stolen from another worker.
+    steal_operations: AtomicU64,
+    /// The number of tasks this worker has taken from its LIFO slot.
+    lifo_hits: AtomicU64,
+}
+
+/// How many tasks in a row a worker takes from its LIFO slot before it goes back to its local queue.
+///
+/// Two tasks that keep waking each other would otherwise keep the slot busy forever, and starve the
+/// tasks in the queue.
+const MAX_LIFO_POLLS_IN_A_ROW: u32 = 3;
+
+/// A task spawned onto a [`Runtime`].
+///
+/// The task is its own waker: waking it puts it back into one of the executor's queues, so only
+/// tasks that have actually been woken get polled again.
+pub(crate) struct Task {
+    /// The task's ID.
+    pub(crate) id: task::Id,
+    /// The name the task was spawned with, if any.
+    pub(crate) name: Option<Arc<str>>,
+    /// Where the task was spawned.
+    pub(crate) location: &'static Location<'static>,
+    /// How urgently the task needs to be polled when it is ready.
+    pub(crate) priority: Priority,
+    /// When the task should be done by, if it was spawned with a deadline.
+    pub(crate) deadline: Option<Instant>,
+    /// The label of the innermost [`Instrumented`] future the task was waiting in after its last
+    /// poll, if any.
+    label: Mutex<Option<Arc<str>>>,
+    /// Set once the task has been polled for the first time.
+    polled: AtomicBool,
+    /// The task's future and waker, until it has completed.
+    future: Mutex<Option<TaskFuture>>,
+    /// Where the task is in its lifecycle, one of the `IDLE`, `SCHEDULED`, `RUNNING`, `NOTIFIED`
+    /// and `COMPLETE` constants.
+    state: AtomicU8,
+    /// The executor the task belongs to.
+    ///
+    /// A waker can outlive the executor, in which case waking it does nothing.
+    shared: Weak<Shared>,
+}
+
+/// The future of a task that hasn't completed yet, and the waker it is polled with.
+///
+/// The waker is created once, when the task is spawned, and handed to every poll. Since it points
+/// back at the task, it is dropped along with the future, so a task that has completed or was
+/// cancelled doesn't keep itself alive.
+struct TaskFuture {
+    future: BoxedFuture,
+    waker: Waker,
+}
+
+/// How a task is spawned onto a [`Runtime`], besides its future.
+#[derive(Default)]
+struct SpawnOptions {
+    /// The name of the task, if any.
+    name: Option<Arc<str>>,
+    /// How urgently the task needs to be polled when it is ready.
+    priority: Priority,
+    /// When the task should be done by, if it has a deadline.
+    deadline: Option<Instant>,
+    /// When the task is queued for the first time, if not right away.
+    start: Option<Instant>,
+}
+
+/// The task is waiting to be woken.
+const IDLE: u8 = 0;
+/// The task is in a queue, waiting to be polled.
+const SCHEDULED: u8 = 1;
+/// The task is being polled.
+const RUNNING: u8 = 2;
+/// The task was woken while it was being polled, so it has to be queued again afterwards.
+const NOTIFIED: u8 = 3;
+/// The task's future has completed and has been dropped.
+const COMPLETE: u8 = 4;
+
+thread_local! {
+    /// The worker the current thread belongs to, if any.
+    ///
+    /// Stores the worker's `Shared` state, to tell executors apart, and the worker's index. The
+    /// state is only referenced weakly, so a worker thread doesn't keep its executor alive.
+    static CURRENT_WORKER: RefCell<Option<(Weak<Shared>, usize)>> = const { RefCell::new(None) };
+}
+
+impl Runtime {
+    /// Create a new `Runtime` that polls spawned tasks on the thread that drives it, with all
+    /// drivers enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     println!("Hello from the future!");
+    /// });
+    /// ```
+    pub fn new() -> Self {
+        Builder::new().enable_all().build()
+    }
+
+    /// Create a new `Runtime` that polls spawned tasks on a pool of `worker_threads` threads, with
+    /// all drivers enabled.
+    ///
+    /// The future passed to [`block_on()`](Runtime::block_on) is still polled by the thread
+    /// that calls it, while the workers poll the spawned tasks, so several tasks can make progress
+    /// in parallel.
+    ///
+    /// Every worker has a local queue. Tasks spawned or woken from a worker go onto its own queue,
+    /// and tasks spawned or woken from anywhere else go onto a global queue. A worker that runs out
+    /// of work first checks the global queue and then steals half of the tasks of another worker.
+    /// How often that happens can be observed through [`Runtime::metrics`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker_threads` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::thread;
+    ///
+    /// let executor = Runtime::new_multi_thread(2);
+    ///
+    /// let name = executor.spawn(async { thread::current().name().unwrap().to_string() });
+    /// let name = executor.block_on(name).unwrap();
+    /// assert!(name.starts_with("mini-executor-worker-"));
+    /// ```
+    pub fn new_multi_thread(worker_threads: usize) -> Self {
+        assert!(
+            worker_threads > 0,
+            "a multi-threaded executor needs at least one worker thread"
+        );
+        Builder::new()
+            .worker_threads(worker_threads)
+            .enable_all()
+            .build()
+    }
+
+    /// Create a [`Builder`] to configure the executor before constructing it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::builder()
+    ///     .worker_threads(2)
+    ///     .thread_name("my-pool")
+    ///     .build();
+    ///
+    /// executor.block_on(async {});
+    /// ```
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Spawn an additional future onto the executor.
+    ///
+    /// The task is polled alongside the future passed to [`block_on()`](Runtime::block_on), and
+    /// it can be spawned before the executor is driven or from within a future that is already
+    /// running on it. The returned [`JoinHandle`] can
+    /// be awaited to get the task's output, or used to [abort](JoinHandle::abort) the task. A task
+    /// spawned after the executor has shut down is cancelled right away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let answer = executor.spawn(async { 40 + 2 });
+    /// executor.spawn(async move {
+    ///     assert_eq!(answer.await.unwrap(), 42);
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn_task(SpawnOptions::default(), future)
+    }
+
+    /// Spawn an additional future onto the executor under the given name.
+    ///
+    /// This works like [`Runtime::spawn`], but the name shows up wherever the task does: in
+    /// [`task::name()`] from inside the task, in [`JoinHandle::name`], in the panic that stops an
+    /// executor configured with [`UnhandledPanic::ShutdownRuntime`], and in [`Runtime::dump`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let fetcher = executor.spawn_named("fetcher", async {
+    ///     println!("{} is fetching", task::name().unwrap());
+    /// });
+    /// assert_eq!(fetcher.name(), Some("fetcher"));
+    ///
+    /// executor.run();
+    /// ```
+    #[track_caller]
+    pub fn spawn_named<F>(&self, name: &str, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            name: Some(name.into()),
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn an additional future onto the executor with the given [`Priority`].
+    ///
+    /// This works like [`Runtime::spawn`], but whenever the executor picks the next task to
+    /// poll, tasks with a higher priority go first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task::Priority;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let executor = Runtime::new();
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for priority in [Priority::Low, Priority::Normal, Priority::High] {
+    ///     let order = order.clone();
+    ///     executor.spawn_with_priority(priority, async move {
+    ///         order.lock().unwrap().push(priority);
+    ///     });
+    /// }
+    /// executor.run();
+    ///
+    /// assert_eq!(*order.lock().unwrap(), [Priority::High, Priority::Normal, Priority::Low]);
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_priority<F>(&self, priority: Priority, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            priority,
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn an additional future onto the executor that should be done by `deadline`.
+    ///
+    /// This works like [`Runtime::spawn`]. The deadline is only taken into account by an
+    /// executor that is built with the [`scheduler::EarliestDeadlineFirst`] scheduler, which polls
+    /// the ready task with the earliest deadline first. Nothing happens when a deadline passes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::scheduler::EarliestDeadlineFirst;
+    /// use mini_executor::runtime::Runtime;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = Runtime::builder().scheduler(EarliestDeadlineFirst::default).build();
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// let now = Instant::now();
+    /// for millis in [30, 10, 20] {
+    ///     let order = order.clone();
+    ///     let deadline = now + Duration::from_millis(millis);
+    ///     executor.spawn_with_deadline(deadline, async move {
+    ///         order.lock().unwrap().push(millis);
+    ///     });
+    /// }
+    /// executor.run();
+    ///
+    /// assert_eq!(*order.lock().unwrap(), [10, 20, 30]);
+    /// ```
+    ///
+    /// [`scheduler::EarliestDeadlineFirst`]: crate::scheduler::EarliestDeadlineFirst
+    #[track_caller]
+    pub fn spawn_with_deadline<F>(&self, deadline: Instant, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            deadline: Some(deadline),
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn an additional future onto the executor that is first polled at `start`.
+    ///
+    /// This works like [`Runtime::spawn`], but the task waits with the time driver until then,
+    /// instead of being queued right away, with no future around it that sleeps. A task whose
+    /// start has passed already is queued the next time the executor looks at its timers. Aborting
+    /// the task before it has started drops its future right away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the time driver isn't enabled, see [`Builder::enable_time`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = Runtime::new();
+    /// let start = Instant::now() + Duration::from_millis(10);
+    ///
+    /// let report = executor.spawn_at(start, async { Instant::now() });
+    /// let started = executor.block_on(report).unwrap();
+    /// assert!(started >= start);
+    /// ```
+    #[track_caller]
+    pub fn spawn_at<F>(&self, start: Instant, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            start: Some(start),
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn an additional future onto the executor that is first polled once `delay` has
+    /// passed, like [`Runtime::spawn_at`].
+    ///
+    /// The delay is counted by the clock of the time driver, which may be
+    /// [paused](crate::time::pause).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the time driver isn't enabled, see [`Builder::enable_time`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::new();
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for millis in [30, 10, 20] {
+    ///     let order = order.clone();
+    ///     executor.spawn_after(Duration::from_millis(millis), async move {
+    ///         order.lock().unwrap().push(millis);
+    ///     });
+    /// }
+    /// executor.run();
+    ///
+    /// assert_eq!(*order.lock().unwrap(), [10, 20, 30]);
+    /// ```
+    #[track_caller]
+    pub fn spawn_after<F>(&self, delay: Duration, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_at(self.shared.time_driver().deadline_after(delay), future)
+    }
+
+    /// List the spawned tasks that have not completed yet, ordered by ID.
+    ///
+    /// This is a snapshot: by the time it is returned, tasks may have been woken or completed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::{Runtime, TaskState};
+    /// use std::future::pending;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.spawn_named("fetcher", pending::<()>());
+    ///
+    /// let dump = executor.dump();
+    /// assert_eq!(dump.len(), 1);
+    /// assert_eq!(dump[0].name.as_deref(), Some("fetcher"));
+    /// assert_eq!(dump[0].state, TaskState::Scheduled);
+    ///
+    /// for task in dump {
+    ///     println!("{}", task);
+    /// }
+    /// ```
+    pub fn dump(&self) -> Vec<TaskDump> {
+        let mut dump: Vec<TaskDump> = self
+            .shared
+            .owned
+            .lock()
+            .values()
+            .map(|task| TaskDump {
+                id: task.id,
+                name: task.name.as_deref().map(String::from),
+                location: task.location,
+                label: task.label.lock().as_deref().map(String::from),
+                state: match task.state.load(Ordering::SeqCst) {
+                    IDLE => TaskState::Idle,
+                    SCHEDULED => TaskState::Scheduled,
+                    _ => TaskState::Running,
+                },
+            })
+            .collect();
+        dump.sort_by_key(|task| task.id);
+        dump
+    }
+
+    /// Run a blocking closure on a dedicated pool of threads, and return a [`JoinHandle`] to await
+    /// its result.
+    ///
+    /// Blocking inside of a future, for example on a synchronous file read or a long computation,
+    /// keeps the executor from polling anything else in the meantime. Moving that work to the
+    /// blocking pool lets the other tasks make progress.
+    ///
+    /// Threads are spawned as needed, up to [`Builder::max_blocking_threads`], and exit after being
+    /// idle for [`Builder::thread_keep_alive`]. Unlike a task, the closure can't be interrupted:
+    /// [aborting](JoinHandle::abort) it only has an effect if it hasn't started yet. Stopping the
+    /// executor cancels the closures that haven't started yet and waits for the others to finish.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let sum = executor.spawn_blocking(|| {
+    ///     thread::sleep(Duration::from_millis(10));
+    ///     (1..=10).sum::<u32>()
+    /// });
+    /// executor.spawn(async move {
+    ///     assert_eq!(sum.await.unwrap(), 55);
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    #[track_caller]
+    pub fn spawn_blocking<F, R>(&self, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.shared.spawn_blocking(f)
+    }
+
+    /// Get a [`Handle`] to spawn tasks onto the executor from anywhere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::thread;
+    ///
+    /// let executor = Runtime::new();
+    /// let handle = executor.handle();
+    ///
+    /// let answer = thread::spawn(move || handle.spawn(async { 40 + 2 })).join().unwrap();
+    /// executor.spawn(async move {
+    ///     assert_eq!(answer.await.unwrap(), 42);
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn handle(&self) -> Handle {
+        Handle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Get an [`Unparker`] to wake the thread driving the executor from anywhere, for example
+    /// from a callback of the operating system.
+    ///
+    /// # Examples
+    ///
+    /// A future that doesn't use its waker, and instead relies on whoever makes it ready to
+    /// unpark the executor:
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::future::poll_fn;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::task::Poll;
+    /// use std::thread;
+    ///
+    /// let executor = Runtime::new();
+    /// let unparker = executor.unparker();
+    ///
+    /// let done = Arc::new(AtomicBool::new(false));
+    /// let callback = thread::spawn({
+    ///     let done = done.clone();
+    ///     move || {
+    ///         done.store(true, Ordering::SeqCst);
+    ///         unparker.unpark();
+    ///     }
+    /// });
+    ///
+    /// executor.block_on(poll_fn(|_| {
+    ///     if done.load(Ordering::SeqCst) {
+    ///         Poll::Ready(())
+    ///     } else {
+    ///         Poll::Pending
+    ///     }
+    /// }));
+    /// callback.join().unwrap();
+    /// ```
+    pub fn unparker(&self) -> Unparker {
+        Unparker::new(&self.shared)
+    }
+
+    /// Get a handle to the executor's runtime metrics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new_multi_thread(2);
+    /// let metrics = executor.metrics();
+    ///
+    /// executor.run();
+    ///
+    /// assert_eq!(metrics.num_workers(), 2);
+    /// let stolen: u64 = (0..metrics.num_workers()).map(|worker| metrics.worker_steal_count(worker)).sum();
+    /// println!("{} tasks were stolen", stolen);
+    /// ```
+    pub fn metrics(&self) -> RuntimeMetrics {
+        RuntimeMetrics {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Run a future to completion on the executor and return its output.
+    ///
+    /// This method will block the current thread until the future has completed. On a
+    /// current-thread executor the spawned tasks are polled in the meantime as well, and on a
+    /// multi-threaded executor the workers keep polling them.
+    ///
+    /// Only futures whose waker has been woken are polled again. Whenever nothing is left to poll,
+    /// the thread is parked until that happens, so the executor doesn't burn CPU while waiting on
+    /// timers or I/O.
+    ///
+    /// The executor can be handed one future after the other, reusing its worker threads and its
+    /// blocking pool. It returns as soon as the future has completed: spawned tasks that haven't
+    /// completed yet are left to a later call to `block_on()` or [`run()`](Runtime::run), or
+    /// cancelled when the executor is dropped. Only one thread should drive the executor at a time.
+    ///
+    /// # Panics
+    ///
+    /// If the future panics, the panic is resumed once the executor has been cleaned up after it,
+    /// so the executor can still be used. If a spawned task panics and the executor is configured
+    /// with [`UnhandledPanic::ShutdownRuntime`], `block_on()` panics as well. Use
+    /// [`try_block_on()`](Runtime::try_block_on) to get either panic as an error instead.
+    ///
+    /// Panics when called from within a future that is already being driven by an executor or by
+    /// [`block_on`], since blocking that thread would keep the outer executor from
+    /// making progress, or deadlock it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let answer = executor.block_on(async { 40 + 2 });
+    /// assert_eq!(answer, 42);
+    ///
+    /// // The executor can be used again.
+    /// let task = executor.spawn(async move { answer * 2 });
+    /// assert_eq!(executor.block_on(task).unwrap(), 84);
+    /// ```
+    ///
+    /// A future that waits for another thread doesn't keep the executor busy in the meantime:
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let (sender, receiver) = futures::channel::oneshot::channel();
+    /// thread::spawn(move || {
+    ///     thread::sleep(Duration::from_millis(10));
+    ///     sender.send(42).unwrap();
+    /// });
+    ///
+    /// let executor = Runtime::new();
+    /// assert_eq!(executor.block_on(receiver), Ok(42));
+    /// ```
+    ///
+    /// Waking a waker by reference reschedules the future just like waking it by value:
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::future::poll_fn;
+    /// use std::task::Poll;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let mut yielded = false;
+    ///     poll_fn(|cx| {
+    ///         if yielded {
+    ///             return Poll::Ready(());
+    ///         }
+    ///         yielded = true;
+    ///         cx.waker().wake_by_ref();
+    ///         Poll::Pending
+    ///     })
+    ///     .await;
+    /// });
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _driver = enter_driver();
+        let _context = enter_context(&self.shared);
+        self.shared.signal.register_current();
+        let waker = waker_for(self.shared.clone());
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+
+        // The future hasn't been polled yet, so it counts as woken.
+        self.shared.main_woken.store(true, Ordering::SeqCst);
+        loop {
+            if self.shared.main_woken.swap(false, Ordering::SeqCst) {
+                if let Poll::Ready(output) = task::budget(|| future.as_mut().poll(&mut context)) {
+                    return output;
+                }
+            }
+
+            self.poll_tasks();
+
+            // Everything that was ready has been polled. If something was woken in the meantime,
+            // this returns right away.
+            self.shared.park(None);
+        }
+    }
+
+    /// Run a future to completion on the executor like [`block_on()`](Runtime::block_on), but
+    /// return an error instead of panicking if the future panics.
+    ///
+    /// The same goes for a spawned task that panics when the executor is configured with
+    /// [`UnhandledPanic::ShutdownRuntime`]. Either way the executor is left in a state in which it
+    /// can be used again, although it has been shut down in the latter case.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called from within a future that is already being driven by an executor, like
+    /// `block_on()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::{ExecutorError, Runtime};
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let result = executor.try_block_on(async { panic!("boom") });
+    /// assert!(matches!(result, Err(ExecutorError::Panicked(_))));
+    ///
+    /// // The executor can be used again.
+    /// assert_eq!(executor.try_block_on(async { 42 }).unwrap(), 42);
+    /// ```
+    pub fn try_block_on<F: Future>(&self, future: F) -> Result<F::Output, ExecutorError> {
+        // The executor's state is kept consistent when a poll unwinds, and the future is dropped
+        // during the unwind, so nothing can be observed in a broken state afterwards.
+        panic::catch_unwind(AssertUnwindSafe(|| self.block_on(future)))
+            .map_err(ExecutorError::Panicked)
+    }
+
+    /// Run the spawned tasks until all of them have completed.
+    ///
+    /// This method will block the current thread until then, polling the tasks on a current-thread
+    /// executor or waiting for the workers to poll them on a multi-threaded one. The executor can
+    /// still be used afterwards.
+    ///
+    /// # Panics
+    ///
+    /// If a spawned task panics and the executor is configured with
+    /// [`UnhandledPanic::ShutdownRuntime`], `run()` panics as well.
+    ///
+    /// Like [`block_on()`](Runtime::block_on), this also panics when called from within a
+    /// future that is already being driven by an executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.spawn(async {
+    ///     println!("Hello from a task!");
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn run(&self) {
+        let _driver = enter_driver();
+        let _context = enter_context(&self.shared);
+
+        if self.flavor == Flavor::MultiThread {
+            // The workers are polling the tasks, so there is nothing left to do here but to wait for
+            // them.
+            let panicked = {
+                let mut queue = self.shared.queue.lock();
+                while queue.unfinished > 0 && !queue.panicked {
+                    queue = self.shared.task_finished.wait(queue);
+                }
+                queue.panicked
+            };
+            if panicked {
+                self.shutdown(None);
+            }
+            return;
+        }
+
+        self.shared.signal.register_current();
+        loop {
+            self.poll_tasks();
+            if self.shared.queue.lock().unfinished == 0 {
+                break;
+            }
+            self.shared.park(None);
+        }
+    }
+
+    /// Poll the spawned tasks that are ready to make progress, then return instead of blocking.
+    ///
+    /// On a current-thread executor the queued tasks are polled until nothing is woken anymore.
+    /// This makes it possible to embed the executor in another loop, like a game loop or a GUI event
+    /// loop, and pump it once per iteration. On a multi-threaded executor the workers keep polling
+    /// the spawned tasks in the background.
+    ///
+    /// Returns `Poll::Ready(())` once every spawned task has completed.
+    ///
+    /// # Panics
+    ///
+    /// If a spawned task panicked and the executor is configured with
+    /// [`UnhandledPanic::ShutdownRuntime`], this panics as well. Like [`run()`](Runtime::run),
+    /// this also panics when called from within a future that is already being driven by an
+    /// executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::task::Poll;
+    ///
+    /// let (sender, receiver) = futures::channel::oneshot::channel::<u32>();
+    /// let executor = Runtime::new();
+    /// executor.spawn(async move {
+    ///     assert_eq!(receiver.await, Ok(42));
+    /// });
+    ///
+    /// // Nothing can happen until the value is sent.
+    /// assert_eq!(executor.run_until_stalled(), Poll::Pending);
+    /// assert_eq!(executor.run_until_stalled(), Poll::Pending);
+    ///
+    /// sender.send(42).unwrap();
+    /// assert_eq!(executor.run_until_stalled(), Poll::Ready(()));
+    /// ```
+    pub fn run_until_stalled(&self) -> Poll<()> {
+        let _driver = enter_driver();
+        let _context = enter_context(&self.shared);
+        self.shared.signal.register_current();
+
+        loop {
+            self.poll_tasks();
+            if self.shared.queue.lock().unfinished == 0 {
+                return Poll::Ready(());
+            }
+
+            // The tasks of the timers that are due, and of the I/O sources that are ready, are
+            // ready as well.
+            if !self.shared.fire_timers()
+                && !self.shared.poll_io()
+                && !self.shared.signal.take_notification()
+            {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Step the executor once, waiting at most `max_wait` for something to do: fire the timers
+    /// that are due, poll the I/O driver, and poll the tasks that are ready.
+    ///
+    /// A current-thread executor never blocks on its own outside of [`block_on()`] and
+    /// [`run()`](Runtime::run), so this lets an existing event loop, like one written in C, drive
+    /// it instead. If no task is queued, the thread waits until a task is woken, the next timer is
+    /// due, an I/O event comes in, or `max_wait` has passed, whichever comes first. Tasks that are
+    /// woken while the ready ones are polled are left for the next tick. On a multi-threaded
+    /// executor, the workers poll the tasks, so this only waits.
+    ///
+    /// Returns how long the embedder can wait before the next tick: zero if tasks are still
+    /// queued, the time until the next timer is due, or `None` if only a task that is woken from
+    /// elsewhere or an I/O event can give the executor something to do.
+    ///
+    /// # Panics
+    ///
+    /// If a spawned task panicked and the executor is configured with
+    /// [`UnhandledPanic::ShutdownRuntime`], this panics as well. Like [`run()`](Runtime::run),
+    /// this also panics when called from within a future that is already being driven by an
+    /// executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::time;
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::builder().enable_time().build();
+    /// let handle = executor.spawn(async {
+    ///     time::sleep(Duration::from_millis(20)).await;
+    ///     42
+    /// });
+    ///
+    /// // The loop of the embedder, which would wait for its own events in between, for at most
+    /// // as long as `tick` returned.
+    /// while !handle.is_finished() {
+    ///     let _next = executor.tick(Duration::from_millis(5));
+    /// }
+    /// assert_eq!(executor.block_on(handle).unwrap(), 42);
+    /// ```
+    ///
+    /// [`block_on()`]: Runtime::block_on
+    pub fn tick(&self, max_wait: Duration) -> Option<Duration> {
+        let _driver = enter_driver();
+        let _context = enter_context(&self.shared);
+        self.shared.signal.register_current();
+
+        if self.shared.queue.lock().tasks.is_empty() {
+            self.shared.park(Some(max_wait));
+        }
+        // The timers that came due while the thread was waiting.
+        self.shared.fire_timers();
+        self.shared.poll_io();
+        self.poll_tasks();
+
+        // A notification that came in while the tasks were polled is only left over if their
+        // queue is empty, which is checked after it is cleared, so the next tick waits.
+        self.shared.signal.take_notification();
+        if !self.shared.queue.lock().tasks.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        self.shared
+            .time
+            .as_ref()
+            .and_then(|time| time.next_timeout())
+    }
+
+    /// Poll the given future exactly once, within the context of the executor, and report whether
+    /// it has completed.
+    ///
+    /// Spawned tasks are not polled, which makes this useful to step through a future by hand, for
+    /// example in a teaching demo or in a unit test of a hand-written future. The future can spawn
+    /// tasks onto the executor with [`Handle::current`].
+    ///
+    /// # Panics
+    ///
+    /// Like [`run()`](Runtime::run), this panics when called from within a future that is
+    /// already being driven by an executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::future::poll_fn;
+    /// use std::pin::pin;
+    /// use std::task::Poll;
+    ///
+    /// let mut remaining = 2;
+    /// let mut future = pin!(poll_fn(move |cx| {
+    ///     if remaining == 0 {
+    ///         return Poll::Ready(());
+    ///     }
+    ///     remaining -= 1;
+    ///     cx.waker().wake_by_ref();
+    ///     Poll::Pending
+    /// }));
+    ///
+    /// let executor = Runtime::new();
+    /// assert_eq!(executor.poll_once(future.as_mut()), Poll::Pending);
+    /// assert_eq!(executor.poll_once(future.as_mut()), Poll::Pending);
+    /// assert_eq!(executor.poll_once(future.as_mut()), Poll::Ready(()));
+    /// ```
+    pub fn poll_once<F: Future>(&self, future: Pin<&mut F>) -> Poll<F::Output> {
+        let _driver = enter_driver();
+        let _context = enter_context(&self.shared);
+        let waker = waker_for(self.shared.clone());
+        let mut context = Context::from_waker(&waker);
+        task::budget(|| future.poll(&mut context))
+    }
+
+    /// On a current-thread executor, poll every task that is currently queued. On a multi-threaded
+    /// executor, resume the panic of a worker that panicked, if any.
+    fn poll_tasks(&self) {
+        if self.flavor == Flavor::CurrentThread {
+            // Tasks that are woken while doing so, and the ones beyond the event interval, are
+            // picked up on the next pass, which gives the future passed to `block_on()` a chance
+            // to run in between.
+            let queued = self.shared.queue.lock().tasks.len();
+            for _ in 0..queued.min(self.shared.event_interval as usize) {
+                let Some(task) = self.shared.pop() else {
+                    break;
+                };
+                self.shared.run_task(task);
+            }
+
+            // Keeps the thread from parking while tasks are left over.
+            if !self.shared.queue.lock().tasks.is_empty() {
+                self.shared.signal.notify();
+            }
+        } else if self.shared.queue.lock().panicked {
+            self.shutdown(None);
+        }
+    }
+
+    /// Shut the executor down, waiting at most `timeout` for its threads to exit.
+    ///
+    /// New tasks are no longer accepted: spawning one returns a [`JoinHandle`] that resolves to
+    /// [`JoinError::Cancelled`] right away. The worker threads stop once they're done with the poll
+    /// they are in, and the blocking closures that haven't started yet are dropped. Then the spawned
+    /// tasks that haven't completed are cancelled, which drops their futures and resolves their
+    /// handles to [`JoinError::Cancelled`]. Blocking closures that are already running can't be
+    /// interrupted, so they keep running after the timeout.
+    ///
+    /// A thread that doesn't exit within `timeout`, because it is stuck in a poll or in a blocking
+    /// closure, is left running in the background, along with the task it is polling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::thread;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = Runtime::new_multi_thread(2);
+    /// let handle = executor.handle();
+    /// executor.spawn_blocking(|| thread::sleep(Duration::from_secs(1)));
+    /// let pending = executor.spawn(std::future::pending::<()>());
+    ///
+    /// let start = Instant::now();
+    /// executor.shutdown_timeout(Duration::from_millis(10));
+    /// assert!(start.elapsed() < Duration::from_secs(1));
+    ///
+    /// let task = handle.spawn(async {});
+    /// futures::executor::block_on(async {
+    ///     assert!(pending.await.unwrap_err().is_cancelled());
+    ///     assert!(task.await.unwrap_err().is_cancelled());
+    /// });
+    /// ```
+    ///
+    /// [`JoinError::Cancelled`]: crate::task::JoinError::Cancelled
+    pub fn shutdown_timeout(self, timeout: Duration) {
+        self.shutdown(Some(Instant::now() + timeout));
+    }
+
+    /// Shut the executor down without waiting for its threads to exit.
+    ///
+    /// This works like [`Runtime::shutdown_timeout`] with a timeout of zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new_multi_thread(2);
+    /// executor.spawn(async {});
+    /// executor.shutdown_background();
+    /// ```
+    pub fn shutdown_background(self) {
+        self.shutdown(Some(Instant::now()));
+    }
+
+    /// Stop the worker threads and the blocking threads, and wait for them to exit until the
+    /// deadline, if any. Then cancel the tasks that haven't completed.
+    ///
+    /// If one of the worker threads panicked, the panic is resumed on the current thread.
+    fn shutdown(&self, deadline: Option<Instant>) {
+        {
+            let _queue = self.shared.queue.lock();
+            self.shared.shutdown.store(true, Ordering::SeqCst);
+            self.shared.work_available.notify_all();
+        }
+
+        self.shared.notify();
+        self.shared.blocking.shutdown(deadline);
+        if let Some(time) = &self.shared.time {
+            time.shutdown();
+        }
+        if let Some(io) = &self.shared.io {
+            io.shutdown();
+        }
+
+        let workers = std::mem::take(&mut *self.shared.worker_handles.lock());
+        if let Some(payload) = join_threads(workers, deadline) {
+            std::panic::resume_unwind(payload);
+        }
+
+        self.shared.cancel_owned();
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            self.shutdown(None);
+        }
+    }
+}
+
+/// A handle to a [`Runtime`] that can be cloned and sent to other threads, obtained with
+/// [`Runtime::handle`].
+///
+/// It lets code that doesn't own the executor spawn tasks onto it, for example a callback of a
+/// library that runs on a thread of its own, also while [`block_on()`](Runtime::block_on) is
+/// blocking another thread. A handle doesn't keep the executor running: once it has shut down,
+/// tasks spawned through the handle are cancelled right away.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use std::thread;
+///
+/// let executor = Runtime::new_multi_thread(2);
+/// let handle = executor.handle();
+///
+/// let callback = thread::spawn(move || {
+///     let doubled = handle.spawn(async { 21 * 2 });
+///     handle.spawn(async move {
+///         assert_eq!(doubled.await.unwrap(), 42);
+///     });
+/// });
+/// callback.join().unwrap();
+///
+/// executor.run();
+/// ```
+#[derive(Clone)]
+pub struct Handle {
+    shared: Arc<Shared>,
+}
+
+impl Handle {
+    /// Spawn a future onto the executor, like [`Runtime::spawn`].
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn_task(SpawnOptions::default(), future)
+    }
+
+    /// Spawn a future onto the executor under the given name, like
+    /// [`Runtime::spawn_named`].
+    #[track_caller]
+    pub fn spawn_named<F>(&self, name: &str, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            name: Some(name.into()),
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn a future onto the executor with the given priority, like
+    /// [`Runtime::spawn_with_priority`].
+    #[track_caller]
+    pub fn spawn_with_priority<F>(&self, priority: Priority, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            priority,
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn a future onto the executor that should be done by `deadline`, like
+    /// [`Runtime::spawn_with_deadline`].
+    #[track_caller]
+    pub fn spawn_with_deadline<F>(&self, deadline: Instant, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            deadline: Some(deadline),
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn a future onto the executor that is first polled at `start`, like
+    /// [`Runtime::spawn_at`].
+    #[track_caller]
+    pub fn spawn_at<F>(&self, start: Instant, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let options = SpawnOptions {
+            start: Some(start),
+            ..SpawnOptions::default()
+        };
+        self.shared.spawn_task(options, future)
+    }
+
+    /// Spawn a future onto the executor that is first polled once `delay` has passed, like
+    /// [`Runtime::spawn_after`].
+    #[track_caller]
+    pub fn spawn_after<F>(&self, delay: Duration, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_at(self.shared.time_driver().deadline_after(delay), future)
+    }
+
+    /// Run a blocking closure on the executor's blocking pool, like
+    /// [`Runtime::spawn_blocking`].
+    #[track_caller]
+    pub fn spawn_blocking<F, R>(&self, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.shared.spawn_blocking(f)
+    }
+}
+
+impl Handle {
+    /// Get an [`Unparker`] to wake the thread driving the executor, like [`Runtime::unparker`].
+    pub fn unparker(&self) -> Unparker {
+        Unparker::new(&self.shared)
+    }
+
+    /// The executor's time driver, if it is enabled.
+    pub(crate) fn time_driver(&self) -> Option<Arc<time::Driver>> {
+        self.shared.time.clone()
+    }
+
+    /// The executor's I/O driver, if it is enabled.
+    pub(crate) fn io_driver(&self) -> Option<Arc<io::Driver>> {
+        self.shared.io.clone()
+    }
+
+    /// Queue `job` on the executor's blocking pool, or hand it back if the pool has shut down.
+    #[cfg(unix)]
+    pub(crate) fn try_spawn_blocking(&self, job: BlockingJob) -> Result<(), BlockingJob> {
+        self.shared.blocking.spawn(job)
+    }
+
+    /// Whether the executor is a current-thread one, without worker threads.
+    pub(crate) fn is_current_thread(&self) -> bool {
+        self.shared.workers.is_empty()
+    }
+
+    /// Get a handle to the executor that is running the current thread.
+    ///
+    /// This works from within the futures and the spawned tasks run by a [`Runtime`], and from
+    /// its blocking closures, so library code doesn't need a handle passed to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of an executor. Use [`Handle::try_current`] to handle that
+    /// case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::{Handle, Runtime};
+    ///
+    /// async fn spawn_helper() -> u32 {
+    ///     Handle::current().spawn(async { 42 }).await.unwrap()
+    /// }
+    ///
+    /// let executor = Runtime::new();
+    /// executor.spawn(async {
+    ///     assert_eq!(spawn_helper().await, 42);
+    /// });
+    /// executor.run();
+    /// ```
+    pub fn current() -> Handle {
+        match Handle::try_current() {
+            Ok(handle) => handle,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Get a handle to the executor that is running the current thread, or a [`TryCurrentError`]
+    /// if called from outside of an executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::{Handle, Runtime};
+    ///
+    /// assert!(Handle::try_current().is_err());
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     assert!(Handle::try_current().is_ok());
+    /// });
+    /// ```
+    pub fn try_current() -> Result<Handle, TryCurrentError> {
+        CONTEXT
+            .with(|context| context.borrow().clone())
+            .map(|shared| Handle { shared })
+            .ok_or(TryCurrentError { _private: () })
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle").finish_non_exhaustive()
+    }
+}
+
+/// Wakes the thread driving a [`Runtime`] from any thread, created with [`Runtime::unparker`] or
+/// [`Handle::unparker`].
+///
+/// Unparking the executor polls the future passed to [`block_on()`](Runtime::block_on) again, as if
+/// its waker had been woken. This is for whatever can't hold on to a waker, like a callback that
+/// was registered with the operating system once, up front. A future that can should use its waker,
+/// which also works for spawned tasks.
+///
+/// An `Unparker` doesn't keep the executor alive: once the executor has been dropped, unparking it
+/// does nothing.
+#[derive(Clone)]
+pub struct Unparker {
+    shared: Weak<Shared>,
+}
+
+impl Unparker {
+    fn new(shared: &Arc<Shared>) -> Self {
+        Self {
+            shared: Arc::downgrade(shared),
+        }
+    }
+
+    /// Wake the thread driving the executor, if it is parked, or make it skip its next park
+    /// otherwise.
+    pub fn unpark(&self) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.wake_by_ref();
+        }
+    }
+}
+
+impl fmt::Debug for Unparker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Unparker").finish_non_exhaustive()
+    }
+}
+
+/// The error returned by [`Handle::try_current`] when called from outside of an executor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryCurrentError {
+    _private: (),
+}
+
+impl fmt::Display for TryCurrentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("must be called from the context of a `Runtime`")
+    }
+}
+
+impl std::error::Error for TryCurrentError {}
+
+/// The error returned by [`Runtime::try_block_on`] when the executor could not drive the future to
+/// completion.
+#[derive(Debug)]
+pub enum ExecutorError {
+    /// The future panicked, or a spawned task did while the executor is configured with
+    /// [`UnhandledPanic::ShutdownRuntime`], with the payload it panicked with.
+    Panicked(Box<dyn Any + Send + 'static>),
+}
+
+impl fmt::Display for ExecutorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutorError::Panicked(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+                match message {
+                    Some(message) => write!(f, "executor panicked with message {:?}", message),
+                    None => f.write_str("executor panicked"),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecutorError {}
+
+thread_local! {
+    /// The executor whose context the current thread is in, if any, as returned by
+    /// [`Handle::current`].
+    static CONTEXT: RefCell<Option<Arc<Shared>>> = const { RefCell::new(None) };
+}
+
+/// Put the current thread into the context of the given executor, until the returned guard is
+/// dropped.
+fn enter_context(shared: &Arc<Shared>) -> ContextGuard {
+    let previous = CONTEXT.with(|context| context.replace(Some(shared.clone())));
+    ContextGuard { previous }
+}
+
+/// Restores the context the current thread was in before [`enter_context`] when dropped.
+struct ContextGuard {
+    previous: Option<Arc<Shared>>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        // The thread-local may already be gone if the guard is dropped while the thread exits.
+        let _ = CONTEXT.try_with(|context| *context.borrow_mut() = previous);
+    }
+}
+
+thread_local! {
+    /// Set while the current thread is driving an executor or a future, to reject nested calls that
+    /// would block it.
+    static DRIVING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Mark the current thread as driving an executor or a future, until the returned guard is dropped.
+///
+/// # Panics
+///
+/// Panics if the current thread is already driving one.
+pub(crate) fn enter_driver() -> DriverGuard {
+    if DRIVING.with(|driving| driving.replace(true)) {
+        panic!(
+            "cannot block the current thread from within a future that is already being driven by an \
+             executor, since that keeps the executor from making progress; await the future instead, \
+             or move the blocking code to `spawn_blocking`"
+        );
+    }
+    DriverGuard { previous: false }
+}
+
+/// Mark the current thread as no longer driving an executor, until the returned guard is dropped.
+pub(crate) fn suspend_driver() -> DriverGuard {
+    DriverGuard {
+        previous: DRIVING.with(|driving| driving.replace(false)),
+    }
+}
+
+/// Restores whether the current thread is driving an executor when dropped.
+pub(crate) struct DriverGuard {
+    previous: bool,
+}
+
+impl Drop for DriverGuard {
+    fn drop(&mut self) {
+        let _ = DRIVING.try_with(|driving| driving.set(self.previous));
+    }
+}
+
+/// Join the given threads, or wait for them until the deadline and leave the ones that haven't
+/// exited by then running in the background.
+///
+/// Returns the payload of the first thread that panicked, if any.
+fn join_threads(
+    handles: Vec<thread::JoinHandle<()>>,
+    deadline: Option<Instant>,
+) -> Option<Box<dyn Any + Send + 'static>> {
+    // The last reference to the executor may be dropped on one of its own threads, which cannot
+    // wait for itself to exit.
+    let current = thread::current().id();
+    let handles: Vec<_> = handles
+        .into_iter()
+        .filter(|handle| handle.thread().id() != current)
+        .collect();
+
+    if let Some(deadline) = deadline {
+        // There is no way to join a thread with a timeout, so check on them every millisecond.
+        while !handles.iter().all(thread::JoinHandle::is_finished) {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(1)));
+        }
+    }
+
+    let mut panic = None;
+    for handle in handles {
+        if deadline.is_some() && !handle.is_finished() {
+            continue;
+        }
+        if let Err(payload) = handle.join() {
+            panic.get_or_insert(payload);
+        }
+    }
+    panic
+}
+
+impl Shared {
+    /// Create the state of an executor, which is going to live at `this`.
+    fn new(builder: &Builder, this: &Weak<Shared>) -> Self {
+        let blocking = BlockingPool::new(
+            format!("{}-blocking", builder.thread_name),
+            builder.thread_stack_size,
+            builder.max_blocking_threads,
+            builder.thread_keep_alive,
+        );
+        Self {
+            unhandled_panic: builder.unhandled_panic,
+            lost_wakeup: builder.lost_wakeup,
+            warn_unobserved_panics: builder.warn_unobserved_panics,
+            event_interval: builder.event_interval,
+            hooks: builder.hooks.clone().map(|hooks| hooks.0),
+            blocking: Arc::new(blocking),
+            time: builder.enable_time.then(|| {
+                let this = this.clone();
+                let unpark = move || {
+                    if let Some(shared) = this.upgrade() {
+                        shared.unpark_for_timer();
+                    }
+                };
+                Arc::new(time::Driver::new(unpark, builder.timer_tick))
+            }),
+            io: builder
+                .enable_io
+                .then(io::Driver::new)
+                .flatten()
+                .map(Arc::new),
+            park: builder.park.clone().map(|park| park.0),
+            queue: Mutex::new(Queue {
+                tasks: (builder.scheduler.0)(),
+                unfinished: 0,
+                panicked: false,
+            }),
+            owned: Mutex::new(HashMap::new()),
+            worker_handles: Mutex::new(Vec::new()),
+            thread_name: builder.thread_name.clone(),
+            thread_stack_size: builder.thread_stack_size,
+            workers: (0..builder.worker_threads)
+                .map(|_| Worker {
+                    local: Mutex::new((builder.scheduler.0)()),
+                    lifo_slot: Mutex::new(None),
+                    steal_count: AtomicU64::new(0),
+                    steal_operations: AtomicU64::new(0),
+                    lifo_hits: AtomicU64::new(0),
+                })
+                .collect(),
+            shutdown: AtomicBool::new(false),
+            work_available: Condvar::new(),
+            task_finished: Condvar::new(),
+            signal: Signal::new(),
+            main_woken: AtomicBool::new(false),
+        }
+    }
+
+    /// The index of the worker of this executor that the current thread is, if any.
+    fn current_worker(&self) -> Option<usize> {
+        CURRENT_WORKER.with(|worker| match &*worker.borrow() {
+            Some((shared, index)) if std::ptr::eq(shared.as_ptr(), self) => Some(*index),
+            _ => None,
+        })
+    }
+
+    /// Spawn the thread of the worker at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread can't be spawned.
+    fn spawn_worker(self: &Arc<Self>, index: usize) {
+        let shared = self.clone();
+        let mut builder = thread::Builder::new().name(format!("{}-{}", self.thread_name, index));
+        if let Some(size) = self.thread_stack_size {
+            builder = builder.stack_size(size);
+        }
+        let handle = builder
+            .spawn(move || shared.run_worker(index))
+            .expect("failed to spawn a worker thread");
+        self.worker_handles.lock().push(handle);
+    }
+
+    /// Spawn a task, see [`Runtime::spawn`].
+    #[track_caller]
+    fn spawn_task<F>(self: &Arc<Self>, options: SpawnOptions, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let location = Location::caller();
+        // Looked up first, so nothing is spawned if the task can't be started later.
+        let time = if options.start.is_some() {
+            Some(self.time_driver().clone())
+        } else {
+            None
+        };
+        let start = options.start.zip(time);
+        let (future, handle) = joinable(
+            future,
+            options.name,
+            location,
+            self.unhandled_panic,
+            self.warn_unobserved_panics,
+        );
+        let task = Arc::new(Task {
+            id: handle.id(),
+            name: handle.name.clone(),
+            location: handle.location,
+            priority: options.priority,
+            deadline: options.deadline,
+            label: Mutex::new(None),
+            polled: AtomicBool::new(false),
+            future: Mutex::new(None),
+            state: AtomicU8::new(if start.is_some() { IDLE } else { SCHEDULED }),
+            shared: Arc::downgrade(self),
+        });
+        let waker = waker_for(task.clone());
+        *task.future.lock() = Some(TaskFuture {
+            future: Box::pin(future),
+            waker: waker.clone(),
+        });
+
+        let spawned = {
+            // Checked while holding the lock, so a task is either spawned before the shutdown
+            // cancels the owned tasks, or not at all.
+            let mut owned = self.owned.lock();
+            let shutdown = self.shutdown.load(Ordering::SeqCst);
+            if !shutdown {
+                owned.insert(task.id, task.clone());
+            }
+            !shutdown
+        };
+        if !spawned {
+            // The waker of the future points back at the task, so the task would keep itself
+            // alive. Dropped outside of the lock, in case dropping the future spawns again.
+            let future = task.future.lock().take();
+            drop(future);
+            return cancelled(handle.name.clone(), location);
+        }
+        self.queue.lock().unfinished += 1;
+        self.with_hooks(|hooks| hooks.on_spawn(&TaskInfo { task: &task }));
+        match start {
+            Some((start, time)) => {
+                // Aborting the task wakes it like after its first poll, to drop its future.
+                handle.set_task_waker(&waker);
+                let delayed = DelayedStart(Arc::downgrade(&task));
+                time.register_waker(start, waker_for(Arc::new(delayed)));
+            }
+            None => self.schedule(task),
+        }
+        handle
+    }
+
+    /// The time driver, for the things that need it to be enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the time driver isn't enabled.
+    #[track_caller]
+    fn time_driver(&self) -> &Arc<time::Driver> {
+        match &self.time {
+            Some(time) => time,
+            None => panic!(
+                "the time driver of this `Runtime` is disabled, enable it with \
+                 `Builder::enable_time`"
+            ),
+        }
+    }
+
+    /// Call `f` with the task hooks, if any are installed.
+    fn with_hooks(&self, f: impl FnOnce(&dyn TaskHooks)) {
+        if let Some(hooks) = &self.hooks {
+            f(&**hooks);
+        }
+    }
+
+    /// Queue a closure on the blocking pool, see [`Runtime::spawn_blocking`].
+    #[track_caller]
+    fn spawn_blocking<F, R>(self: &Arc<Self>, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        // The closure is wrapped like a task to reuse the handling of its output, panics and
+        // cancellation. It completes in its first poll.
+        let location = Location::caller();
+        let (future, handle) = joinable(
+            async move { f() },
+            None,
+            location,
+            UnhandledPanic::Ignore,
+            self.warn_unobserved_panics,
+        );
+        let shared = Arc::downgrade(self);
+        // Dropping a closure the pool rejects cancels it, which the handle reports.
+        let _ = self.blocking.spawn(Box::new(move || {
+            let _context = shared.upgrade().map(|shared| enter_context(&shared));
+            // Not `block_on`, to let the closure call it.
+            let _ = std::pin::pin!(future).poll(&mut Context::from_waker(Waker::noop()));
+        }));
+        handle
+    }
+
+    /// Queue a task that has been spawned or woken, so it gets polled.
+    ///
+    /// A task scheduled from one of the executor's workers goes onto that worker's local queue, any
+    /// other task onto the global queue.
+    fn schedule(&self, task: Arc<Task>) {
+        match self.current_worker() {
+            Some(index) => self.workers[index]
+                .local
+                .lock()
+                .push(ScheduledTask { task }),
+            None => self.queue.lock().tasks.push(ScheduledTask { task }),
+        }
+        self.work_available.notify_one();
+
+        // A current-thread executor polls the task itself, so it must not stay parked.
+        if self.workers.is_empty() {
+            self.notify();
+        } else {
+            self.unpark_io();
+        }
+    }
+
+    /// Queue a task that was woken, rather than spawned or rescheduled after its poll.
+    ///
+    /// On a worker, the task goes into the worker's LIFO slot, and the task that was there before,
+    /// if any, to the back of its local queue. Anywhere else, this works like `schedule`.
+    fn schedule_woken(&self, task: Arc<Task>) {
+        let Some(index) = self.current_worker() else {
+            return self.schedule(task);
+        };
+
+        let worker = &self.workers[index];
+        let bumped = worker.lifo_slot.lock().replace(task);
+        if let Some(bumped) = bumped {
+            worker.local.lock().push(ScheduledTask { task: bumped });
+            self.work_available.notify_one();
+            self.unpark_io();
+        }
+    }
+
+    /// Take the next task to poll out of the global queue.
+    fn pop(&self) -> Option<Arc<Task>> {
+        self.queue
+            .lock()
+            .tasks
+            .pop()
+            .map(|scheduled| scheduled.task)
+    }
+
+    /// Poll a task that was taken out of a queue once.
+    ///
+    /// A task that is woken while it is being polled is queued again right away, a task that is
+    /// still pending otherwise waits for its waker.
+    fn run_task(&self, task: Arc<Task>) {
+        let mut future = task.future.lock();
+        let Some(TaskFuture { future: fut, waker }) = future.as_mut() else {
+            // The future was dropped while the task was queued, so it is done with.
+            task.state.store(COMPLETE, Ordering::SeqCst);
+            return;
+        };
+        task.state.store(RUNNING, Ordering::SeqCst);
+        let mut context = Context::from_waker(waker);
+        if !task.polled.swap(true, Ordering::Relaxed) {
+            self.with_hooks(|hooks| hooks.on_first_poll(&TaskInfo { task: &task }));
+        }
+
+        let hooked = self
+            .hooks
+            .as_ref()
+            .map(|hooks| enter_hooked(hooks.clone(), task.clone()));
+        // The future catches the panics of the task itself, so this only unwinds if the executor is
+        // configured to shut down on a panic. The task is done with either way.
+        let polled = panic::catch_unwind(AssertUnwindSafe(|| {
+            task::record_pending_label(|| fut.as_mut().poll(&mut context))
+        }));
+        let (poll, label) = match polled {
+            Ok(polled) => polled,
+            Err(payload) => {
+                *future = None;
+                drop(future);
+                self.finish(&task);
+                panic::resume_unwind(payload);
+            }
+        };
+        if poll.is_ready() {
+            *future = None;
+            drop(future);
+            self.finish(&task);
+            return;
+        }
+        drop(future);
+        *task.label.lock() = label;
+        self.with_hooks(|hooks| hooks.on_yield(&TaskInfo { task: &task }));
+
+        // Checked before the task goes idle: if no waker is left, none can be created anymore, so
+        // the task stays idle unless it was woken during the poll.
+        let lost =
+            self.lost_wakeup != LostWakeup::Ignore && self.has_no_wakers(&task, hooked.is_some());
+        if task
+            .state
+            .compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            // The task was woken while it was being polled.
+            task.state.store(SCHEDULED, Ordering::SeqCst);
+            self.schedule(task);
+        } else if lost {
+            self.report_lost_wakeup(&task);
+        }
+    }
+
+    /// Whether nothing but the executor itself refers to a task that is being polled, so there is
+    /// no clone of its waker that could wake it.
+    ///
+    /// Every clone of a task's waker holds the task, so this compares how often the task is held
+    /// to how often the executor holds it: in `task` itself, in the owned tasks, in the waker
+    /// cached along with its future, in the waker kept to wake the task once its `JoinHandle`
+    /// aborts it, and for the hooks, if `hooked`. A task dump that holds the task right
+    /// now only makes this miss a lost wakeup.
+    fn has_no_wakers(&self, task: &Arc<Task>, hooked: bool) -> bool {
+        // Once the executor shuts down, it no longer holds the task among its owned tasks.
+        if self.shutdown.load(Ordering::SeqCst) {
+            return false;
+        }
+        let held = 4 + usize::from(hooked);
+        Arc::strong_count(task) <= held
+    }
+
+    /// Report a task that returned `Poll::Pending` without a way to be woken, as configured with
+    /// [`Builder::lost_wakeup`].
+    fn report_lost_wakeup(&self, task: &Task) {
+        let message = format!(
+            "{task} returned `Poll::Pending` without waking or keeping a clone of its waker, so it \
+             will never be polled again"
+        );
+        match self.lost_wakeup {
+            LostWakeup::Ignore => {}
+            LostWakeup::Warn => eprintln!("warning: {message}"),
+            LostWakeup::Panic => panic!("{message}"),
+        }
+    }
+
+    /// Mark a task whose future has been dropped after its last poll as completed.
+    fn finish(&self, task: &Arc<Task>) {
+        task.state.store(COMPLETE, Ordering::SeqCst);
+        self.owned.lock().remove(&task.id);
+        self.with_hooks(|hooks| hooks.on_complete(&TaskInfo { task }));
+        self.complete();
+    }
+
+    /// Park the thread that drives the executor in `block_on()` or `run()` until it is notified.
+    ///
+    /// The timers that are due are fired first, and the thread is parked until the next one is
+    /// due at the latest. If the clock of the time driver is paused, it is moved ahead to the next
+    /// timer instead, since nothing else can happen before then. A blocking closure may still wake
+    /// a task earlier.
+    ///
+    /// With an I/O driver, the thread waits for I/O events in the meantime, unless a worker does.
+    /// With a [`Park`] set with [`Builder::park`], the thread blocks on it instead.
+    ///
+    /// The thread is parked for `max_wait` at most, if it is `Some`.
+    fn park(&self, max_wait: Option<Duration>) {
+        self.fire_timers();
+        self.poll_io();
+        if self.signal.take_notification() {
+            return;
+        }
+        let timeout = match &self.time {
+            Some(time) => {
+                if !self.blocking.is_busy() && time.advance_to_next_timer() {
+                    return;
+                }
+                time.next_timeout()
+            }
+            None => None,
+        };
+        let timeout = match (timeout, max_wait) {
+            (Some(timeout), Some(max_wait)) => Some(timeout.min(max_wait)),
+            (timeout, max_wait) => timeout.or(max_wait),
+        };
+        if let Some(park) = &self.park {
+            park.park(timeout);
+            return;
+        }
+        if let Some(park) = self.io.as_ref().and_then(|io| io.park()) {
+            // Checked again now that `notify` wakes the driver.
+            if !self.signal.take_notification() {
+                park.wait(timeout);
+            }
+            return;
+        }
+        match timeout {
+            Some(timeout) => self.signal.park_timeout(timeout),
+            None => self.signal.park(),
+        }
+    }
+
+    /// Wake the timers of the time driver that are due, if it is enabled, and return whether
+    /// there were any.
+    fn fire_timers(&self) -> bool {
+        self.time.as_ref().is_some_and(|time| time.fire_due())
+    }
+
+    /// Wake the tasks of the I/O sources that are ready, without waiting, if the I/O driver is
+    /// enabled, and return whether there were any.
+    fn poll_io(&self) -> bool {
+        self.io.as_ref().is_some_and(|io| io.poll())
+    }
+
+    /// Wake the thread that waits for I/O events, if any, since it doesn't wait for
+    /// `work_available` or the signal.
+    fn unpark_io(&self) {
+        if let Some(io) = &self.io {
+            io.unpark();
+        }
+    }
+
+    /// Notify the thread that drives the executor, wherever it waits.
+    fn notify(&self) {
+        self.signal.notify();
+        self.unpark_io();
+        if let Some(park) = &self.park {
+            park.unpark();
+        }
+    }
+
+    /// Unpark the threads that may be parked until a later deadline than the one of a timer that
+    /// was just registered, so they fire it in time.
+    fn unpark_for_timer(&self) {
+        {
+            let _queue = self.queue.lock();
+            self.work_available.notify_all();
+        }
+        self.notify();
+    }
+
+    /// Record that a task has completed.
+    fn complete(&self) {
+        let mut queue = self.queue.lock();
+        queue.unfinished -= 1;
+        self.task_finished.notify_all();
+    }
+
+    /// Drop the futures of the spawned tasks that haven't completed, which resolves their handles
+    /// to [`JoinError::Cancelled`].
+    ///
+    /// A task that is being polled by a thread that didn't exit in time is left alone.
+    fn cancel_owned(&self) {
+        let owned = std::mem::take(&mut *self.owned.lock());
+        #[cfg(feature = "debug-waker")]
+        let mut cancelled = Vec::new();
+        for task in owned.into_values() {
+            let future = match task.future.try_lock() {
+                Some(mut future) => future.take(),
+                None => continue,
+            };
+            if future.is_some() {
+                task.state.store(COMPLETE, Ordering::SeqCst);
+                // Dropped outside of the lock, since the future's destructor may wake other tasks.
+                drop(future);
+                self.complete();
+                #[cfg(feature = "debug-waker")]
+                cancelled.push(task);
+            }
+        }
+
+        while self.queue.lock().tasks.pop().is_some() {}
+        for worker in &self.workers {
+            while worker.local.lock().pop().is_some() {}
+            worker.lifo_slot.lock().take();
+        }
+        self.notify();
+
+        #[cfg(feature = "debug-waker")]
+        for task in cancelled {
+            checks::outstanding_wakers(&task);
+        }
+    }
+
+    /// The loop run by every worker thread of a multi-threaded executor.
+    ///
+    /// A worker takes the next task from its LIFO slot or else from its local queue, and polls it
+    /// once. Every [`Builder::event_interval`] tasks, and when it runs out of work, it looks at the
+    /// global queue first. Then it tries to steal from the other workers. If there is nothing to do
+    /// anywhere, it sleeps until a task is spawned or woken, or until the executor shuts down.
+    ///
+    /// A worker whose thread was handed off to a task in [`task::block_in_place`] exits once that
+    /// task's poll is done, since another thread has taken over by then.
+    fn run_worker(self: Arc<Self>, index: usize) {
+        let _guard = WorkerGuard(&self);
+        let _driver = enter_driver();
+        let _context = enter_context(&self);
+        CURRENT_WORKER.with(|worker| *worker.borrow_mut() = Some((Arc::downgrade(&self), index)));
+        let worker = &self.workers[index];
+        let mut lifo_polls = 0;
+        let mut polls: u32 = 0;
+
+        loop {
+            if self.shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+
+            // Without this, tasks that keep waking each other on the local queue would keep the
+            // worker from ever getting to the global queue, or to the timers when every worker is
+            // busy.
+            polls = polls.wrapping_add(1);
+            let next = if polls.is_multiple_of(self.event_interval) {
+                self.fire_timers();
+                self.poll_io();
+                self.pop()
+                    .or_else(|| self.next_task(worker, &mut lifo_polls))
+            } else {
+                self.next_task(worker, &mut lifo_polls)
+            };
+            let Some(task) = next.or_else(|| self.steal(index)) else {
+                // The tasks of the timers and I/O sources woken here are queued on this worker.
+                if !self.fire_timers() && !self.poll_io() {
+                    self.park_worker();
+                }
+                continue;
+            };
+
+            self.run_task(task);
+            if self.current_worker() != Some(index) {
+                return;
+            }
+        }
+    }
+
+    /// Park a worker that has run out of work until a task is queued, the next timer is due, or
+    /// the executor shuts down.
+    ///
+    /// Every idle worker wakes up for the next timer, and the first one to get to it fires it. One
+    /// idle worker at a time waits for I/O events instead of `work_available`.
+    fn park_worker(&self) {
+        let io = self.io.as_ref().and_then(|io| io.park());
+        let queue = self.queue.lock();
+        if !queue.tasks.is_empty() || self.shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+        // Read under the lock of the queue, which a newly registered timer takes to unpark the
+        // workers, so an earlier deadline isn't missed.
+        let timeout = self.time.as_ref().and_then(|time| time.next_timeout());
+        if let Some(io) = io {
+            // Queuing a task after the queue was checked wakes the driver, since it is parked.
+            drop(queue);
+            io.wait(timeout);
+            return;
+        }
+        match timeout {
+            Some(timeout) => drop(self.work_available.wait_timeout(queue, timeout)),
+            None => drop(self.work_available.wait(queue)),
+        }
+    }
+
+    /// Take the next task for a worker to poll, without stealing.
+    ///
+    /// That is the task in the worker's LIFO slot, unless it has been taken from there too often in
+    /// a row or the scheduler says a waiting task runs before it. Otherwise it's the next task of
+    /// the worker's local queue or of the global queue, whichever the scheduler says runs first, and
+    /// the local one if it doesn't say. `lifo_polls` counts how many tasks in a row were taken from
+    /// the LIFO slot.
+    fn next_task(&self, worker: &Worker, lifo_polls: &mut u32) -> Option<Arc<Task>> {
+        let global = self.queue.lock().tasks.peek().cloned();
+        let mut local = worker.local.lock();
+
+        let lifo = worker.lifo_slot.lock().take();
+        if let Some(task) = lifo {
+            let task = ScheduledTask { task };
+            let outranked = [global.as_ref(), local.peek()]
+                .into_iter()
+                .flatten()
+                .any(|waiting| local.runs_before(waiting, &task));
+            if *lifo_polls < MAX_LIFO_POLLS_IN_A_ROW && !outranked {
+                *lifo_polls += 1;
+                worker.lifo_hits.fetch_add(1, Ordering::Relaxed);
+                return Some(task.task);
+            }
+            local.push(task);
+        }
+        *lifo_polls = 0;
+
+        let global_first = match (&global, local.peek()) {
+            (Some(global), Some(next)) => local.runs_before(global, next),
+            (global, None) => global.is_some(),
+            (None, Some(_)) => false,
+        };
+        if global_first {
+            drop(local);
+            // Another worker may have taken the task in the meantime.
+            return self
+                .pop()
+                .or_else(|| worker.local.lock().pop().map(|scheduled| scheduled.task));
+        }
+        let task = local.pop().map(|scheduled| scheduled.task);
+        drop(local);
+        task.or_else(|| self.pop())
+    }
+
+    /// Hand the worker the current thread belongs to, if any, over to a new thread, so the current
+    /// thread can block without keeping the worker's tasks from being polled.
+    ///
+    /// Returns whether the current thread was a worker.
+    pub(crate) fn hand_off_current_worker() -> bool {
+        let Some((shared, index)) = CURRENT_WORKER.with(|worker| worker.borrow_mut().take()) else {
+            return false;
+        };
+        let Some(shared) = shared.upgrade() else {
+            return true;
+        };
+
+        // A worker that is spawned while shutting down would not be joined, and there is nothing
+        // left for it to do anyway.
+        let _queue = shared.queue.lock();
+        if !shared.shutdown.load(Ordering::SeqCst) {
+            shared.spawn_worker(index);
+        }
+        true
+    }
+
+    /// Steal half of the tasks of another worker for the worker at `index`.
+    ///
+    /// The tasks are popped from the victim's queue, so its scheduler decides which ones are stolen.
+    /// The first stolen task is returned to be polled right away, the rest goes onto the thief's
+    /// local queue. Victims are tried in order, starting with the worker after the thief.
+    fn steal(&self, index: usize) -> Option<Arc<Task>> {
+        let workers = self.workers.len();
+        for offset in 1..workers {
+            let victim = &self.workers[(index + offset) % workers];
+            let mut stolen = {
+                let mut local = victim.local.lock();
+                let len = local.len();
+                if len == 0 {
+                    continue;
+                }
+                (0..len.div_ceil(2))
+                    .filter_map(|_| local.pop())
+                    .collect::<VecDeque<_>>()
+            };
+
+            let thief = &self.workers[index];
+            thief
+                .steal_count
+                .fetch_add(stolen.len() as u64, Ordering::Relaxed);
+            thief.steal_operations.fetch_add(1, Ordering::Relaxed);
+
+            let task = stolen.pop_front();
+            let mut local = thief.local.lock();
+            stolen
+                .into_iter()
+                .for_each(|scheduled| local.push(scheduled));
+            return task.map(|scheduled| scheduled.task);
+        }
+        None
+    }
+}
+
+/// Waking the waker of the future passed to `block_on()` marks it to be polled again and unparks
+/// the thread driving it.
+impl Wake for Shared {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.main_woken.store(true, Ordering::SeqCst);
+        self.notify();
+    }
+}
+
+/// The waker of the timer that queues a task spawned with [`Runtime::spawn_at`] for the first
+/// time.
+///
+/// It only holds on to the task weakly, so a task that is aborted long before it would have
+/// started is freed once it has completed.
+struct DelayedStart(Weak<Task>);
+
+impl Wake for DelayedStart {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let Some(task) = self.0.upgrade() else {
+            return;
+        };
+        if task.state.load(Ordering::SeqCst) != COMPLETE {
+            task.wake_by_ref();
+        }
+    }
+}
+
+/// Waking a task's waker queues the task, unless it is already queued or has completed.
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            let next = match state {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                #[cfg(feature = "debug-waker")]
+                COMPLETE => return checks::woken_after_completion(self),
+                _ => return,
+            };
+            match self
+                .state
+                .compare_exchange(state, next, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(actual) => state = actual,
+            }
+        }
+
+        let Some(shared) = self.shared.upgrade() else {
+            return;
+        };
+        #[cfg(feature = "debug-waker")]
+        checks::woken_from(self, &shared);
+        shared.with_hooks(|hooks| hooks.on_wake(&TaskInfo { task: self }));
+        // A task that is being polled is queued again by `run_task` once the poll is done.
+        if state == IDLE {
+            shared.schedule_woken(self.clone());
+        }
+    }
+}
+
+/// Names a task in diagnostics, like "task 3 'fetcher' spawned at src/main.rs:12:5".
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.id)?;
+        if let Some(name) = &self.name {
+            write!(f, " '{}'", name)?;
+        }
+        write!(f, " spawned at {}", self.location)
+    }
+}
+
+/// The checks of the `debug-waker` feature, which print a warning to standard error when a task's
+/// waker is misused.
+///
+/// None of the misuses break the executor, but each of them usually means that a future is waiting
+/// for something that will never happen.
+#[cfg(feature = "debug-waker")]
+mod checks {
+    use std::sync::Arc;
+
+    use super::{Shared, Task, CONTEXT};
+
+    /// Warn about a waker of `task` that is woken after the task has completed, or was cancelled.
+    pub(super) fn woken_after_completion(task: &Task) {
+        eprintln!(
+            "warning: a waker of {task} was woken after the task completed or was cancelled, which \
+             does nothing. Whatever stored the waker outlived the task, and may be waiting to wake \
+             it for nothing: drop wakers along with the future they were handed to."
+        );
+    }
+
+    /// Warn about `task`, which belongs to `shared`, being woken on a thread that is in the context
+    /// of a different executor.
+    pub(super) fn woken_from(task: &Task, shared: &Arc<Shared>) {
+        let other = CONTEXT.with(|context| {
+            context
+                .borrow()
+                .as_ref()
+                .is_some_and(|current| !Arc::ptr_eq(current, shared))
+        });
+        if other {
+            eprintln!(
+                "warning: {task} was woken from within a different runtime than the one it was \
+                 spawned onto. If the waker was stored by a future that is now polled by this \
+                 runtime, it wakes the wrong task: register the waker of the latest poll instead."
+            );
+        }
+    }
+
+    /// Warn about the wakers of `task`, which was cancelled when its executor shut down, that are
+    /// still around.
+    ///
+    /// Its future and the queues have been dropped by now, so whoever holds `task` besides the
+    /// caller holds a waker.
+    pub(super) fn outstanding_wakers(task: &Arc<Task>) {
+        let wakers = Arc::strong_count(task) - 1;
+        if wakers > 0 {
+            eprintln!(
+                "warning: the runtime was dropped while {wakers} waker(s) of {task} were still \
+                 around. Waking them does nothing, so whoever holds them and waits for the task to \
+                 react will wait forever: shut the runtime down only after they are dropped."
+            );
+        }
+    }
+}
+
+/// Tells `block_on()` and `run()` to stop waiting when a worker thread unwinds because a task
+/// panicked.
+struct WorkerGuard<'a>(&'a Shared);
+
+impl Drop for WorkerGuard<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let mut queue = self.0.queue.lock();
+            queue.panicked = true;
+            self.0.task_finished.notify_all();
+            self.0.notify();
+        }
+    }
+}
+
+/// Parks a thread until it is notified.
+///
+/// Parking alone is not enough to tell a real wake-up from a spurious one, so a flag records
+/// whether the thread has been notified since it last checked.
+pub(crate) struct Signal {
+    /// The thread to unpark when notified.
+    thread: Mutex<Option<Thread>>,
+    /// Set by [`notify`](Signal::notify), and cleared when the parked thread wakes up.
+    notified: AtomicBool,
+}
+
+impl Signal {
+    pub(crate) fn new() -> Self {
+        Self {
+            thread: Mutex::new(None),
+            notified: AtomicBool::new(false),
+        }
+    }
+
+    /// Make the current thread the one that gets unparked when notified.
+    pub(crate) fn register_current(&self) {
+        *self.thread.lock() = Some(thread::current());
+    }
+
+    /// Record the notification and unpark the registered thread, if it is parked.
+    pub(crate) fn notify(&self) {
+        self.notified.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.lock().as_ref() {
+            thread.unpark();
+        }
+    }
+
+    /// Clear the notification, and return whether there was one.
+    pub(crate) fn take_notification(&self) -> bool {
+        self.notified.swap(false, Ordering::SeqCst)
+    }
+
+    /// Park the current thread until [`notify`](Signal::notify) is called, or return right away if it
+    /// has been called since the last time this returned.
+    pub(crate) fn park(&self) {
+        while !self.take_notification() {
+            thread::park();
+        }
+    }
+
+    /// Like [`park`](Signal::park), but return once `timeout` has passed at the latest.
+    pub(crate) fn park_timeout(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while !self.take_notification() {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+}
+
+/// Waking a bare signal just notifies it.
+impl Wake for Signal {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify();
+    }
+}
+
+/// A closure queued on the [`BlockingPool`].
+pub(crate) type BlockingJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// The pool of threads that runs the closures passed to [`Runtime::spawn_blocking`].
+///
+/// Threads are spawned when a closure is queued and no thread is idle, up to a limit. A thread that
+/// has been idle for a while exits again.
+struct BlockingPool {
+    /// The queued closures and the bookkeeping of the threads.
+    state: Mutex<BlockingState>,
+    /// Signalled when a closure is queued or the pool shuts down, so idle threads pick it up.
+    job_available: Condvar,
+    /// The name of the blocking threads.
+    thread_name: String,
+    /// The stack size of the blocking threads, if configured.
+    thread_stack_size: Option<usize>,
+    /// The maximum number of blocking threads that are alive at the same time.
+    max_threads: usize,
+    /// How long a blocking thread waits for a closure before it exits.
+    keep_alive: Duration,
+}
+
+/// The part of the [`BlockingPool`] that is protected by its lock.
+struct BlockingState {
+    /// The closures that have been queued but not picked up by a thread yet.
+    jobs: VecDeque<BlockingJob>,
+    /// The number of blocking threads that are alive.
+    threads: usize,
+    /// The number of blocking threads that are waiting for a closure.
+    idle: usize,
+    /// Set when the threads should exit, and no more closures are accepted.
+    shutdown: bool,
+    /// The join handles of the threads that have been spawned.
+    handles: Vec<thread::JoinHandle<()>>,
+}
+
+impl BlockingPool {
+    fn new(
+        thread_name: String,
+        thread_stack_size: Option<usize>,
+        max_threads: usize,
+        keep_alive: Duration,
+    ) -> Self {
+        Self {
+            state: Mutex::new(BlockingState {
+                jobs: VecDeque::new(),
+                threads: 0,
+                idle: 0,
+                shutdown: false,
+                handles: Vec::new(),
+            }),
+            job_available: Condvar::new(),
+            thread_name,
+            thread_stack_size,
+            max_threads,
+            keep_alive,
+        }
+    }
+
+    /// Whether a closure is queued or running.
+    fn is_busy(&self) -> bool {
+        let state = self.state.lock();
+        !state.jobs.is_empty() || state.idle < state.threads
+    }
+
+    /// Queue a closure, spawning a thread for it if none is idle and the limit allows it, or hand
+    /// it back if the pool has shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a blocking thread can't be spawned.
+    fn spawn(self: &Arc<Self>, job: BlockingJob) -> Result<(), BlockingJob> {
+        let mut state = self.state.lock();
+        if state.shutdown {
+            return Err(job);
+        }
+        state.jobs.push_back(job);
+
+        // Every queued closure is picked up by a thread that is idle right now, or by the next
+        // thread that becomes idle.
+        if state.jobs.len() <= state.idle {
+            self.job_available.notify_one();
+            return Ok(());
+        }
+        if state.threads == self.max_threads {
+            return Ok(());
+        }
+
+        state.threads += 1;
+        state.handles.retain(|handle| !handle.is_finished());
+        let pool = self.clone();
+        let mut builder = thread::Builder::new().name(self.thread_name.clone());
+        if let Some(size) = self.thread_stack_size {
+            builder = builder.stack_size(size);
+        }
+        let handle = builder
+            .spawn(move || pool.run_thread())
+            .expect("failed to spawn a blocking thread");
+        state.handles.push(handle);
+        Ok(())
+    }
+
+    /// The loop run by every blocking thread.
+    fn run_thread(&self) {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock();
+                continue;
+            }
+            if state.shutdown {
+                break;
+            }
+
+            state.idle += 1;
+            let (next, timeout) = self.job_available.wait_timeout(state, self.keep_alive);
+            state = next;
+            state.idle -= 1;
+            if timeout.timed_out() && state.jobs.is_empty() {
+                break;
+            }
+        }
+        state.threads -= 1;
+    }
+
+    /// Drop the closures that haven't started, let the threads exit once they're done with the
+    /// closures they are running, and wait for them until the deadline, if any.
+    fn shutdown(&self, deadline: Option<Instant>) {
+        let (jobs, handles) = {
+            let mut state = self.state.lock();
+            state.shutdown = true;
+            self.job_available.notify_all();
+            (
+                std::mem::take(&mut state.jobs),
+                std::mem::take(&mut state.handles),
+            )
+        };
+
+        // Dropping the closures cancels them.
+        drop(jobs);
+        join_threads(handles, deadline);
+    }
+}
+
+/// A builder to configure a [`Runtime`], created with [`Runtime::builder`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::{Runtime, UnhandledPanic};
+///
+/// let executor = Runtime::builder()
+///     .worker_threads(4)
+///     .thread_name("fetcher")
+///     .thread_stack_size(256 * 1024)
+///     .unhandled_panic(UnhandledPanic::ShutdownRuntime)
+///     .enable_all()
+///     .build();
+///
+/// executor.block_on(async {
+///     println!("Hello from the future!");
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    worker_threads: usize,
+    thread_name: String,
+    thread_stack_size: Option<usize>,
+    max_blocking_threads: usize,
+    thread_keep_alive: Duration,
+    unhandled_panic: UnhandledPanic,
+    lost_wakeup: LostWakeup,
+    warn_unobserved_panics: bool,
+    event_interval: u32,
+    scheduler: SchedulerFactory,
+    hooks: Option<InstalledHooks>,
+    enable_time: bool,
+    timer_tick: Option<Duration>,
+    enable_io: bool,
+    park: Option<InstalledPark>,
+}
+
+/// The [`Park`] set with [`Builder::park`].
+#[derive(Clone)]
+struct InstalledPark(Arc<dyn Park>);
+
+impl fmt::Debug for InstalledPark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("InstalledPark { .. }")
+    }
+}
+
+/// The [`TaskHooks`] set with [`Builder::task_hooks`].
+#[derive(Clone)]
+struct InstalledHooks(Arc<dyn TaskHooks>);
+
+impl fmt::Debug for InstalledHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("InstalledHooks { .. }")
+    }
+}
+
+/// Creates the [`Scheduler`] of each queue of ready tasks, set with [`Builder::scheduler`].
+#[derive(Clone)]
+struct SchedulerFactory(Arc<dyn Fn() -> Box<dyn Scheduler> + Send + Sync>);
+
+impl fmt::Debug for SchedulerFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("SchedulerFactory { .. }")
+    }
+}
+
+impl Builder {
+    /// Create a builder with the default configuration: a current-thread executor that keeps
+    /// running when a spawned task panics.
+    pub fn new() -> Self {
+        Self {
+            worker_threads: 0,
+            thread_name: String::from("mini-executor-worker"),
+            thread_stack_size: None,
+            max_blocking_threads: 512,
+            thread_keep_alive: Duration::from_secs(10),
+            unhandled_panic: UnhandledPanic::default(),
+            lost_wakeup: LostWakeup::default(),
+            warn_unobserved_panics: false,
+            event_interval: 61,
+            scheduler: SchedulerFactory(Arc::new(|| Box::new(Prioritized::default()))),
+            hooks: None,
+            enable_time: false,
+            timer_tick: None,
+            enable_io: false,
+            park: None,
+        }
+    }
+
+    /// Set the number of worker threads that poll the spawned tasks.
+    ///
+    /// With the default of zero, every task is polled on the thread that drives the executor with
+    /// [`block_on()`](Runtime::block_on) or [`run()`](Runtime::run). Any other number
+    /// makes the executor multi-threaded, see [`Runtime::new_multi_thread`].
+    pub fn worker_threads(&mut self, worker_threads: usize) -> &mut Self {
+        self.worker_threads = worker_threads;
+        self
+    }
+
+    /// Set the name of the worker threads.
+    ///
+    /// The index of each worker is appended to it, so the default name gives threads called
+    /// `mini-executor-worker-0`, `mini-executor-worker-1` and so on. The threads of the blocking
+    /// pool get `-blocking` appended instead, and the thread of the timers `-timer`, see
+    /// [`timer_tick`](Builder::timer_tick).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::thread;
+    ///
+    /// let executor = Runtime::builder().worker_threads(1).thread_name("fetcher").build();
+    ///
+    /// let name = executor.spawn(async { thread::current().name().unwrap().to_string() });
+    /// executor.spawn(async move {
+    ///     assert_eq!(name.await.unwrap(), "fetcher-0");
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn thread_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Set the stack size, in bytes, of the worker threads and the blocking threads.
+    ///
+    /// Defaults to the standard library's default for spawned threads.
+    pub fn thread_stack_size(&mut self, size: usize) -> &mut Self {
+        self.thread_stack_size = Some(size);
+        self
+    }
+
+    /// Set the maximum number of threads that run closures passed to
+    /// [`spawn_blocking`](Runtime::spawn_blocking) at the same time.
+    ///
+    /// Closures that are spawned while all of them are busy wait in a queue. Defaults to 512.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_blocking_threads` is zero.
+    pub fn max_blocking_threads(&mut self, max_blocking_threads: usize) -> &mut Self {
+        assert!(
+            max_blocking_threads > 0,
+            "the blocking pool needs at least one thread"
+        );
+        self.max_blocking_threads = max_blocking_threads;
+        self
+    }
+
+    /// Set how long an idle blocking thread waits for another closure before it exits.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn thread_keep_alive(&mut self, keep_alive: Duration) -> &mut Self {
+        self.thread_keep_alive = keep_alive;
+        self
+    }
+
+    /// Set how many tasks are polled in a row before the executor checks for other work.
+    ///
+    /// On a current-thread executor, that other work is polling the future passed to
+    /// [`block_on()`](Runtime::block_on) and picking up wakeups. On a multi-threaded one, it is
+    /// the global queue, which a worker would otherwise only look at once its local queue is empty.
+    /// A lower interval reacts faster to that work, a higher one spends less time checking for it.
+    /// Defaults to 61.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task;
+    ///
+    /// let executor = Runtime::builder().event_interval(1).build();
+    ///
+    /// let busy = executor.spawn(async {
+    ///     for _ in 0..10 {
+    ///         task::yield_now().await;
+    ///     }
+    /// });
+    /// // The handle passed to `block_on` is polled after every poll of the task, instead of after
+    /// // every 61 polls.
+    /// executor.block_on(busy).unwrap();
+    /// ```
+    pub fn event_interval(&mut self, interval: u32) -> &mut Self {
+        assert!(interval > 0, "the event interval must be at least one");
+        self.event_interval = interval;
+        self
+    }
+
+    /// Set what happens when a spawned task panics.
+    ///
+    /// Defaults to [`UnhandledPanic::Ignore`].
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use mini_executor::runtime::{Runtime, UnhandledPanic};
+    ///
+    /// let executor = Runtime::builder()
+    ///     .unhandled_panic(UnhandledPanic::ShutdownRuntime)
+    ///     .build();
+    ///
+    /// executor.spawn(async { panic!("boom") });
+    ///
+    /// // Panics, because the task did.
+    /// executor.run();
+    /// ```
+    pub fn unhandled_panic(&mut self, behavior: UnhandledPanic) -> &mut Self {
+        self.unhandled_panic = behavior;
+        self
+    }
+
+    /// Set what happens when a spawned task returns `Poll::Pending` without having woken or kept
+    /// a clone of its waker, which leaves it waiting forever.
+    ///
+    /// Defaults to [`LostWakeup::Ignore`]. Checking costs a little after every poll, so this is
+    /// meant for debugging hand-written futures.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use mini_executor::runtime::{LostWakeup, Runtime};
+    /// use std::future::Future;
+    /// use std::pin::Pin;
+    /// use std::task::{Context, Poll};
+    ///
+    /// /// Waits for something, but forgets to keep the waker to be woken when it happens.
+    /// struct Forgetful;
+    ///
+    /// impl Future for Forgetful {
+    ///     type Output = ();
+    ///
+    ///     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+    ///         Poll::Pending
+    ///     }
+    /// }
+    ///
+    /// let executor = Runtime::builder().lost_wakeup(LostWakeup::Panic).build();
+    /// let handle = executor.spawn_named("forgetful", Forgetful);
+    ///
+    /// // Panics with a message naming the task 'forgetful', rather than hanging.
+    /// executor.block_on(handle);
+    /// ```
+    pub fn lost_wakeup(&mut self, behavior: LostWakeup) -> &mut Self {
+        self.lost_wakeup = behavior;
+        self
+    }
+
+    /// Set whether to print a warning to standard error when a spawned task panics and nobody is
+    /// going to await its [`JoinHandle`], because it was dropped.
+    ///
+    /// The warning names the task and where it was spawned, so a task that fails in the background
+    /// doesn't go unnoticed. It is printed when the later of the two happens: the panic, or the drop
+    /// of the handle. Defaults to `false`. The same goes for [`spawn_blocking`](Runtime::spawn_blocking).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::builder().warn_unobserved_panics(true).build();
+    ///
+    /// // Prints a warning naming this line once the task has panicked.
+    /// drop(executor.spawn(async { panic!("boom") }));
+    /// executor.run();
+    /// ```
+    pub fn warn_unobserved_panics(&mut self, enabled: bool) -> &mut Self {
+        self.warn_unobserved_panics = enabled;
+        self
+    }
+
+    /// Set the policy that decides which of the ready tasks is polled next.
+    ///
+    /// `new_scheduler` is called once for the global queue and once for the local queue of every
+    /// worker. Defaults to [`scheduler::Prioritized`](crate::scheduler::Prioritized). See the
+    /// [`scheduler`](crate::scheduler) module for the other built-in policies, and [`Scheduler`] to
+    /// write one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::scheduler::Lifo;
+    /// use mini_executor::runtime::Runtime;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let executor = Runtime::builder().scheduler(Lifo::default).build();
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for i in 0..3 {
+    ///     let order = order.clone();
+    ///     executor.spawn(async move { order.lock().unwrap().push(i) });
+    /// }
+    /// executor.run();
+    ///
+    /// assert_eq!(*order.lock().unwrap(), [2, 1, 0]);
+    /// ```
+    pub fn scheduler<S, F>(&mut self, new_scheduler: F) -> &mut Self
+    where
+        S: Scheduler,
+        F: Fn() -> S + Send + Sync + 'static,
+    {
+        self.scheduler = SchedulerFactory(Arc::new(move || Box::new(new_scheduler())));
+        self
+    }
+
+    /// Install callbacks for the lifecycle events of the spawned tasks, see [`TaskHooks`].
+    pub fn task_hooks(&mut self, hooks: impl TaskHooks) -> &mut Self {
+        self.hooks = Some(InstalledHooks(Arc::new(hooks)));
+        self
+    }
+
+    /// Enable the time driver, which the futures of the [`time`] module need.
+    ///
+    /// The driver doesn't need a thread of its own: the timers are fired by the threads of the
+    /// executor, which park only until the next timer is due when they run out of work.
+    pub fn enable_time(&mut self) -> &mut Self {
+        self.enable_time = true;
+        self
+    }
+
+    /// Fire the timers from a thread of their own once every `tick`, instead of parking the
+    /// threads of the executor only until the next timer is due.
+    ///
+    /// This is a coarse timer mode for platforms where parking with a precise timeout is costly
+    /// or unavailable: a timer fires up to one tick late, and the threads of the executor park
+    /// until they are woken, by a task or by the thread of the timers. That thread wakes up every
+    /// tick, also while there are no timers. It has no effect unless the time driver is enabled
+    /// with [`enable_time`](Builder::enable_time).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::time;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = Runtime::builder()
+    ///     .enable_time()
+    ///     .timer_tick(Duration::from_millis(10))
+    ///     .build();
+    ///
+    /// let start = Instant::now();
+    /// executor.block_on(async { time::sleep(Duration::from_millis(25)).await });
+    /// assert!(start.elapsed() >= Duration::from_millis(25));
+    /// ```
+    pub fn timer_tick(&mut self, tick: Duration) -> &mut Self {
+        assert!(tick > Duration::ZERO, "`tick` must be non-zero");
+        self.timer_tick = Some(tick);
+        self
+    }
+
+    /// Enable the I/O driver, which the types of the [`io`] module need.
+    ///
+    /// Like the time driver, it doesn't need a thread of its own: a thread of the executor that
+    /// runs out of work waits for I/O events until the next timer is due, which it can only do to
+    /// the millisecond on Linux and Windows. The driver is built on `epoll` on Linux, on `kqueue` on
+    /// macOS and the BSDs, and on `WSAPoll` for sockets on Windows. With the `mio` feature, `mio`
+    /// waits for the events on every Unix platform instead. On platforms without an I/O driver,
+    /// this has no effect.
+    pub fn enable_io(&mut self) -> &mut Self {
+        self.enable_io = true;
+        self
+    }
+
+    /// Set what the thread that drives the executor blocks on while it has nothing to do, instead
+    /// of parking, or waiting for I/O events.
+    ///
+    /// This lets the executor share its thread with another source of events, like the event loop
+    /// of a GUI toolkit. `park` is called with the time until the next timer is due, and unparked
+    /// whenever a task is woken. See [`Park`] for what it has to do.
+    pub fn park(&mut self, park: impl Park) -> &mut Self {
+        self.park = Some(InstalledPark(Arc::new(park)));
+        self
+    }
+
+    /// Enable both the time and the I/O driver.
+    pub fn enable_all(&mut self) -> &mut Self {
+        self.enable_time().enable_io()
+    }
+
+    /// Create the configured `Runtime`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a worker thread, or the thread of the timers, can't be spawned.
+    pub fn build(&mut self) -> Runtime {
+        let shared = Arc::new_cyclic(|shared| Shared::new(self, shared));
+        if let Some(time) = &shared.time {
+            let name = format!("{}-timer", self.thread_name);
+            time.start_ticking(name, self.thread_stack_size);
+        }
+        for index in 0..self.worker_threads {
+            shared.spawn_worker(index);
+        }
+
+        let flavor = if self.worker_threads == 0 {
+            Flavor::CurrentThread
+        } else {
+            Flavor::MultiThread
+        };
+
+        Runtime { shared, flavor }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `LocalExecutor` is a single-threaded executor for futures that are not `Send`.
+///
+/// It works like a current-thread [`Runtime`], but the main future and the spawned tasks never
+/// leave the thread that calls [`run()`](LocalExecutor::run). That allows them to hold an `Rc` or a
+/// `RefCell` across an `.await`, and lets the executor itself use `Rc` and `RefCell` internally
+/// instead of `Arc` and `Mutex`.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::LocalExecutor;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let log = Rc::new(RefCell::new(Vec::new()));
+///
+/// let task_log = log.clone();
+/// let executor = LocalExecutor::new(async move {
+///     task_log.borrow_mut().push("main");
+/// });
+/// let task_log = log.clone();
+/// executor.spawn(async move {
+///     task_log.borrow_mut().push("task");
+/// });
+///
+/// executor.run();
+/// assert_eq!(*log.borrow(), ["main", "task"]);
+/// ```
+pub struct LocalExecutor {
+    /// The main future, until it has completed.
+    future: RefCell<Option<LocalBoxedFuture>>,
+    /// The tasks spawned onto the executor that have not completed yet, by ID.
+    ///
+    /// A task is taken out of the map while it is polled, so a running task can spawn more tasks.
+    tasks: RefCell<HashMap<task::Id, LocalTask>>,
+    /// The IDs of the tasks that have been woken, shared with their wakers.
+    ///
+    /// Wakers have to be `Send` and `Sync` even if the futures are not, so they can't refer to the
+    /// executor itself.
+    ready: Arc<LocalReady>,
+}
+
+impl LocalExecutor {
+    /// Create a new `LocalExecutor` with the given future.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::LocalExecutor;
+    /// use std::rc::Rc;
+    ///
+    /// let shared = Rc::new(5);
+    /// let executor = LocalExecutor::new(async move {
+    ///     println!("Hello from a future holding {}!", shared);
+    /// });
+    /// ```
+    pub fn new<F>(future: F) -> Rc<Self>
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        Rc::new(Self {
+            future: RefCell::new(Some(Box::pin(future))),
+            tasks: RefCell::new(HashMap::new()),
+            ready: Arc::new(LocalReady::new()),
+        })
+    }
+
+    /// Spawn an additional future onto the executor.
+    ///
+    /// Neither the future nor its output need to be `Send`. The returned [`JoinHandle`] can be
+    /// awaited to get the task's output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::LocalExecutor;
+    /// use std::rc::Rc;
+    ///
+    /// let executor = LocalExecutor::new(async {});
+    ///
+    /// let answer = executor.spawn(async { Rc::new(42) });
+    /// executor.spawn(async move {
+    ///     assert_eq!(*answer.await.unwrap(), 42);
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.spawn_task(None, future)
+    }
+
+    /// Spawn an additional future onto the executor under the given name.
+    ///
+    /// This works like [`Runtime::spawn_named`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::LocalExecutor;
+    /// use mini_executor::task;
+    ///
+    /// let executor = LocalExecutor::new(async {});
+    ///
+    /// executor.spawn_named("printer", async {
+    ///     assert_eq!(task::name().as_deref(), Some("printer"));
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    #[track_caller]
+    pub fn spawn_named<F>(&self, name: &str, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.spawn_task(Some(name.into()), future)
+    }
+
+    /// Spawn a task with an optional name, see [`LocalExecutor::spawn_named`].
+    #[track_caller]
+    fn spawn_task<F>(&self, name: Option<Arc<str>>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        spawn_local_task(&self.tasks, &self.ready, name, future)
+    }
+
+    /// Run the `LocalExecutor` to completion.
+    ///
+    /// This method will block the current thread until the future and every spawned task have
+    /// completed. Only futures whose waker has been woken are polled again, and the thread is parked
+    /// whenever nothing is left to poll.
+    ///
+    /// # Panics
+    ///
+    /// Like [`Runtime::run`], this panics when called from within a future that is already
+    /// being driven by an executor, and so do [`LocalExecutor::run_until_stalled`] and
+    /// [`LocalExecutor::poll_once`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::LocalExecutor;
+    ///
+    /// let executor = LocalExecutor::new(async {
+    ///     println!("Hello from the future!");
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn run(self: Rc<Self>) {
+        let _driver = enter_driver();
+        self.ready.signal.register_current();
+        let waker = waker_for(self.ready.clone());
+        let mut context = Context::from_waker(&waker);
+
+        // Poll the future and the spawned tasks until all of them are completed.
+        while !self.poll_pass(&mut context) {
+            self.ready.signal.park();
+        }
+    }
+
+    /// Poll everything that is ready to make progress, then return instead of blocking.
+    ///
+    /// The main future, if it has been woken, and the queued tasks are polled until none of them
+    /// has been woken since it was last polled.
+    ///
+    /// Returns `Poll::Ready(())` once the main future and every spawned task have completed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::LocalExecutor;
+    /// use std::task::Poll;
+    ///
+    /// let (sender, receiver) = futures::channel::oneshot::channel::<u32>();
+    /// let executor = LocalExecutor::new(async {});
+    /// executor.spawn(async move {
+    ///     assert_eq!(receiver.await, Ok(42));
+    /// });
+    ///
+    /// assert_eq!(executor.run_until_stalled(), Poll::Pending);
+    ///
+    /// sender.send(42).unwrap();
+    /// assert_eq!(executor.run_until_stalled(), Poll::Ready(()));
+    /// ```
+    pub fn run_until_stalled(&self) -> Poll<()> {
+        let _driver = enter_driver();
+        self.ready.signal.register_current();
+        let waker = waker_for(self.ready.clone());
+        let mut context = Context::from_waker(&waker);
+
+        loop {
+            if self.poll_pass(&mut context) {
+                return Poll::Ready(());
+            }
+            if !self.ready.signal.take_notification() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Poll the main future exactly once and report whether it has completed.
+    ///
+    /// This works like [`Runtime::poll_once`]: spawned tasks are not polled, and once the main
+    /// future has completed this keeps returning `Poll::Ready(())`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::LocalExecutor;
+    /// use std::task::Poll;
+    ///
+    /// let (sender, receiver) = futures::channel::oneshot::channel::<()>();
+    /// let executor = LocalExecutor::new(async move {
+    ///     receiver.await.unwrap();
+    /// });
+    ///
+    /// assert_eq!(executor.poll_once(), Poll::Pending);
+    /// sender.send(()).unwrap();
+    /// assert_eq!(executor.poll_once(), Poll::Ready(()));
+    /// ```
+    pub fn poll_once(&self) -> Poll<()> {
+        let _driver = enter_driver();
+        let waker = waker_for(self.ready.clone());
+        let mut context = Context::from_waker(&waker);
+        self.poll_main(&mut context)
+    }
+
+    /// Poll the main future once, unless it has already completed.
+    fn poll_main(&self, context: &mut Context<'_>) -> Poll<()> {
+        self.ready.main_woken.store(false, Ordering::SeqCst);
+        let mut future = self.future.borrow_mut();
+        if let Some(fut) = future.as_mut() {
+            let poll = panic::catch_unwind(AssertUnwindSafe(|| {
+                task::budget(|| fut.as_mut().poll(context))
+            }));
+            match poll {
+                Ok(poll) if poll.is_ready() => *future = None,
+                Ok(_) => {}
+                Err(payload) => {
+                    // A future that panicked must not be polled again.
+                    *future = None;
+                    drop(future);
+                    panic::resume_unwind(payload);
+                }
+            }
+        }
+        if future.is_none() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Poll the main future, if it has been woken, and every queued task once.
+    ///
+    /// Returns whether the main future and every spawned task have completed.
+    fn poll_pass(&self, context: &mut Context<'_>) -> bool {
+        let main_done = if self.ready.main_woken.load(Ordering::SeqCst) {
+            self.poll_main(context).is_ready()
+        } else {
+            self.future.borrow().is_none()
+        };
+
+        poll_local_tasks(&self.tasks, &self.ready);
+        main_done && self.tasks.borrow().is_empty()
+    }
+}
+
+/// Run a future to completion on the current thread and return its output.
+///
+/// This works like [`Runtime::block_on`], without an executor to spawn tasks onto, just like
+/// `futures::executor::block_on`.
+///
+/// # Panics
+///
+/// Like [`Runtime::block_on`], this panics when called from within a future that is already being
+/// driven by an executor. Inside a task, await the future instead, or move the blocking code to
+/// [`spawn_blocking`](Runtime::spawn_blocking).
+///
+/// ```should_panic
+/// use mini_executor::block_on;
+///
+/// block_on(async {
+///     // Panics instead of blocking the thread that is polling the outer future.
+///     block_on(async {});
+/// });
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::block_on;
+///
+/// let answer = block_on(async { 40 + 2 });
+/// assert_eq!(answer, 42);
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let _driver = enter_driver();
+    // There are no tasks to schedule, so waking the future just unparks the current thread.
+    let signal = Arc::new(Signal::new());
+    signal.register_current();
+    let waker = waker_for(signal.clone());
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+
+    // Poll the future until it's completed, parking the thread while it is pending.
+    loop {
+        if let Poll::Ready(output) = task::budget(|| future.as_mut().poll(&mut context)) {
+            return output;
+        }
+        signal.park();
+    }
+}
+
+/// A handle to the runtime metrics of a [`Runtime`], obtained with [`Runtime::metrics`].
+///
+/// The metrics are read live, so they keep changing while the executor is running.
+#[derive(Clone)]
+pub struct RuntimeMetrics {
+    shared: Arc<Shared>,
+}
+
+impl RuntimeMetrics {
+    /// The number of worker threads, zero for a current-thread executor.
+    pub fn num_workers(&self) -> usize {
+        self.shared.workers.len()
+    }
+
+    /// The number of tasks the given worker has stolen from other workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker` is not less than [`num_workers()`](RuntimeMetrics::num_workers).
+    pub fn worker_steal_count(&self, worker: usize) -> u64 {
+        self.shared.workers[worker]
+            .steal_count
+            .load(Ordering::Relaxed)
+    }
+
+    /// The number of times the given worker has stolen tasks from another worker.
+    ///
+    /// A single steal operation takes half of the victim's local queue, so this is at most
+    /// [`worker_steal_count`](RuntimeMetrics::worker_steal_count).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker` is not less than [`num_workers()`](RuntimeMetrics::num_workers).
+    pub fn worker_steal_operations(&self, worker: usize) -> u64 {
+        self.shared.workers[worker]
+            .steal_operations
+            .load(Ordering::Relaxed)
+    }
+
+    /// The number of times the given worker has polled a task from its LIFO slot, which holds the
+    /// task that was woken last by the task the worker was polling.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker` is not less than [`num_workers()`](RuntimeMetrics::num_workers).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new_multi_thread(1);
+    /// let (sender, receiver) = futures::channel::oneshot::channel();
+    ///
+    /// let receiver = executor.spawn(receiver);
+    /// // Sending wakes the receiving task, which is polled right after the sender.
+    /// executor.spawn(async move { sender.send(42).unwrap() });
+    ///
+    /// assert_eq!(executor.block_on(receiver).unwrap(), Ok(42));
+    /// assert_eq!(executor.metrics().worker_lifo_hit_count(0), 1);
+    /// ```
+    pub fn worker_lifo_hit_count(&self, worker: usize) -> u64 {
+        self.shared.workers[worker]
+            .lifo_hits
+            .load(Ordering::Relaxed)
+    }
+
+    /// The number of tasks currently sitting in the given worker's local queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker` is not less than [`num_workers()`](RuntimeMetrics::num_workers).
+    pub fn worker_local_queue_depth(&self, worker: usize) -> usize {
+        self.shared.workers[worker].local.lock().len()
+    }
+
+    /// The number of tasks currently sitting in the global queue.
+    pub fn injection_queue_depth(&self) -> usize {
+        self.shared.queue.lock().tasks.len()
+    }
+
+    /// The number of timers registered with the time driver, like the deadline of a
+    /// [`Sleep`](time::Sleep) polled for the first time.
+    ///
+    /// This and the other timer statistics are zero if the time driver isn't enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::time;
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     // The sleep fires, and the timeout is cancelled once the sleep completes.
+    ///     let sleep = time::sleep(Duration::from_millis(10));
+    ///     time::timeout(Duration::from_secs(60), sleep).await.unwrap();
+    /// });
+    ///
+    /// let metrics = executor.metrics();
+    /// assert_eq!(metrics.timers_registered(), 2);
+    /// assert_eq!(metrics.timers_fired(), 1);
+    /// assert_eq!(metrics.timers_cancelled(), 1);
+    /// assert_eq!(metrics.active_timers(), 0);
+    /// ```
+    pub fn timers_registered(&self) -> u64 {
+        self.timer_stats().registered
+    }
+
+    /// The number of timers that have fired, waking the task waiting for them.
+    pub fn timers_fired(&self) -> u64 {
+        self.timer_stats().fired
+    }
+
+    /// The number of timers that were dropped before they fired.
+    pub fn timers_cancelled(&self) -> u64 {
+        self.timer_stats().cancelled
+    }
+
+    /// The number of timers that are registered and haven't fired yet.
+    pub fn active_timers(&self) -> usize {
+        self.timer_stats().active
+    }
+
+    /// The number of times the time driver has moved a timer around to keep the timers ordered.
+    ///
+    /// The timing wheel moves a timer to a finer level as its deadline comes closer, at most once
+    /// per level, no matter how many other timers there are. The binary heap of the `timer_heap`
+    /// feature swaps a timer with its parent or child, up to the logarithm of the number of
+    /// timers, whenever a timer is inserted or fires.
+    pub fn timer_moves(&self) -> u64 {
+        self.timer_stats().moves
+    }
+
+    fn timer_stats(&self) -> time::Stats {
+        self.shared
+            .time
+            .as_ref()
+            .map(|driver| driver.stats())
+            .unwrap_or_default()
+    }
+}
+
+/// Callbacks for the lifecycle events of the tasks spawned onto a [`Runtime`], installed with
+/// [`Builder::task_hooks`].
+///
+/// Every callback does nothing by default. They are called on the thread the event happens on, so
+/// they should return quickly, and they must not spawn tasks or block on futures themselves.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::{Runtime, TaskHooks, TaskInfo};
+/// use std::any::Any;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Default)]
+/// struct Counters {
+///     spawned: AtomicUsize,
+///     completed: AtomicUsize,
+///     panicked: AtomicUsize,
+/// }
+///
+/// #[derive(Clone, Default)]
+/// struct CountingHooks(Arc<Counters>);
+///
+/// impl TaskHooks for CountingHooks {
+///     fn on_spawn(&self, _task: &TaskInfo<'_>) {
+///         self.0.spawned.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn on_complete(&self, _task: &TaskInfo<'_>) {
+///         self.0.completed.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn on_panic(&self, task: &TaskInfo<'_>, _payload: &(dyn Any + Send)) {
+///         println!("task {} panicked", task.id());
+///         self.0.panicked.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// let hooks = CountingHooks::default();
+/// let executor = Runtime::builder().task_hooks(hooks.clone()).build();
+///
+/// executor.spawn(async {});
+/// executor.spawn(async { panic!("boom") });
+/// executor.run();
+///
+/// assert_eq!(hooks.0.spawned.load(Ordering::SeqCst), 2);
+/// assert_eq!(hooks.0.completed.load(Ordering::SeqCst), 2);
+/// assert_eq!(hooks.0.panicked.load(Ordering::SeqCst), 1);
+/// ```
+pub trait TaskHooks: Send + Sync + 'static {
+    /// Called when a task has been spawned, before it is queued for its first poll.
+    fn on_spawn(&self, task: &TaskInfo<'_>) {
+        let _ = task;
+    }
+
+    /// Called right before a task is polled for the first time.
+    fn on_first_poll(&self, task: &TaskInfo<'_>) {
+        let _ = task;
+    }
+
+    /// Called when a task that hasn't completed is woken, also when it is already queued.
+    fn on_wake(&self, task: &TaskInfo<'_>) {
+        let _ = task;
+    }
+
+    /// Called when a poll of a task returned `Poll::Pending`, handing the thread back to the
+    /// executor.
+    fn on_yield(&self, task: &TaskInfo<'_>) {
+        let _ = task;
+    }
+
+    /// Called when a task has completed, after its future has been dropped. This includes tasks
+    /// that were aborted or panicked, but not the ones cancelled by a shutdown.
+    fn on_complete(&self, task: &TaskInfo<'_>) {
+        let _ = task;
+    }
+
+    /// Called when a poll of a task panicked, with the panic's payload, before
+    /// [`on_complete`](TaskHooks::on_complete).
+    fn on_panic(&self, task: &TaskInfo<'_>, payload: &(dyn Any + Send)) {
+        let _ = (task, payload);
+    }
+}
+
+/// What [`TaskHooks`] get to know about a task.
+pub struct TaskInfo<'a> {
+    task: &'a Task,
+}
+
+impl TaskInfo<'_> {
+    /// The ID of the task.
+    pub fn id(&self) -> task::Id {
+        self.task.id
+    }
+
+    /// The name the task was spawned with, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.task.name.as_deref()
+    }
+
+    /// The priority the task was spawned with.
+    pub fn priority(&self) -> Priority {
+        self.task.priority
+    }
+
+    /// The deadline the task was spawned with, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.task.deadline
+    }
+
+    /// Where the task was spawned.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.task.location
+    }
+}
+
+impl fmt::Debug for TaskInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskInfo")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .field("location", &self.location())
+            .finish()
+    }
+}
+
+/// The hooks of an executor, together with the task of that executor being polled.
+type HookedTask = (Arc<dyn TaskHooks>, Arc<Task>);
+
+thread_local! {
+    /// The task being polled on this thread, if its executor has hooks installed, so a panic of the
+    /// task can be reported to them.
+    static HOOKED_TASK: RefCell<Option<HookedTask>> = const { RefCell::new(None) };
+}
+
+/// Make `task` the one a panic on this thread is reported to `hooks` for, until the returned guard
+/// is dropped.
+fn enter_hooked(hooks: Arc<dyn TaskHooks>, task: Arc<Task>) -> HookedGuard {
+    HookedGuard {
+        previous: HOOKED_TASK.with(|hooked| hooked.replace(Some((hooks, task)))),
+    }
+}
+
+/// Restores the task a panic was reported for before [`enter_hooked`] when dropped.
+struct HookedGuard {
+    previous: Option<HookedTask>,
+}
+
+impl Drop for HookedGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        let _ = HOOKED_TASK.try_with(|hooked| *hooked.borrow_mut() = previous);
+    }
+}
+
+/// Report the panic of the task that is being polled on this thread to the executor's hooks, if
+/// any.
+pub(crate) fn report_panic(payload: &(dyn Any + Send)) {
+    let hooked = HOOKED_TASK.with(|hooked| hooked.borrow().clone());
+    if let Some((hooks, task)) = hooked {
+        hooks.on_panic(&TaskInfo { task: &task }, payload);
+    }
+}
+
+/// A snapshot of a spawned task, as listed by [`Runtime::dump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskDump {
+    /// The task's ID.
+    pub id: task::Id,
+    /// The name the task was spawned with, if any.
+    pub name: Option<String>,
+    /// Where the task was spawned.
+    pub location: &'static Location<'static>,
+    /// The label of the innermost [`Instrumented`] future the task was waiting in when it last
+    /// returned `Poll::Pending`, if any.
+    ///
+    /// [`Instrumented`]: crate::task::Instrumented
+    pub label: Option<String>,
+    /// What the task was doing when the snapshot was taken.
+    pub state: TaskState,
+}
+
+impl fmt::Display for TaskDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.id)?;
+        if let Some(name) = &self.name {
+            write!(f, " '{}'", name)?;
+        }
+        write!(f, " spawned at {}: {:?}", self.location, self.state)?;
+        if let Some(label) = &self.label {
+            write!(f, " in {}", label)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a spawned task is doing, as reported by [`Runtime::dump`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// The task is waiting to be woken.
+    Idle,
+    /// The task has been spawned or woken, and is waiting to be polled.
+    Scheduled,
+    /// The task is being polled.
+    Running,
+}
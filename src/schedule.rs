@@ -0,0 +1,467 @@
+//! Running jobs on a recurring schedule, like a small cron.
+//!
+//! A [`Schedule`] says when a job runs: every fixed period with [`Schedule::every`], or at the
+//! times that match a cron expression with [`Schedule::cron`]. [`spawn`] starts a job that calls
+//! a closure each time the schedule fires and spawns the future it returns as a new task, and the
+//! [`Job`] it returns pauses, resumes and cancels it.
+//!
+//! Jobs wait with the time driver of the [`Runtime`] they are spawned on, so they follow its
+//! clock, and a [paused](crate::time::pause) clock lets tests run a schedule of days instantly.
+//!
+//! [`Runtime`]: crate::runtime::Runtime
+
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::runtime::Handle;
+use crate::sync::AtomicWaker;
+use crate::task::JoinHandle;
+use crate::time;
+
+/// When a job runs, see [`spawn`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::schedule::Schedule;
+/// use std::time::{Duration, SystemTime};
+///
+/// // At 9:30 on weekdays.
+/// let schedule = Schedule::cron("30 9 * * 1-5").unwrap();
+///
+/// // Monday, 1 January 2024, 00:00 UTC.
+/// let monday = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+/// let next = schedule.next_after(monday).unwrap();
+/// assert_eq!(next, monday + Duration::from_secs(9 * 3600 + 30 * 60));
+///
+/// let schedule: Schedule = "@daily".parse().unwrap();
+/// assert_eq!(schedule.next_after(next), Some(monday + Duration::from_secs(24 * 3600)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// A schedule that fires every `period`, starting one period after the job is spawned.
+    ///
+    /// The times are fixed when the job is spawned: a firing that is missed, because the job was
+    /// paused or the executor was busy, is skipped instead of moving the ones after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    #[track_caller]
+    pub fn every(period: Duration) -> Schedule {
+        assert!(period > Duration::ZERO, "`period` must be non-zero");
+        Schedule {
+            kind: Kind::Every(period),
+        }
+    }
+
+    /// Parse a cron expression into a schedule that fires at the minutes it matches, in UTC.
+    ///
+    /// The expression has the five fields of a crontab, separated by whitespace: the minute
+    /// (0-59), the hour (0-23), the day of the month (1-31), the month (1-12) and the day of the
+    /// week (0-7, where both 0 and 7 are Sunday). Each field is `*` for every value, a value, a
+    /// range like `1-5`, any of them followed by a step like `*/15`, or a comma separated list of
+    /// these. As in cron, a day matches if either day field does when both are restricted.
+    ///
+    /// The shortcuts `@yearly` (or `@annually`), `@monthly`, `@weekly`, `@daily` (or
+    /// `@midnight`) and `@hourly` are accepted as well.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParseError`] if `expression` isn't a valid cron expression.
+    pub fn cron(expression: &str) -> Result<Schedule, ParseError> {
+        let expression = expression.trim();
+        let fields = match expression {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            _ => expression,
+        };
+        let cron = Cron::parse(fields).map_err(|reason| ParseError {
+            expression: expression.to_string(),
+            reason,
+        })?;
+        Ok(Schedule {
+            kind: Kind::Cron(cron),
+        })
+    }
+
+    /// The first time after `time` that the schedule fires at, or `None` if there is none.
+    ///
+    /// A cron schedule returns `None` if it matches no day, like `0 0 31 2 *`. A fixed period
+    /// doesn't have times of its own, so it fires one `period` after `time`.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        match &self.kind {
+            Kind::Every(period) => time.checked_add(*period),
+            Kind::Cron(cron) => cron.next_after(time),
+        }
+    }
+
+    /// The first time after `now` that a job spawned at `start` runs at, read from the clock of
+    /// the time driver, where `wall` is the time of day at `start`.
+    fn next_run(&self, start: Instant, wall: SystemTime, now: Instant) -> Option<Instant> {
+        match &self.kind {
+            Kind::Every(period) => {
+                let periods = now.duration_since(start).as_nanos() / period.as_nanos() + 1;
+                let elapsed = period.as_nanos().checked_mul(periods)?;
+                start.checked_add(duration_from_nanos(elapsed)?)
+            }
+            Kind::Cron(cron) => {
+                let next = cron.next_after(wall.checked_add(now.duration_since(start))?)?;
+                start.checked_add(next.duration_since(wall).ok()?)
+            }
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ParseError;
+
+    /// Parse a cron expression, see [`Schedule::cron`].
+    fn from_str(expression: &str) -> Result<Schedule, ParseError> {
+        Schedule::cron(expression)
+    }
+}
+
+fn duration_from_nanos(nanos: u128) -> Option<Duration> {
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// The error returned by [`Schedule::cron`] for an invalid cron expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    expression: String,
+    reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid cron expression {:?}: {}",
+            self.expression, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The longest a valid cron expression can go without firing, from the 29th of February of one
+/// leap year to the next one, across a century that isn't a leap year.
+const MAX_DAYS: u64 = 366 * 9;
+
+/// The values each field of a cron expression matches, one bit per value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Cron {
+    fn parse(expression: &str) -> Result<Cron, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let &[minutes, hours, days_of_month, months, days_of_week] = &fields[..] else {
+            return Err(format!("expected 5 fields, found {}", fields.len()));
+        };
+
+        let mut days_of_week_bits = parse_field(days_of_week, "day of the week", 0, 7)?;
+        // Sunday is both 0 and 7.
+        if days_of_week_bits & (1 << 7) != 0 {
+            days_of_week_bits = (days_of_week_bits | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            minutes: parse_field(minutes, "minute", 0, 59)?,
+            hours: parse_field(hours, "hour", 0, 23)?,
+            days_of_month: parse_field(days_of_month, "day of the month", 1, 31)?,
+            months: parse_field(months, "month", 1, 12)?,
+            days_of_week: days_of_week_bits,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+
+    fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        // The first whole minute after `time`.
+        let minutes = time.duration_since(UNIX_EPOCH).ok()?.as_secs() / 60 + 1;
+        let first_day = minutes / (24 * 60);
+        let mut minute_of_day = minutes % (24 * 60);
+        for day in first_day..first_day + MAX_DAYS {
+            if self.matches_day(day) {
+                if let Some(minute) = self.first_minute_from(minute_of_day) {
+                    let secs = (day * 24 * 60 + minute) * 60;
+                    return UNIX_EPOCH.checked_add(Duration::from_secs(secs));
+                }
+            }
+            minute_of_day = 0;
+        }
+        None
+    }
+
+    fn matches_day(&self, days_since_epoch: u64) -> bool {
+        let (month, day) = month_and_day(days_since_epoch);
+        // The 1st of January 1970 was a Thursday.
+        let weekday = (days_since_epoch + 4) % 7;
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        let day_of_month = self.days_of_month & (1 << day) != 0;
+        let day_of_week = self.days_of_week & (1 << weekday) != 0;
+        if self.any_day_of_month || self.any_day_of_week {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        }
+    }
+
+    /// The first matching minute of a day at or after `minute_of_day`.
+    fn first_minute_from(&self, minute_of_day: u64) -> Option<u64> {
+        let first_hour = minute_of_day / 60;
+        for hour in first_hour..24 {
+            if self.hours & (1 << hour) == 0 {
+                continue;
+            }
+            let first_minute = if hour == first_hour {
+                minute_of_day % 60
+            } else {
+                0
+            };
+            let minutes = self.minutes >> first_minute;
+            if minutes != 0 {
+                return Some(hour * 60 + first_minute + u64::from(minutes.trailing_zeros()));
+            }
+        }
+        None
+    }
+}
+
+/// Parse one field of a cron expression into the values it matches between `min` and `max`.
+fn parse_field(field: &str, name: &str, min: u64, max: u64) -> Result<u64, String> {
+    let parse = |value: &str| match value.parse::<u64>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!(
+            "invalid {} {:?}, expected {}-{}",
+            name, value, min, max
+        )),
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u64>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("invalid step {:?} in the {}", step, name)),
+            },
+            None => (part, None),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (parse(first)?, parse(last)?),
+            // A single value with a step runs to the end of the range, like in cron.
+            None if step.is_some() => (parse(range)?, max),
+            None => (parse(range)?, parse(range)?),
+        };
+        if first > last {
+            return Err(format!("invalid {} range {:?}", name, range));
+        }
+        for value in (first..=last).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// The month (1-12) and the day of the month (1-31) of a day counted from the 1st of January 1970.
+fn month_and_day(days_since_epoch: u64) -> (u64, u64) {
+    // Howard Hinnant's `civil_from_days`, with years starting in March so that the leap day is
+    // the last day of the year.
+    let days = days_since_epoch + 719_468;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    (month, day)
+}
+
+/// Spawn a job onto the current executor that calls `job` every time `schedule` fires, and
+/// spawns the future it returns as a new task.
+///
+/// The runs are tasks of their own, so a run that takes longer than the time until the next one
+/// doesn't delay it, and a run that panics doesn't stop the job. The job runs until it is
+/// [cancelled](Job::cancel), or until the executor shuts down; dropping the [`Job`] leaves it
+/// running.
+///
+/// A cron schedule reads the time of day once when the job is spawned, and follows the clock of
+/// the time driver from then on.
+///
+/// # Panics
+///
+/// Panics if called from outside of a [`Runtime`], or if its time driver is disabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::schedule::{self, Schedule};
+/// use mini_executor::time;
+/// use std::time::Duration;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     time::pause();
+///
+///     let job = schedule::spawn(Schedule::every(Duration::from_secs(60)), || async {
+///         println!("cleaning up");
+///     });
+///     time::sleep(Duration::from_secs(150)).await;
+///     assert_eq!(job.runs(), 2);
+///
+///     // The runs while the job is paused are skipped.
+///     job.pause();
+///     time::sleep(Duration::from_secs(120)).await;
+///     assert_eq!(job.runs(), 2);
+///
+///     job.resume();
+///     time::sleep(Duration::from_secs(60)).await;
+///     assert_eq!(job.runs(), 3);
+///
+///     job.cancel();
+/// });
+/// ```
+///
+/// [`Runtime`]: crate::runtime::Runtime
+#[track_caller]
+pub fn spawn<F, Fut>(schedule: Schedule, mut job: F) -> Job
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let handle = Handle::current();
+    let start = time::now();
+    let wall = SystemTime::now();
+    let state = Arc::new(JobState {
+        paused: AtomicBool::new(false),
+        resumed: AtomicWaker::new(),
+        runs: AtomicU64::new(0),
+    });
+
+    let task = handle.spawn({
+        let handle = handle.clone();
+        let state = state.clone();
+        async move {
+            while let Some(deadline) = schedule.next_run(start, wall, time::now()) {
+                time::sleep_until(deadline).await;
+                if state.paused.load(Ordering::Acquire) {
+                    state.wait_for_resume().await;
+                    continue;
+                }
+                state.runs.fetch_add(1, Ordering::Relaxed);
+                drop(handle.spawn(job()));
+            }
+        }
+    });
+    Job { state, task }
+}
+
+struct JobState {
+    paused: AtomicBool,
+    resumed: AtomicWaker,
+    runs: AtomicU64,
+}
+
+impl JobState {
+    async fn wait_for_resume(&self) {
+        std::future::poll_fn(|cx| {
+            self.resumed.register(cx.waker());
+            if self.paused.load(Ordering::Acquire) {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await
+    }
+}
+
+/// A handle to a job started with [`spawn`], to pause, resume or cancel it.
+///
+/// Dropping the handle leaves the job running.
+pub struct Job {
+    state: Arc<JobState>,
+    task: JoinHandle<()>,
+}
+
+impl Job {
+    /// Pause the job, so that it skips its runs until it is [resumed](Job::resume).
+    ///
+    /// Runs that have already started are not affected.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Release);
+    }
+
+    /// Resume a paused job, which runs again at the next time its schedule fires.
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::Release);
+        self.state.resumed.wake();
+    }
+
+    /// Whether the job is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Acquire)
+    }
+
+    /// Cancel the job, so that it doesn't run again.
+    ///
+    /// Runs that have already started are not affected.
+    pub fn cancel(&self) {
+        self.task.abort();
+    }
+
+    /// Whether the job was cancelled, or its schedule won't fire anymore.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// How many times the job has run.
+    pub fn runs(&self) -> u64 {
+        self.state.runs.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("paused", &self.is_paused())
+            .field("runs", &self.runs())
+            .finish_non_exhaustive()
+    }
+}
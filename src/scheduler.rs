@@ -0,0 +1,301 @@
+//! The policies that decide which of the tasks that are ready gets polled next.
+//!
+//! Every queue of ready tasks, the global one and the local one of every worker, is a
+//! [`Scheduler`]. The executor pushes the tasks that are spawned or woken into one of them, and
+//! pops the next task to poll from it. Which scheduler the executor uses is configured with
+//! [`Builder::scheduler`](crate::runtime::Builder::scheduler), and defaults to [`Prioritized`].
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::runtime::Task;
+use crate::task::{self, Priority};
+
+/// A queue of tasks that are ready to be polled, ordered by a scheduling policy.
+///
+/// A multi-threaded executor creates one scheduler per worker and one for the global queue.
+/// Before polling a task, a worker compares the next task of its own queue with the next task
+/// of the global queue with [`runs_before`](Scheduler::runs_before), and polls the one that
+/// comes first. An idle worker steals half of the tasks of another worker by popping them.
+///
+/// # Examples
+///
+/// A scheduler that polls the tasks with the smallest ID, which are the ones that were spawned
+/// first, before the others:
+///
+/// ```
+/// use mini_executor::scheduler::{ScheduledTask, Scheduler};
+/// use mini_executor::runtime::Runtime;
+/// use std::collections::BTreeMap;
+///
+/// #[derive(Default)]
+/// struct OldestFirst(BTreeMap<mini_executor::task::Id, ScheduledTask>);
+///
+/// impl Scheduler for OldestFirst {
+///     fn push(&mut self, task: ScheduledTask) {
+///         self.0.insert(task.id(), task);
+///     }
+///
+///     fn pop(&mut self) -> Option<ScheduledTask> {
+///         self.0.pop_first().map(|(_, task)| task)
+///     }
+///
+///     fn peek(&self) -> Option<&ScheduledTask> {
+///         self.0.values().next()
+///     }
+///
+///     fn len(&self) -> usize {
+///         self.0.len()
+///     }
+///
+///     fn runs_before(&self, a: &ScheduledTask, b: &ScheduledTask) -> bool {
+///         a.id() < b.id()
+///     }
+/// }
+///
+/// let executor = Runtime::builder().scheduler(OldestFirst::default).build();
+/// let answer = executor.spawn(async { 42 });
+/// assert_eq!(executor.block_on(answer).unwrap(), 42);
+/// ```
+pub trait Scheduler: Send + 'static {
+    /// Add a task that is ready to be polled.
+    fn push(&mut self, task: ScheduledTask);
+
+    /// Take the task that should be polled next.
+    fn pop(&mut self) -> Option<ScheduledTask>;
+
+    /// The task [`pop`](Scheduler::pop) would return, without taking it.
+    fn peek(&self) -> Option<&ScheduledTask>;
+
+    /// The number of tasks that are waiting to be polled.
+    fn len(&self) -> usize;
+
+    /// Whether no task is waiting to be polled.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `a` should be polled before `b`, when the two are in different queues.
+    ///
+    /// Defaults to `false`, which keeps a worker on its own queue for as long as it has tasks.
+    fn runs_before(&self, a: &ScheduledTask, b: &ScheduledTask) -> bool {
+        let _ = (a, b);
+        false
+    }
+}
+
+/// A task that is ready to be polled, as seen by a [`Scheduler`].
+#[derive(Clone)]
+pub struct ScheduledTask {
+    pub(crate) task: Arc<Task>,
+}
+
+impl ScheduledTask {
+    /// The ID of the task.
+    pub fn id(&self) -> task::Id {
+        self.task.id
+    }
+
+    /// The name the task was spawned with, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.task.name.as_deref()
+    }
+
+    /// The priority the task was spawned with.
+    pub fn priority(&self) -> Priority {
+        self.task.priority
+    }
+
+    /// The deadline the task was spawned with, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.task.deadline
+    }
+}
+
+impl fmt::Debug for ScheduledTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScheduledTask")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .field("priority", &self.priority())
+            .field("deadline", &self.deadline())
+            .finish()
+    }
+}
+
+/// Poll the tasks in the order they became ready, ignoring their priority.
+#[derive(Debug, Default)]
+pub struct Fifo(VecDeque<ScheduledTask>);
+
+impl Scheduler for Fifo {
+    fn push(&mut self, task: ScheduledTask) {
+        self.0.push_back(task);
+    }
+
+    fn pop(&mut self) -> Option<ScheduledTask> {
+        self.0.pop_front()
+    }
+
+    fn peek(&self) -> Option<&ScheduledTask> {
+        self.0.front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Poll the task that became ready last first, ignoring their priority.
+///
+/// This keeps the data of freshly woken tasks hot in the cache, at the cost of fairness: tasks
+/// that keep waking each other starve the ones that have been waiting longer.
+#[derive(Debug, Default)]
+pub struct Lifo(Vec<ScheduledTask>);
+
+impl Scheduler for Lifo {
+    fn push(&mut self, task: ScheduledTask) {
+        self.0.push(task);
+    }
+
+    fn pop(&mut self) -> Option<ScheduledTask> {
+        self.0.pop()
+    }
+
+    fn peek(&self) -> Option<&ScheduledTask> {
+        self.0.last()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Poll the tasks with the highest [`Priority`] first, and the ones with the same priority in
+/// the order they became ready.
+///
+/// This is the default scheduler. Every priority has a first-in, first-out bucket of its own.
+#[derive(Debug, Default)]
+pub struct Prioritized {
+    /// The buckets, from the highest priority to the lowest.
+    buckets: [VecDeque<ScheduledTask>; 3],
+}
+
+impl Prioritized {
+    /// The index of the bucket the tasks with the given priority go into.
+    fn bucket(priority: Priority) -> usize {
+        match priority {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+impl Scheduler for Prioritized {
+    fn push(&mut self, task: ScheduledTask) {
+        self.buckets[Self::bucket(task.priority())].push_back(task);
+    }
+
+    fn pop(&mut self) -> Option<ScheduledTask> {
+        self.buckets.iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    fn peek(&self) -> Option<&ScheduledTask> {
+        self.buckets.iter().find_map(VecDeque::front)
+    }
+
+    fn len(&self) -> usize {
+        self.buckets.iter().map(VecDeque::len).sum()
+    }
+
+    fn runs_before(&self, a: &ScheduledTask, b: &ScheduledTask) -> bool {
+        a.priority() > b.priority()
+    }
+}
+
+/// Poll the task with the earliest deadline first, set with
+/// [`spawn_with_deadline`](crate::runtime::Runtime::spawn_with_deadline), ignoring their priority.
+///
+/// The tasks are kept in a min-heap on their deadline. Tasks without a deadline come after all
+/// the others, and tasks with the same deadline, or none, are polled in the order they became
+/// ready.
+#[derive(Debug, Default)]
+pub struct EarliestDeadlineFirst {
+    heap: BinaryHeap<Reverse<ByDeadline>>,
+    /// How many tasks have been pushed so far, to break ties between equal deadlines.
+    pushed: u64,
+}
+
+impl EarliestDeadlineFirst {
+    /// Whether a task with deadline `a` is due before one with deadline `b`.
+    fn earlier(a: Option<Instant>, b: Option<Instant>) -> bool {
+        // No deadline sorts after every deadline.
+        (a.is_none(), a) < (b.is_none(), b)
+    }
+}
+
+impl Scheduler for EarliestDeadlineFirst {
+    fn push(&mut self, task: ScheduledTask) {
+        self.heap.push(Reverse(ByDeadline {
+            deadline: task.deadline(),
+            order: self.pushed,
+            task,
+        }));
+        self.pushed += 1;
+    }
+
+    fn pop(&mut self) -> Option<ScheduledTask> {
+        self.heap.pop().map(|Reverse(entry)| entry.task)
+    }
+
+    fn peek(&self) -> Option<&ScheduledTask> {
+        self.heap.peek().map(|Reverse(entry)| &entry.task)
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn runs_before(&self, a: &ScheduledTask, b: &ScheduledTask) -> bool {
+        Self::earlier(a.deadline(), b.deadline())
+    }
+}
+
+/// A task in the heap of [`EarliestDeadlineFirst`], ordered by its deadline and then by when it
+/// was pushed.
+#[derive(Debug)]
+struct ByDeadline {
+    deadline: Option<Instant>,
+    order: u64,
+    task: ScheduledTask,
+}
+
+impl ByDeadline {
+    fn key(&self) -> (bool, Option<Instant>, u64) {
+        // No deadline sorts after every deadline.
+        (self.deadline.is_none(), self.deadline, self.order)
+    }
+}
+
+impl PartialEq for ByDeadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ByDeadline {}
+
+impl PartialOrd for ByDeadline {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByDeadline {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
@@ -0,0 +1,55 @@
+//! Waiting for the signals the process receives inside of async code, like the one of Ctrl-C.
+//!
+//! [`ctrl_c`] waits until the user presses Ctrl-C in the terminal, to shut a server down
+//! gracefully instead of being killed. On Unix, the `unix` module waits for any signal, like the
+//! `SIGTERM` a service manager sends to stop a process, as a stream. On Windows, the `windows`
+//! module does the same for the control events of the console, like the one sent when it is
+//! closed.
+//!
+//! A signal handler can't do much more than write to a file descriptor, so on Unix, the handler
+//! of this module writes a byte to a pipe of every stream that waits for the signal, and the
+//! pipes are registered with the I/O driver, which wakes the tasks that wait for them. Windows
+//! calls the handler of the console on a thread of its own, which wakes the tasks right away. No
+//! thread is spawned to wait for the signals either way.
+//!
+//! On Unix, all functions of this module panic if called from outside of a
+//! [`Runtime`](crate::runtime::Runtime), or from within one whose I/O driver isn't enabled.
+
+use std::io;
+
+#[cfg(unix)]
+pub mod unix;
+#[cfg(windows)]
+pub mod windows;
+
+/// Wait until the process receives the signal of Ctrl-C, `SIGINT` on Unix, and `CTRL_C_EVENT` on
+/// Windows.
+///
+/// The first call installs a handler for the signal, which stays installed for as long as the
+/// process runs: from then on, Ctrl-C no longer terminates the process, but only wakes the tasks
+/// that wait for it. On Windows, it terminates the process again while no task waits for it.
+///
+/// # Errors
+///
+/// Returns an error if the handler can't be installed, or on Unix, if the pipe it writes to can't
+/// be created.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::signal;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     signal::ctrl_c().await.unwrap();
+///     println!("shutting down");
+/// });
+/// ```
+pub async fn ctrl_c() -> io::Result<()> {
+    #[cfg(unix)]
+    unix::signal(unix::SignalKind::interrupt())?.recv().await?;
+    #[cfg(windows)]
+    windows::ctrl_c()?.recv().await;
+    Ok(())
+}
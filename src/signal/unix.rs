@@ -0,0 +1,353 @@
+//! Waiting for any signal on Unix, as a [`Stream`].
+
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::IntoRawFd;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+use crate::io::{pipe_fds, Interest, Registration};
+
+/// The highest signal number that can be waited for, which is the highest one of Linux.
+const MAX_SIGNAL: usize = 64;
+
+/// The number of times each signal has been received, by its number, since its handler was
+/// installed.
+static RECEIVED: [AtomicU64; MAX_SIGNAL + 1] = [const { AtomicU64::new(0) }; MAX_SIGNAL + 1];
+
+/// Whether the handler of each signal has been installed, by its number.
+static INSTALLED: Mutex<[bool; MAX_SIGNAL + 1]> = Mutex::new([false; MAX_SIGNAL + 1]);
+
+/// The first of the pipes the handler writes to, which link to each other.
+static LISTENERS: AtomicPtr<Listener> = AtomicPtr::new(ptr::null_mut());
+
+/// A kind of signal, by its number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SignalKind(libc::c_int);
+
+impl SignalKind {
+    /// The signal with the number `signum`, like `libc::SIGWINCH`.
+    pub const fn from_raw(signum: libc::c_int) -> SignalKind {
+        SignalKind(signum)
+    }
+
+    /// The number of the signal.
+    pub const fn as_raw_value(&self) -> libc::c_int {
+        self.0
+    }
+
+    /// `SIGINT`, sent by the terminal when the user presses Ctrl-C.
+    pub const fn interrupt() -> SignalKind {
+        SignalKind(libc::SIGINT)
+    }
+
+    /// `SIGTERM`, sent to ask the process to terminate, like by `kill` or a service manager.
+    pub const fn terminate() -> SignalKind {
+        SignalKind(libc::SIGTERM)
+    }
+
+    /// `SIGHUP`, sent when the terminal is closed, and by convention to make a daemon reload its
+    /// configuration.
+    pub const fn hangup() -> SignalKind {
+        SignalKind(libc::SIGHUP)
+    }
+
+    /// `SIGQUIT`, sent by the terminal when the user presses Ctrl-\\.
+    pub const fn quit() -> SignalKind {
+        SignalKind(libc::SIGQUIT)
+    }
+
+    /// `SIGALRM`, sent when a timer set with `alarm` expires.
+    pub const fn alarm() -> SignalKind {
+        SignalKind(libc::SIGALRM)
+    }
+
+    /// `SIGCHLD`, sent when a child process exits or stops.
+    pub const fn child() -> SignalKind {
+        SignalKind(libc::SIGCHLD)
+    }
+
+    /// `SIGPIPE`, sent when writing to a pipe or a socket whose other end is closed.
+    pub const fn pipe() -> SignalKind {
+        SignalKind(libc::SIGPIPE)
+    }
+
+    /// `SIGUSR1`, which has no meaning of its own.
+    pub const fn user_defined1() -> SignalKind {
+        SignalKind(libc::SIGUSR1)
+    }
+
+    /// `SIGUSR2`, which has no meaning of its own.
+    pub const fn user_defined2() -> SignalKind {
+        SignalKind(libc::SIGUSR2)
+    }
+
+    /// `SIGWINCH`, sent when the size of the terminal changes.
+    pub const fn window_change() -> SignalKind {
+        SignalKind(libc::SIGWINCH)
+    }
+}
+
+/// Wait for the signals of `kind` the process receives, as a [`Stream`].
+///
+/// The first call for a kind of signal installs a handler for it, which stays installed for as
+/// long as the process runs, and replaces what the signal did before: a `SIGTERM` no longer
+/// terminates the process, for example, but only wakes the streams that wait for it. Every
+/// stream of the kind returns every signal that was received after it was created, but the
+/// signals that are received before a stream is polled again are returned once.
+///
+/// # Errors
+///
+/// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput) if the signal
+/// can't be handled, like `SIGKILL` or `SIGSEGV`, or an error if the pipe the handler writes to
+/// can't be created.
+///
+/// # Panics
+///
+/// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+/// whose I/O driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::signal::unix::{signal, SignalKind};
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let mut reload = signal(SignalKind::user_defined1()).unwrap();
+///     // Safety: sending a signal to the process itself doesn't touch any memory.
+///     unsafe { libc::kill(libc::getpid(), libc::SIGUSR1) };
+///     reload.recv().await.unwrap();
+/// });
+/// ```
+#[track_caller]
+pub fn signal(kind: SignalKind) -> io::Result<Signal> {
+    let signum = kind.0;
+    let forbidden = [
+        libc::SIGKILL,
+        libc::SIGSTOP,
+        libc::SIGSEGV,
+        libc::SIGBUS,
+        libc::SIGILL,
+        libc::SIGFPE,
+    ];
+    if signum <= 0 || signum as usize > MAX_SIGNAL || forbidden.contains(&signum) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the signal can't be handled",
+        ));
+    }
+
+    install(signum)?;
+    let listener = Claim(Listener::claim()?);
+    let registration = Registration::new(listener.0.read, Interest::READABLE)?;
+    // Counted before the listener is published, so no signal after it is missed.
+    let seen = RECEIVED[signum as usize].load(Ordering::SeqCst);
+    listener.0.signum.store(signum, Ordering::SeqCst);
+    Ok(Signal {
+        registration,
+        listener,
+        kind,
+        seen,
+    })
+}
+
+/// The signals of a kind the process receives, returned by [`signal`].
+#[must_use = "streams do nothing unless polled"]
+pub struct Signal {
+    // Dropped before the listener is released, so its pipe is deregistered first.
+    registration: Registration,
+    listener: Claim,
+    kind: SignalKind,
+    /// The number of the signals of the kind that have been returned, or were received before.
+    seen: u64,
+}
+
+impl Signal {
+    /// Wait for the next signal, see [`signal`].
+    pub async fn recv(&mut self) -> io::Result<()> {
+        let received = poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await;
+        received.expect("a signal stream never ends")
+    }
+
+    /// The kind of signal the stream waits for.
+    pub fn kind(&self) -> SignalKind {
+        self.kind
+    }
+}
+
+impl Stream for Signal {
+    type Item = io::Result<()>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<()>>> {
+        let this = self.get_mut();
+        loop {
+            let received = RECEIVED[this.kind.0 as usize].load(Ordering::SeqCst);
+            if received != this.seen {
+                this.seen = received;
+                return Poll::Ready(Some(Ok(())));
+            }
+            let read = this.listener.0.read;
+            match this
+                .registration
+                .poll_io(cx, Interest::READABLE, || drain(read))
+            {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(error)) => return Poll::Ready(Some(Err(error))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signal")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A pipe the handler writes to when the signal it listens for is received.
+///
+/// The handler can't take a lock, or free memory, so the listeners are never freed: one that is
+/// released is claimed again by the next stream, and keeps its pipe open in between.
+struct Listener {
+    /// The signal the pipe is written to for, or `0` while no stream has claimed it.
+    signum: AtomicI32,
+    claimed: AtomicBool,
+    read: libc::c_int,
+    write: libc::c_int,
+    /// The listener after this one, which never changes once it is linked.
+    next: *mut Listener,
+}
+
+// Safety: `next` is only ever read, and points to another listener that is never freed.
+unsafe impl Sync for Listener {}
+
+impl Listener {
+    /// Claim a listener that was released, or create a new one.
+    fn claim() -> io::Result<&'static Listener> {
+        let mut node = LISTENERS.load(Ordering::Acquire);
+        while !node.is_null() {
+            // Safety: the listeners are never freed.
+            let listener = unsafe { &*node };
+            if listener
+                .claimed
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                // The bytes the handler wrote for the stream that had it before.
+                let _ = drain(listener.read);
+                return Ok(listener);
+            }
+            node = listener.next;
+        }
+
+        let (read, write) = pipe_fds()?;
+        let listener = Box::leak(Box::new(Listener {
+            signum: AtomicI32::new(0),
+            claimed: AtomicBool::new(true),
+            read: read.into_raw_fd(),
+            write: write.into_raw_fd(),
+            next: LISTENERS.load(Ordering::Acquire),
+        }));
+        while let Err(head) =
+            LISTENERS.compare_exchange(listener.next, listener, Ordering::AcqRel, Ordering::Acquire)
+        {
+            listener.next = head;
+        }
+        Ok(listener)
+    }
+}
+
+/// A listener that has been claimed, which is released when this is dropped.
+struct Claim(&'static Listener);
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        // Stop writing to the pipe, and let the next stream claim it.
+        self.0.signum.store(0, Ordering::SeqCst);
+        self.0.claimed.store(false, Ordering::Release);
+    }
+}
+
+/// Install the handler of `signum`, unless it has been already.
+fn install(signum: libc::c_int) -> io::Result<()> {
+    let mut installed = INSTALLED.lock().unwrap_or_else(|error| error.into_inner());
+    if installed[signum as usize] {
+        return Ok(());
+    }
+    // Safety: the handler only touches atomics and writes to pipes, which is async-signal-safe.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signum, &action, ptr::null_mut()) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    installed[signum as usize] = true;
+    Ok(())
+}
+
+/// The handler of every signal: count it, and write a byte to the pipe of every stream that
+/// waits for it.
+extern "C" fn handler(signum: libc::c_int) {
+    // Safety: the location of `errno` is valid on the thread the signal interrupted.
+    let errno = unsafe { *errno_location() };
+    RECEIVED[signum as usize].fetch_add(1, Ordering::SeqCst);
+    let mut node = LISTENERS.load(Ordering::Acquire);
+    while !node.is_null() {
+        // Safety: the listeners are never freed.
+        let listener = unsafe { &*node };
+        if listener.signum.load(Ordering::SeqCst) == signum {
+            // A pipe that is full has a byte to wake its stream already.
+            unsafe { libc::write(listener.write, [1u8].as_ptr().cast(), 1) };
+        }
+        node = listener.next;
+    }
+    // The interrupted code may be about to read the `errno` of a call the `write` replaced.
+    unsafe { *errno_location() = errno };
+}
+
+/// The location of `errno` of the current thread.
+unsafe fn errno_location() -> *mut libc::c_int {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    return libc::__errno_location();
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    ))]
+    return libc::__error();
+    #[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+    return libc::__errno();
+}
+
+/// Read everything from the pipe `fd`, and fail with `WouldBlock` if it was empty.
+fn drain(fd: libc::c_int) -> io::Result<()> {
+    let mut buf = [0u8; 64];
+    let mut drained = false;
+    loop {
+        // Safety: the buffer has room for `buf.len()` bytes.
+        let read = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if read < 0 {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::WouldBlock if drained => Ok(()),
+                _ => Err(error),
+            };
+        }
+        drained = true;
+    }
+}
@@ -0,0 +1,199 @@
+//! Waiting for the control events of the console on Windows, as a [`Stream`].
+
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures::Stream;
+
+use crate::sync::AtomicWaker;
+
+const CTRL_C_EVENT: u32 = 0;
+const CTRL_BREAK_EVENT: u32 = 1;
+const CTRL_CLOSE_EVENT: u32 = 2;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetConsoleCtrlHandler(handler: Option<extern "system" fn(u32) -> i32>, add: i32) -> i32;
+}
+
+/// The number of times each event has been received, by its number, since the handler was
+/// installed.
+static RECEIVED: [AtomicU64; 3] = [const { AtomicU64::new(0) }; 3];
+
+/// Whether the handler has been installed.
+static INSTALLED: Mutex<bool> = Mutex::new(false);
+
+/// The wakers of the streams, with the events they wait for.
+static LISTENERS: Mutex<Vec<Arc<Listener>>> = Mutex::new(Vec::new());
+
+/// Wait for the `CTRL_C_EVENT`s the console sends, when the user presses Ctrl-C, as a [`Stream`].
+///
+/// The first stream of any event installs a handler, which stays installed for as long as the
+/// process runs. While a stream of an event exists, the event no longer terminates the process,
+/// but only wakes the streams that wait for it. Every stream returns every event that was
+/// received after it was created, but the events that are received before a stream is polled
+/// again are returned once.
+///
+/// # Errors
+///
+/// Returns an error if the handler can't be installed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::signal::windows;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let mut ctrl_c = windows::ctrl_c().unwrap();
+///     ctrl_c.recv().await;
+///     println!("shutting down");
+/// });
+/// ```
+pub fn ctrl_c() -> io::Result<CtrlEvent> {
+    CtrlEvent::new(CTRL_C_EVENT)
+}
+
+/// Wait for the `CTRL_BREAK_EVENT`s the console sends, when the user presses Ctrl-Break, or when
+/// the process is told to stop by `GenerateConsoleCtrlEvent`, see [`ctrl_c`].
+///
+/// # Errors
+///
+/// Returns an error if the handler can't be installed.
+pub fn ctrl_break() -> io::Result<CtrlEvent> {
+    CtrlEvent::new(CTRL_BREAK_EVENT)
+}
+
+/// Wait for the `CTRL_CLOSE_EVENT`s the console sends, when the user closes it, see [`ctrl_c`].
+///
+/// Windows ends the process once the handler returns, or after a few seconds at the latest, so
+/// while a stream of the event exists, the handler wakes the streams and never returns, to give
+/// the tasks that wait for it that time to shut down.
+///
+/// # Errors
+///
+/// Returns an error if the handler can't be installed.
+pub fn ctrl_close() -> io::Result<CtrlEvent> {
+    CtrlEvent::new(CTRL_CLOSE_EVENT)
+}
+
+/// The control events of a kind the console sends, returned by [`ctrl_c`], [`ctrl_break`] and
+/// [`ctrl_close`].
+#[must_use = "streams do nothing unless polled"]
+pub struct CtrlEvent {
+    listener: Arc<Listener>,
+    /// The number of the events of the kind that have been returned, or were received before.
+    seen: u64,
+}
+
+/// The waker of a stream, which the handler wakes for the event it waits for.
+struct Listener {
+    event: u32,
+    waker: AtomicWaker,
+}
+
+impl CtrlEvent {
+    fn new(event: u32) -> io::Result<CtrlEvent> {
+        install()?;
+        let listener = Arc::new(Listener {
+            event,
+            waker: AtomicWaker::new(),
+        });
+        let seen = RECEIVED[event as usize].load(Ordering::SeqCst);
+        lock(&LISTENERS).push(listener.clone());
+        Ok(CtrlEvent { listener, seen })
+    }
+
+    /// Wait for the next event, see [`ctrl_c`].
+    pub async fn recv(&mut self) {
+        let received = poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await;
+        received.expect("a stream of control events never ends")
+    }
+}
+
+impl Stream for CtrlEvent {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        let this = self.get_mut();
+        // Registered before the count is read, so an event in between wakes the new waker.
+        this.listener.waker.register(cx.waker());
+        let received = RECEIVED[this.listener.event as usize].load(Ordering::SeqCst);
+        if received == this.seen {
+            return Poll::Pending;
+        }
+        this.seen = received;
+        Poll::Ready(Some(()))
+    }
+}
+
+impl Drop for CtrlEvent {
+    fn drop(&mut self) {
+        lock(&LISTENERS).retain(|listener| !Arc::ptr_eq(listener, &self.listener));
+    }
+}
+
+impl fmt::Debug for CtrlEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event = match self.listener.event {
+            CTRL_C_EVENT => "CTRL_C_EVENT",
+            CTRL_BREAK_EVENT => "CTRL_BREAK_EVENT",
+            _ => "CTRL_CLOSE_EVENT",
+        };
+        f.debug_struct("CtrlEvent")
+            .field("event", &event)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Install the handler, unless it has been already.
+fn install() -> io::Result<()> {
+    let mut installed = lock(&INSTALLED);
+    if !*installed {
+        // Safety: the handler is a function that lives for as long as the process.
+        if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        *installed = true;
+    }
+    Ok(())
+}
+
+/// The handler of the control events, which Windows calls on a thread of its own: count the
+/// event, and wake the streams that wait for it.
+///
+/// Returns `0` for an event no stream waits for, so that Windows goes on to the next handler,
+/// whose default ends the process.
+extern "system" fn handler(event: u32) -> i32 {
+    let Some(received) = RECEIVED.get(event as usize) else {
+        return 0;
+    };
+    received.fetch_add(1, Ordering::SeqCst);
+    let mut handled = false;
+    for listener in lock(&LISTENERS).iter() {
+        if listener.event == event {
+            listener.waker.wake();
+            handled = true;
+        }
+    }
+    if handled && event == CTRL_CLOSE_EVENT {
+        // Windows ends the process once this returns, so it doesn't, until Windows ends it
+        // anyway, or the process exits on its own.
+        loop {
+            thread::park();
+        }
+    }
+    handled as i32
+}
+
+/// Lock `mutex`, which a panic while it was locked leaves consistent.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
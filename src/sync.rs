@@ -0,0 +1,483 @@
+//! Synchronization primitives for writing futures by hand: [`AtomicWaker`] for a future that
+//! waits on its own, and [`WakerSet`] for many tasks waiting for the same thing.
+
+use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{MutexGuard, PoisonError, TryLockError, WaitTimeoutResult};
+use std::task::Waker;
+use std::time::Duration;
+
+/// A slot for at most one [`Waker`], that can be registered and woken from different threads
+/// without a lock.
+///
+/// This is the building block of most futures that wait for an event: the future registers the
+/// waker of its task every time it is polled and finds that the event hasn't happened yet, and the
+/// code that makes the event happen wakes it. Registering a waker replaces the previous one, so
+/// only the task that polled last is woken.
+///
+/// A future has to check for the event again after registering its waker, since the event may
+/// have happened in between, and a wake that comes before the registration is lost.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::sync::AtomicWaker;
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+/// use std::task::{Context, Poll};
+/// use std::thread;
+///
+/// #[derive(Default)]
+/// struct Flag {
+///     set: AtomicBool,
+///     waker: AtomicWaker,
+/// }
+///
+/// impl Flag {
+///     fn set(&self) {
+///         self.set.store(true, Ordering::SeqCst);
+///         self.waker.wake();
+///     }
+/// }
+///
+/// struct FlagSet(Arc<Flag>);
+///
+/// impl Future for FlagSet {
+///     type Output = ();
+///
+///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         // Quick check to avoid registering if the flag is already set.
+///         if self.0.set.load(Ordering::SeqCst) {
+///             return Poll::Ready(());
+///         }
+///
+///         self.0.waker.register(cx.waker());
+///
+///         // Check again, in case the flag was set before the waker was registered.
+///         if self.0.set.load(Ordering::SeqCst) {
+///             Poll::Ready(())
+///         } else {
+///             Poll::Pending
+///         }
+///     }
+/// }
+///
+/// let flag = Arc::new(Flag::default());
+/// let setter = flag.clone();
+/// thread::spawn(move || setter.set());
+///
+/// Runtime::new().block_on(FlagSet(flag));
+/// ```
+pub struct AtomicWaker {
+    /// Whether a thread is registering a waker or taking it out, as a combination of the
+    /// `REGISTERING` and `WAKING` bits.
+    state: AtomicU8,
+    /// The registered waker. Only the thread that moved `state` out of `WAITING` may access it.
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+/// No thread is accessing the waker of an [`AtomicWaker`].
+const WAITING: u8 = 0;
+/// A thread is registering a waker.
+const REGISTERING: u8 = 0b01;
+/// A thread is taking the waker out, to wake it.
+const WAKING: u8 = 0b10;
+
+// Safety: the waker is only accessed by the one thread that holds the `REGISTERING` or the `WAKING`
+// bit, which makes an `AtomicWaker` a lock around its waker that is never blocked on.
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    /// Create an `AtomicWaker` without a waker.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Register `waker` to be woken by the next call to [`wake`](AtomicWaker::wake), replacing the
+    /// previous one.
+    ///
+    /// If the waker is woken concurrently, `waker` is woken right away instead. Registering the same
+    /// waker again doesn't clone it.
+    ///
+    /// Only one thread should register wakers at a time, which is the case when only the future
+    /// that owns the `AtomicWaker` does. A concurrent registration is ignored.
+    pub fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|state| state)
+        {
+            WAITING => {
+                // Safety: this thread holds the `REGISTERING` bit, so no other thread accesses
+                // the waker until it is cleared again.
+                unsafe {
+                    let slot = &mut *self.waker.get();
+                    match slot {
+                        Some(registered) if registered.will_wake(waker) => {}
+                        _ => *slot = Some(waker.clone()),
+                    }
+                }
+
+                if let Err(state) = self.state.compare_exchange(
+                    REGISTERING,
+                    WAITING,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    // `wake` was called while registering, and left the waker to this thread.
+                    debug_assert_eq!(state, REGISTERING | WAKING);
+                    // Safety: both bits are still set, so no other thread accesses the waker.
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            WAKING => {
+                // The waker is being woken right now, which may be before the event this waker
+                // waits for has happened. Wake this one too, so its task polls again.
+                waker.wake_by_ref();
+            }
+            state => {
+                // Another thread is registering a waker concurrently.
+                debug_assert!(state == REGISTERING || state == REGISTERING | WAKING);
+            }
+        }
+    }
+
+    /// Wake the registered waker, if any, and remove it.
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Remove the registered waker, if any, and return it.
+    ///
+    /// If a waker is being registered concurrently, this returns `None` and the registering thread
+    /// wakes it instead.
+    pub fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            WAITING => {
+                // Safety: this thread set the `WAKING` bit, so no other thread accesses the waker
+                // until it is cleared again.
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                waker
+            }
+            state => {
+                // Either a waker is being registered and will be woken by the registering thread,
+                // or another thread is taking it out already.
+                debug_assert!(state & (REGISTERING | WAKING) != 0);
+                None
+            }
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for AtomicWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("AtomicWaker { .. }")
+    }
+}
+
+/// A list of the wakers of any number of tasks waiting for the same thing, to build primitives
+/// like an async mutex, a semaphore or a notification on.
+///
+/// A future that has to wait [`insert`](WakerSet::insert)s its waker, and keeps the returned
+/// [`WaitKey`] to [`update`](WakerSet::update) the waker when it is polled again and to
+/// [`remove`](WakerSet::remove) it when it stops waiting, also when it is dropped. Whoever makes
+/// progress possible wakes the waiters with [`notify_one`](WakerSet::notify_one), first come first
+/// served, or all of them at once with [`notify_all`](WakerSet::notify_all).
+///
+/// A waiter that is notified is taken out of the set. If it is dropped without acting on the
+/// notification, it should pass it on to the next waiter, which [`remove`](WakerSet::remove) tells
+/// it to do.
+///
+/// # Examples
+///
+/// A gate that tasks wait on until it is opened:
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::sync::{WaitKey, WakerSet};
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+/// use std::task::{Context, Poll};
+///
+/// #[derive(Default)]
+/// struct Gate {
+///     open: AtomicBool,
+///     waiters: WakerSet,
+/// }
+///
+/// impl Gate {
+///     fn open(&self) {
+///         self.open.store(true, Ordering::SeqCst);
+///         self.waiters.notify_all();
+///     }
+///
+///     fn wait(&self) -> Wait<'_> {
+///         Wait { gate: self, key: None }
+///     }
+/// }
+///
+/// struct Wait<'a> {
+///     gate: &'a Gate,
+///     key: Option<WaitKey>,
+/// }
+///
+/// impl Future for Wait<'_> {
+///     type Output = ();
+///
+///     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         let this = &mut *self;
+///         if !this.gate.open.load(Ordering::SeqCst) {
+///             match &this.key {
+///                 Some(key) if this.gate.waiters.update(key, cx.waker()) => {}
+///                 _ => this.key = Some(this.gate.waiters.insert(cx.waker())),
+///             }
+///             // Check again, in case the gate was opened before the waker was registered.
+///             if !this.gate.open.load(Ordering::SeqCst) {
+///                 return Poll::Pending;
+///             }
+///         }
+///         if let Some(key) = this.key.take() {
+///             this.gate.waiters.remove(key);
+///         }
+///         Poll::Ready(())
+///     }
+/// }
+///
+/// impl Drop for Wait<'_> {
+///     fn drop(&mut self) {
+///         if let Some(key) = self.key.take() {
+///             self.gate.waiters.remove(key);
+///         }
+///     }
+/// }
+///
+/// let executor = Runtime::new();
+/// let gate = Arc::new(Gate::default());
+/// for _ in 0..3 {
+///     let gate = gate.clone();
+///     executor.spawn(async move { gate.wait().await });
+/// }
+///
+/// assert!(executor.run_until_stalled().is_pending());
+/// assert_eq!(gate.waiters.len(), 3);
+///
+/// gate.open();
+/// assert!(executor.run_until_stalled().is_ready());
+/// ```
+pub struct WakerSet {
+    inner: Mutex<Waiters>,
+}
+
+/// The part of a [`WakerSet`] that is protected by its lock.
+struct Waiters {
+    /// The wakers of the waiting tasks, by the key they were inserted under, so the ones that
+    /// have waited longest come first.
+    wakers: BTreeMap<u64, Waker>,
+    /// The key of the next waker that is inserted.
+    next_key: u64,
+}
+
+/// The key of a waker in a [`WakerSet`], returned by [`WakerSet::insert`].
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct WaitKey(u64);
+
+impl WakerSet {
+    /// Create an empty `WakerSet`.
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(Waiters {
+                wakers: BTreeMap::new(),
+                next_key: 0,
+            }),
+        }
+    }
+
+    /// Add a waiter with the given waker, behind the ones that are already waiting.
+    pub fn insert(&self, waker: &Waker) -> WaitKey {
+        let mut inner = self.inner.lock();
+        let key = inner.next_key;
+        inner.next_key += 1;
+        inner.wakers.insert(key, waker.clone());
+        WaitKey(key)
+    }
+
+    /// Replace the waker of a waiter, keeping its place in line.
+    ///
+    /// Returns `false` without doing anything if the waiter has been notified since, in which case
+    /// it has to be inserted again to keep waiting.
+    pub fn update(&self, key: &WaitKey, waker: &Waker) -> bool {
+        let mut inner = self.inner.lock();
+        match inner.wakers.get_mut(&key.0) {
+            Some(registered) => {
+                if !registered.will_wake(waker) {
+                    registered.clone_from(waker);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a waiter that stops waiting.
+    ///
+    /// Returns `true` if it was still waiting, and `false` if it has been notified. A waiter that
+    /// was notified by [`notify_one`](WakerSet::notify_one) but doesn't act on it should call
+    /// `notify_one` again, so the notification isn't lost.
+    pub fn remove(&self, key: WaitKey) -> bool {
+        self.inner.lock().wakers.remove(&key.0).is_some()
+    }
+
+    /// Wake the waiter that has waited longest, and take it out of the set.
+    ///
+    /// Returns whether there was a waiter to wake.
+    pub fn notify_one(&self) -> bool {
+        let waker = self.inner.lock().wakers.pop_first();
+        match waker {
+            Some((_, waker)) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wake all waiters, and take them out of the set.
+    ///
+    /// Returns the number of waiters that were woken.
+    pub fn notify_all(&self) -> usize {
+        // Woken outside of the lock, since a waker may insert into the set again right away.
+        let wakers = std::mem::take(&mut self.inner.lock().wakers);
+        let woken = wakers.len();
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+        woken
+    }
+
+    /// The number of waiters in the set.
+    pub fn len(&self) -> usize {
+        self.inner.lock().wakers.len()
+    }
+
+    /// Whether no waiter is in the set.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for WakerSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for WakerSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WakerSet")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A [`std::sync::Mutex`] that can be locked again after a thread panicked while holding it.
+///
+/// The executor calls code it doesn't control, like futures, wakers, schedulers and task hooks,
+/// and some of it runs while one of its locks is held. If that code panics, a `std` lock would be
+/// poisoned, and every later `lock().unwrap()` would panic with a `PoisonError` that says nothing
+/// about the original panic. The executor updates its state so that it stays consistent at every
+/// point such code can panic, so its locks ignore the poisoning instead.
+pub(crate) struct Mutex<T: ?Sized>(std::sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self(std::sync::Mutex::new(value))
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Lock the mutex, blocking the current thread until it can.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the mutex if no other thread holds it.
+    pub(crate) fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        match self.0.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f.debug_tuple("Mutex").field(&&*guard).finish(),
+            None => f.pad("Mutex { <locked> }"),
+        }
+    }
+}
+
+/// A [`std::sync::Condvar`] to wait on with the guard of a [`Mutex`].
+pub(crate) struct Condvar(std::sync::Condvar);
+
+impl Condvar {
+    pub(crate) const fn new() -> Self {
+        Self(std::sync::Condvar::new())
+    }
+
+    /// Release the lock and block the current thread until notified, then lock it again.
+    pub(crate) fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.0.wait(guard).unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Like [`wait`](Condvar::wait), but give up once `timeout` has passed.
+    pub(crate) fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Duration,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        self.0
+            .wait_timeout(guard, timeout)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn notify_one(&self) {
+        self.0.notify_one();
+    }
+
+    pub(crate) fn notify_all(&self) {
+        self.0.notify_all();
+    }
+}
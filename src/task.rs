@@ -0,0 +1,2083 @@
+//! Spawning tasks, waiting for them, and information about the tasks spawned onto an executor
+//! and storage that is local to them.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+use crate::runtime::{
+    enter_driver, report_panic, suspend_driver, Handle, Runtime, Shared, Signal, UnhandledPanic,
+};
+use crate::sync::{AtomicWaker, Mutex};
+use crate::waker::{register, waker_for};
+
+/// An ID that uniquely identifies a spawned task, among all the tasks of all the executors of
+/// the process.
+///
+/// IDs are never reused, so they can be used to correlate log lines or metrics with a task,
+/// also after it has completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Id(u64);
+
+impl Id {
+    /// Get a fresh, unique task ID.
+    pub(crate) fn next() -> Self {
+        /// Hands out the IDs of spawned tasks.
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Id(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+thread_local! {
+    /// The ID and the name of the task that is being polled on the current thread, if any.
+    static CURRENT: RefCell<Option<(Id, Option<Arc<str>>)>> = const { RefCell::new(None) };
+}
+
+/// The ID of the task that is currently running.
+///
+/// # Panics
+///
+/// Panics if called from outside of a spawned task, for example from the main future. Use
+/// [`try_id()`] to handle that case.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+///
+/// let executor = Runtime::new();
+///
+/// executor.spawn(async {
+///     println!("[task {}] fetching", task::id());
+/// });
+///
+/// executor.run();
+/// ```
+pub fn id() -> Id {
+    try_id().expect("`task::id()` called outside of a spawned task")
+}
+
+/// The ID of the task that is currently running, or `None` if called from outside of a spawned
+/// task.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+///
+/// let executor = Runtime::new();
+/// executor.spawn(async {
+///     assert!(task::try_id().is_some());
+/// });
+///
+/// executor.block_on(async {
+///     assert_eq!(task::try_id(), None);
+/// });
+/// executor.run();
+/// ```
+pub fn try_id() -> Option<Id> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|(id, _)| *id))
+}
+
+/// The name of the task that is currently running, or `None` if it was spawned without one or
+/// if called from outside of a spawned task.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+///
+/// let executor = Runtime::new();
+///
+/// executor.spawn_named("fetcher", async {
+///     assert_eq!(task::name().as_deref(), Some("fetcher"));
+/// });
+/// executor.spawn(async {
+///     assert_eq!(task::name(), None);
+/// });
+///
+/// executor.run();
+/// ```
+pub fn name() -> Option<String> {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        current
+            .as_ref()
+            .and_then(|(_, name)| name.as_deref().map(String::from))
+    })
+}
+
+thread_local! {
+    /// The labels of the `Instrumented` futures being polled on this thread, innermost last.
+    static LABELS: RefCell<Vec<Arc<str>>> = const { RefCell::new(Vec::new()) };
+    /// The label of the innermost `Instrumented` future that returned `Poll::Pending` during
+    /// the poll of the task that is running.
+    static PENDING_IN: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// The label of the innermost future attached with [`FutureExt::named`] that is currently
+/// being polled, or `None` if there is none.
+///
+/// See [`FutureExt::named`] for an example.
+///
+/// [`FutureExt::named`]: FutureExt::named
+pub fn label() -> Option<String> {
+    LABELS.with(|labels| labels.borrow().last().map(|label| String::from(&**label)))
+}
+
+/// Run `poll`, a poll of the `Instrumented` future with the given label.
+pub(crate) fn instrument<R>(label: &Arc<str>, poll: impl FnOnce() -> Poll<R>) -> Poll<R> {
+    /// Pops the label again when dropped, also if the poll panics.
+    struct Pop;
+
+    impl Drop for Pop {
+        fn drop(&mut self) {
+            let _ = LABELS.try_with(|labels| labels.borrow_mut().pop());
+        }
+    }
+
+    LABELS.with(|labels| labels.borrow_mut().push(label.clone()));
+    let _pop = Pop;
+    let result = poll();
+    if result.is_pending() {
+        // An inner labeled future that is pending has already been recorded.
+        PENDING_IN.with(|pending_in| {
+            pending_in.borrow_mut().get_or_insert_with(|| label.clone());
+        });
+    }
+    result
+}
+
+/// Run `f`, a poll of a task, and return the label of the innermost `Instrumented` future that
+/// returned `Poll::Pending` during it, if any.
+pub(crate) fn record_pending_label<R>(f: impl FnOnce() -> R) -> (R, Option<Arc<str>>) {
+    let previous = PENDING_IN.with(|pending_in| pending_in.replace(None));
+    let result = f();
+    let label = PENDING_IN.with(|pending_in| pending_in.replace(previous));
+    (result, label)
+}
+
+/// Run a blocking closure inline, on the current thread, without keeping the executor from
+/// polling other tasks.
+///
+/// When called from a task on a worker thread of a multi-threaded executor, the worker's tasks
+/// are handed over to a fresh thread first, which takes over as the worker for good. The current
+/// thread runs `f` and finishes polling the task, and then exits. This is useful when the
+/// blocking code can't be moved to another thread with
+/// [`spawn_blocking`](crate::runtime::Runtime::spawn_blocking), for example an FFI call into a
+/// library that is bound to the thread.
+///
+/// Anywhere else `f` just runs. On a current-thread executor, nothing else is polled until it
+/// returns.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let executor = Runtime::new_multi_thread(1);
+///
+/// let slow = executor.spawn(async {
+///     task::block_in_place(|| thread::sleep(Duration::from_millis(50)));
+/// });
+/// // Polled by the thread that took over while the other one sleeps.
+/// let fast = executor.spawn(async { 42 });
+/// executor.spawn(async move {
+///     assert_eq!(fast.await.unwrap(), 42);
+///     slow.await.unwrap();
+/// });
+///
+/// executor.run();
+/// ```
+pub fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    if Shared::hand_off_current_worker() {
+        // The thread no longer drives the executor, so `f` may drive another one.
+        let _driver = suspend_driver();
+        f()
+    } else {
+        f()
+    }
+}
+
+/// Run `f` as part of the task with the given ID and name, so [`id()`] and [`name()`] return
+/// them.
+pub(crate) fn enter<R>(id: Id, name: Option<Arc<str>>, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.replace(Some((id, name))));
+    let result = budget(f);
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// The number of times a task can make progress on a resource, like awaiting a [`JoinHandle`]
+/// that is ready or calling [`consume_budget()`], before it is forced to yield.
+const INITIAL_BUDGET: u8 = 128;
+
+thread_local! {
+    /// What's left of the budget of the task that is being polled on this thread, or `None` if
+    /// the task is unconstrained or nothing is being polled.
+    static BUDGET: Cell<Option<u8>> = const { Cell::new(None) };
+}
+
+/// Run `f`, a single poll of a task or of a future passed to `block_on`, with a fresh budget.
+pub(crate) fn budget<R>(f: impl FnOnce() -> R) -> R {
+    with_budget(Some(INITIAL_BUDGET), f)
+}
+
+/// Run `f` with the given budget, and restore the previous one afterwards, also if `f` panics.
+fn with_budget<R>(budget: Option<u8>, f: impl FnOnce() -> R) -> R {
+    /// Puts the previous budget back when dropped.
+    struct Restore(Option<u8>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = BUDGET.try_with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(BUDGET.with(|current| current.replace(budget)));
+    f()
+}
+
+/// Take one unit from the budget of the current task.
+///
+/// Returns `Poll::Pending` once the budget is used up, after waking the task, so it is polled
+/// again after the other tasks that are ready have had their turn.
+pub(crate) fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    BUDGET.with(|current| match current.get() {
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(left) => {
+            current.set(Some(left - 1));
+            Poll::Ready(())
+        }
+        None => Poll::Ready(()),
+    })
+}
+
+/// Consume a unit of the current task's budget, and yield to the executor if it is used up.
+///
+/// Every poll of a task starts with a budget, and resources that are immediately ready, like a
+/// [`JoinHandle`] of a task that has already completed, take from it. Once
+/// it is used up, they return `Poll::Pending` and the task is rescheduled, so a task awaiting a
+/// long chain of ready futures can't keep the others from running. A loop that does a lot of
+/// work without awaiting anything that takes from the budget can call this to opt in.
+///
+/// Outside of a task, and inside of [`unconstrained()`], this never yields.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// let executor = Runtime::new();
+/// let other_ran = Arc::new(AtomicBool::new(false));
+///
+/// let busy = executor.spawn({
+///     let other_ran = other_ran.clone();
+///     async move {
+///         for _ in 0..1000 {
+///             task::consume_budget().await;
+///         }
+///         // The other task got its turn while this one was yielding.
+///         assert!(other_ran.load(Ordering::SeqCst));
+///     }
+/// });
+/// executor.spawn(async move { other_ran.store(true, Ordering::SeqCst) });
+///
+/// executor.block_on(busy).unwrap();
+/// ```
+pub async fn consume_budget() {
+    std::future::poll_fn(poll_proceed).await
+}
+
+/// Yield to the executor once, so the other tasks that are ready get a turn before the current
+/// one is polled again.
+///
+/// The first poll wakes the task and returns `Poll::Pending`, which puts it at the back of the
+/// queue. Unlike [`consume_budget()`], this always yields, also outside of a task.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+/// use std::sync::{Arc, Mutex};
+///
+/// let executor = Runtime::new();
+/// let order = Arc::new(Mutex::new(Vec::new()));
+///
+/// for name in ["a", "b"] {
+///     let order = order.clone();
+///     executor.spawn(async move {
+///         for _ in 0..2 {
+///             order.lock().unwrap().push(name);
+///             task::yield_now().await;
+///         }
+///     });
+/// }
+/// executor.run();
+///
+/// assert_eq!(*order.lock().unwrap(), ["a", "b", "a", "b"]);
+/// ```
+pub async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+/// Run `future` without a budget, so it is never forced to yield.
+///
+/// This is an opt-out for futures that have to run to completion as quickly as possible, at
+/// the risk of keeping other tasks from running in the meantime.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task;
+/// use std::pin::pin;
+///
+/// async fn busy() {
+///     for _ in 0..1000 {
+///         task::consume_budget().await;
+///     }
+/// }
+///
+/// let executor = Runtime::new();
+///
+/// // Yields once the budget is used up...
+/// assert!(executor.poll_once(pin!(busy())).is_pending());
+/// // ...unless it is unconstrained.
+/// assert!(executor.poll_once(pin!(task::unconstrained(busy()))).is_ready());
+/// ```
+pub fn unconstrained<F: Future>(future: F) -> Unconstrained<F> {
+    Unconstrained {
+        future: Box::pin(future),
+    }
+}
+
+/// A future that runs another one without a budget, returned by [`unconstrained()`].
+pub struct Unconstrained<F> {
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Unconstrained<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        with_budget(None, || self.future.as_mut().poll(cx))
+    }
+}
+
+/// A key for task-local storage, declared with the [`task_local!`](crate::task_local) macro.
+///
+/// A task-local value is set for the duration of a future with [`LocalKey::scope`], and can be
+/// accessed with [`LocalKey::with`] from anywhere inside that future, also from nested futures
+/// and functions that know nothing about it. Unlike a thread-local value, it stays with the
+/// future when the future is polled on another thread.
+pub struct LocalKey<T: 'static> {
+    #[doc(hidden)]
+    pub inner: std::thread::LocalKey<RefCell<Option<T>>>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    /// Set the task-local value to `value` while `future` is being polled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::{block_on, task_local};
+    ///
+    /// task_local! {
+    ///     static REQUEST_ID: u32;
+    /// }
+    ///
+    /// async fn handle() -> u32 {
+    ///     REQUEST_ID.with(|id| *id)
+    /// }
+    ///
+    /// let id = block_on(REQUEST_ID.scope(7, handle()));
+    /// assert_eq!(id, 7);
+    /// ```
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+        TaskLocalFuture {
+            key: self,
+            slot: Some(value),
+            future: Box::pin(future),
+        }
+    }
+
+    /// Set the task-local value to `value` while `f` runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::task_local;
+    ///
+    /// task_local! {
+    ///     static USER: String;
+    /// }
+    ///
+    /// let len = USER.sync_scope(String::from("ferris"), || USER.with(|user| user.len()));
+    /// assert_eq!(len, 6);
+    /// ```
+    pub fn sync_scope<R>(&'static self, value: T, f: impl FnOnce() -> R) -> R {
+        let mut slot = Some(value);
+        self.enter(&mut slot, f)
+    }
+
+    /// Run `f` with a reference to the task-local value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value has not been set with [`LocalKey::scope`] or
+    /// [`LocalKey::sync_scope`]. Use [`LocalKey::try_with`] to handle that case.
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        self.try_with(f)
+            .expect("cannot access a task-local value without setting it with `LocalKey::scope`")
+    }
+
+    /// Run `f` with a reference to the task-local value, or return an [`AccessError`] if it has
+    /// not been set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::task_local;
+    ///
+    /// task_local! {
+    ///     static DEPTH: usize;
+    /// }
+    ///
+    /// assert!(DEPTH.try_with(|depth| *depth).is_err());
+    /// DEPTH.sync_scope(1, || assert_eq!(DEPTH.try_with(|depth| *depth), Ok(1)));
+    /// ```
+    pub fn try_with<R>(&'static self, f: impl FnOnce(&T) -> R) -> Result<R, AccessError> {
+        self.inner
+            .try_with(|slot| slot.borrow().as_ref().map(f))
+            .ok()
+            .flatten()
+            .ok_or(AccessError { _private: () })
+    }
+
+    /// Get a copy of the task-local value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value has not been set, like [`LocalKey::with`].
+    pub fn get(&'static self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Move the value in `slot` into the task-local storage while `f` runs, and back afterwards,
+    /// also if `f` panics.
+    fn enter<R>(&'static self, slot: &mut Option<T>, f: impl FnOnce() -> R) -> R {
+        struct Restore<'a, T: 'static> {
+            key: &'static LocalKey<T>,
+            slot: &'a mut Option<T>,
+        }
+
+        impl<T: 'static> Drop for Restore<'_, T> {
+            fn drop(&mut self) {
+                self.key
+                    .inner
+                    .with(|value| std::mem::swap(self.slot, &mut *value.borrow_mut()));
+            }
+        }
+
+        self.inner
+            .with(|value| std::mem::swap(slot, &mut *value.borrow_mut()));
+        let _restore = Restore { key: self, slot };
+        f()
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+/// A future that sets a task-local value while the future inside of it is polled, returned by
+/// [`LocalKey::scope`].
+pub struct TaskLocalFuture<T: 'static, F> {
+    key: &'static LocalKey<T>,
+    slot: Option<T>,
+    future: Pin<Box<F>>,
+}
+
+// The future is boxed and the value is never pinned, so nothing needs to stay in place.
+impl<T: 'static, F> Unpin for TaskLocalFuture<T, F> {}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let future = &mut this.future;
+        this.key.enter(&mut this.slot, || future.as_mut().poll(cx))
+    }
+}
+
+/// The error returned by [`LocalKey::try_with`] when the task-local value has not been set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessError {
+    _private: (),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("task-local value not set")
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// A pinned, boxed future that has to stay on the thread it was created on, as stored by the
+/// [`LocalExecutor`].
+pub(crate) type LocalBoxedFuture = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+/// How urgently a spawned task needs to be polled, set with
+/// [`spawn_with_priority`](Runtime::spawn_with_priority).
+///
+/// With the default [`scheduler::Prioritized`], whenever the executor picks the next task to poll,
+/// it takes one with the highest priority among those that are ready, and the one that has been
+/// waiting the longest among equals. A task that is being polled is never interrupted, so a high
+/// priority task still waits for the current poll to return. Low priority tasks can starve as long
+/// as more important ones keep being ready. Other schedulers may ignore the priority.
+///
+/// Priorities are ordered from `Low` to `High`.
+///
+/// [`scheduler::Prioritized`]: crate::scheduler::Prioritized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Bulk work that only runs when nothing else is ready.
+    Low,
+    /// The priority of tasks spawned with [`spawn`](Runtime::spawn).
+    #[default]
+    Normal,
+    /// Control-plane work that runs before anything else that is ready.
+    High,
+}
+
+/// A task spawned onto a [`LocalExecutor`].
+pub(crate) struct LocalTask {
+    /// The task's future.
+    future: LocalBoxedFuture,
+    /// What the task's waker points at.
+    wake: Arc<LocalTaskWaker>,
+    /// The task's waker, created once when the task is spawned.
+    waker: Waker,
+}
+
+/// The part of a [`LocalExecutor`] or [`LocalSet`] that its wakers point at.
+pub(crate) struct LocalReady {
+    /// The IDs of the tasks that have been spawned or woken and are waiting to be polled.
+    queue: Mutex<VecDeque<Id>>,
+    /// Set by the main future's waker, and cleared right before the main future is polled again.
+    pub(crate) main_woken: AtomicBool,
+    /// Parks and unparks the thread that is running the executor.
+    pub(crate) signal: Signal,
+    /// The waker of the future that last drove a `LocalSet`, woken along with the signal when a
+    /// task is queued.
+    driver: Mutex<Option<Waker>>,
+}
+
+impl LocalReady {
+    pub(crate) fn new() -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+            // The main future has never been polled, so it is as good as woken.
+            main_woken: AtomicBool::new(true),
+            signal: Signal::new(),
+            driver: Mutex::new(None),
+        }
+    }
+}
+
+/// What the waker of a task spawned onto a [`LocalExecutor`] points at.
+struct LocalTaskWaker {
+    /// The ID of the task to queue when woken.
+    id: Id,
+    /// Set while the task is queued, so waking it several times only queues it once.
+    scheduled: AtomicBool,
+    /// Where to queue the task.
+    ready: Arc<LocalReady>,
+}
+
+/// Waking the main future's waker marks it to be polled again and unparks the thread running it.
+impl Wake for LocalReady {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.main_woken.store(true, Ordering::SeqCst);
+        self.signal.notify();
+    }
+}
+
+/// Waking a task's waker queues its ID, unless it is already queued.
+impl Wake for LocalTaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.scheduled.swap(true, Ordering::SeqCst) {
+            self.ready.queue.lock().push_back(self.id);
+            self.ready.signal.notify();
+            let driver = self.ready.driver.lock().clone();
+            if let Some(driver) = driver {
+                driver.wake();
+            }
+        }
+    }
+}
+
+/// Add a task to the tasks of a [`LocalExecutor`] or [`LocalSet`], and queue it for its first poll.
+#[track_caller]
+pub(crate) fn spawn_local_task<F>(
+    tasks: &RefCell<HashMap<Id, LocalTask>>,
+    ready: &Arc<LocalReady>,
+    name: Option<Arc<str>>,
+    future: F,
+) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    let (future, handle) = joinable(
+        future,
+        name,
+        Location::caller(),
+        UnhandledPanic::Ignore,
+        false,
+    );
+    let wake = Arc::new(LocalTaskWaker {
+        id: handle.id(),
+        scheduled: AtomicBool::new(false),
+        ready: ready.clone(),
+    });
+
+    tasks.borrow_mut().insert(
+        handle.id(),
+        LocalTask {
+            future: Box::pin(future),
+            wake: wake.clone(),
+            waker: waker_for(wake.clone()),
+        },
+    );
+    wake.wake();
+    handle
+}
+
+/// Give every task that is currently queued one poll. Tasks woken while doing so are picked up by
+/// the next call.
+pub(crate) fn poll_local_tasks(tasks: &RefCell<HashMap<Id, LocalTask>>, ready: &LocalReady) {
+    let queued = ready.queue.lock().len();
+    for _ in 0..queued {
+        let Some(id) = ready.queue.lock().pop_front() else {
+            break;
+        };
+        let Some(mut task) = tasks.borrow_mut().remove(&id) else {
+            continue;
+        };
+
+        task.wake.scheduled.store(false, Ordering::SeqCst);
+        if task
+            .future
+            .as_mut()
+            .poll(&mut Context::from_waker(&task.waker))
+            .is_pending()
+        {
+            tasks.borrow_mut().insert(id, task);
+        }
+    }
+}
+
+/// A set of tasks that are not `Send`, run on the thread that drives the set.
+///
+/// Unlike a [`LocalExecutor`], a `LocalSet` is not an executor of its own: it is driven by awaiting
+/// [`run_until`](LocalSet::run_until), typically inside [`Runtime::block_on`]. That way the
+/// tasks spawned with [`spawn_local`] can hold an `Rc` across an `.await` and stay on the thread
+/// calling `block_on`, while the rest of the program is spawned onto the worker threads.
+///
+/// Dropping the set drops the tasks that have not completed yet, so awaiting their handles returns
+/// [`JoinError::Cancelled`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::{spawn, spawn_local};
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task::LocalSet;
+/// use std::rc::Rc;
+///
+/// let executor = Runtime::new_multi_thread(2);
+/// let local = LocalSet::new();
+///
+/// let total = local.block_on(&executor, async {
+///     let shared = Rc::new(40);
+///
+///     // Runs on a worker thread.
+///     let two = spawn(async { 2 });
+///
+///     // Runs on this thread, and may hold on to the `Rc`.
+///     let local = spawn_local({
+///         let shared = shared.clone();
+///         async move { *shared + two.await.unwrap() }
+///     });
+///     local.await.unwrap()
+/// });
+/// assert_eq!(total, 42);
+/// ```
+///
+/// [`LocalExecutor`]: crate::runtime::LocalExecutor
+pub struct LocalSet {
+    inner: Rc<LocalSetInner>,
+}
+
+/// The part of a [`LocalSet`] that [`spawn_local`] reaches through [`CURRENT_LOCAL_SET`].
+struct LocalSetInner {
+    /// The tasks spawned onto the set that have not completed yet, by ID.
+    tasks: RefCell<HashMap<Id, LocalTask>>,
+    /// The queue of tasks to poll, shared with their wakers.
+    ready: Arc<LocalReady>,
+}
+
+thread_local! {
+    /// The `LocalSet` that is being driven on this thread, if any.
+    static CURRENT_LOCAL_SET: RefCell<Option<Rc<LocalSetInner>>> = const { RefCell::new(None) };
+}
+
+/// Restores the `LocalSet` that was being driven before [`LocalSet::enter`] when dropped.
+struct LocalSetGuard {
+    previous: Option<Rc<LocalSetInner>>,
+}
+
+impl Drop for LocalSetGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        let _ = CURRENT_LOCAL_SET.try_with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+impl LocalSet {
+    /// Create an empty `LocalSet`.
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(LocalSetInner {
+                tasks: RefCell::new(HashMap::new()),
+                ready: Arc::new(LocalReady::new()),
+            }),
+        }
+    }
+
+    /// Spawn a future that is not `Send` onto the set.
+    ///
+    /// The task is polled once the set is driven by [`run_until`](LocalSet::run_until) or by
+    /// awaiting the set. From inside the set, [`spawn_local`] does the same thing.
+    #[track_caller]
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        spawn_local_task(&self.inner.tasks, &self.inner.ready, None, future)
+    }
+
+    /// Run `future` to completion, and the tasks of the set alongside it.
+    ///
+    /// The tasks that haven't completed when `future` does stay in the set, and are polled the next
+    /// time the set is driven.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::spawn_local;
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task::LocalSet;
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// let executor = Runtime::new();
+    /// let local = LocalSet::new();
+    /// let counter = Rc::new(Cell::new(0));
+    ///
+    /// executor.block_on(local.run_until({
+    ///     let counter = counter.clone();
+    ///     async move {
+    ///         let tasks: Vec<_> = (0..3)
+    ///             .map(|_| {
+    ///                 let counter = counter.clone();
+    ///                 spawn_local(async move { counter.set(counter.get() + 1) })
+    ///             })
+    ///             .collect();
+    ///         for task in tasks {
+    ///             task.await.unwrap();
+    ///         }
+    ///     }
+    /// }));
+    /// assert_eq!(counter.get(), 3);
+    /// ```
+    pub async fn run_until<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        std::future::poll_fn(|cx| {
+            let _entered = self.enter();
+            register(&mut self.inner.ready.driver.lock(), cx.waker());
+
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready(output);
+            }
+            self.poll_tasks(cx);
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Run `future` to completion on `executor`, with the tasks of the set alongside it.
+    ///
+    /// This is a shorthand for `executor.block_on(local_set.run_until(future))`.
+    pub fn block_on<F: Future>(&self, executor: &Runtime, future: F) -> F::Output {
+        executor.block_on(self.run_until(future))
+    }
+
+    /// Make this the set [`spawn_local`] spawns onto, until the returned guard is dropped.
+    fn enter(&self) -> LocalSetGuard {
+        LocalSetGuard {
+            previous: CURRENT_LOCAL_SET.with(|current| current.replace(Some(self.inner.clone()))),
+        }
+    }
+
+    /// Poll the queued tasks once, and return whether every task of the set has completed.
+    ///
+    /// If tasks were woken while polling, the future driving the set is woken so it polls them
+    /// after handing the thread back to its executor.
+    fn poll_tasks(&self, cx: &mut Context<'_>) -> bool {
+        poll_local_tasks(&self.inner.tasks, &self.inner.ready);
+        if !self.inner.ready.queue.lock().is_empty() {
+            cx.waker().wake_by_ref();
+        }
+        self.inner.tasks.borrow().is_empty()
+    }
+}
+
+/// Awaiting a `LocalSet` drives it until every task spawned onto it has completed.
+impl Future for LocalSet {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let _entered = self.enter();
+        register(&mut self.inner.ready.driver.lock(), cx.waker());
+
+        if self.poll_tasks(cx) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Default for LocalSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for LocalSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalSet")
+            .field("tasks", &self.inner.tasks.borrow().len())
+            .finish()
+    }
+}
+
+impl Drop for LocalSet {
+    fn drop(&mut self) {
+        // The tasks are dropped with the set entered and outside of the borrow, since dropping a
+        // future may spawn more tasks.
+        let _entered = self.enter();
+        loop {
+            let tasks = std::mem::take(&mut *self.inner.tasks.borrow_mut());
+            if tasks.is_empty() {
+                break;
+            }
+            drop(tasks);
+        }
+        *self.inner.ready.driver.lock() = None;
+    }
+}
+
+/// Spawn a future onto the executor that is running the current thread.
+///
+/// This is a shorthand for [`Handle::current()`](Handle::current)`.spawn(future)`, so a task can
+/// spawn siblings, like one task per connection, without a handle being passed to it.
+///
+/// # Panics
+///
+/// Panics if called from outside of an executor, like [`Handle::current`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::spawn;
+/// use mini_executor::runtime::Runtime;
+///
+/// let executor = Runtime::new();
+///
+/// let total = executor.block_on(async {
+///     let connections: Vec<_> = (1..=3).map(|id| spawn(async move { id * 10 })).collect();
+///
+///     let mut total = 0;
+///     for connection in connections {
+///         total += connection.await.unwrap();
+///     }
+///     total
+/// });
+/// assert_eq!(total, 60);
+/// ```
+#[track_caller]
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Handle::current().spawn(future)
+}
+
+/// Spawn a future that is not `Send` onto the [`LocalSet`] that is being driven on the current
+/// thread.
+///
+/// The task is polled on this thread whenever the set is driven, see [`LocalSet`] for an example.
+///
+/// # Panics
+///
+/// Panics if called from outside of a `LocalSet`.
+#[track_caller]
+pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    let current = CURRENT_LOCAL_SET.with(|current| current.borrow().clone());
+    match current {
+        Some(set) => spawn_local_task(&set.tasks, &set.ready, None, future),
+        None => panic!("`spawn_local` called from outside of a `LocalSet`"),
+    }
+}
+
+/// Wrap a future so its output is handed to a [`JoinHandle`].
+///
+/// Returns the wrapped future, which is what the executor stores and polls, and the handle. A panic
+/// of the future is caught and handed to the handle as well, and with
+/// [`UnhandledPanic::ShutdownRuntime`] the wrapped future panics in turn to stop the executor. If
+/// the wrapped future is dropped before it completed, the handle returns [`JoinError::Cancelled`].
+pub(crate) fn joinable<F: Future>(
+    future: F,
+    name: Option<Arc<str>>,
+    location: &'static Location<'static>,
+    unhandled_panic: UnhandledPanic,
+    warn_unobserved: bool,
+) -> (impl Future<Output = ()>, JoinHandle<F::Output>) {
+    let id = Id::next();
+    let task_name = name.clone();
+    let state = Arc::new(Mutex::new(JoinState {
+        output: None,
+        cancelled: false,
+        waker: None,
+        task_waker: None,
+        detached: false,
+        warn_unobserved,
+    }));
+
+    let task_state = state.clone();
+    let completion = Completion {
+        state: Some(state.clone()),
+        id,
+        name: name.clone(),
+        location,
+    };
+    let task = async move {
+        let mut completion = completion;
+        let output = {
+            let mut future = std::pin::pin!(future);
+            std::future::poll_fn(|cx| {
+                {
+                    let mut state = task_state.lock();
+                    if state.cancelled {
+                        return Poll::Ready(Err(JoinError::Cancelled));
+                    }
+                    register(&mut state.task_waker, cx.waker());
+                }
+                let poll = enter(id, task_name.clone(), || {
+                    panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx)))
+                });
+                match poll {
+                    Ok(poll) => poll.map(Ok),
+                    Err(payload) => {
+                        report_panic(&*payload);
+                        Poll::Ready(Err(JoinError::Panic(payload)))
+                    }
+                }
+            })
+            .await
+        };
+
+        // The future has been dropped by now, also if the task was aborted or panicked.
+        let panicked = output.as_ref().is_err_and(JoinError::is_panic);
+        completion.complete(output);
+
+        if panicked && unhandled_panic == UnhandledPanic::ShutdownRuntime {
+            let task = match task_name {
+                Some(name) => format!("spawned task '{}'", name),
+                None => String::from("a spawned task"),
+            };
+            panic!(
+                "{} (spawned at {}) panicked and the executor is configured to shut down on an \
+                 unhandled panic",
+                task, location
+            );
+        }
+    };
+
+    let handle = JoinHandle {
+        id,
+        name,
+        location,
+        state,
+    };
+    (task, handle)
+}
+
+/// Hands the output of a task to its [`JoinHandle`] and wakes whoever is awaiting it.
+///
+/// If the task is dropped before it completed, for example because the executor shut down, the
+/// handle is told that it was cancelled instead.
+struct Completion<T> {
+    state: Option<Arc<Mutex<JoinState<T>>>>,
+    /// The ID, name and spawn location of the task, to name it in a warning about a panic that
+    /// nobody awaits.
+    id: Id,
+    name: Option<Arc<str>>,
+    location: &'static Location<'static>,
+}
+
+impl<T> Completion<T> {
+    fn complete(&mut self, output: Result<T, JoinError>) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+        let (waker, task_waker) = {
+            let mut state = state.lock();
+            let panicked = output.as_ref().is_err_and(JoinError::is_panic);
+            if state.detached && state.warn_unobserved && panicked {
+                // Nobody is going to take the output from the handle.
+                if let Err(error) = &output {
+                    warn_unobserved(self.id, self.name.as_deref(), self.location, error);
+                }
+            }
+            state.output = Some(output);
+            (state.waker.take(), state.task_waker.take())
+        };
+        // The task's own waker is no longer needed to abort it, and would keep the task alive for
+        // as long as its handle.
+        drop(task_waker);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        self.complete(Err(JoinError::Cancelled));
+    }
+}
+
+/// Create the handle of a task that was cancelled before it could be spawned, because the executor
+/// has been shut down.
+pub(crate) fn cancelled<T>(
+    name: Option<Arc<str>>,
+    location: &'static Location<'static>,
+) -> JoinHandle<T> {
+    let state = Arc::new(Mutex::new(JoinState {
+        output: Some(Err(JoinError::Cancelled)),
+        cancelled: true,
+        waker: None,
+        task_waker: None,
+        detached: false,
+        warn_unobserved: false,
+    }));
+    JoinHandle {
+        id: Id::next(),
+        name,
+        location,
+        state,
+    }
+}
+
+/// The state shared between a spawned task and its [`JoinHandle`].
+struct JoinState<T> {
+    /// The output of the task, or why there is none, once it has completed.
+    output: Option<Result<T, JoinError>>,
+    /// Set by [`JoinHandle::abort`], so the task drops its future the next time it is polled.
+    cancelled: bool,
+    /// The waker of whoever is awaiting the `JoinHandle`, if it has been polled already.
+    waker: Option<Waker>,
+    /// The waker of the task itself, if it has been polled already, to run it once it is aborted.
+    task_waker: Option<Waker>,
+    /// Set when the `JoinHandle` is dropped, so nobody is going to take the output anymore.
+    detached: bool,
+    /// Whether to warn about a panic of the task that nobody takes from the `JoinHandle`, see
+    /// [`Builder::warn_unobserved_panics`](crate::runtime::Builder::warn_unobserved_panics).
+    warn_unobserved: bool,
+}
+
+/// Warn that a task panicked while nobody was going to await its [`JoinHandle`].
+fn warn_unobserved(
+    id: Id,
+    name: Option<&str>,
+    location: &'static Location<'static>,
+    error: &JoinError,
+) {
+    let name = name.map(|name| format!(" '{name}'")).unwrap_or_default();
+    eprintln!(
+        "warning: the `JoinHandle` of task {id}{name} spawned at {location} was dropped without \
+         being awaited, so nobody sees that the {error}"
+    );
+}
+
+/// The reason a task did not hand its output to its [`JoinHandle`].
+#[derive(Debug)]
+pub enum JoinError {
+    /// The task was aborted with [`JoinHandle::abort`] before it completed.
+    Cancelled,
+    /// The task panicked, with the payload it panicked with.
+    Panic(Box<dyn Any + Send + 'static>),
+}
+
+impl JoinError {
+    /// Whether the task was aborted.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, JoinError::Cancelled)
+    }
+
+    /// Whether the task panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JoinError::Panic(_))
+    }
+
+    /// Get the payload the task panicked with, to resume the panic with
+    /// [`std::panic::resume_unwind`] for example.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task did not panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let task = executor.spawn(async { panic!("boom") });
+    /// executor.spawn(async move {
+    ///     let payload = task.await.unwrap_err().into_panic();
+    ///     assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        match self {
+            JoinError::Panic(payload) => payload,
+            JoinError::Cancelled => panic!("`JoinError::into_panic` called on a cancelled task"),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Cancelled => f.write_str("task was cancelled"),
+            JoinError::Panic(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+                match message {
+                    Some(message) => write!(f, "task panicked with message {:?}", message),
+                    None => f.write_str("task panicked"),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// A handle to a task spawned with [`Runtime::spawn`] or
+/// [`LocalExecutor::spawn`](crate::runtime::LocalExecutor::spawn).
+///
+/// Awaiting a `JoinHandle` waits for the task to complete and returns its output, or a [`JoinError`]
+/// if the task was aborted or panicked. Dropping the handle does not cancel the task; it keeps
+/// running on the executor. If it panics then, nobody finds out, unless the executor is configured
+/// with [`Builder::warn_unobserved_panics`](crate::runtime::Builder::warn_unobserved_panics).
+#[must_use = "dropping a `JoinHandle` detaches the task, and nobody sees whether it panicked"]
+pub struct JoinHandle<T> {
+    id: Id,
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) location: &'static Location<'static>,
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// The ID of the task, the same one [`id()`] returns from inside of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let handle = executor.spawn(async { task::id() });
+    /// let id = handle.id();
+    /// executor.spawn(async move {
+    ///     assert_eq!(handle.await.unwrap(), id);
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// The name the task was spawned with, if it was spawned with
+    /// [`spawn_named`](Runtime::spawn_named).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Where the task was spawned, the call of [`Runtime::spawn`] or one of the other `spawn`
+    /// functions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let handle = executor.spawn(async {});
+    /// assert_eq!(handle.location().line(), line!() - 1);
+    /// ```
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Abort the task.
+    ///
+    /// The task's future is dropped the next time the executor gets to the task, without being
+    /// polled again, and awaiting the handle returns [`JoinError::Cancelled`]. Aborting a task that
+    /// has already completed does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::future::pending;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let runaway = executor.spawn(pending::<()>());
+    /// runaway.abort();
+    /// executor.spawn(async move {
+    ///     assert!(runaway.await.unwrap_err().is_cancelled());
+    /// });
+    ///
+    /// executor.run();
+    /// ```
+    pub fn abort(&self) {
+        abort(&self.state);
+    }
+
+    /// Keep the waker of the task, to run it when it is aborted before its first poll.
+    pub(crate) fn set_task_waker(&self, waker: &Waker) {
+        register(&mut self.state.lock().task_waker, waker);
+    }
+
+    /// Whether the task has completed, so that awaiting the handle returns right away.
+    ///
+    /// An aborted task has only completed once the executor has dropped its future.
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().output.is_some()
+    }
+
+    /// Block the current thread until the task has completed and return its output, or give the
+    /// handle back if it hasn't completed within `timeout`.
+    ///
+    /// This is meant for synchronous code outside of the executor, like a test harness that
+    /// declares a run hung if a task takes too long. The executor has to make progress on another
+    /// thread in the meantime, like the workers of a multi-threaded one do.
+    ///
+    /// # Panics
+    ///
+    /// Like [`Runtime::block_on`], this panics when called from within a future that is already
+    /// being driven by an executor, since blocking the thread would keep the executor from
+    /// running the task.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use std::future::pending;
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::new_multi_thread(2);
+    ///
+    /// let quick = executor.spawn(async { 42 });
+    /// assert_eq!(quick.join_timeout(Duration::from_secs(10)).unwrap().unwrap(), 42);
+    ///
+    /// let hung = executor.spawn(pending::<()>());
+    /// let Err(hung) = hung.join_timeout(Duration::from_millis(10)) else {
+    ///     panic!("the task completed");
+    /// };
+    /// assert!(!hung.is_finished());
+    /// hung.abort();
+    /// ```
+    pub fn join_timeout(self, timeout: Duration) -> Result<Result<T, JoinError>, Self> {
+        let _driver = enter_driver();
+        let deadline = Instant::now().checked_add(timeout);
+        let signal = Arc::new(Signal::new());
+        signal.register_current();
+        let waker = waker_for(signal.clone());
+
+        loop {
+            {
+                let mut state = self.state.lock();
+                if let Some(output) = state.output.take() {
+                    return Ok(output);
+                }
+                register(&mut state.waker, &waker);
+            }
+
+            // A timeout too long to be added to the current time never ends.
+            let Some(deadline) = deadline else {
+                signal.park();
+                continue;
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(self);
+            }
+            signal.park_timeout(deadline - now);
+        }
+    }
+}
+
+/// Abort the task whose [`JoinHandle`] shares `state`, see [`JoinHandle::abort`].
+fn abort<T>(state: &Mutex<JoinState<T>>) {
+    let task_waker = {
+        let mut state = state.lock();
+        if state.output.is_some() {
+            return;
+        }
+        state.cancelled = true;
+        state.task_waker.take()
+    };
+    if let Some(waker) = task_waker {
+        waker.wake();
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("location", &self.location)
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+/// Dropping the handle detaches the task, and warns about its panic if it already panicked and
+/// the executor is configured to.
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        let output = {
+            let mut state = self.state.lock();
+            state.detached = true;
+            match state.output {
+                Some(Err(JoinError::Panic(_))) if state.warn_unobserved => state.output.take(),
+                _ => None,
+            }
+        };
+        if let Some(Err(error)) = output {
+            warn_unobserved(self.id, self.name.as_deref(), self.location, &error);
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        if state.output.is_none() {
+            register(&mut state.waker, cx.waker());
+            return Poll::Pending;
+        }
+
+        // Only an output that is ready takes from the budget, so a task can wait on many handles.
+        if poll_proceed(cx).is_pending() {
+            return Poll::Pending;
+        }
+        Poll::Ready(state.output.take().unwrap())
+    }
+}
+
+/// A collection of tasks spawned onto an executor, whose outputs are returned in the order in which
+/// the tasks complete.
+///
+/// All tasks in a `JoinSet` have the same output type `T`. Tasks are added with
+/// [`JoinSet::spawn`] and their outputs retrieved with [`JoinSet::join_next`]. Dropping the set
+/// aborts the tasks that are still in it.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task::JoinSet;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let mut set = JoinSet::new();
+///     for i in 0..10 {
+///         set.spawn(async move { i });
+///     }
+///
+///     let mut seen = [false; 10];
+///     while let Some(output) = set.join_next().await {
+///         seen[output.unwrap()] = true;
+///     }
+///     assert!(seen.iter().all(|&seen| seen));
+/// });
+/// ```
+pub struct JoinSet<T> {
+    handles: Vec<JoinHandle<T>>,
+}
+
+impl<T> JoinSet<T> {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self {
+            handles: Vec::new(),
+        }
+    }
+
+    /// The number of tasks in the set whose output hasn't been returned by
+    /// [`join_next`](JoinSet::join_next) yet.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Whether the set contains no tasks.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Wait for one of the tasks in the set to complete and return its output, or `None` if the
+    /// set is empty.
+    ///
+    /// A task that was aborted or panicked returns a [`JoinError`], like its [`JoinHandle`] would.
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        std::future::poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    /// Poll every task of the set, and remove and return the output of the first one that has
+    /// completed.
+    fn poll_join_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, JoinError>>> {
+        if self.handles.is_empty() {
+            return Poll::Ready(None);
+        }
+        for index in 0..self.handles.len() {
+            if let Poll::Ready(output) = Pin::new(&mut self.handles[index]).poll(cx) {
+                // The output has just been taken from the handle.
+                drop(self.handles.swap_remove(index));
+                return Poll::Ready(Some(output));
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Abort all tasks in the set.
+    ///
+    /// The tasks stay in the set, so [`join_next`](JoinSet::join_next) still returns their
+    /// outputs, [`JoinError::Cancelled`] for the ones that hadn't completed yet.
+    pub fn abort_all(&mut self) {
+        self.handles.iter().for_each(JoinHandle::abort);
+    }
+}
+
+impl<T: Send + 'static> JoinSet<T> {
+    /// Spawn a future onto the executor that is running the current thread, and add it to the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of an executor, like [`Handle::current`].
+    #[track_caller]
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.spawn_on(future, &Handle::current());
+    }
+
+    /// Spawn a future onto the executor of the given handle, and add it to the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task::JoinSet;
+    ///
+    /// let executor = Runtime::new();
+    ///
+    /// let mut set = JoinSet::new();
+    /// set.spawn_on(async { 42 }, &executor.handle());
+    ///
+    /// let output = executor.block_on(set.join_next());
+    /// assert_eq!(output.unwrap().unwrap(), 42);
+    /// ```
+    #[track_caller]
+    pub fn spawn_on<F>(&mut self, future: F, handle: &Handle)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.handles.push(handle.spawn(future));
+    }
+}
+
+impl<T> Default for JoinSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for JoinSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinSet").field("len", &self.len()).finish()
+    }
+}
+
+impl<T> Drop for JoinSet<T> {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
+
+/// Run `f` with a [`Scope`] to spawn tasks with, and wait for all of them to complete before
+/// returning the output of the future `f` returns.
+///
+/// A task spawned with [`Scope::spawn`] can't outlive the scope: the scope future only resolves
+/// once its future and every task spawned into it have completed. If the scope future is dropped
+/// before that, or its future panics, the tasks that are still running are aborted. Since the
+/// scope future can be forgotten instead of dropped, the tasks still have to be `'static`.
+///
+/// # Panics
+///
+/// Panics when polled from outside of an executor, like [`Handle::current`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::task::{self, scope};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let executor = Runtime::new();
+/// let total = Arc::new(AtomicUsize::new(0));
+///
+/// let counter = total.clone();
+/// executor.block_on(scope(|s| async move {
+///     for i in 1..=3 {
+///         let counter = counter.clone();
+///         s.spawn(async move {
+///             task::yield_now().await;
+///             counter.fetch_add(i, Ordering::SeqCst);
+///         });
+///     }
+/// }));
+///
+/// // Every task of the scope has completed by now.
+/// assert_eq!(total.load(Ordering::SeqCst), 6);
+/// ```
+pub async fn scope<F, Fut>(f: F) -> Fut::Output
+where
+    F: FnOnce(Scope) -> Fut,
+    Fut: Future,
+{
+    let scope = Scope {
+        inner: Arc::new(ScopeInner {
+            handle: Handle::current(),
+            state: Mutex::new(ScopeState {
+                running: 0,
+                closed: false,
+                waker: None,
+                aborts: Vec::new(),
+            }),
+        }),
+    };
+    let close = CloseScope(scope.inner.clone());
+
+    let output = f(scope).await;
+    std::future::poll_fn(|cx| {
+        let mut state = close.0.state.lock();
+        if state.running == 0 {
+            return Poll::Ready(());
+        }
+        register(&mut state.waker, cx.waker());
+        Poll::Pending
+    })
+    .await;
+    output
+}
+
+/// A handle to spawn tasks that have to complete before the [`scope`] they belong to does.
+///
+/// It is passed to the closure given to [`scope`], and can be cloned to move it into the tasks.
+#[derive(Clone)]
+pub struct Scope {
+    inner: Arc<ScopeInner>,
+}
+
+impl Scope {
+    /// Spawn a future onto the executor as part of the scope.
+    ///
+    /// The task is spawned onto the executor that is running the scope, and the returned
+    /// [`JoinHandle`] works like the one of [`Runtime::spawn`]. A task spawned after the scope
+    /// has resolved or has been dropped is cancelled right away.
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        {
+            let mut state = self.inner.state.lock();
+            if state.closed {
+                return cancelled(None, Location::caller());
+            }
+            state.running += 1;
+        }
+
+        // Not spawned while holding the lock, since a task spawned onto an executor that has shut
+        // down is dropped right away, which takes the lock to leave the scope.
+        let member = ScopeMember(self.inner.clone());
+        let handle = self.inner.handle.spawn(async move {
+            let _member = member;
+            future.await
+        });
+
+        let join_state = handle.state.clone();
+        let mut state = self.inner.state.lock();
+        if state.closed {
+            abort(&join_state);
+        } else {
+            state.aborts.push(Box::new(move || abort(&join_state)));
+        }
+        handle
+    }
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let running = self.inner.state.lock().running;
+        f.debug_struct("Scope").field("running", &running).finish()
+    }
+}
+
+/// The state of a [`scope`], shared with its [`Scope`] handles and its tasks.
+struct ScopeInner {
+    /// The executor the tasks of the scope are spawned onto.
+    handle: Handle,
+    state: Mutex<ScopeState>,
+}
+
+struct ScopeState {
+    /// The number of tasks of the scope that haven't completed yet.
+    running: usize,
+    /// Set once the scope has resolved or has been dropped, after which no tasks can be added.
+    closed: bool,
+    /// The waker of the scope future, once it waits for its tasks.
+    waker: Option<Waker>,
+    /// Aborts one of the tasks of the scope each.
+    aborts: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+/// Held by every task of a scope, and dropped when the task completes or is cancelled.
+struct ScopeMember(Arc<ScopeInner>);
+
+impl Drop for ScopeMember {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.0.state.lock();
+            state.running -= 1;
+            if state.running > 0 {
+                return;
+            }
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Closes a scope when its future resolves or is dropped, aborting the tasks that are still
+/// running in the latter case.
+struct CloseScope(Arc<ScopeInner>);
+
+impl Drop for CloseScope {
+    fn drop(&mut self) {
+        let aborts = {
+            let mut state = self.0.state.lock();
+            state.closed = true;
+            if state.running == 0 {
+                Vec::new()
+            } else {
+                std::mem::take(&mut state.aborts)
+            }
+        };
+        aborts.into_iter().for_each(|abort| abort());
+    }
+}
+
+/// Wrap a future so it can be aborted remotely with the returned [`AbortHandle`].
+///
+/// This is a shorthand for [`AbortHandle::new_pair`] and [`Abortable::new`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::block_on;
+/// use mini_executor::task::{abortable, Aborted};
+/// use std::future::pending;
+///
+/// let (future, handle) = abortable(pending::<()>());
+/// handle.abort();
+/// assert_eq!(block_on(future), Err(Aborted));
+/// ```
+pub fn abortable<F: Future>(future: F) -> (Abortable<F>, AbortHandle) {
+    let (handle, registration) = AbortHandle::new_pair();
+    (Abortable::new(future, registration), handle)
+}
+
+/// A future that can be aborted with an [`AbortHandle`], created with [`abortable`] or
+/// [`Abortable::new`].
+///
+/// Unlike [`JoinHandle::abort`], this works for any future, whether it has been spawned or not.
+/// Once aborted, it resolves to `Err(Aborted)` the next time it is polled, without polling the
+/// future inside of it again.
+pub struct Abortable<F> {
+    future: Pin<Box<F>>,
+    inner: Arc<AbortInner>,
+}
+
+impl<F> Abortable<F> {
+    /// Wrap `future` so it can be aborted with the [`AbortHandle`] that belongs to `registration`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::block_on;
+    /// use mini_executor::task::{AbortHandle, Abortable};
+    ///
+    /// let (handle, registration) = AbortHandle::new_pair();
+    /// let future = Abortable::new(async { 42 }, registration);
+    ///
+    /// // Not aborted, so the future completes as usual.
+    /// assert_eq!(block_on(future), Ok(42));
+    /// assert!(!handle.is_aborted());
+    /// ```
+    pub fn new(future: F, registration: AbortRegistration) -> Self {
+        Self {
+            future: Box::pin(future),
+            inner: registration.inner,
+        }
+    }
+
+    /// Whether the future has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.load(Ordering::SeqCst)
+    }
+}
+
+impl<F: Future> Future for Abortable<F> {
+    type Output = Result<F::Output, Aborted>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_aborted() {
+            return Poll::Ready(Err(Aborted));
+        }
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        // Registered before checking again, so an abort in between is not missed.
+        self.inner.waker.register(cx.waker());
+        if self.is_aborted() {
+            return Poll::Ready(Err(Aborted));
+        }
+        Poll::Pending
+    }
+}
+
+impl<F> fmt::Debug for Abortable<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Abortable")
+            .field("aborted", &self.is_aborted())
+            .finish()
+    }
+}
+
+/// A handle to abort an [`Abortable`] future from anywhere, also from another thread.
+///
+/// The handle can be cloned, and aborting any of the clones aborts the future.
+#[derive(Debug, Clone)]
+pub struct AbortHandle {
+    inner: Arc<AbortInner>,
+}
+
+impl AbortHandle {
+    /// Create an `AbortHandle` and the [`AbortRegistration`] to pass to [`Abortable::new`].
+    pub fn new_pair() -> (AbortHandle, AbortRegistration) {
+        let inner = Arc::new(AbortInner {
+            aborted: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        });
+        (
+            AbortHandle {
+                inner: inner.clone(),
+            },
+            AbortRegistration { inner },
+        )
+    }
+
+    /// Abort the future.
+    ///
+    /// The task polling the future is woken, and the future resolves to `Err(Aborted)` when it is
+    /// polled next. Its future is dropped along with the `Abortable`. Aborting a future that has
+    /// already completed does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task::abortable;
+    /// use std::future::pending;
+    /// use std::thread;
+    ///
+    /// let executor = Runtime::new();
+    /// let (future, handle) = abortable(pending::<()>());
+    ///
+    /// thread::spawn(move || handle.abort());
+    /// assert!(executor.block_on(future).is_err());
+    /// ```
+    pub fn abort(&self) {
+        self.inner.aborted.store(true, Ordering::SeqCst);
+        self.inner.waker.wake();
+    }
+
+    /// Whether [`abort`](AbortHandle::abort) has been called on this handle or one of its clones.
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.load(Ordering::SeqCst)
+    }
+}
+
+/// Ties an [`Abortable`] future to its [`AbortHandle`], created with [`AbortHandle::new_pair`].
+#[derive(Debug)]
+pub struct AbortRegistration {
+    inner: Arc<AbortInner>,
+}
+
+/// The state shared by an [`Abortable`] future and its [`AbortHandle`]s.
+#[derive(Debug)]
+struct AbortInner {
+    aborted: AtomicBool,
+    /// The waker of the last poll of the future, to wake it when it is aborted.
+    waker: AtomicWaker,
+}
+
+/// The error an [`Abortable`] future resolves to when it has been aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aborted;
+
+impl fmt::Display for Aborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("future was aborted")
+    }
+}
+
+impl std::error::Error for Aborted {}
+
+/// Extension methods for futures, implemented for every [`Future`].
+pub trait FutureExt: Future + Sized {
+    /// Attach a label to the future, to tell what a task is doing from the outside.
+    ///
+    /// While the future is being polled, [`label()`] returns the label, to add it to log
+    /// lines for example. When a task returns `Poll::Pending` while waiting in the future, the
+    /// label shows up in the task's entry of [`Runtime::dump`]. Labeled futures can be
+    /// nested, in which case the innermost label wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::task::{self, FutureExt};
+    /// use std::future::pending;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.spawn_named("handler", async {
+    ///     assert_eq!(task::label().as_deref(), None);
+    ///     async {
+    ///         assert_eq!(task::label().as_deref(), Some("db-query"));
+    ///         pending::<()>().await
+    ///     }
+    ///     .named("db-query")
+    ///     .await
+    /// });
+    /// assert!(executor.run_until_stalled().is_pending());
+    ///
+    /// let dump = executor.dump();
+    /// assert_eq!(dump[0].label.as_deref(), Some("db-query"));
+    /// assert_eq!(
+    ///     dump[0].to_string(),
+    ///     format!("task {} 'handler' spawned at {}: Idle in db-query", dump[0].id, dump[0].location),
+    /// );
+    /// ```
+    fn named(self, label: &str) -> Instrumented<Self> {
+        Instrumented {
+            label: label.into(),
+            future: Box::pin(self),
+        }
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+/// A future with a label attached, returned by [`FutureExt::named`].
+pub struct Instrumented<F> {
+    label: Arc<str>,
+    future: Pin<Box<F>>,
+}
+
+impl<F> Instrumented<F> {
+    /// The label attached to the future.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        instrument(&this.label, || this.future.as_mut().poll(cx))
+    }
+}
+
+impl<F> fmt::Debug for Instrumented<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instrumented")
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+/// Declare task-local storage keys of type [`task::LocalKey`](crate::task::LocalKey).
+///
+/// The syntax mirrors [`std::thread_local!`], except that the keys have no initial value: they are
+/// only set inside of [`LocalKey::scope`](crate::task::LocalKey::scope).
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::task_local;
+/// use mini_executor::runtime::Runtime;
+///
+/// task_local! {
+///     /// The ID of the request that is being handled.
+///     pub static REQUEST_ID: u64;
+///     static USER: String;
+/// }
+///
+/// let executor = Runtime::new();
+/// for id in 0..3 {
+///     executor.spawn(REQUEST_ID.scope(id, async move {
+///         assert_eq!(REQUEST_ID.get(), id);
+///     }));
+/// }
+/// executor.run();
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = {
+            ::std::thread_local! {
+                static __KEY: ::std::cell::RefCell<::std::option::Option<$t>> =
+                    const { ::std::cell::RefCell::new(::std::option::Option::None) };
+            }
+            $crate::task::LocalKey { inner: __KEY }
+        };
+        $crate::task_local!($($rest)*);
+    };
+}
@@ -0,0 +1,1229 @@
+//! Waiting for time to pass inside of async code.
+//!
+//! Futures like [`Sleep`] and [`Timeout`] register their deadline with the time driver of the
+//! [`Runtime`] they are created on, which wakes them once the deadline has passed. The time driver
+//! is enabled with [`Builder::enable_time`], and by the constructors of `Runtime`, like
+//! [`Runtime::new`].
+//!
+//! A [`DelayQueue`] holds many items that each expire after their own delay, like the idle
+//! timeouts of connections, with a single timer.
+//!
+//! Tests that wait for time to pass can [`pause`] the clock of the time driver, which then only
+//! moves when it is told to with [`advance`], or when the executor has nothing else to do, so they
+//! run instantly and always the same way. [`now`] reads the clock, paused or not.
+//!
+//! [`Runtime`]: crate::runtime::Runtime
+//! [`Runtime::new`]: crate::runtime::Runtime::new
+//! [`Builder::enable_time`]: crate::runtime::Builder::enable_time
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Stream;
+
+use crate::runtime::Handle;
+use crate::sync::{Condvar, Mutex};
+
+#[cfg(feature = "timer_heap")]
+mod heap;
+#[cfg(not(feature = "timer_heap"))]
+mod wheel;
+
+#[cfg(feature = "timer_heap")]
+use heap::Heap as Queue;
+#[cfg(not(feature = "timer_heap"))]
+use wheel::Wheel as Queue;
+
+/// Wait until `duration` has passed.
+///
+/// Unlike [`std::thread::sleep`], this doesn't block the thread, so the executor keeps polling
+/// other tasks in the meantime.
+///
+/// # Panics
+///
+/// Panics if called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one
+/// whose time driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time;
+/// use std::time::{Duration, Instant};
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let start = Instant::now();
+///     time::sleep(Duration::from_millis(10)).await;
+///     assert!(start.elapsed() >= Duration::from_millis(10));
+/// });
+/// ```
+#[track_caller]
+pub fn sleep(duration: Duration) -> Sleep {
+    let driver = Driver::current();
+    let deadline = after(driver.now(), duration);
+    Sleep::new(driver, deadline)
+}
+
+/// Wait until `deadline` has passed.
+///
+/// This completes right away if the deadline has passed already.
+///
+/// # Panics
+///
+/// Like [`sleep`], this panics if called from outside of a [`Runtime`](crate::runtime::Runtime),
+/// or from within one whose time driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time;
+/// use std::time::{Duration, Instant};
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let deadline = Instant::now() + Duration::from_millis(10);
+///     time::sleep_until(deadline).await;
+///     assert!(Instant::now() >= deadline);
+/// });
+/// ```
+#[track_caller]
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::new(Driver::current(), deadline)
+}
+
+/// Run `future` until `duration` has passed, and return [`Elapsed`] if it hasn't completed by
+/// then.
+///
+/// The future is dropped as soon as the time is up, which cancels whatever it was doing. It is
+/// polled before the deadline is checked, so a future that is ready right away always completes.
+///
+/// # Panics
+///
+/// Like [`sleep`], this panics if called from outside of a [`Runtime`](crate::runtime::Runtime),
+/// or from within one whose time driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time::{self, Elapsed};
+/// use std::future::pending;
+/// use std::time::Duration;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let answer = time::timeout(Duration::from_secs(1), async { 42 }).await;
+///     assert_eq!(answer, Ok(42));
+///
+///     let never = time::timeout(Duration::from_millis(10), pending::<()>()).await;
+///     assert_eq!(never, Err(Elapsed));
+/// });
+/// ```
+#[track_caller]
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future: Some(Box::pin(future)),
+        sleep: sleep(duration),
+    }
+}
+
+/// Create an [`Interval`] that ticks right away, and then every `period`.
+///
+/// # Panics
+///
+/// Panics if `period` is zero. Like [`sleep`], this also panics if called from outside of a
+/// [`Runtime`](crate::runtime::Runtime), or from within one whose time driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time;
+/// use std::time::{Duration, Instant};
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let start = Instant::now();
+///     let mut heartbeat = time::interval(Duration::from_millis(10));
+///
+///     for _ in 0..3 {
+///         heartbeat.tick().await;
+///         println!("still alive");
+///     }
+///     // The first tick completes right away.
+///     assert!(start.elapsed() >= Duration::from_millis(20));
+/// });
+/// ```
+#[track_caller]
+pub fn interval(period: Duration) -> Interval {
+    assert!(
+        period > Duration::ZERO,
+        "the period of an interval must be greater than zero"
+    );
+    let driver = Driver::current();
+    let now = driver.now();
+    Interval {
+        sleep: Sleep::new(driver, now),
+        period,
+        missed_tick_behavior: MissedTickBehavior::default(),
+    }
+}
+
+/// The current instant, as told by the clock of the current runtime's time driver.
+///
+/// This is [`Instant::now`] unless the clock has been [paused](pause), in which case it is the
+/// instant the clock was paused at, plus the time it has been [advanced](advance) by since.
+///
+/// # Panics
+///
+/// Like [`sleep`], this panics if called from outside of a [`Runtime`](crate::runtime::Runtime),
+/// or from within one whose time driver isn't enabled.
+#[track_caller]
+pub fn now() -> Instant {
+    Driver::current().now()
+}
+
+/// Pause the clock of the current runtime's time driver, for tests that wait for time to pass.
+///
+/// While the clock is paused, the time only moves when it is told to with [`advance`], and when
+/// the executor has nothing left to do: rather than parking the thread until the next timer is
+/// due, the executor moves the clock to that timer's deadline right away. So a test that sleeps
+/// for an hour completes instantly, and timers always fire in the same order, no matter how busy
+/// the machine is. The clock doesn't move on its own while a [blocking
+/// closure](crate::runtime::Runtime::spawn_blocking) is running, since it may still wake a task.
+///
+/// Read the paused clock with [`now`], since [`Instant::now`] keeps reporting the real time.
+///
+/// # Panics
+///
+/// Panics if the clock is paused already, or if called from within a multi-threaded runtime,
+/// since its workers keep polling tasks while the clock moves. Like [`sleep`], this also panics if
+/// called from outside of a [`Runtime`](crate::runtime::Runtime), or from within one whose time
+/// driver isn't enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time;
+/// use std::time::{Duration, Instant};
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     time::pause();
+///     let start = Instant::now();
+///     let virtual_start = time::now();
+///
+///     time::sleep(Duration::from_secs(3600)).await;
+///
+///     assert!(time::now() - virtual_start >= Duration::from_secs(3600));
+///     assert!(start.elapsed() < Duration::from_secs(60));
+/// });
+/// ```
+#[track_caller]
+pub fn pause() {
+    let Ok(handle) = Handle::try_current() else {
+        panic!("there is no time driver, `time` must be used from within a `Runtime`");
+    };
+    assert!(
+        handle.is_current_thread(),
+        "the clock can only be paused on a current-thread `Runtime`"
+    );
+    let driver = Driver::current();
+    assert!(!driver.is_paused(), "the clock is paused already");
+    driver.pause();
+}
+
+/// Let the clock of the current runtime's time driver run again after [`pause`].
+///
+/// The clock goes on from where it was paused and advanced to, at the pace of the real time.
+///
+/// # Panics
+///
+/// Panics if the clock isn't paused. Like [`sleep`], this also panics if called from outside of a
+/// [`Runtime`](crate::runtime::Runtime), or from within one whose time driver isn't enabled.
+#[track_caller]
+pub fn resume() {
+    let driver = Driver::current();
+    assert!(driver.is_paused(), "the clock isn't paused");
+    driver.resume();
+}
+
+/// Move the paused clock of the current runtime's time driver ahead by `duration`, and fire the
+/// timers that are due by then.
+///
+/// The tasks waiting for those timers are polled before this completes.
+///
+/// # Panics
+///
+/// Panics if the clock isn't [paused](pause). Like [`sleep`], this also panics if called from
+/// outside of a [`Runtime`](crate::runtime::Runtime), or from within one whose time driver isn't
+/// enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time;
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     time::pause();
+///     let ticks = Arc::new(AtomicU32::new(0));
+///     let counter = ticks.clone();
+///     mini_executor::spawn(async move {
+///         let mut interval = time::interval(Duration::from_secs(1));
+///         loop {
+///             interval.tick().await;
+///             counter.fetch_add(1, Ordering::SeqCst);
+///         }
+///     });
+///
+///     // The first tick is due right away.
+///     time::advance(Duration::ZERO).await;
+///     assert_eq!(ticks.load(Ordering::SeqCst), 1);
+///
+///     time::advance(Duration::from_millis(2500)).await;
+///     assert_eq!(ticks.load(Ordering::SeqCst), 3);
+/// });
+/// ```
+pub async fn advance(duration: Duration) {
+    let driver = Driver::current();
+    assert!(
+        driver.is_paused(),
+        "the clock can only be advanced while it is paused"
+    );
+    driver.advance(duration);
+    // The tasks whose timers fired are queued before this one.
+    crate::task::yield_now().await;
+}
+
+/// The deadline `duration` after `now`.
+fn after(now: Instant, duration: Duration) -> Instant {
+    // A duration too long to be added to the current time never ends.
+    now.checked_add(duration).unwrap_or_else(far_future)
+}
+
+/// A deadline that is so far away that it is never reached.
+fn far_future() -> Instant {
+    // About 30 years, which still fits into an `Instant` on every platform.
+    Instant::now() + Duration::from_secs(86400 * 365 * 30)
+}
+
+/// A future that completes once its deadline has passed, created with [`sleep`] or
+/// [`sleep_until`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    driver: Arc<Driver>,
+    deadline: Instant,
+    /// The ID of the timer registered with the driver, once the future has been polled.
+    entry: Option<u64>,
+}
+
+impl Sleep {
+    fn new(driver: Arc<Driver>, deadline: Instant) -> Self {
+        Self {
+            driver,
+            deadline,
+            entry: None,
+        }
+    }
+
+    /// The instant at which the future completes.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Whether the deadline has passed.
+    pub fn is_elapsed(&self) -> bool {
+        self.driver.now() >= self.deadline
+    }
+
+    /// Change the deadline to `deadline`, whether the future has completed already or not.
+    ///
+    /// A timer that is registered already is moved to the new deadline, and keeps the waker it
+    /// was polled with last, so the future doesn't have to be created again, or polled before its
+    /// new deadline.
+    ///
+    /// # Examples
+    ///
+    /// Giving up on a connection once it has been idle for 50 milliseconds:
+    ///
+    /// ```
+    /// use futures::{select, FutureExt, StreamExt};
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::time;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = Runtime::new();
+    /// let (sender, mut messages) = futures::channel::mpsc::unbounded();
+    /// executor.spawn(async move {
+    ///     for message in 0..3 {
+    ///         time::sleep(Duration::from_millis(10)).await;
+    ///         sender.unbounded_send(message).unwrap();
+    ///     }
+    ///     // Keep the connection open without sending anything.
+    ///     std::future::pending::<()>().await;
+    /// });
+    ///
+    /// let received = executor.block_on(async {
+    ///     let idle_timeout = Duration::from_millis(50);
+    ///     let mut idle = time::sleep(idle_timeout);
+    ///     let mut received = Vec::new();
+    ///     loop {
+    ///         select! {
+    ///             message = messages.next() => {
+    ///                 received.push(message.unwrap());
+    ///                 idle.reset(Instant::now() + idle_timeout);
+    ///             }
+    ///             () = (&mut idle).fuse() => break received,
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(received, [0, 1, 2]);
+    /// ```
+    pub fn reset(&mut self, deadline: Instant) {
+        self.deadline = deadline;
+        self.driver.reset(deadline, &mut self.entry);
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if this.is_elapsed() {
+            if let Some(entry) = this.entry.take() {
+                this.driver.cancel(entry);
+            }
+            return Poll::Ready(());
+        }
+        this.driver
+            .register(this.deadline, &mut this.entry, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry {
+            self.driver.cancel(entry);
+        }
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A future that runs another one until a deadline, created with [`timeout`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    /// The future, until it has completed or the deadline has passed.
+    future: Option<Pin<Box<F>>>,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    /// The instant at which the future is cancelled if it hasn't completed.
+    pub fn deadline(&self) -> Instant {
+        self.sleep.deadline()
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self
+            .future
+            .as_mut()
+            .expect("`Timeout` polled after it completed");
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            self.future = None;
+            return Poll::Ready(Ok(output));
+        }
+
+        if Pin::new(&mut self.sleep).poll(cx).is_ready() {
+            self.future = None;
+            return Poll::Ready(Err(Elapsed));
+        }
+        Poll::Pending
+    }
+}
+
+impl<F> fmt::Debug for Timeout<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("deadline", &self.deadline())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The error a [`Timeout`] future resolves to when its deadline has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Ticks every period, created with [`interval`].
+///
+/// A tick is due at the start of every period. If ticks are missed because the last one was taken
+/// too late, the [`MissedTickBehavior`] decides when the next ones are.
+pub struct Interval {
+    /// Completes when the next tick is due.
+    sleep: Sleep,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+/// What an [`Interval`] does when ticks were missed, set with
+/// [`Interval::set_missed_tick_behavior`].
+///
+/// Say an interval with a period of 10 milliseconds ticked at 0 milliseconds, but was only polled
+/// again at 35 milliseconds, when the ticks at 10, 20 and 30 milliseconds were due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTickBehavior {
+    /// Catch up on the missed ticks as fast as possible: they complete right away, one after the
+    /// other. Then ticks are due on the original schedule again, at 40 milliseconds in the example.
+    #[default]
+    Burst,
+    /// Complete one tick right away, and count the period from then on: the next tick is due at
+    /// 45 milliseconds in the example.
+    Delay,
+    /// Complete one tick right away, and skip the others: the next tick is due on the original
+    /// schedule, at 40 milliseconds in the example.
+    Skip,
+}
+
+impl Interval {
+    /// Wait for the next tick, and return the instant at which it was due.
+    ///
+    /// The instant is when the tick was scheduled, not when it completed, so it doesn't drift.
+    pub async fn tick(&mut self) -> Instant {
+        std::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Poll for the next tick, for futures written by hand, see [`tick`](Interval::tick).
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let due = self.sleep.deadline;
+        let now = self.sleep.driver.now();
+        let mut next = due + self.period;
+        if next <= now {
+            next = match self.missed_tick_behavior {
+                MissedTickBehavior::Burst => next,
+                MissedTickBehavior::Delay => now + self.period,
+                MissedTickBehavior::Skip => {
+                    let missed = (now - due).as_nanos() / self.period.as_nanos();
+                    let skipped = self.period.as_nanos() * (missed + 1);
+                    due + Duration::from_nanos(skipped.try_into().unwrap_or(u64::MAX))
+                }
+            };
+        }
+        self.sleep.reset(next);
+        Poll::Ready(due)
+    }
+
+    /// The time between two ticks.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// What the interval does when ticks were missed.
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    /// Set what the interval does when ticks were missed. Defaults to
+    /// [`MissedTickBehavior::Burst`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mini_executor::runtime::Runtime;
+    /// use mini_executor::time::{self, MissedTickBehavior};
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let executor = Runtime::new();
+    /// executor.block_on(async {
+    ///     let mut cleanup = time::interval(Duration::from_millis(10));
+    ///     cleanup.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ///
+    ///     let first = cleanup.tick().await;
+    ///     // A cleanup that takes longer than two periods.
+    ///     thread::sleep(Duration::from_millis(25));
+    ///     // The missed tick, which completes right away.
+    ///     cleanup.tick().await;
+    ///     // Due a period after the missed tick completed, rather than at 20 milliseconds.
+    ///     let next = cleanup.tick().await;
+    ///     assert!(next - first >= Duration::from_millis(35));
+    /// });
+    /// ```
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+}
+
+impl fmt::Debug for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interval")
+            .field("next_tick", &self.sleep.deadline)
+            .field("period", &self.period)
+            .field("missed_tick_behavior", &self.missed_tick_behavior)
+            .finish()
+    }
+}
+
+/// A queue of items that are each returned once their delay has passed, in the order of their
+/// deadlines.
+///
+/// An item is inserted with [`insert`](DelayQueue::insert), which returns a [`Key`] to
+/// [`reset`](DelayQueue::reset) or [`remove`](DelayQueue::remove) it before it expires. The expired
+/// items are returned by [`next_expired`](DelayQueue::next_expired), or by polling the queue as a
+/// [`Stream`]. Only the earliest deadline is registered with the time driver, so the queue holds
+/// any number of items with a single timer.
+///
+/// # Examples
+///
+/// Closing connections that have been idle for a while:
+///
+/// ```
+/// use mini_executor::runtime::Runtime;
+/// use mini_executor::time::DelayQueue;
+/// use std::time::Duration;
+///
+/// let executor = Runtime::new();
+/// executor.block_on(async {
+///     let mut idle = DelayQueue::new();
+///     let first = idle.insert("first connection", Duration::from_millis(10));
+///     idle.insert("second connection", Duration::from_millis(20));
+///     let third = idle.insert("third connection", Duration::from_millis(30));
+///
+///     // The first connection received data, so it starts over.
+///     idle.reset(first, Duration::from_millis(40));
+///     // The third connection was closed by the peer.
+///     idle.remove(third);
+///
+///     let mut closed = Vec::new();
+///     while let Some(expired) = idle.next_expired().await {
+///         closed.push(expired.into_inner());
+///     }
+///     assert_eq!(closed, ["second connection", "first connection"]);
+/// });
+/// ```
+pub struct DelayQueue<T> {
+    /// The items that haven't expired yet, and their deadlines, by the ID of their key.
+    items: HashMap<u64, (T, Instant)>,
+    /// The deadline and the ID of every item, to find the earliest one.
+    deadlines: BTreeSet<(Instant, u64)>,
+    /// The ID of the next key.
+    next_id: u64,
+    /// The driver of the runtime the first item was inserted on.
+    driver: Option<Arc<Driver>>,
+    /// Completes when the earliest item expires, once the queue has been polled.
+    sleep: Option<Sleep>,
+}
+
+/// Identifies an item in a [`DelayQueue`], returned when it is inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(u64);
+
+/// An item of a [`DelayQueue`] whose delay has passed.
+#[derive(Debug)]
+pub struct Expired<T> {
+    value: T,
+    key: Key,
+    deadline: Instant,
+}
+
+impl<T> DelayQueue<T> {
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        Self {
+            items: HashMap::new(),
+            deadlines: BTreeSet::new(),
+            next_id: 0,
+            driver: None,
+            sleep: None,
+        }
+    }
+
+    /// Insert `value`, to be returned once `timeout` has passed.
+    ///
+    /// # Panics
+    ///
+    /// Like [`sleep`], this panics if called from outside of a
+    /// [`Runtime`](crate::runtime::Runtime), or from within one whose time driver isn't enabled.
+    #[track_caller]
+    pub fn insert(&mut self, value: T, timeout: Duration) -> Key {
+        let deadline = after(self.driver().now(), timeout);
+        self.insert_at(value, deadline)
+    }
+
+    /// Insert `value`, to be returned once `deadline` has passed.
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`insert`](DelayQueue::insert).
+    #[track_caller]
+    pub fn insert_at(&mut self, value: T, deadline: Instant) -> Key {
+        self.driver();
+        let id = self.next_id;
+        self.next_id += 1;
+        self.items.insert(id, (value, deadline));
+        self.deadlines.insert((deadline, id));
+        Key(id)
+    }
+
+    /// Remove the item of `key` and return it, or `None` if it has expired or been removed
+    /// already.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let (value, deadline) = self.items.remove(&key.0)?;
+        self.deadlines.remove(&(deadline, key.0));
+        Some(value)
+    }
+
+    /// Return the item of `key` once `timeout` has passed from now, instead of at its old
+    /// deadline.
+    ///
+    /// Returns `false` and does nothing if the item has expired or been removed already.
+    pub fn reset(&mut self, key: Key, timeout: Duration) -> bool {
+        // There is no driver before the first item has been inserted, and no item to reset either.
+        let Some(driver) = &self.driver else {
+            return false;
+        };
+        let deadline = after(driver.now(), timeout);
+        self.reset_at(key, deadline)
+    }
+
+    /// Return the item of `key` once `deadline` has passed, instead of at its old deadline.
+    ///
+    /// Returns `false` and does nothing if the item has expired or been removed already.
+    pub fn reset_at(&mut self, key: Key, deadline: Instant) -> bool {
+        let Some((_, old)) = self.items.get_mut(&key.0) else {
+            return false;
+        };
+        self.deadlines.remove(&(*old, key.0));
+        self.deadlines.insert((deadline, key.0));
+        *old = deadline;
+        true
+    }
+
+    /// The item of `key`, if it hasn't expired or been removed yet.
+    pub fn get(&self, key: Key) -> Option<&T> {
+        self.items.get(&key.0).map(|(value, _)| value)
+    }
+
+    /// The deadline of the item of `key`, if it hasn't expired or been removed yet.
+    pub fn deadline(&self, key: Key) -> Option<Instant> {
+        self.items.get(&key.0).map(|&(_, deadline)| deadline)
+    }
+
+    /// The number of items that haven't expired yet.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the queue holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Remove all items.
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.deadlines.clear();
+        self.sleep = None;
+    }
+
+    /// The driver of the runtime the first item was inserted on, or of the current one.
+    #[track_caller]
+    fn driver(&mut self) -> &Arc<Driver> {
+        if self.driver.is_none() {
+            self.driver = Some(Driver::current());
+        }
+        self.driver.as_ref().unwrap()
+    }
+
+    /// Wait for the next item to expire, and remove and return it, or `None` if the queue is
+    /// empty.
+    ///
+    /// Once `None` has been returned, inserting more items makes the queue return them again.
+    pub async fn next_expired(&mut self) -> Option<Expired<T>> {
+        std::future::poll_fn(|cx| self.poll_expired(cx)).await
+    }
+
+    /// Poll for the next item to expire, for futures written by hand, see
+    /// [`next_expired`](DelayQueue::next_expired).
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        let Some(&(deadline, id)) = self.deadlines.first() else {
+            self.sleep = None;
+            return Poll::Ready(None);
+        };
+
+        // Set when the first item was inserted.
+        let driver = self.driver.as_ref().unwrap();
+        if driver.now() < deadline {
+            // Follow the earliest deadline, which may have changed since the queue was polled last.
+            match &mut self.sleep {
+                Some(sleep) if sleep.deadline != deadline => sleep.reset(deadline),
+                Some(_) => {}
+                None => self.sleep = Some(Sleep::new(driver.clone(), deadline)),
+            }
+            let sleep = self.sleep.as_mut().unwrap();
+            if Pin::new(sleep).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        self.deadlines.pop_first();
+        let (value, _) = self.items.remove(&id).unwrap();
+        Poll::Ready(Some(Expired {
+            value,
+            key: Key(id),
+            deadline,
+        }))
+    }
+}
+
+// The items are never pinned.
+impl<T> Unpin for DelayQueue<T> {}
+
+impl<T> Stream for DelayQueue<T> {
+    type Item = Expired<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        self.get_mut().poll_expired(cx)
+    }
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for DelayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DelayQueue")
+            .field("len", &self.len())
+            .field(
+                "next_deadline",
+                &self.deadlines.first().map(|&(deadline, _)| deadline),
+            )
+            .finish()
+    }
+}
+
+impl<T> Expired<T> {
+    /// The key the item was inserted with.
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// The deadline the item had when it expired.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// The item itself.
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    /// Take the item itself.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// The time driver of a [`Runtime`](crate::runtime::Runtime), which wakes the timers whose
+/// deadline has passed.
+///
+/// The timers are kept in a hierarchical timing wheel, or in a binary heap with the `timer_heap`
+/// feature. The driver has no thread of its own: a thread of the runtime that runs out of work
+/// fires the timers that are due, and parks only until the earliest deadline, see
+/// [`next_timeout`](Driver::next_timeout). Registering a timer that is due earlier than all others
+/// unparks those threads, so they can park for less.
+///
+/// With a coarse tick, see [`Builder::timer_tick`](crate::runtime::Builder::timer_tick), a thread
+/// of its own fires the timers that are due once every tick instead, and the threads of the
+/// runtime park until they are woken.
+pub(crate) struct Driver {
+    timers: Mutex<Timers>,
+    /// Unparks the threads of the runtime, when the earliest deadline has moved closer.
+    unpark: Box<dyn Fn() + Send + Sync>,
+    /// How often the tick thread fires the timers, if there is one.
+    tick: Option<Duration>,
+    /// Notified when the driver shuts down, to stop the tick thread.
+    shut_down: Condvar,
+    /// The tick thread, until the driver shuts down.
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+/// The timers registered with a [`Driver`].
+struct Timers {
+    /// The timers that haven't fired yet, by ID.
+    queue: Queue,
+    /// The ID of the next timer to be registered.
+    next_id: u64,
+    clock: Clock,
+    stats: Stats,
+    /// Set once the runtime shuts down, to stop the tick thread.
+    shutdown: bool,
+}
+
+/// The clock of a [`Driver`], which can be paused.
+///
+/// The time is kept as an [`Instant`] the clock was at, and the real instant at which the clock
+/// moved on from there, unless it is paused.
+struct Clock {
+    /// The time of the clock, when it was last paused or resumed, plus how far it was advanced.
+    base: Instant,
+    /// The real instant at which the clock was at `base`, or `None` while it is paused.
+    unfrozen: Option<Instant>,
+}
+
+impl Clock {
+    fn now(&self) -> Instant {
+        match self.unfrozen {
+            Some(unfrozen) => self.base + unfrozen.elapsed(),
+            None => self.base,
+        }
+    }
+}
+
+/// What a [`Driver`] has done so far, as reported by [`RuntimeMetrics`].
+///
+/// [`RuntimeMetrics`]: crate::runtime::RuntimeMetrics
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Stats {
+    /// The number of timers that were registered.
+    pub(crate) registered: u64,
+    /// The number of timers that fired.
+    pub(crate) fired: u64,
+    /// The number of timers that were cancelled before they fired.
+    pub(crate) cancelled: u64,
+    /// The number of times a timer was moved within the data structure the timers are kept in.
+    pub(crate) moves: u64,
+    /// The number of timers that haven't fired yet.
+    pub(crate) active: usize,
+}
+
+impl Driver {
+    /// Create a driver that calls `unpark` when a timer is registered that is due earlier than
+    /// all others, or that fires the timers every `tick` once [started](Driver::start_ticking).
+    pub(crate) fn new(unpark: impl Fn() + Send + Sync + 'static, tick: Option<Duration>) -> Self {
+        let start = Instant::now();
+        Self {
+            timers: Mutex::new(Timers {
+                queue: Queue::new(start),
+                next_id: 0,
+                clock: Clock {
+                    base: start,
+                    unfrozen: Some(start),
+                },
+                stats: Stats::default(),
+                shutdown: false,
+            }),
+            unpark: Box::new(unpark),
+            tick,
+            shut_down: Condvar::new(),
+            thread: Mutex::new(None),
+        }
+    }
+
+    /// Start the tick thread, if the driver has a tick.
+    ///
+    /// The thread only holds on to the driver while it fires the timers, so it also stops once the
+    /// runtime is dropped without being shut down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread can't be spawned.
+    pub(crate) fn start_ticking(self: &Arc<Self>, thread_name: String, stack_size: Option<usize>) {
+        let Some(tick) = self.tick else {
+            return;
+        };
+        let mut builder = thread::Builder::new().name(thread_name);
+        if let Some(size) = stack_size {
+            builder = builder.stack_size(size);
+        }
+        let driver = Arc::downgrade(self);
+        let handle = builder
+            .spawn(move || Self::run_ticks(&driver, tick))
+            .expect("failed to spawn the timer thread");
+        *self.thread.lock() = Some(handle);
+    }
+
+    /// The loop run by the tick thread, until the driver shuts down.
+    fn run_ticks(driver: &Weak<Self>, tick: Duration) {
+        while let Some(driver) = driver.upgrade() {
+            let timers = driver.timers.lock();
+            if timers.shutdown {
+                return;
+            }
+            let (timers, _) = driver.shut_down.wait_timeout(timers, tick);
+            if timers.shutdown {
+                return;
+            }
+            drop(timers);
+            driver.fire_due();
+        }
+    }
+
+    /// Get the driver of the runtime the current thread is in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread isn't in a runtime, or if its time driver isn't enabled.
+    #[track_caller]
+    fn current() -> Arc<Self> {
+        let Ok(handle) = Handle::try_current() else {
+            panic!("there is no time driver, `time` must be used from within a `Runtime`");
+        };
+        match handle.time_driver() {
+            Some(driver) => driver,
+            None => panic!(
+                "the time driver of the current `Runtime` is disabled, enable it with \
+                 `Builder::enable_time`"
+            ),
+        }
+    }
+
+    /// The current instant of the driver's clock.
+    fn now(&self) -> Instant {
+        self.timers.lock().clock.now()
+    }
+
+    /// The deadline `duration` from now, by the driver's clock.
+    pub(crate) fn deadline_after(&self, duration: Duration) -> Instant {
+        after(self.now(), duration)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.timers.lock().clock.unfrozen.is_none()
+    }
+
+    fn pause(&self) {
+        let mut timers = self.timers.lock();
+        timers.clock.base = timers.clock.now();
+        timers.clock.unfrozen = None;
+    }
+
+    fn resume(&self) {
+        self.timers.lock().clock.unfrozen = Some(Instant::now());
+        // The threads of the runtime park until the next timer by the real time again.
+        self.unpark(true);
+    }
+
+    /// Move the paused clock ahead by `duration`, and wake the timers that are due by then.
+    fn advance(&self, duration: Duration) {
+        let mut timers = self.timers.lock();
+        timers.clock.base = after(timers.clock.base, duration);
+        drop(timers);
+        self.fire_due();
+    }
+
+    /// Wake the timers whose deadline has passed, and return whether there were any.
+    pub(crate) fn fire_due(&self) -> bool {
+        let due = Self::take_due(&mut self.timers.lock());
+        // Woken outside of the lock, since waking a task may register another timer.
+        let fired = !due.is_empty();
+        due.into_iter().for_each(Waker::wake);
+        fired
+    }
+
+    /// How long a thread of the runtime that has run out of work parks at most, to fire the next
+    /// timer in time, or `None` if there are no timers.
+    ///
+    /// While the clock is paused, waiting for the real time to pass doesn't bring the next deadline
+    /// closer, so this is `None` as well, and so it is with a tick thread that fires the timers.
+    pub(crate) fn next_timeout(&self) -> Option<Duration> {
+        if self.tick.is_some() {
+            return None;
+        }
+        let timers = self.timers.lock();
+        timers.clock.unfrozen?;
+        let deadline = timers.queue.next_deadline()?;
+        Some(deadline.saturating_duration_since(timers.clock.now()))
+    }
+
+    /// Move the paused clock ahead to the next deadline at which timers are due, and wake them.
+    ///
+    /// Called by a current-thread executor that has nothing else to do. Returns whether any
+    /// timers were woken, which is never the case while the clock runs.
+    pub(crate) fn advance_to_next_timer(&self) -> bool {
+        let mut timers = self.timers.lock();
+        if timers.clock.unfrozen.is_some() {
+            return false;
+        }
+        // The next deadline of the queue may be one at which its timers are only moved around.
+        while let Some(deadline) = timers.queue.next_deadline() {
+            timers.clock.base = timers.clock.base.max(deadline);
+            let due = Self::take_due(&mut timers);
+            if !due.is_empty() {
+                drop(timers);
+                due.into_iter().for_each(Waker::wake);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Remove the timers that are due by the clock, and return their wakers.
+    fn take_due(timers: &mut Timers) -> Vec<Waker> {
+        let mut due = Vec::new();
+        let now = timers.clock.now();
+        timers.queue.take_due(now, &mut due);
+        timers.stats.fired += due.len() as u64;
+        due
+    }
+
+    /// Register the timer `entry` to wake `waker` at `deadline`, or update its waker if it is
+    /// registered already.
+    fn register(&self, deadline: Instant, entry: &mut Option<u64>, waker: &Waker) {
+        let mut timers = self.timers.lock();
+        if let Some(id) = *entry {
+            if let Some(registered) = timers.queue.waker_mut(id) {
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+                return;
+            }
+        }
+
+        // The timer hasn't been registered yet, or it was woken before its future noticed that its
+        // deadline has passed.
+        let (id, earliest) = Self::insert(&mut timers, deadline, waker.clone());
+        *entry = Some(id);
+        drop(timers);
+        self.unpark(earliest);
+    }
+
+    /// Move the timer `entry` to `deadline`, if it is registered and hasn't been woken yet.
+    ///
+    /// Otherwise its future registers a new timer when it is polled next.
+    fn reset(&self, deadline: Instant, entry: &mut Option<u64>) {
+        let Some(id) = entry.take() else {
+            return;
+        };
+        let mut timers = self.timers.lock();
+        let Some(waker) = timers.queue.remove(id) else {
+            return;
+        };
+        // The timer is moved by cancelling it and registering a new one, since its ID may still
+        // be in the queue.
+        timers.stats.cancelled += 1;
+        let (id, earliest) = Self::insert(&mut timers, deadline, waker);
+        *entry = Some(id);
+        drop(timers);
+        self.unpark(earliest);
+    }
+
+    /// Register a timer that wakes `waker` at `deadline`, without a future that can cancel it.
+    pub(crate) fn register_waker(&self, deadline: Instant, waker: Waker) {
+        let mut timers = self.timers.lock();
+        let (_, earliest) = Self::insert(&mut timers, deadline, waker);
+        drop(timers);
+        self.unpark(earliest);
+    }
+
+    /// Unpark the threads of the runtime if a timer was registered that is now the earliest one,
+    /// unless they don't park until the next timer since a tick thread fires them.
+    fn unpark(&self, earliest: bool) {
+        if earliest && self.tick.is_none() {
+            (self.unpark)();
+        }
+    }
+
+    /// Insert a new timer, and return its ID and whether it is now the earliest one.
+    ///
+    /// The runtime must be unparked if it is, once the lock is released, since the runtime takes
+    /// its own locks before the driver's.
+    fn insert(timers: &mut Timers, deadline: Instant, waker: Waker) -> (u64, bool) {
+        let id = timers.next_id;
+        timers.next_id += 1;
+        timers.stats.registered += 1;
+        let earliest = timers
+            .queue
+            .next_deadline()
+            .is_none_or(|next| deadline < next);
+        timers.queue.insert(id, deadline, waker);
+        (id, earliest)
+    }
+
+    /// Remove the timer `id`, if it hasn't been woken yet.
+    fn cancel(&self, id: u64) {
+        let waker = {
+            let mut timers = self.timers.lock();
+            let waker = timers.queue.remove(id);
+            if waker.is_some() {
+                timers.stats.cancelled += 1;
+            }
+            waker
+        };
+        // Dropped outside of the lock, like any waker that may be the last reference to a task.
+        drop(waker);
+    }
+
+    /// What the driver has done so far.
+    pub(crate) fn stats(&self) -> Stats {
+        let timers = self.timers.lock();
+        Stats {
+            moves: timers.queue.moves(),
+            active: timers.queue.len(),
+            ..timers.stats
+        }
+    }
+
+    /// Stop the tick thread, if any, and drop the timers that haven't been woken, once the
+    /// runtime has shut down.
+    ///
+    /// Their futures never complete anymore.
+    pub(crate) fn shutdown(&self) {
+        let wakers = {
+            let mut timers = self.timers.lock();
+            timers.shutdown = true;
+            self.shut_down.notify_one();
+            timers.queue.drain()
+        };
+        // Dropped outside of the lock, like any waker that may be the last reference to a task.
+        drop(wakers);
+
+        let handle = self.thread.lock().take();
+        if let Some(handle) = handle {
+            // The last reference to the runtime may be dropped by a waker woken on the thread.
+            if handle.thread().id() != thread::current().id() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! The binary heap the time driver keeps its timers in with the `timer_heap` feature.
+
+use std::collections::HashMap;
+use std::task::Waker;
+use std::time::Instant;
+
+/// How many cancelled timers the heap holds at least before it is rebuilt.
+const SLACK: usize = 64;
+
+/// Timers kept in a binary min-heap, ordered by their deadline.
+///
+/// Inserting a timer and firing the earliest one takes a logarithmic number of swaps, which is
+/// what [`moves`](Heap::moves) counts. A cancelled timer is only removed from the map of wakers,
+/// and left in the heap until it comes up, which keeps cancelling cheap. Once the heap holds more
+/// cancelled timers than live ones, it is rebuilt.
+pub(super) struct Heap {
+    /// The deadline and ID of every timer, and of cancelled timers that haven't come up yet.
+    heap: Vec<(Instant, u64)>,
+    /// The waker of every timer that hasn't fired or been cancelled, by ID.
+    wakers: HashMap<u64, Waker>,
+    /// The number of times a timer was moved within the heap.
+    moves: u64,
+}
+
+impl Heap {
+    pub(super) fn new(_start: Instant) -> Self {
+        Self {
+            heap: Vec::new(),
+            wakers: HashMap::new(),
+            moves: 0,
+        }
+    }
+
+    /// Insert the timer `id`, which wakes `waker` at `deadline`.
+    pub(super) fn insert(&mut self, id: u64, deadline: Instant, waker: Waker) {
+        self.wakers.insert(id, waker);
+        self.heap.push((deadline, id));
+        self.sift_up(self.heap.len() - 1);
+    }
+
+    /// Get the waker of the timer `id`, if it hasn't fired yet.
+    pub(super) fn waker_mut(&mut self, id: u64) -> Option<&mut Waker> {
+        self.wakers.get_mut(&id)
+    }
+
+    /// Remove the timer `id`, and return its waker if it hasn't fired yet.
+    pub(super) fn remove(&mut self, id: u64) -> Option<Waker> {
+        let waker = self.wakers.remove(&id);
+        if self.heap.len() > 2 * self.wakers.len() + SLACK {
+            self.rebuild();
+        }
+        waker
+    }
+
+    /// The earliest deadline of the timers, if there are any.
+    ///
+    /// This may be the deadline of a timer that was cancelled, which is only noticed once it comes
+    /// up.
+    pub(super) fn next_deadline(&self) -> Option<Instant> {
+        self.heap.first().map(|&(deadline, _)| deadline)
+    }
+
+    /// Remove the timers that are due at `now`, and add their wakers to `due`.
+    pub(super) fn take_due(&mut self, now: Instant, due: &mut Vec<Waker>) {
+        while let Some(&(deadline, id)) = self.heap.first() {
+            if deadline > now {
+                break;
+            }
+            let last = self.heap.len() - 1;
+            self.heap.swap(0, last);
+            self.heap.pop();
+            self.sift_down(0);
+            due.extend(self.wakers.remove(&id));
+        }
+    }
+
+    /// The number of timers that haven't fired yet.
+    pub(super) fn len(&self) -> usize {
+        self.wakers.len()
+    }
+
+    /// The number of times a timer was moved within the heap, by swapping it with its parent or
+    /// one of its children.
+    pub(super) fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    /// Remove all timers, and return their wakers.
+    pub(super) fn drain(&mut self) -> Vec<Waker> {
+        self.heap.clear();
+        self.wakers.drain().map(|(_, waker)| waker).collect()
+    }
+
+    /// Drop the cancelled timers from the heap, and restore its order.
+    fn rebuild(&mut self) {
+        self.heap.retain(|(_, id)| self.wakers.contains_key(id));
+        for index in (0..self.heap.len() / 2).rev() {
+            self.sift_down(index);
+        }
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.heap[parent] <= self.heap[index] {
+                break;
+            }
+            self.heap.swap(parent, index);
+            self.moves += 1;
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let mut smallest = index;
+            for child in [2 * index + 1, 2 * index + 2] {
+                if child < self.heap.len() && self.heap[child] < self.heap[smallest] {
+                    smallest = child;
+                }
+            }
+            if smallest == index {
+                break;
+            }
+            self.heap.swap(smallest, index);
+            self.moves += 1;
+            index = smallest;
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! The hierarchical timing wheel the time driver keeps its timers in by default.
+
+use std::collections::HashMap;
+use std::mem;
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+/// The number of slots of each level.
+const SLOTS: usize = 64;
+/// The number of bits of a tick that select the slot within a level.
+const SLOT_BITS: u32 = SLOTS.trailing_zeros();
+/// The number of levels.
+const LEVELS: usize = 6;
+/// The number of ticks the wheel spans, about two years with a tick of a millisecond.
+const SPAN: u64 = 1 << (SLOT_BITS as usize * LEVELS);
+
+/// Timers kept in a hierarchical timing wheel, with a resolution of a millisecond.
+///
+/// Every level is a ring of 64 slots. A slot of the lowest level holds the timers due within one
+/// tick, a slot of the next level those due within 64 ticks, and so on. A timer goes into the
+/// lowest level whose slots are still fine enough to tell it apart from the current tick. Once a
+/// slot of a higher level is reached, its timers cascade down into the lower levels, until they
+/// are due. Inserting, cancelling and firing a timer is constant time, and each timer cascades at
+/// most once per level.
+///
+/// A deadline is rounded up to the next tick, so a timer may fire up to a tick late, but never
+/// early.
+pub(super) struct Wheel {
+    /// The instant of tick zero.
+    start: Instant,
+    /// The tick up to which the timers have been fired.
+    elapsed: u64,
+    levels: [Level; LEVELS],
+    /// The timers that were already due when they were inserted.
+    expired: HashMap<u64, Entry>,
+    /// Where each timer is, by ID.
+    index: HashMap<u64, Location>,
+    /// The number of times a timer cascaded to a lower level.
+    moves: u64,
+}
+
+/// One ring of slots of a [`Wheel`].
+struct Level {
+    /// A bit for every slot that holds timers.
+    occupied: u64,
+    slots: [HashMap<u64, Entry>; SLOTS],
+}
+
+/// A timer in a [`Wheel`].
+struct Entry {
+    /// The tick the timer is due at.
+    tick: u64,
+    waker: Waker,
+}
+
+/// Where a timer is in a [`Wheel`].
+#[derive(Clone, Copy)]
+enum Location {
+    Slot { level: usize, slot: usize },
+    Expired,
+}
+
+/// The next slot of a [`Wheel`] that holds timers, and the tick at which it is reached.
+struct Expiration {
+    level: usize,
+    slot: usize,
+    tick: u64,
+}
+
+impl Wheel {
+    pub(super) fn new(start: Instant) -> Self {
+        Self {
+            start,
+            elapsed: 0,
+            levels: std::array::from_fn(|_| Level {
+                occupied: 0,
+                slots: std::array::from_fn(|_| HashMap::new()),
+            }),
+            expired: HashMap::new(),
+            index: HashMap::new(),
+            moves: 0,
+        }
+    }
+
+    /// Insert the timer `id`, which wakes `waker` at `deadline`.
+    pub(super) fn insert(&mut self, id: u64, deadline: Instant, waker: Waker) {
+        let tick = self.tick_at(deadline);
+        self.insert_entry(id, Entry { tick, waker });
+    }
+
+    /// Get the waker of the timer `id`, if it hasn't fired yet.
+    pub(super) fn waker_mut(&mut self, id: u64) -> Option<&mut Waker> {
+        let entry = match *self.index.get(&id)? {
+            Location::Slot { level, slot } => self.levels[level].slots[slot].get_mut(&id),
+            Location::Expired => self.expired.get_mut(&id),
+        };
+        entry.map(|entry| &mut entry.waker)
+    }
+
+    /// Remove the timer `id`, and return its waker if it hasn't fired yet.
+    pub(super) fn remove(&mut self, id: u64) -> Option<Waker> {
+        let entry = match self.index.remove(&id)? {
+            Location::Slot { level, slot } => {
+                let level = &mut self.levels[level];
+                let entry = level.slots[slot].remove(&id);
+                if level.slots[slot].is_empty() {
+                    level.occupied &= !(1 << slot);
+                }
+                entry
+            }
+            Location::Expired => self.expired.remove(&id),
+        };
+        entry.map(|entry| entry.waker)
+    }
+
+    /// The earliest instant at which timers may be due, if there are any.
+    ///
+    /// This may be the instant a slot of a higher level is reached, whose timers only cascade
+    /// down then and are due later.
+    pub(super) fn next_deadline(&self) -> Option<Instant> {
+        if !self.expired.is_empty() {
+            return Some(self.instant_of(self.elapsed));
+        }
+        self.next_expiration()
+            .map(|expiration| self.instant_of(expiration.tick))
+    }
+
+    /// Remove the timers that are due at `now`, and add their wakers to `due`.
+    pub(super) fn take_due(&mut self, now: Instant, due: &mut Vec<Waker>) {
+        for (id, entry) in self.expired.drain() {
+            self.index.remove(&id);
+            due.push(entry.waker);
+        }
+
+        let now = self.tick_at_or_before(now);
+        while let Some(expiration) = self.next_expiration() {
+            if expiration.tick > now {
+                break;
+            }
+            self.elapsed = expiration.tick;
+            let level = &mut self.levels[expiration.level];
+            level.occupied &= !(1 << expiration.slot);
+            let entries = mem::take(&mut level.slots[expiration.slot]);
+            for (id, entry) in entries {
+                if entry.tick <= self.elapsed {
+                    self.index.remove(&id);
+                    due.push(entry.waker);
+                } else {
+                    self.moves += 1;
+                    self.insert_entry(id, entry);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+    }
+
+    /// The number of timers that haven't fired yet.
+    pub(super) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// The number of times a timer was moved within the wheel, by cascading to a lower level.
+    pub(super) fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    /// Remove all timers, and return their wakers.
+    pub(super) fn drain(&mut self) -> Vec<Waker> {
+        self.index.clear();
+        let expired = self.expired.drain().map(|(_, entry)| entry.waker);
+        let slots = self.levels.iter_mut().flat_map(|level| {
+            level.occupied = 0;
+            level
+                .slots
+                .iter_mut()
+                .flat_map(|slot| slot.drain().map(|(_, entry)| entry.waker))
+        });
+        expired.chain(slots).collect()
+    }
+
+    fn insert_entry(&mut self, id: u64, entry: Entry) {
+        if entry.tick <= self.elapsed {
+            self.index.insert(id, Location::Expired);
+            self.expired.insert(id, entry);
+            return;
+        }
+
+        let level = level_for(self.elapsed, entry.tick);
+        let slot = (entry.tick >> (SLOT_BITS as usize * level)) as usize % SLOTS;
+        self.index.insert(id, Location::Slot { level, slot });
+        let level = &mut self.levels[level];
+        level.occupied |= 1 << slot;
+        level.slots[slot].insert(id, entry);
+    }
+
+    /// Find the next slot that holds timers, looking at the lowest level first, since its slots
+    /// are all reached before the next slot of a higher level.
+    fn next_expiration(&self) -> Option<Expiration> {
+        self.levels.iter().enumerate().find_map(|(index, level)| {
+            if level.occupied == 0 {
+                return None;
+            }
+            let slot_range = 1u64 << (SLOT_BITS as usize * index);
+            let level_range = slot_range << SLOT_BITS;
+            let now_slot = (self.elapsed / slot_range) as usize % SLOTS;
+            let occupied = level.occupied.rotate_right(now_slot as u32);
+            let slot = (occupied.trailing_zeros() as usize + now_slot) % SLOTS;
+
+            let level_start = self.elapsed & !(level_range - 1);
+            let mut tick = level_start + slot as u64 * slot_range;
+            // A slot before the current one is only reached when the ring wraps around.
+            if tick <= self.elapsed {
+                tick += level_range;
+            }
+            Some(Expiration {
+                level: index,
+                slot,
+                tick,
+            })
+        })
+    }
+
+    /// The first tick at or after `instant`.
+    fn tick_at(&self, instant: Instant) -> u64 {
+        let since_start = instant.saturating_duration_since(self.start);
+        let ticks = since_start.as_nanos().div_ceil(1_000_000);
+        ticks.try_into().unwrap_or(u64::MAX)
+    }
+
+    /// The last tick at or before `instant`.
+    fn tick_at_or_before(&self, instant: Instant) -> u64 {
+        let ticks = instant.saturating_duration_since(self.start).as_millis();
+        ticks.try_into().unwrap_or(u64::MAX)
+    }
+
+    fn instant_of(&self, tick: u64) -> Instant {
+        self.start + Duration::from_millis(tick)
+    }
+}
+
+/// The level a timer due at `tick` goes into: the one of the highest bit in which `tick` differs
+/// from the `elapsed` one.
+///
+/// A timer beyond the span of the wheel goes into the highest level, and keeps cascading into it
+/// until it is within the span.
+fn level_for(elapsed: u64, tick: u64) -> usize {
+    let mut masked = (elapsed ^ tick) | (SLOTS as u64 - 1);
+    if masked >= SPAN {
+        masked = SPAN - 1;
+    }
+    let significant = 63 - masked.leading_zeros() as usize;
+    significant / SLOT_BITS as usize
+}
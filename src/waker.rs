@@ -0,0 +1,379 @@
+//! Helpers to create wakers, for example to poll a hand-written [`Future`](std::future::Future)
+//! in a test without an executor.
+//!
+//! The wakers the executor hands to the futures it polls are built the same way: everything they
+//! can point at implements [`Wake`], and is turned into a [`Waker`] with [`Waker::from`], which
+//! needs no unsafe code. With the `raw_waker` feature the waker's vtable is written out by hand
+//! instead, to show what `Waker::from` does under the hood.
+//!
+//! [`CountingWaker`] records what a future does with its waker, to assert on it in a test.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+
+/// Create a waker that does nothing when woken.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::waker::noop_waker;
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::task::{Context, Poll};
+///
+/// let waker = noop_waker();
+/// let mut context = Context::from_waker(&waker);
+///
+/// let future = pin!(async { 42 });
+/// assert_eq!(future.poll(&mut context), Poll::Ready(42));
+/// ```
+pub fn noop_waker() -> Waker {
+    Waker::noop().clone()
+}
+
+/// Get a reference to a waker that does nothing when woken, without creating one.
+pub fn noop_waker_ref() -> &'static Waker {
+    Waker::noop()
+}
+
+/// Create a waker that calls `wake` every time it is woken.
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::waker::waker_fn;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let wakes = Arc::new(AtomicUsize::new(0));
+/// let waker = waker_fn({
+///     let wakes = wakes.clone();
+///     move || {
+///         wakes.fetch_add(1, Ordering::SeqCst);
+///     }
+/// });
+///
+/// waker.wake_by_ref();
+/// waker.clone().wake();
+/// assert_eq!(wakes.load(Ordering::SeqCst), 2);
+/// ```
+pub fn waker_fn<F>(wake: F) -> Waker
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    Waker::from(Arc::new(WakerFn(wake)))
+}
+
+/// What a waker created by [`waker_fn`] points at.
+struct WakerFn<F>(F);
+
+impl<F: Fn() + Send + Sync + 'static> Wake for WakerFn<F> {
+    fn wake(self: Arc<Self>) {
+        (self.0)();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        (self.0)();
+    }
+}
+
+/// Something that can be woken through an `Arc`, like [`Wake`], for types written in the style of
+/// the `futures` crate.
+///
+/// Only [`wake_by_ref`](ArcWake::wake_by_ref) has to be implemented. Turn an `Arc` of it into a
+/// [`Waker`] with [`waker`].
+///
+/// # Examples
+///
+/// ```
+/// use mini_executor::waker::{self, ArcWake};
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Default)]
+/// struct Flag(AtomicBool);
+///
+/// impl ArcWake for Flag {
+///     fn wake_by_ref(arc_self: &Arc<Self>) {
+///         arc_self.0.store(true, Ordering::SeqCst);
+///     }
+/// }
+///
+/// let flag = Arc::new(Flag::default());
+/// let waker = waker::waker(flag.clone());
+///
+/// waker.wake();
+/// assert!(flag.0.load(Ordering::SeqCst));
+/// ```
+pub trait ArcWake: Send + Sync + 'static {
+    /// Wake the task this points at, without consuming the `Arc`.
+    fn wake_by_ref(arc_self: &Arc<Self>);
+
+    /// Wake the task this points at.
+    fn wake(self: Arc<Self>) {
+        Self::wake_by_ref(&self);
+    }
+}
+
+/// Create a waker that calls [`ArcWake::wake`] on `wake` when it is woken.
+pub fn waker<W: ArcWake>(wake: Arc<W>) -> Waker {
+    Waker::from(Arc::new(ArcWaker(wake)))
+}
+
+/// What a waker created by [`waker`] points at.
+///
+/// `Wake` can't be implemented for every [`ArcWake`] type directly, so this forwards to it.
+struct ArcWaker<W>(Arc<W>);
+
+impl<W: ArcWake> Wake for ArcWaker<W> {
+    fn wake(self: Arc<Self>) {
+        // Hand the `Arc` over if this was the last waker, so `W` can consume it.
+        match Arc::try_unwrap(self) {
+            Ok(ArcWaker(wake)) => W::wake(wake),
+            Err(this) => W::wake_by_ref(&this.0),
+        }
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        W::wake_by_ref(&self.0);
+    }
+}
+
+/// A source of wakers that counts what is done with them, to assert how a future treats its
+/// waker in a test.
+///
+/// Every waker created by [`waker`](CountingWaker::waker), and every clone of one, counts towards
+/// the same `CountingWaker`. All of them [`will_wake`](Waker::will_wake) each other.
+///
+/// # Examples
+///
+/// [`AtomicWaker`](crate::sync::AtomicWaker) only clones a waker that is registered for the first
+/// time, and hands its own clone over when woken:
+///
+/// ```
+/// use mini_executor::sync::AtomicWaker;
+/// use mini_executor::waker::CountingWaker;
+///
+/// let counter = CountingWaker::new();
+/// let waker = counter.waker();
+///
+/// let slot = AtomicWaker::new();
+/// slot.register(&waker);
+/// slot.register(&waker);
+/// assert_eq!(counter.clones(), 1);
+///
+/// slot.wake();
+/// assert_eq!(counter.wakes(), 1);
+/// assert_eq!(counter.wakes_by_ref(), 0);
+///
+/// drop(waker);
+/// assert_eq!(counter.drops(), 1);
+/// assert_eq!(counter.alive(), 0);
+/// ```
+#[derive(Default)]
+pub struct CountingWaker {
+    counts: Arc<WakerCounts>,
+}
+
+/// What the wakers of a [`CountingWaker`] point at.
+#[derive(Default)]
+struct WakerCounts {
+    created: AtomicUsize,
+    clones: AtomicUsize,
+    wakes: AtomicUsize,
+    wakes_by_ref: AtomicUsize,
+    drops: AtomicUsize,
+}
+
+impl CountingWaker {
+    /// Create a `CountingWaker` with all counts at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a waker that counts towards this `CountingWaker`.
+    ///
+    /// This counts as neither a clone nor a wake, only towards [`alive`](CountingWaker::alive).
+    pub fn waker(&self) -> Waker {
+        self.counts.created.fetch_add(1, Ordering::SeqCst);
+        counting::waker(self.counts.clone())
+    }
+
+    /// How many times one of the wakers has been cloned.
+    pub fn clones(&self) -> usize {
+        self.counts.clones.load(Ordering::SeqCst)
+    }
+
+    /// How many times one of the wakers has been woken by value, with [`Waker::wake`].
+    pub fn wakes(&self) -> usize {
+        self.counts.wakes.load(Ordering::SeqCst)
+    }
+
+    /// How many times one of the wakers has been woken by reference, with [`Waker::wake_by_ref`].
+    pub fn wakes_by_ref(&self) -> usize {
+        self.counts.wakes_by_ref.load(Ordering::SeqCst)
+    }
+
+    /// How many times one of the wakers has been woken, by value or by reference.
+    pub fn woken(&self) -> usize {
+        self.wakes() + self.wakes_by_ref()
+    }
+
+    /// How many of the wakers have been dropped without being woken by value.
+    pub fn drops(&self) -> usize {
+        self.counts.drops.load(Ordering::SeqCst)
+    }
+
+    /// How many of the wakers are still around: the ones that have been created or cloned, and not
+    /// been dropped or woken by value since.
+    pub fn alive(&self) -> usize {
+        let counts = &self.counts;
+        let made = counts.created.load(Ordering::SeqCst) + counts.clones.load(Ordering::SeqCst);
+        made - counts.drops.load(Ordering::SeqCst) - counts.wakes.load(Ordering::SeqCst)
+    }
+}
+
+impl fmt::Debug for CountingWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountingWaker")
+            .field("clones", &self.clones())
+            .field("wakes", &self.wakes())
+            .field("wakes_by_ref", &self.wakes_by_ref())
+            .field("drops", &self.drops())
+            .finish()
+    }
+}
+
+/// The vtable of the wakers of a [`CountingWaker`].
+///
+/// It can't be built with [`Wake`], since `Wake` has no say in how a waker is cloned or dropped.
+mod counting {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    use super::WakerCounts;
+
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+
+    /// Create a waker that counts towards `counts`.
+    pub(super) fn waker(counts: Arc<WakerCounts>) -> Waker {
+        let raw = RawWaker::new(Arc::into_raw(counts).cast::<()>(), &VTABLE);
+        // Safety: the functions of the vtable treat the pointer as the `Arc` it was created from.
+        unsafe { Waker::from_raw(raw) }
+    }
+
+    unsafe fn clone(ptr: *const ()) -> RawWaker {
+        let counts = ptr.cast::<WakerCounts>();
+        (*counts).clones.fetch_add(1, Ordering::SeqCst);
+        Arc::increment_strong_count(counts);
+        RawWaker::new(ptr, &VTABLE)
+    }
+
+    unsafe fn wake(ptr: *const ()) {
+        let counts = Arc::from_raw(ptr.cast::<WakerCounts>());
+        counts.wakes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe fn wake_by_ref(ptr: *const ()) {
+        let counts = ptr.cast::<WakerCounts>();
+        (*counts).wakes_by_ref.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe fn drop(ptr: *const ()) {
+        let counts = Arc::from_raw(ptr.cast::<WakerCounts>());
+        counts.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Create a waker that calls [`Wake::wake`] on `wakeable` when it is woken.
+#[cfg(not(feature = "raw_waker"))]
+pub(crate) fn waker_for<W: Wake + Send + Sync + 'static>(wakeable: Arc<W>) -> Waker {
+    Waker::from(wakeable)
+}
+
+#[cfg(feature = "raw_waker")]
+pub(crate) use raw::waker_for;
+
+/// Store a clone of `waker` in `slot`, to be woken later, unless the waker in there already wakes
+/// the same task.
+///
+/// A future is usually polled with the same waker over and over, so this saves cloning it, and
+/// dropping the previous clone, on every poll.
+pub(crate) fn register(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(registered) if registered.will_wake(waker) => {}
+        _ => *slot = Some(waker.clone()),
+    }
+}
+
+/// A waker built from a hand-written [`RawWakerVTable`](std::task::RawWakerVTable).
+#[cfg(feature = "raw_waker")]
+mod raw {
+    use std::task::{RawWaker, RawWakerVTable};
+
+    use super::{Arc, Wake, Waker};
+
+    /// Create a waker that calls [`Wake::wake`] on `wakeable` when it is woken.
+    pub(crate) fn waker_for<W: Wake + Send + Sync + 'static>(wakeable: Arc<W>) -> Waker {
+        let raw_waker = RawWaker::new(Arc::into_raw(wakeable).cast::<()>(), vtable::<W>());
+        unsafe { Waker::from_raw(raw_waker) }
+    }
+
+    // The vtable for creating a custom waker for the `Runtime`.
+    // There is one per type a waker can point at, so the functions know what is behind the raw
+    // pointer.
+    fn vtable<W: Wake + Send + Sync + 'static>() -> &'static RawWakerVTable {
+        &const {
+            RawWakerVTable::new(
+                clone_waker::<W>,
+                wake_waker::<W>,
+                wake_by_ref_waker::<W>,
+                drop_waker::<W>,
+            )
+        }
+    }
+
+    // This function is responsible for cloning the waker.
+    // It takes a raw pointer (ptr) to what the waker points at, reconstructs the Arc from the raw
+    // pointer, creates a new RawWaker by cloning the Arc, and then forgets the original Arc to
+    // avoid double-dropping.
+    // This function is called when a waker is cloned.
+    unsafe fn clone_waker<W: Wake + Send + Sync + 'static>(ptr: *const ()) -> RawWaker {
+        let wakeable = Arc::from_raw(ptr.cast::<W>());
+        let raw_waker = RawWaker::new(Arc::into_raw(wakeable.clone()).cast::<()>(), vtable::<W>());
+        std::mem::forget(wakeable);
+        raw_waker
+    }
+
+    // This function is responsible for waking the waker.
+    // It takes a raw pointer (ptr) to what the waker points at, reconstructs the Arc from the raw
+    // pointer, wakes it, and then drops the Arc.
+    // Running the executor from here would drive it re-entrantly from inside a task that is being
+    // polled, so the executor is only told what to poll next.
+    // This function is called when a waker is woken up and needs to be executed.
+    unsafe fn wake_waker<W: Wake + Send + Sync + 'static>(ptr: *const ()) {
+        let wakeable = Arc::from_raw(ptr.cast::<W>());
+        W::wake(wakeable);
+    }
+
+    // This function is responsible for waking the waker by reference.
+    // It does the same as wake_waker, but only borrows the Arc behind the raw pointer (ptr), since
+    // the waker itself stays alive.
+    // This function is called when a future wakes its waker without giving it up, for example
+    // `cx.waker().wake_by_ref()`.
+    unsafe fn wake_by_ref_waker<W: Wake + Send + Sync + 'static>(ptr: *const ()) {
+        let wakeable = std::mem::ManuallyDrop::new(Arc::from_raw(ptr.cast::<W>()));
+        W::wake_by_ref(&wakeable);
+    }
+
+    // This function is responsible for dropping the waker.
+    // It takes a raw pointer (ptr) to what the waker points at, reconstructs the Arc from the raw
+    // pointer, and then drops it.
+    // This function is called when a waker is dropped and its resources need to be released.
+    unsafe fn drop_waker<W: Wake + Send + Sync + 'static>(ptr: *const ()) {
+        drop(Arc::from_raw(ptr.cast::<W>()));
+    }
+}